@@ -0,0 +1,7 @@
+fn main() {
+    // The server of the gRPC API, its clients are generated by the integrators from the same file
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/tracker.proto"], &["proto"])
+        .expect("Could not compile the gRPC API protos");
+}
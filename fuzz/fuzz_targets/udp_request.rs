@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use torrust_tracker::udp::request::parse;
+
+// Every packet must be parsed or rejected with a `ParseError`, never panic
+fuzz_target!(|payload: &[u8]| {
+    if let Err(malformed_request) = parse(payload) {
+        assert_eq!(malformed_request.transaction_id.is_some(), payload.len() >= 16);
+    }
+});
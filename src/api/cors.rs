@@ -0,0 +1,121 @@
+//! The CORS headers of the API, so the web dashboards hosted on another origin can call it from the browsers.
+
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use thiserror::Error;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::config::Cors;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("invalid origin {origin}")]
+    InvalidOrigin { origin: String },
+
+    #[error("invalid method {method}")]
+    InvalidMethod { method: String },
+
+    #[error("invalid header {header}")]
+    InvalidHeader { header: String },
+
+    #[error("credentials can not be allowed for any origin")]
+    CredentialsForAnyOrigin,
+}
+
+/// # Errors
+///
+/// Will return an `Error` if an origin, a method or a header of the `config` is invalid, or if the credentials
+/// are allowed for any origin, which browsers refuse.
+pub fn layer(config: &Cors) -> Result<CorsLayer, Error> {
+    let allow_origin = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        if config.allow_credentials {
+            return Err(Error::CredentialsForAnyOrigin);
+        }
+        AllowOrigin::from(Any)
+    } else {
+        let origins = config
+            .allowed_origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin).map_err(|_| Error::InvalidOrigin { origin: origin.clone() }))
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let methods = config
+        .allowed_methods
+        .iter()
+        .map(|method| {
+            Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|_| Error::InvalidMethod { method: method.clone() })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let headers = config
+        .allowed_headers
+        .iter()
+        .map(|header| HeaderName::from_bytes(header.as_bytes()).map_err(|_| Error::InvalidHeader { header: header.clone() }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(config.allow_credentials)
+        .max_age(Duration::from_secs(config.max_age)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{header, Method, Request};
+    use axum::routing::get;
+    use axum::Router;
+    use hyper::service::Service;
+
+    use super::{layer, Error};
+    use crate::config::Cors;
+
+    fn dashboard_cors() -> Cors {
+        Cors {
+            enabled: true,
+            allowed_origins: vec![String::from("https://dashboard.example.com")],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn the_preflight_requests_of_the_allowed_origins_should_be_answered() {
+        let mut router = Router::new()
+            .route("/api/stats", get(|| async { "stats" }))
+            .layer(layer(&dashboard_cors()).unwrap());
+
+        for (origin, allowed) in [("https://dashboard.example.com", true), ("https://evil.example.com", false)] {
+            let request = Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/api/stats")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.call(request).await.unwrap();
+
+            assert_eq!(
+                response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_some(),
+                allowed,
+                "{origin}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_credentials_should_not_be_allowed_for_any_origin() {
+        let cors = Cors {
+            allowed_origins: vec![String::from("*")],
+            allow_credentials: true,
+            ..dashboard_cors()
+        };
+
+        assert_eq!(layer(&cors).unwrap_err(), Error::CredentialsForAnyOrigin);
+    }
+}
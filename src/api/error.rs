@@ -0,0 +1,208 @@
+//! The errors of the API, answered with the HTTP status of their cause and a JSON body with a code the clients
+//! can match, a message and the details of the error.
+
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::debug;
+use serde_json::json;
+use thiserror::Error;
+
+use super::rate_limit;
+use super::resource::error::{self as resource, ErrorResponse};
+use crate::databases;
+use crate::tracker::reload;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("unauthorized")]
+    Unauthorized,
+
+    #[error("token not valid")]
+    InvalidToken,
+
+    #[error("the API is read-only")]
+    ReadOnly,
+
+    #[error(transparent)]
+    RateLimited(#[from] rate_limit::Error),
+
+    #[error("{resource} not found")]
+    NotFound { resource: &'static str },
+
+    #[error("{resource} already exists")]
+    AlreadyExists { resource: &'static str },
+
+    #[error("invalid {param}: {value}")]
+    InvalidParam { param: String, value: String },
+
+    #[error("{reason}")]
+    InvalidRequest { status: StatusCode, reason: String },
+
+    #[error("failed to {action}: {source}")]
+    Database {
+        action: &'static str,
+        source: databases::error::Error,
+    },
+
+    #[error("invalid configuration: {reason}")]
+    InvalidConfig { reason: String },
+}
+
+impl Error {
+    /// The error of the database `action` on a `resource`, which is not found or already exists if that is why
+    /// it failed.
+    #[must_use]
+    pub fn database(action: &'static str, resource: &'static str, source: databases::error::Error) -> Self {
+        match source {
+            databases::error::Error::NotFound => Error::NotFound { resource },
+            databases::error::Error::ConstraintViolation { .. } => Error::AlreadyExists { resource },
+            source => Error::Database { action, source },
+        }
+    }
+
+    /// A request with an invalid field, like its body or its query params.
+    #[must_use]
+    pub fn unprocessable(reason: impl Into<String>) -> Self {
+        Error::InvalidRequest {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            reason: reason.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::Unauthorized | Error::InvalidToken => StatusCode::UNAUTHORIZED,
+            Error::ReadOnly => StatusCode::FORBIDDEN,
+            Error::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::NotFound { .. } => StatusCode::NOT_FOUND,
+            Error::AlreadyExists { .. } => StatusCode::CONFLICT,
+            Error::InvalidParam { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::InvalidRequest { status, .. } => *status,
+            Error::Database { source, .. } if source.is_transient() => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Database { .. } | Error::InvalidConfig { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Unauthorized => "unauthorized",
+            Error::InvalidToken => "invalid_token",
+            Error::ReadOnly => "read_only",
+            Error::RateLimited(rate_limit::Error::TooManyRequests) => "too_many_requests",
+            Error::RateLimited(rate_limit::Error::LockedOut { .. }) => "locked_out",
+            Error::NotFound { .. } => "not_found",
+            Error::AlreadyExists { .. } => "already_exists",
+            Error::InvalidParam { .. } => "invalid_param",
+            Error::InvalidRequest { .. } => "invalid_request",
+            Error::Database { source, .. } if source.is_transient() => "database_unavailable",
+            Error::Database { .. } => "database_error",
+            Error::InvalidConfig { .. } => "invalid_config",
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::RateLimited(e) => Some(json!({ "retry_in": e.retry_in() })),
+            Error::NotFound { resource } | Error::AlreadyExists { resource } => Some(json!({ "resource": resource })),
+            Error::InvalidParam { param, value } => Some(json!({ "param": param, "value": value })),
+            _ => None,
+        }
+    }
+}
+
+/// The message of an error response, kept in its extensions so the middleware can rewrite its body.
+#[derive(Debug, Clone)]
+pub struct Reason(pub String);
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        debug!("{:?}", self);
+
+        let message = self.to_string();
+        let body = Json(ErrorResponse {
+            error: resource::Error {
+                code: self.code().to_string(),
+                message: message.clone(),
+                details: self.details(),
+            },
+        });
+
+        let mut response = match &self {
+            Error::RateLimited(e) => (self.status(), [(header::RETRY_AFTER, e.retry_in().to_string())], body).into_response(),
+            _ => (self.status(), body).into_response(),
+        };
+        response.extensions_mut().insert(Reason(message));
+        response
+    }
+}
+
+impl From<reload::Error> for Error {
+    fn from(e: reload::Error) -> Self {
+        match e {
+            reload::Error::Config { source } => Error::InvalidConfig {
+                reason: source.to_string(),
+            },
+            reload::Error::Database { source } => Error::Database {
+                action: "reload",
+                source,
+            },
+        }
+    }
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Error::InvalidRequest {
+            status: rejection.status(),
+            reason: rejection.body_text(),
+        }
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(rejection: PathRejection) -> Self {
+        Error::InvalidRequest {
+            status: rejection.status(),
+            reason: rejection.body_text(),
+        }
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Error::InvalidRequest {
+            status: rejection.status(),
+            reason: rejection.body_text(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::Error;
+    use crate::databases;
+
+    #[test]
+    fn the_missing_and_the_duplicated_records_should_be_answered_as_not_found_and_conflicts() {
+        let not_found = Error::database("delete key", "key", databases::error::Error::NotFound);
+        assert_eq!((not_found.status(), not_found.code()), (StatusCode::NOT_FOUND, "not_found"));
+
+        let duplicated = Error::database(
+            "generate key",
+            "key",
+            databases::error::Error::ConstraintViolation {
+                source: "key already exists".into(),
+            },
+        );
+        assert_eq!(
+            (duplicated.status(), duplicated.code()),
+            (StatusCode::CONFLICT, "already_exists")
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! The live events of the tracker streamed to the API clients with Server-Sent Events, so the dashboards
+//! and the indexes react to them instead of polling the stats.
+//!
+//! The stream subscribes a single listener to the events of the tracker when its first client connects,
+//! which broadcasts them to all the clients. A client falling behind misses the events it had no room for.
+
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use axum::extract::{Query, State};
+use axum::response::sse::{self, KeepAlive, Sse};
+use futures::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+
+use super::resource::event::Event;
+use crate::tracker::{self, events};
+
+const CHANNEL_BUFFER_SIZE: usize = 1024;
+
+pub struct EventStream {
+    tracker: Arc<tracker::Tracker>,
+    sender: OnceLock<broadcast::Sender<Arc<events::Event>>>,
+}
+
+impl EventStream {
+    #[must_use]
+    pub fn new(tracker: Arc<tracker::Tracker>) -> Self {
+        Self {
+            tracker,
+            sender: OnceLock::new(),
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<events::Event>> {
+        self.sender
+            .get_or_init(|| {
+                let (sender, _receiver) = broadcast::channel(CHANNEL_BUFFER_SIZE);
+                self.tracker.subscribe(Arc::new(Broadcaster(sender.clone())));
+                sender
+            })
+            .subscribe()
+    }
+}
+
+struct Broadcaster(broadcast::Sender<Arc<events::Event>>);
+
+#[async_trait]
+impl events::EventListener for Broadcaster {
+    async fn on_event(&self, event: &events::Event) {
+        // It fails when there are no clients
+        let _ = self.0.send(Arc::new(event.clone()));
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventsQuery {
+    // The comma separated types of the events to stream, like `torrent_added,torrent_completed`, all of them when not set
+    types: Option<String>,
+}
+
+/// Streams the events of the tracker until the client disconnects
+pub async fn stream_events(
+    State(event_stream): State<Arc<EventStream>>,
+    Query(events_query): Query<EventsQuery>,
+) -> Sse<impl Stream<Item = Result<sse::Event, axum::Error>>> {
+    let types: Option<Vec<String>> = events_query
+        .types
+        .map(|types| types.split(',').map(ToString::to_string).collect());
+
+    let events = stream::unfold(event_stream.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let events = events.filter_map(move |event| {
+        let event = Event::from(&*event);
+        let streamed = types
+            .as_ref()
+            .is_none_or(|types| types.iter().any(|name| name == event.name()));
+
+        std::future::ready(streamed.then(|| {
+            sse::Event::default()
+                .event(event.name())
+                .json_data(&event)
+                .map_err(axum::Error::new)
+        }))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
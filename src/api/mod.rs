@@ -1,2 +1,6 @@
+pub mod cors;
+pub mod error;
+pub mod event_stream;
+pub mod rate_limit;
 pub mod resource;
 pub mod server;
@@ -0,0 +1,188 @@
+//! Limits the requests to the API of each IP address and of each access token, and locks out the IP addresses
+//! sending invalid access tokens, for longer after each one, so the tokens can not be guessed.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::config::ApiRateLimit;
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::udp::rate_limit::RateLimiter;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("too many requests")]
+    TooManyRequests,
+
+    #[error("too many invalid tokens, retry in {retry_in} seconds")]
+    LockedOut { retry_in: u64 },
+}
+
+impl Error {
+    /// Seconds to wait before the next request
+    #[must_use]
+    pub fn retry_in(&self) -> u64 {
+        match self {
+            Error::TooManyRequests => 1,
+            Error::LockedOut { retry_in } => *retry_in,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FailedAttempts {
+    count: u32,
+    last_at: DurationSinceUnixEpoch,
+    locked_until: DurationSinceUnixEpoch,
+}
+
+pub struct Guard {
+    config: ApiRateLimit,
+    per_ip: Option<Mutex<RateLimiter<IpAddr>>>,
+    per_token: Option<Mutex<RateLimiter<String>>>,
+    failed_attempts: Mutex<HashMap<IpAddr, FailedAttempts>>,
+}
+
+impl Guard {
+    #[must_use]
+    pub fn new(config: &ApiRateLimit) -> Self {
+        Self {
+            config: config.clone(),
+            per_ip: (config.requests_per_second_per_ip > 0)
+                .then(|| Mutex::new(RateLimiter::new(config.requests_per_second_per_ip, config.burst_per_ip))),
+            per_token: (config.requests_per_second_per_token > 0)
+                .then(|| Mutex::new(RateLimiter::new(config.requests_per_second_per_token, config.burst_per_token))),
+            failed_attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks the request of the `ip` made at `now`, before its token is.
+    ///
+    /// # Errors
+    ///
+    /// Will return an `Error` if the `ip` is locked out or made too many requests.
+    pub async fn check_ip(&self, ip: IpAddr, now: DurationSinceUnixEpoch) -> Result<(), Error> {
+        if let Some(failed_attempts) = self.failed_attempts.lock().await.get(&ip) {
+            if failed_attempts.locked_until > now {
+                let wait = failed_attempts.locked_until - now;
+                return Err(Error::LockedOut {
+                    retry_in: wait.as_secs() + u64::from(wait.subsec_nanos() > 0),
+                });
+            }
+        }
+
+        match &self.per_ip {
+            Some(per_ip) if !per_ip.lock().await.check(ip, now) => Err(Error::TooManyRequests),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the request made at `now` with the valid `token`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an `Error` if too many requests were made with the `token`.
+    pub async fn check_token(&self, token: &str, now: DurationSinceUnixEpoch) -> Result<(), Error> {
+        match &self.per_token {
+            Some(per_token) if !per_token.lock().await.check(token.to_string(), now) => Err(Error::TooManyRequests),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records an invalid token sent by the `ip` at `now`, which locks it out after `max_failed_attempts`.
+    pub async fn record_failed_attempt(&self, ip: IpAddr, now: DurationSinceUnixEpoch) {
+        if self.config.max_failed_attempts == 0 {
+            return;
+        }
+
+        let max_lockout = Duration::from_secs(self.config.max_lockout);
+        let mut failed_attempts = self.failed_attempts.lock().await;
+
+        // The attempts are forgotten after the longest lockout without any other one
+        failed_attempts.retain(|_, attempts| attempts.last_at + max_lockout > now);
+
+        let attempts = failed_attempts.entry(ip).or_insert(FailedAttempts {
+            count: 0,
+            last_at: now,
+            locked_until: DurationSinceUnixEpoch::ZERO,
+        });
+        attempts.count += 1;
+        attempts.last_at = now;
+
+        if let Some(lockouts) = attempts.count.checked_sub(self.config.max_failed_attempts) {
+            let lockout = self
+                .config
+                .lockout
+                .saturating_mul(2_u64.saturating_pow(lockouts))
+                .min(self.config.max_lockout);
+            attempts.locked_until = now + Duration::from_secs(lockout);
+        }
+    }
+
+    /// Forgets the invalid tokens sent by the `ip` once it sends a valid one.
+    pub async fn record_successful_attempt(&self, ip: IpAddr) {
+        self.failed_attempts.lock().await.remove(&ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use super::{Error, Guard};
+    use crate::config::ApiRateLimit;
+
+    fn sample_ip() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1))
+    }
+
+    #[tokio::test]
+    async fn an_ip_should_be_locked_out_for_longer_after_each_invalid_token_over_the_max_attempts() {
+        let guard = Guard::new(&ApiRateLimit {
+            max_failed_attempts: 2,
+            lockout: 10,
+            max_lockout: 30,
+            ..Default::default()
+        });
+        let now = Duration::from_secs(100);
+
+        guard.record_failed_attempt(sample_ip(), now).await;
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Ok(()));
+
+        guard.record_failed_attempt(sample_ip(), now).await;
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Err(Error::LockedOut { retry_in: 10 }));
+
+        let now = Duration::from_secs(110);
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Ok(()));
+
+        guard.record_failed_attempt(sample_ip(), now).await;
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Err(Error::LockedOut { retry_in: 20 }));
+
+        guard.record_failed_attempt(sample_ip(), now).await;
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Err(Error::LockedOut { retry_in: 30 }));
+
+        guard.record_successful_attempt(sample_ip()).await;
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn the_requests_should_be_limited_by_ip_and_by_token() {
+        let guard = Guard::new(&ApiRateLimit {
+            requests_per_second_per_ip: 1,
+            burst_per_ip: 1,
+            requests_per_second_per_token: 1,
+            burst_per_token: 1,
+            ..Default::default()
+        });
+        let now = Duration::from_secs(100);
+
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Ok(()));
+        assert_eq!(guard.check_ip(sample_ip(), now).await, Err(Error::TooManyRequests));
+        assert_eq!(guard.check_token("MyAccessToken", now).await, Ok(()));
+        assert_eq!(guard.check_token("MyAccessToken", now).await, Err(Error::TooManyRequests));
+    }
+}
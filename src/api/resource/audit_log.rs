@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tracker::audit;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct AuditLogEntry {
+    pub created_at: u64,
+    pub token_name: String,
+    pub ip: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+}
+
+impl From<audit::Entry> for AuditLogEntry {
+    fn from(entry: audit::Entry) -> Self {
+        AuditLogEntry {
+            created_at: entry.created_at.as_secs(),
+            token_name: entry.token_name,
+            ip: entry.ip.map(|ip| ip.to_string()),
+            method: entry.method,
+            path: entry.path,
+            status: entry.status,
+        }
+    }
+}
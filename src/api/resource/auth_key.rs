@@ -9,6 +9,10 @@ use crate::tracker::auth;
 pub struct AuthKey {
     pub key: String,
     pub valid_until: Option<u64>,
+    pub label: Option<String>,
+    pub created_at: Option<u64>,
+    pub last_used: Option<u64>,
+    pub announces: u64,
 }
 
 impl From<AuthKey> for auth::Key {
@@ -18,6 +22,14 @@ impl From<AuthKey> for auth::Key {
             valid_until: auth_key_resource
                 .valid_until
                 .map(|valid_until| DurationSinceUnixEpoch::new(valid_until, 0)),
+            label: auth_key_resource.label,
+            created_at: auth_key_resource
+                .created_at
+                .map(|created_at| DurationSinceUnixEpoch::new(created_at, 0)),
+            last_used: auth_key_resource
+                .last_used
+                .map(|last_used| DurationSinceUnixEpoch::new(last_used, 0)),
+            announces: auth_key_resource.announces,
         }
     }
 }
@@ -27,15 +39,48 @@ impl From<auth::Key> for AuthKey {
         AuthKey {
             key: auth_key.key,
             valid_until: auth_key.valid_until.map(|valid_until| valid_until.as_secs()),
+            label: auth_key.label,
+            created_at: auth_key.created_at.map(|created_at| created_at.as_secs()),
+            last_used: auth_key.last_used.map(|last_used| last_used.as_secs()),
+            announces: auth_key.announces,
         }
     }
 }
 
+/// The `auth_keys` as CSV, with a `key,valid_until,label` header and the missing values left empty.
+#[must_use]
+pub fn to_csv(auth_keys: &[AuthKey]) -> String {
+    let mut csv = String::from("key,valid_until,label\r\n");
+
+    for auth_key in auth_keys {
+        csv.push_str(&format!(
+            "{},{},{}\r\n",
+            csv_field(&auth_key.key),
+            auth_key
+                .valid_until
+                .map(|valid_until| valid_until.to_string())
+                .unwrap_or_default(),
+            auth_key.label.as_deref().map(csv_field).unwrap_or_default()
+        ));
+    }
+
+    csv
+}
+
+/// Quotes the fields with a comma, a quote or a line break, doubling their quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
-    use super::AuthKey;
+    use super::{to_csv, AuthKey};
     use crate::protocol::clock::{Current, TimeNow};
     use crate::tracker::auth;
 
@@ -46,13 +91,21 @@ mod tests {
         let auth_key_resource = AuthKey {
             key: "IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM".to_string(), // cspell:disable-line
             valid_until: Some(duration_in_secs),
+            label: Some("alice".to_string()),
+            created_at: Some(0),
+            last_used: None,
+            announces: 3,
         };
 
         assert_eq!(
             auth::Key::from(auth_key_resource),
             auth::Key {
                 key: "IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM".to_string(), // cspell:disable-line
-                valid_until: Some(Current::add(&Duration::new(duration_in_secs, 0)).unwrap())
+                valid_until: Some(Current::add(&Duration::new(duration_in_secs, 0)).unwrap()),
+                label: Some("alice".to_string()),
+                created_at: Some(Duration::ZERO),
+                last_used: None,
+                announces: 3,
             }
         );
     }
@@ -64,13 +117,21 @@ mod tests {
         let auth_key = auth::Key {
             key: "IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM".to_string(), // cspell:disable-line
             valid_until: Some(Current::add(&Duration::new(duration_in_secs, 0)).unwrap()),
+            label: Some("alice".to_string()),
+            created_at: Some(Duration::ZERO),
+            last_used: None,
+            announces: 3,
         };
 
         assert_eq!(
             AuthKey::from(auth_key),
             AuthKey {
                 key: "IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM".to_string(), // cspell:disable-line
-                valid_until: Some(duration_in_secs)
+                valid_until: Some(duration_in_secs),
+                label: Some("alice".to_string()),
+                created_at: Some(0),
+                last_used: None,
+                announces: 3,
             }
         );
     }
@@ -80,10 +141,43 @@ mod tests {
         assert_eq!(
             serde_json::to_string(&AuthKey {
                 key: "IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM".to_string(), // cspell:disable-line
-                valid_until: Some(60)
+                valid_until: Some(60),
+                label: Some("alice".to_string()),
+                created_at: Some(0),
+                last_used: None,
+                announces: 3,
             })
             .unwrap(),
-            "{\"key\":\"IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM\",\"valid_until\":60}" // cspell:disable-line
+            "{\"key\":\"IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM\",\"valid_until\":60,\"label\":\"alice\",\"created_at\":0,\"last_used\":null,\"announces\":3}" // cspell:disable-line
+        );
+    }
+
+    #[test]
+    fn it_should_be_written_as_csv_quoting_the_labels_when_needed() {
+        let auth_keys = vec![
+            AuthKey {
+                key: "IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM".to_string(), // cspell:disable-line
+                valid_until: Some(60),
+                label: Some("alice, \"admin\"".to_string()),
+                created_at: Some(0),
+                last_used: None,
+                announces: 0,
+            },
+            AuthKey {
+                key: "YZSl4lMZupRuOpSRC3krIKR5BPB14nrJ".to_string(), // cspell:disable-line
+                valid_until: None,
+                label: None,
+                created_at: Some(0),
+                last_used: None,
+                announces: 0,
+            },
+        ];
+
+        assert_eq!(
+            to_csv(&auth_keys),
+            "key,valid_until,label\r\n\
+             IaWDneuFNZi8IB4MPA3qW1CD0M30EZSM,60,\"alice, \"\"admin\"\"\"\r\n\
+             YZSl4lMZupRuOpSRC3krIKR5BPB14nrJ,,\r\n" // cspell:disable-line
         );
     }
 }
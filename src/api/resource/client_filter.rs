@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tracker::client_filter::{self, Mode};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ClientFilter {
+    pub mode: Mode,
+    pub peer_id_prefixes: Vec<String>,
+    pub user_agents: Vec<String>,
+}
+
+impl From<&client_filter::ClientFilter> for ClientFilter {
+    fn from(client_filter: &client_filter::ClientFilter) -> Self {
+        ClientFilter {
+            mode: client_filter.mode(),
+            peer_id_prefixes: client_filter.peer_id_prefixes().cloned().collect(),
+            user_agents: client_filter.user_agents().cloned().collect(),
+        }
+    }
+}
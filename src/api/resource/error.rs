@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// The body of the failed requests, like `{"error": {"code": "not_found", "message": "torrent not found", "details": {"resource": "torrent"}}}`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ErrorResponse {
+    pub error: Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub code: String,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
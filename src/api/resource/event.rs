@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use super::peer::Peer;
+use crate::tracker::events;
+
+/// An event of the tracker streamed by the API, the keys are created without their secret
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    TorrentAdded {
+        info_hash: String,
+    },
+    PeerAnnounced {
+        info_hash: String,
+        peer: Peer,
+    },
+    PeerStopped {
+        info_hash: String,
+        peer: Peer,
+    },
+    TorrentCompleted {
+        info_hash: String,
+        peer: Peer,
+    },
+    TorrentScraped {
+        info_hash: String,
+    },
+    KeyCreated {
+        label: Option<String>,
+        valid_until: Option<u64>,
+    },
+}
+
+impl Event {
+    /// The `type` of the event, like `peer_announced`
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::TorrentAdded { .. } => "torrent_added",
+            Event::PeerAnnounced { .. } => "peer_announced",
+            Event::PeerStopped { .. } => "peer_stopped",
+            Event::TorrentCompleted { .. } => "torrent_completed",
+            Event::TorrentScraped { .. } => "torrent_scraped",
+            Event::KeyCreated { .. } => "key_created",
+        }
+    }
+}
+
+impl From<&events::Event> for Event {
+    fn from(event: &events::Event) -> Self {
+        match event {
+            events::Event::TorrentAdded { info_hash } => Event::TorrentAdded {
+                info_hash: info_hash.to_string(),
+            },
+            events::Event::PeerAnnounced { info_hash, peer } => Event::PeerAnnounced {
+                info_hash: info_hash.to_string(),
+                peer: Peer::from(*peer),
+            },
+            events::Event::PeerStopped { info_hash, peer } => Event::PeerStopped {
+                info_hash: info_hash.to_string(),
+                peer: Peer::from(*peer),
+            },
+            events::Event::TorrentCompleted { info_hash, peer } => Event::TorrentCompleted {
+                info_hash: info_hash.to_string(),
+                peer: Peer::from(*peer),
+            },
+            events::Event::TorrentScraped { info_hash } => Event::TorrentScraped {
+                info_hash: info_hash.to_string(),
+            },
+            events::Event::KeyCreated { key } => Event::KeyCreated {
+                label: key.label.clone(),
+                valid_until: key.valid_until.map(|valid_until| valid_until.as_secs()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Event;
+    use crate::tracker::{auth, events};
+
+    #[test]
+    fn the_created_keys_should_be_streamed_without_their_secret() {
+        let event = Event::from(&events::Event::KeyCreated {
+            key: auth::Key {
+                key: String::from("YZSl4lMZupRuOpSRC3krIKR5BPB14nrJ"),
+                valid_until: Some(Duration::from_secs(1_700_000_000)),
+                label: Some(String::from("alice")),
+                ..Default::default()
+            },
+        });
+
+        assert_eq!(event.name(), "key_created");
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"key_created","label":"alice","valid_until":1700000000}"#
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::databases::PoolState;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Degraded,
+    Unavailable,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Health {
+    pub status: Status,
+    pub components: Components,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Components {
+    pub database: Database,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Database {
+    pub status: Status,
+    pub driver: String,
+    pub error: Option<String>,
+    pub pool: Option<Pool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Pool {
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub max_connections: u32,
+    pub exhausted: bool,
+}
+
+impl From<PoolState> for Pool {
+    fn from(pool_state: PoolState) -> Self {
+        Pool {
+            connections: pool_state.connections,
+            idle_connections: pool_state.idle_connections,
+            max_connections: pool_state.max_connections,
+            exhausted: pool_state.is_exhausted(),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+/// The verbosity of the logs, like `info` or `debug`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LogLevel {
+    pub level: String,
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tracker::maintenance::Report;
+
+/// What a maintenance flush saved into the database and removed from memory.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Flush {
+    pub torrents_saved: usize,
+    pub keys_saved: usize,
+    pub torrents_removed: usize,
+    pub peers_removed: usize,
+    pub peers_saved: usize,
+}
+
+impl From<Report> for Flush {
+    fn from(report: Report) -> Self {
+        Flush {
+            torrents_saved: report.torrents_saved,
+            keys_saved: report.keys_saved,
+            torrents_removed: report.torrents_removed,
+            peers_removed: report.peers_removed,
+            peers_saved: report.peers_saved,
+        }
+    }
+}
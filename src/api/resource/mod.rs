@@ -7,7 +7,14 @@
 //! - [ ] `StatsResource`
 //! - [ ] ...
 
+pub mod audit_log;
 pub mod auth_key;
+pub mod client_filter;
+pub mod error;
+pub mod event;
+pub mod health;
+pub mod log_level;
+pub mod maintenance;
 pub mod peer;
 pub mod stats;
 pub mod torrent;
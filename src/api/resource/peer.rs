@@ -6,13 +6,28 @@ use crate::tracker;
 pub struct Peer {
     pub peer_id: Id,
     pub peer_addr: String,
+    pub ip: String,
+    pub port: u16,
     #[deprecated(since = "2.0.0", note = "please use `updated_milliseconds_ago` instead")]
     pub updated: u128,
     pub updated_milliseconds_ago: u128,
+    // The seconds since the Unix epoch of the last announce
+    pub last_announce: u64,
     pub uploaded: i64,
     pub downloaded: i64,
     pub left: i64,
     pub event: String,
+    // A BEP 21 partial seed
+    pub paused: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+/// A peer of the IP looked up, with the swarm it is in
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct SwarmPeer {
+    pub info_hash: String,
+    pub peer: Peer,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -36,12 +51,50 @@ impl From<tracker::peer::Peer> for Peer {
         Peer {
             peer_id: Id::from(peer.peer_id),
             peer_addr: peer.peer_addr.to_string(),
+            ip: peer.peer_addr.ip().to_string(),
+            port: peer.peer_addr.port(),
             updated: peer.updated.as_millis(),
             updated_milliseconds_ago: peer.updated.as_millis(),
+            last_announce: peer.updated.as_secs(),
             uploaded: peer.uploaded.0,
             downloaded: peer.downloaded.0,
             left: peer.left.0,
             event: format!("{:?}", peer.event),
+            paused: peer.paused,
+            country: peer.country.map(|country| country.to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+    use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+
+    use super::Peer;
+    use crate::protocol::clock::DurationSinceUnixEpoch;
+    use crate::tracker;
+
+    #[test]
+    fn the_peer_resource_should_have_the_ip_port_and_last_announce_of_the_peer() {
+        let peer = Peer::from(tracker::peer::Peer {
+            peer_id: tracker::peer::Id(*b"-qB00000000000000000"),
+            peer_addr: SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080),
+            updated: DurationSinceUnixEpoch::new(1_669_397_478, 934_000_000),
+            uploaded: NumberOfBytes(10),
+            downloaded: NumberOfBytes(20),
+            left: NumberOfBytes(0),
+            event: AnnounceEvent::Completed,
+            paused: false,
+            key: None,
+            country: None,
+        });
+
+        assert_eq!(peer.peer_addr, "[::1]:8080");
+        assert_eq!((peer.ip.as_str(), peer.port), ("::1", 8080));
+        assert_eq!(peer.last_announce, 1_669_397_478);
+        assert_eq!(peer.peer_id.client.as_deref(), Some("qBittorrent"));
+        assert_eq!((peer.uploaded, peer.downloaded), (10, 20));
+    }
+}
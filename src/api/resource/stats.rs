@@ -1,6 +1,11 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+use crate::databases::metrics::{self, Histogram, Snapshot};
+use crate::tracker::statistics::{HistoryBucket, ListenerMetrics, Metrics, WindowMetrics};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Stats {
     pub torrents: u32,
     pub seeders: u32,
@@ -18,4 +23,243 @@ pub struct Stats {
     pub udp6_connections_handled: u32,
     pub udp6_announces_handled: u32,
     pub udp6_scrapes_handled: u32,
+    pub torrents_evicted: u32,
+    pub peers_evicted: u32,
+    pub udp_packets_dropped: u32,
+    pub http: Http,
+    pub udp: Udp,
+    pub windows: Windows,
+    // Requests of each UDP and HTTP tracker bind address, like `udp://0.0.0.0:6969`
+    #[serde(default)]
+    pub listeners: BTreeMap<String, Listener>,
+    // Peers in each country, empty unless the GeoIP lookup is enabled
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub countries: BTreeMap<String, u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<Database>,
+    // The last 24 hours in buckets of five minutes, only when requested with `history=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<HistoryItem>>,
+}
+
+/// The statistics over the rolling windows ending now.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Windows {
+    pub last_minute: Window,
+    pub last_five_minutes: Window,
+    pub last_hour: Window,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Window {
+    pub seconds: u64,
+    pub announces_per_second: f64,
+    pub scrapes_per_second: f64,
+    pub unique_peers: u64,
+    pub tcp_announces: u64,
+    pub udp_announces: u64,
+    pub ipv4_announces: u64,
+    pub ipv6_announces: u64,
+    pub tcp_scrapes: u64,
+    pub udp_scrapes: u64,
+    pub ipv4_scrapes: u64,
+    pub ipv6_scrapes: u64,
+}
+
+impl From<WindowMetrics> for Window {
+    fn from(metrics: WindowMetrics) -> Self {
+        Window {
+            seconds: metrics.secs,
+            announces_per_second: metrics.announces_per_second(),
+            scrapes_per_second: metrics.scrapes_per_second(),
+            unique_peers: metrics.unique_peers,
+            tcp_announces: metrics.counters.tcp_announces(),
+            udp_announces: metrics.counters.udp_announces(),
+            ipv4_announces: metrics.counters.ipv4_announces(),
+            ipv6_announces: metrics.counters.ipv6_announces(),
+            tcp_scrapes: metrics.counters.tcp_scrapes(),
+            udp_scrapes: metrics.counters.udp_scrapes(),
+            ipv4_scrapes: metrics.counters.ipv4_scrapes(),
+            ipv6_scrapes: metrics.counters.ipv6_scrapes(),
+        }
+    }
+}
+
+/// The announces of the HTTP trackers not following the spec, and their errors, statuses and response times.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Http {
+    pub tracker_ids_ignored: u64,
+    pub errors_sent: u64,
+    // Responses by status code
+    pub statuses: BTreeMap<u16, u64>,
+    pub announce: Requests,
+    pub scrape: Requests,
+}
+
+impl From<&Metrics> for Http {
+    fn from(metrics: &Metrics) -> Self {
+        Http {
+            tracker_ids_ignored: metrics.http_tracker_ids_ignored,
+            errors_sent: metrics.http_errors_sent,
+            statuses: metrics.http_responses_by_status.clone(),
+            announce: Requests::from(&metrics.http_announce_response_time),
+            scrape: Requests::from(&metrics.http_scrape_response_time),
+        }
+    }
+}
+
+/// The malformed requests, shed connects, errors and response times of the UDP trackers.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Udp {
+    pub requests_malformed: u64,
+    pub connects_shed: u64,
+    pub errors_sent: u64,
+    pub connect: Requests,
+    pub announce: Requests,
+    pub scrape: Requests,
+}
+
+/// The requests of a kind handled by the HTTP or UDP trackers, with their response times.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Requests {
+    pub count: u64,
+    pub response_time: Latency,
+}
+
+impl From<&Histogram> for Requests {
+    fn from(histogram: &Histogram) -> Self {
+        Requests {
+            count: histogram.count,
+            response_time: Latency::from(histogram),
+        }
+    }
+}
+
+impl From<&Metrics> for Udp {
+    fn from(metrics: &Metrics) -> Self {
+        Udp {
+            requests_malformed: metrics.udp_requests_malformed,
+            connects_shed: metrics.udp_connects_shed,
+            errors_sent: metrics.udp_errors_sent,
+            connect: Requests::from(&metrics.udp_connect_response_time),
+            announce: Requests::from(&metrics.udp_announce_response_time),
+            scrape: Requests::from(&metrics.udp_scrape_response_time),
+        }
+    }
+}
+
+/// The requests handled by a listener since the tracker started, and when it got the last one.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Listener {
+    pub requests: u64,
+    pub announces: u64,
+    pub scrapes: u64,
+    pub errors: u64,
+    pub last_request: Option<u64>,
+}
+
+impl From<&ListenerMetrics> for Listener {
+    fn from(metrics: &ListenerMetrics) -> Self {
+        Listener {
+            requests: metrics.requests,
+            announces: metrics.announces,
+            scrapes: metrics.scrapes,
+            errors: metrics.errors,
+            last_request: metrics.last_request.map(|last_request| last_request.as_secs()),
+        }
+    }
+}
+
+/// The announces handled from `start` to the next bucket, and the peers and torrents at its end.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct HistoryItem {
+    pub start: u64,
+    pub announces: u64,
+    pub peers: u64,
+    pub torrents: u64,
+}
+
+impl From<HistoryBucket> for HistoryItem {
+    fn from(bucket: HistoryBucket) -> Self {
+        HistoryItem {
+            start: bucket.start,
+            announces: bucket.announces,
+            peers: bucket.peers,
+            torrents: bucket.torrents,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Database {
+    pub queries: Vec<Query>,
+    pub pool_wait: PoolWait,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Query {
+    pub method: String,
+    pub count: u64,
+    pub errors: u64,
+    pub latency: Latency,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PoolWait {
+    pub checkouts: u64,
+    pub timeouts: u64,
+    pub wait: Latency,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Latency {
+    pub total_micros: u64,
+    pub max_micros: u64,
+    pub buckets: Vec<Bucket>,
+}
+
+/// The number of samples that took up to `le_millis`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Bucket {
+    pub le_millis: u64,
+    pub count: u64,
+}
+
+impl From<&Histogram> for Latency {
+    fn from(histogram: &Histogram) -> Self {
+        Latency {
+            total_micros: u64::try_from(histogram.total.as_micros()).unwrap_or(u64::MAX),
+            max_micros: u64::try_from(histogram.max.as_micros()).unwrap_or(u64::MAX),
+            buckets: metrics::LATENCY_BUCKETS
+                .iter()
+                .zip(histogram.buckets)
+                .map(|(le_millis, count)| Bucket {
+                    le_millis: *le_millis,
+                    count,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Snapshot> for Database {
+    fn from(snapshot: Snapshot) -> Self {
+        Database {
+            queries: snapshot
+                .queries
+                .iter()
+                .map(|(method, query_metrics)| Query {
+                    method: (*method).to_string(),
+                    count: query_metrics.latency.count,
+                    errors: query_metrics.errors,
+                    latency: Latency::from(&query_metrics.latency),
+                })
+                .collect(),
+            pool_wait: PoolWait {
+                checkouts: snapshot.pool_wait.wait.count,
+                timeouts: snapshot.pool_wait.timeouts,
+                wait: Latency::from(&snapshot.pool_wait.wait),
+            },
+        }
+    }
 }
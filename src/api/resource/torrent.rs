@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -6,10 +8,33 @@ pub struct Torrent {
     pub seeders: u32,
     pub completed: u32,
     pub leechers: u32,
+    // The leechers that are BEP 21 partial seeds
+    pub partial_seeds: u32,
+    // Peers in each country, empty unless the GeoIP lookup is enabled
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub countries: BTreeMap<String, u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub peers: Option<Vec<super::peer::Peer>>,
 }
 
+/// See [`crate::tracker::swarm_health`].
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SwarmHealth {
+    pub info_hash: String,
+    pub seeders: u32,
+    pub leechers: u32,
+    pub ratio: f64,
+    pub churn: u32,
+    pub last_activity: u64,
+    pub score: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct WhitelistEntry {
+    pub info_hash: String,
+    pub whitelisted: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct ListItem {
     pub info_hash: String,
@@ -19,3 +44,10 @@ pub struct ListItem {
     // todo: this is always None. Remove field from endpoint?
     pub peers: Option<Vec<super::peer::Peer>>,
 }
+
+/// How many times a torrent was completed in the interval starting at `timestamp`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CompletedHistoryItem {
+    pub timestamp: u64,
+    pub completed: u32,
+}
@@ -1,25 +1,158 @@
 use std::cmp::min;
-use std::collections::{HashMap, HashSet};
-use std::net::SocketAddr;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{ConnectInfo, DefaultBodyLimit, FromRequest, FromRequestParts, OriginalUri, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderName, HeaderValue, Method, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{async_trait, Json, Router};
+use ipnet::IpNet;
+use log::error;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use warp::{filters, reply, serve, Filter};
 
-use super::resource::auth_key::AuthKey;
+use super::cors;
+use super::error::{Error, Reason};
+use super::event_stream::{stream_events, EventStream};
+use super::rate_limit;
+use super::resource::audit_log::AuditLogEntry;
+use super::resource::auth_key::{self, AuthKey};
+use super::resource::client_filter::ClientFilter;
+use super::resource::health::{self, Health};
+use super::resource::log_level::LogLevel;
+use super::resource::maintenance;
 use super::resource::peer;
-use super::resource::stats::Stats;
-use super::resource::torrent::{ListItem, Torrent};
+use super::resource::stats::{self, Stats};
+use super::resource::torrent::{CompletedHistoryItem, ListItem, SwarmHealth, Torrent, WhitelistEntry};
+use crate::http::middleware::log_request;
+use crate::http::tls::Certificates;
+use crate::logging;
+use crate::protocol::clock::{Current, Time, TimeNow};
 use crate::protocol::info_hash::InfoHash;
-use crate::tracker;
+use crate::tracker::{self, audit, blacklist, statistics, torrent_query};
+
+/// Enough for tens of thousands of info hashes in a single whitelist request.
+const MAX_WHITELIST_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// The most keys generated by a single batch request.
+const MAX_KEYS_PER_BATCH: usize = 10_000;
 
 #[derive(Deserialize, Debug)]
 struct TorrentInfoQuery {
     offset: Option<u32>,
     limit: Option<u32>,
+    sort: Option<torrent_query::Sort>,
+    order: Option<torrent_query::Order>,
+    min_seeders: Option<u32>,
+    #[serde(alias = "infohash_prefix")]
+    info_hash_prefix: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PeersQuery {
+    ip: IpAddr,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatsQuery {
+    history: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+struct WhitelistQuery {
+    offset: Option<u32>,
+    limit: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CompletedHistoryQuery {
+    since: Option<u64>,
+    interval: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RemoveTorrentQuery {
+    persistent_stat: Option<bool>,
+    whitelist: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AuditLogQuery {
+    token_name: Option<String>,
+    ip: Option<IpAddr>,
+    since: Option<u64>,
+    until: Option<u64>,
+    offset: Option<u32>,
+    limit: Option<u32>,
 }
 
+#[derive(Deserialize, Debug)]
+struct KeyQuery {
+    label: Option<String>,
+}
+
+/// Either `seconds_valid` from now or `never_expires`.
+#[derive(Deserialize, Debug)]
+struct KeyExpiry {
+    seconds_valid: Option<u64>,
+    #[serde(default)]
+    never_expires: bool,
+}
+
+impl KeyExpiry {
+    /// The lifetime of the key, `None` if it never expires.
+    fn lifetime(&self) -> Result<Option<Duration>, Error> {
+        match (self.seconds_valid, self.never_expires) {
+            (Some(seconds_valid), false) => Ok(Some(Duration::from_secs(seconds_valid))),
+            (None, true) => Ok(None),
+            _ => Err(Error::unprocessable("either seconds_valid or never_expires is required")),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct NewKey {
+    label: Option<String>,
+    #[serde(flatten)]
+    expiry: KeyExpiry,
+}
+
+/// The keys share the expiry, and they never expire without one.
+#[derive(Deserialize, Debug)]
+struct NewKeyBatch {
+    count: usize,
+    label_prefix: Option<String>,
+    #[serde(flatten)]
+    expiry: KeyExpiry,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum KeyBatchFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Debug)]
+struct KeyBatchQuery {
+    #[serde(default)]
+    format: KeyBatchFormat,
+}
+
+#[derive(Deserialize)]
+struct AuthToken {
+    token: Option<String>,
+}
+
+/// The body of the successful actions, and of the failed ones of the unversioned routes.
 #[derive(Serialize, Debug)]
 #[serde(tag = "status", rename_all = "snake_case")]
 enum ActionStatus<'a> {
@@ -27,301 +160,985 @@ enum ActionStatus<'a> {
     Err { reason: std::borrow::Cow<'a, str> },
 }
 
-impl warp::reject::Reject for ActionStatus<'static> {}
+type ActionResult = Result<Json<ActionStatus<'static>>, Error>;
+
+fn parse_param<T: FromStr>(param: &str, value: String) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::InvalidParam {
+        param: param.to_string(),
+        value,
+    })
+}
+
+/// A path param parsed with `FromStr`, answered with an `Error::InvalidParam` if it does not parse.
+struct PathParam<T>(T);
+
+#[async_trait]
+impl<S: Send + Sync, T: FromStr> FromRequestParts<S> for PathParam<T> {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<Vec<(String, String)>>::from_request_parts(parts, state).await?;
+
+        match params.into_iter().next() {
+            Some((param, value)) => parse_param(&param, value).map(PathParam),
+            None => Err(Error::NotFound { resource: "route" }),
+        }
+    }
+}
+
+/// The query params, answered with an `Error::InvalidRequest` if they do not deserialize.
+struct QueryParams<T>(T);
+
+#[async_trait]
+impl<S: Send + Sync, T: DeserializeOwned> FromRequestParts<S> for QueryParams<T> {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(QueryParams(query))
+    }
+}
+
+/// The JSON body, answered with an `Error::InvalidRequest` if it does not deserialize.
+struct JsonBody<T>(T);
+
+#[async_trait]
+impl<S, B, T> FromRequest<S, B> for JsonBody<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = Error;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<T>::from_request(request, state).await?;
+        Ok(JsonBody(body))
+    }
+}
+
+/// The `/` of a CIDR range has to be percent-encoded in the path, like in `10.0.0.0%2F8`.
+fn parse_blacklist_range(range: &str) -> Result<IpNet, Error> {
+    blacklist::parse_range(range).map_err(|_| Error::InvalidParam {
+        param: "cidr".to_string(),
+        value: range.to_string(),
+    })
+}
+
+/// The access tokens of the API, taken from the current configuration so they can be reloaded, and the limits
+/// of the requests made with them.
+struct Access {
+    tracker: Arc<tracker::Tracker>,
+    guard: rate_limit::Guard,
+}
 
-fn authenticate(tokens: HashMap<String, String>) -> impl Filter<Extract = (), Error = warp::reject::Rejection> + Clone {
-    #[derive(Deserialize)]
-    struct AuthToken {
-        token: Option<String>,
+impl Access {
+    /// The name of the access token, if it is one.
+    fn token_name(&self, token: &str) -> Option<String> {
+        self.tracker
+            .config()
+            .http_api
+            .access_tokens
+            .iter()
+            .find(|(_, access_token)| *access_token == token)
+            .map(|(name, _)| name.clone())
     }
+}
+
+/// The name of the access token of an authenticated request, for the audit log.
+#[derive(Debug, Clone)]
+struct TokenName(String);
+
+/// `ConnectInfo` is set by the `Server` of axum, and for each request of the Unix domain socket
+fn remote_ip<B>(request: &Request<B>) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(remote_addr)| remote_addr.ip())
+}
+
+async fn authenticate<B>(
+    State(access): State<Arc<Access>>,
+    QueryParams(auth_token): QueryParams<AuthToken>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, Error> {
+    let now = Current::now();
+    let ip = remote_ip(&request);
 
-    let tokens: HashSet<String> = tokens.into_iter().map(|(_, v)| v).collect();
+    if let Some(ip) = ip {
+        access.guard.check_ip(ip, now).await?;
+    }
 
-    let tokens = Arc::new(tokens);
-    warp::filters::any::any()
-        .map(move || tokens.clone())
-        .and(filters::query::query::<AuthToken>())
-        .and_then(|tokens: Arc<HashSet<String>>, token: AuthToken| async move {
-            match token.token {
-                Some(token) => {
-                    if !tokens.contains(&token) {
-                        return Err(warp::reject::custom(ActionStatus::Err {
-                            reason: "token not valid".into(),
-                        }));
-                    }
+    let token_name = auth_token.token.as_deref().and_then(|token| access.token_name(token));
 
-                    Ok(())
-                }
-                None => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "unauthorized".into(),
-                })),
+    match (auth_token.token, token_name) {
+        (Some(token), Some(token_name)) => {
+            if let Some(ip) = ip {
+                access.guard.record_successful_attempt(ip).await;
             }
-        })
-        .untuple_one()
-}
-
-#[allow(clippy::too_many_lines)]
-pub fn start(socket_addr: SocketAddr, tracker: &Arc<tracker::Tracker>) -> impl warp::Future<Output = ()> {
-    // GET /api/torrents?offset=:u32&limit=:u32
-    // View torrent list
-    let api_torrents = tracker.clone();
-    let view_torrent_list = filters::method::get()
-        .and(filters::path::path("torrents"))
-        .and(filters::path::end())
-        .and(filters::query::query())
-        .map(move |limits| {
-            let tracker = api_torrents.clone();
-            (limits, tracker)
-        })
-        .and_then(|(limits, tracker): (TorrentInfoQuery, Arc<tracker::Tracker>)| async move {
-            let offset = limits.offset.unwrap_or(0);
-            let limit = min(limits.limit.unwrap_or(1000), 4000);
-
-            let db = tracker.get_torrents().await;
-            let results: Vec<_> = db
-                .iter()
-                .map(|(info_hash, torrent_entry)| {
-                    let (seeders, completed, leechers) = torrent_entry.get_stats();
-                    ListItem {
-                        info_hash: info_hash.to_string(),
-                        seeders,
-                        completed,
-                        leechers,
-                        peers: None,
-                    }
-                })
-                .skip(offset as usize)
-                .take(limit as usize)
-                .collect();
-
-            Result::<_, warp::reject::Rejection>::Ok(reply::json(&results))
-        });
+            access.guard.check_token(&token, now).await?;
 
-    // GET /api/stats
-    // View tracker status
-    let api_stats = tracker.clone();
-    let view_stats_list = filters::method::get()
-        .and(filters::path::path("stats"))
-        .and(filters::path::end())
-        .map(move || api_stats.clone())
-        .and_then(|tracker: Arc<tracker::Tracker>| async move {
-            let mut results = Stats {
-                torrents: 0,
-                seeders: 0,
-                completed: 0,
-                leechers: 0,
-                tcp4_connections_handled: 0,
-                tcp4_announces_handled: 0,
-                tcp4_scrapes_handled: 0,
-                tcp6_connections_handled: 0,
-                tcp6_announces_handled: 0,
-                tcp6_scrapes_handled: 0,
-                udp4_connections_handled: 0,
-                udp4_announces_handled: 0,
-                udp4_scrapes_handled: 0,
-                udp6_connections_handled: 0,
-                udp6_announces_handled: 0,
-                udp6_scrapes_handled: 0,
-            };
-
-            let db = tracker.get_torrents().await;
-
-            db.values().for_each(|torrent_entry| {
-                let (seeders, completed, leechers) = torrent_entry.get_stats();
-                results.seeders += seeders;
-                results.completed += completed;
-                results.leechers += leechers;
-                results.torrents += 1;
-            });
-
-            let stats = tracker.get_stats().await;
-
-            #[allow(clippy::cast_possible_truncation)]
-            {
-                results.tcp4_connections_handled = stats.tcp4_connections_handled as u32;
-                results.tcp4_announces_handled = stats.tcp4_announces_handled as u32;
-                results.tcp4_scrapes_handled = stats.tcp4_scrapes_handled as u32;
-                results.tcp6_connections_handled = stats.tcp6_connections_handled as u32;
-                results.tcp6_announces_handled = stats.tcp6_announces_handled as u32;
-                results.tcp6_scrapes_handled = stats.tcp6_scrapes_handled as u32;
-                results.udp4_connections_handled = stats.udp4_connections_handled as u32;
-                results.udp4_announces_handled = stats.udp4_announces_handled as u32;
-                results.udp4_scrapes_handled = stats.udp4_scrapes_handled as u32;
-                results.udp6_connections_handled = stats.udp6_connections_handled as u32;
-                results.udp6_announces_handled = stats.udp6_announces_handled as u32;
-                results.udp6_scrapes_handled = stats.udp6_scrapes_handled as u32;
+            request.extensions_mut().insert(TokenName(token_name));
+
+            Ok(next.run(request).await)
+        }
+        (Some(_), None) => {
+            if let Some(ip) = ip {
+                access.guard.record_failed_attempt(ip, now).await;
             }
 
-            Result::<_, warp::reject::Rejection>::Ok(reply::json(&results))
-        });
+            Err(Error::InvalidToken)
+        }
+        (None, _) => Err(Error::Unauthorized),
+    }
+}
 
-    // GET /api/torrent/:info_hash
-    // View torrent info
-    let t2 = tracker.clone();
-    let view_torrent_info = filters::method::get()
-        .and(filters::path::path("torrent"))
-        .and(filters::path::param())
-        .and(filters::path::end())
-        .map(move |info_hash: InfoHash| {
-            let tracker = t2.clone();
-            (info_hash, tracker)
-        })
-        .and_then(|(info_hash, tracker): (InfoHash, Arc<tracker::Tracker>)| async move {
-            let db = tracker.get_torrents().await;
-            let torrent_entry_option = db.get(&info_hash);
-
-            let torrent_entry = match torrent_entry_option {
-                Some(torrent_entry) => torrent_entry,
-                None => {
-                    return Result::<_, warp::reject::Rejection>::Ok(reply::json(&"torrent not known"));
-                }
-            };
-            let (seeders, completed, leechers) = torrent_entry.get_stats();
-
-            let peers = torrent_entry.get_peers(None);
-
-            let peer_resources = peers.iter().map(|peer| peer::Peer::from(**peer)).collect();
-
-            Ok(reply::json(&Torrent {
-                info_hash: info_hash.to_string(),
-                seeders,
-                completed,
-                leechers,
-                peers: Some(peer_resources),
-            }))
+/// Rejects the changes, the requests with another method than `GET`, when the API is read-only.
+async fn reject_changes_when_read_only<B>(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, Error> {
+    if tracker.config().http_api.read_only && !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return Err(Error::ReadOnly);
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Records the changes made with the API, the requests with another method than `GET`, in the audit log.
+async fn record_audit_log<B>(State(tracker): State<Arc<tracker::Tracker>>, request: Request<B>, next: Next<B>) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+
+    let token_name = request
+        .extensions()
+        .get::<TokenName>()
+        .map(|TokenName(token_name)| token_name.clone())
+        .unwrap_or_default();
+    let ip = remote_ip(&request);
+    let method = request.method().to_string();
+    // The path without the prefix of the nested routes is in the `uri`
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path().to_string(), |OriginalUri(uri)| uri.path().to_string());
+
+    let response = next.run(request).await;
+
+    let entry = audit::Entry {
+        created_at: Current::now(),
+        token_name,
+        ip,
+        method,
+        path,
+        status: response.status().as_u16(),
+    };
+    if let Err(e) = tracker.record_audit_log_entry(&entry).await {
+        error!("Could not record {} {} in the audit log: {}", entry.method, entry.path, e);
+    }
+
+    response
+}
+
+/// Marks the responses of the unversioned routes as deprecated, so the clients can move to the `/api/v1` ones, and
+/// answers their errors with the former `{"status": "err", "reason": ...}` body.
+async fn deprecate_unversioned_route<B>(request: Request<B>, next: Next<B>) -> Response {
+    let mut response = next.run(request).await;
+    if let Some(Reason(reason)) = response.extensions_mut().remove::<Reason>() {
+        let (mut parts, _) = response.into_parts();
+        // The length of the replaced body is set again by the new one
+        parts.headers.remove(header::CONTENT_LENGTH);
+        response = (parts, Json(ActionStatus::Err { reason: reason.into() })).into_response();
+    }
+    response
+        .headers_mut()
+        .insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    response
+}
+
+async fn route_not_found() -> Error {
+    Error::NotFound { resource: "route" }
+}
+
+/// # Panics
+///
+/// Will panic if the `socket_addr` cannot be bound.
+pub fn start(socket_addr: SocketAddr, tracker: &Arc<tracker::Tracker>) -> impl Future<Output = ()> {
+    let server = axum::Server::bind(&socket_addr)
+        .serve(routes(tracker).into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            tokio::signal::ctrl_c().await.expect("Failed to listen to shutdown signal.");
         });
 
-    // DELETE /api/whitelist/:info_hash
-    // Delete info hash from whitelist
-    let t3 = tracker.clone();
-    let delete_torrent = filters::method::delete()
-        .and(filters::path::path("whitelist"))
-        .and(filters::path::param())
-        .and(filters::path::end())
-        .map(move |info_hash: InfoHash| {
-            let tracker = t3.clone();
-            (info_hash, tracker)
+    async move {
+        if let Err(e) = server.await {
+            error!("Could not serve the API on {}: {}", socket_addr, e);
+        }
+    }
+}
+
+/// Serves the API in TLS mode, with the current certificate of `certificates` for each new connection
+///
+/// # Panics
+///
+/// Will panic if the `socket_addr` cannot be bound.
+pub async fn start_tls(socket_addr: SocketAddr, certificates: Arc<Certificates>, tracker: &Arc<tracker::Tracker>) {
+    crate::http::server::serve_tls(
+        socket_addr,
+        certificates,
+        routes(tracker),
+        crate::http::connections::Connections::default(),
+    )
+    .await;
+}
+
+/// Serves the API on the Unix domain socket at `path`.
+///
+/// # Panics
+///
+/// Will panic if the `path` cannot be bound.
+#[cfg(unix)]
+pub async fn start_unix(path: &std::path::Path, tracker: &Arc<tracker::Tracker>) {
+    crate::http::unix::serve(path, routes(tracker), crate::http::connections::Connections::default()).await;
+}
+
+fn routes(tracker: &Arc<tracker::Tracker>) -> Router {
+    let access = Access {
+        tracker: tracker.clone(),
+        guard: rate_limit::Guard::new(&tracker.config().http_api.rate_limit),
+    };
+
+    let api_routes = Router::new()
+        // GET /api/v1/torrents?offset=:u32&limit=:u32&sort=:field&order=asc|desc&min_seeders=:u32&info_hash_prefix=:hex
+        // View torrent list, sorted by info_hash, seeders, leechers or completed
+        .route("/torrents", get(view_torrent_list))
+        // GET /api/v1/peers?ip=:ip
+        // View the peers of the IP address, with the swarms they are in
+        .route("/peers", get(view_peers_by_ip))
+        // GET /api/v1/events?types=:type,:type
+        // Stream the live events of the tracker with Server-Sent Events
+        .route(
+            "/events",
+            get(stream_events).with_state(Arc::new(EventStream::new(tracker.clone()))),
+        )
+        // GET /api/v1/stats
+        // View tracker status
+        .route("/stats", get(view_stats_list))
+        // GET /api/v1/torrent/:info_hash
+        // View torrent info, with all its peers
+        // DELETE /api/v1/torrent/:info_hash?persistent_stat=:bool&whitelist=:bool
+        // Remove the torrent with its peers, and its saved completed stat or its whitelist entry
+        .route("/torrent/:info_hash", get(view_torrent_info).delete(remove_torrent))
+        // DELETE /api/v1/torrent/:info_hash/peer/:peer_id
+        // Remove the peer from the torrent swarm, the peer id in hex
+        .route("/torrent/:info_hash/peer/:peer_id", delete(remove_torrent_peer))
+        // GET /api/v1/torrent/:info_hash/health
+        // View the torrent swarm health, scored from 0 to 100
+        .route("/torrent/:info_hash/health", get(view_torrent_health))
+        // GET /api/v1/torrent/:info_hash/completed?since=:u64&interval=:u64
+        // View how many times the torrent was completed per interval, it requires `record_completed_events`
+        .route("/torrent/:info_hash/completed", get(view_torrent_completed_history))
+        // GET /api/v1/whitelist/:info_hash
+        // Check whether the info hash is whitelisted
+        // POST /api/v1/whitelist/:info_hash
+        // Add info hash to whitelist
+        // DELETE /api/v1/whitelist/:info_hash
+        // Delete info hash from whitelist
+        .route(
+            "/whitelist/:info_hash",
+            get(view_whitelisted_torrent).post(add_torrent).delete(delete_torrent),
+        )
+        // GET /api/v1/whitelist?offset=:u32&limit=:u32
+        // View the whitelisted info hashes, sorted
+        // POST /api/v1/whitelist
+        // Add a list of info hashes to whitelist
+        // DELETE /api/v1/whitelist
+        // Delete a list of info hashes from whitelist
+        .route(
+            "/whitelist",
+            get(view_whitelist)
+                .post(add_torrents)
+                .delete(delete_torrents)
+                .layer(DefaultBodyLimit::max(MAX_WHITELIST_BODY_SIZE)),
+        )
+        // GET /api/v1/whitelist/reload
+        // Reload whitelist
+        .route("/whitelist/reload", get(reload_whitelist))
+        // POST /api/v1/key
+        // Generate new key, with a JSON body like {"label": "alice", "seconds_valid": 3600} or {"never_expires": true}
+        .route("/key", post(generate_key))
+        // POST /api/v1/key/:seconds_valid?label=:string
+        // Generate new key
+        // PATCH /api/v1/key/:key
+        // Extend the key expiry, with a JSON body like {"seconds_valid": 3600} or {"never_expires": true}
+        // DELETE /api/v1/key/:key
+        // Delete key
+        .route("/key/:key", post(create_key).patch(update_key).delete(delete_key))
+        // GET /api/v1/keys
+        // View the keys with their usage
+        .route("/keys", get(view_key_list))
+        // POST /api/v1/keys/batch?format=json|csv
+        // Generate many keys, with a JSON body like {"count": 100, "label_prefix": "user-", "seconds_valid": 3600}
+        .route("/keys/batch", post(generate_keys))
+        // GET /api/v1/keys/reload
+        // Reload keys
+        .route("/keys/reload", get(reload_keys))
+        // POST /api/v1/blacklist/:cidr
+        // Add an IP address or range to the blacklist
+        // DELETE /api/v1/blacklist/:cidr
+        // Delete an IP address or range from the blacklist
+        .route("/blacklist/:cidr", post(add_blacklist_range).delete(delete_blacklist_range))
+        // POST /api/v1/ban/ip/:ip
+        // Blacklist the IP address and remove its peers from all the swarms
+        .route("/ban/ip/:ip", post(ban_ip))
+        // POST /api/v1/reload
+        // Reload the whitelist, the blacklist and the keys from the database, and the log level and the client filter
+        // from the configuration file
+        .route("/reload", post(reload))
+        // POST /api/v1/maintenance/flush
+        // Save the buffered stats and the peers, and remove the inactive peers and the peerless torrents, right away
+        .route("/maintenance/flush", post(flush))
+        // GET /api/v1/log-level
+        // View the log level
+        // PUT /api/v1/log-level
+        // Change the log level, with a JSON body like {"level": "debug"}
+        .route("/log-level", get(view_log_level).put(update_log_level))
+        // POST /api/v1/maintenance
+        // Start rejecting the announces
+        // DELETE /api/v1/maintenance
+        // Accept the announces again
+        .route("/maintenance", post(start_maintenance).delete(stop_maintenance))
+        // GET /api/v1/client_filter
+        // View the client filter
+        .route("/client_filter", get(view_client_filter))
+        // POST /api/v1/client_filter/peer_id/:prefix
+        // Add a peer id prefix to the client filter
+        // DELETE /api/v1/client_filter/peer_id/:prefix
+        // Delete a peer id prefix from the client filter
+        .route(
+            "/client_filter/peer_id/:prefix",
+            post(add_client_filter_peer_id_prefix).delete(delete_client_filter_peer_id_prefix),
+        )
+        // POST /api/v1/client_filter/user_agent/:pattern
+        // Add a User-Agent pattern to the client filter
+        // DELETE /api/v1/client_filter/user_agent/:pattern
+        // Delete a User-Agent pattern from the client filter
+        .route(
+            "/client_filter/user_agent/:pattern",
+            post(add_client_filter_user_agent).delete(delete_client_filter_user_agent),
+        )
+        // GET /api/v1/audit-log?token_name=:string&ip=:ip&since=:u64&until=:u64&offset=:u32&limit=:u32
+        // View the changes made with the API, from the newest
+        .route("/audit-log", get(view_audit_log))
+        .route_layer(middleware::from_fn_with_state(tracker.clone(), reject_changes_when_read_only))
+        .route_layer(middleware::from_fn_with_state(tracker.clone(), record_audit_log))
+        .route_layer(middleware::from_fn_with_state(Arc::new(access), authenticate));
+
+    let api_routes = Router::new()
+        // GET /api/v1/health
+        // Check the tracker health, it does not require authentication so load balancers can use it
+        .route("/health", get(view_health))
+        .merge(api_routes);
+
+    let router = Router::new()
+        .nest("/api/v1", api_routes.clone())
+        // The unversioned routes are kept as aliases of the version 1 until the next release
+        .nest(
+            "/api",
+            api_routes.route_layer(middleware::from_fn(deprecate_unversioned_route)),
+        )
+        .fallback(route_not_found)
+        .route_layer(middleware::from_fn(log_request))
+        .with_state(tracker.clone());
+
+    // The preflight requests have no token, so they are answered before the authentication
+    if tracker.config().http_api.cors.enabled {
+        router.layer(
+            cors::layer(&tracker.config().http_api.cors).unwrap_or_else(|e| panic!("Invalid CORS configuration of the API: {e}")),
+        )
+    } else {
+        router
+    }
+}
+
+async fn view_torrent_list(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    QueryParams(torrent_info_query): QueryParams<TorrentInfoQuery>,
+) -> Json<Vec<ListItem>> {
+    let query = torrent_query::Query {
+        offset: torrent_info_query.offset.unwrap_or(0) as usize,
+        limit: min(torrent_info_query.limit.unwrap_or(1000), 4000) as usize,
+        sort: torrent_info_query.sort.unwrap_or_default(),
+        order: torrent_info_query.order,
+        min_seeders: torrent_info_query.min_seeders.unwrap_or(0),
+        info_hash_prefix: torrent_info_query.info_hash_prefix,
+    };
+
+    let results: Vec<_> = tracker
+        .get_torrents_page(&query)
+        .await
+        .into_iter()
+        .map(|(info_hash, (seeders, completed, leechers))| ListItem {
+            info_hash: info_hash.to_string(),
+            seeders,
+            completed,
+            leechers,
+            peers: None,
         })
-        .and_then(|(info_hash, tracker): (InfoHash, Arc<tracker::Tracker>)| async move {
-            match tracker.remove_torrent_from_whitelist(&info_hash).await {
-                Ok(_) => Ok(warp::reply::json(&ActionStatus::Ok)),
-                Err(_) => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "failed to remove torrent from whitelist".into(),
-                })),
-            }
-        });
+        .collect();
+
+    Json(results)
+}
+
+async fn view_stats_list(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    QueryParams(stats_query): QueryParams<StatsQuery>,
+) -> Json<Stats> {
+    // A single read guard of the metrics, released before awaiting the other stats, as a second one could wait
+    // forever behind a writer waiting for the first one
+    let (http, udp) = {
+        let metrics = tracker.get_stats().await;
+        (stats::Http::from(&*metrics), stats::Udp::from(&*metrics))
+    };
+    let listeners = tracker
+        .get_listener_stats()
+        .iter()
+        .map(|(listener, metrics)| (listener.clone(), stats::Listener::from(metrics)))
+        .collect();
+
+    let mut results = Stats {
+        torrents: 0,
+        seeders: 0,
+        completed: 0,
+        leechers: 0,
+        tcp4_connections_handled: 0,
+        tcp4_announces_handled: 0,
+        tcp4_scrapes_handled: 0,
+        tcp6_connections_handled: 0,
+        tcp6_announces_handled: 0,
+        tcp6_scrapes_handled: 0,
+        udp4_connections_handled: 0,
+        udp4_announces_handled: 0,
+        udp4_scrapes_handled: 0,
+        udp6_connections_handled: 0,
+        udp6_announces_handled: 0,
+        udp6_scrapes_handled: 0,
+        torrents_evicted: 0,
+        peers_evicted: 0,
+        udp_packets_dropped: 0,
+        http,
+        udp,
+        windows: stats::Windows {
+            last_minute: tracker.get_window_stats(statistics::LAST_MINUTE_SECS).await.into(),
+            last_five_minutes: tracker.get_window_stats(statistics::LAST_FIVE_MINUTES_SECS).await.into(),
+            last_hour: tracker.get_window_stats(statistics::LAST_HOUR_SECS).await.into(),
+        },
+        listeners,
+        countries: tracker
+            .get_countries()
+            .await
+            .into_iter()
+            .map(|(country, peers)| (country.to_string(), peers))
+            .collect(),
+        database: tracker.get_database_metrics().map(stats::Database::from),
+        history: None,
+    };
+
+    if stats_query.history.unwrap_or_default() {
+        results.history = Some(
+            tracker
+                .get_stats_history()
+                .await
+                .into_iter()
+                .map(stats::HistoryItem::from)
+                .collect(),
+        );
+    }
+
+    let torrents_stats = tracker.get_torrents_stats().await;
+
+    torrents_stats.iter().for_each(|(_, (seeders, completed, leechers))| {
+        results.seeders += seeders;
+        results.completed += completed;
+        results.leechers += leechers;
+        results.torrents += 1;
+    });
+
+    let stats = tracker.get_stats().await;
 
-    // POST /api/whitelist/:info_hash
-    // Add info hash to whitelist
-    let t4 = tracker.clone();
-    let add_torrent = filters::method::post()
-        .and(filters::path::path("whitelist"))
-        .and(filters::path::param())
-        .and(filters::path::end())
-        .map(move |info_hash: InfoHash| {
-            let tracker = t4.clone();
-            (info_hash, tracker)
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        results.tcp4_connections_handled = stats.tcp4_connections_handled as u32;
+        results.tcp4_announces_handled = stats.tcp4_announces_handled as u32;
+        results.tcp4_scrapes_handled = stats.tcp4_scrapes_handled as u32;
+        results.tcp6_connections_handled = stats.tcp6_connections_handled as u32;
+        results.tcp6_announces_handled = stats.tcp6_announces_handled as u32;
+        results.tcp6_scrapes_handled = stats.tcp6_scrapes_handled as u32;
+        results.udp4_connections_handled = stats.udp4_connections_handled as u32;
+        results.udp4_announces_handled = stats.udp4_announces_handled as u32;
+        results.udp4_scrapes_handled = stats.udp4_scrapes_handled as u32;
+        results.udp6_connections_handled = stats.udp6_connections_handled as u32;
+        results.udp6_announces_handled = stats.udp6_announces_handled as u32;
+        results.udp6_scrapes_handled = stats.udp6_scrapes_handled as u32;
+        results.torrents_evicted = stats.torrents_evicted as u32;
+        results.peers_evicted = stats.peers_evicted as u32;
+        results.udp_packets_dropped = stats.udp_packets_dropped as u32;
+    }
+
+    Json(results)
+}
+
+async fn view_peers_by_ip(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    QueryParams(peers_query): QueryParams<PeersQuery>,
+) -> Json<Vec<peer::SwarmPeer>> {
+    let peers = tracker
+        .get_peers_by_ip(&peers_query.ip)
+        .await
+        .into_iter()
+        .map(|(info_hash, peer)| peer::SwarmPeer {
+            info_hash: info_hash.to_string(),
+            peer: peer::Peer::from(peer),
         })
-        .and_then(|(info_hash, tracker): (InfoHash, Arc<tracker::Tracker>)| async move {
-            match tracker.add_torrent_to_whitelist(&info_hash).await {
-                Ok(..) => Ok(warp::reply::json(&ActionStatus::Ok)),
-                Err(..) => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "failed to whitelist torrent".into(),
-                })),
-            }
-        });
+        .collect();
+
+    Json(peers)
+}
+
+async fn view_torrent_info(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    PathParam(info_hash): PathParam<InfoHash>,
+) -> Result<Json<Torrent>, Error> {
+    let Some((seeders, completed, leechers)) = tracker.get_torrent_stats(&info_hash).await else {
+        return Err(Error::NotFound { resource: "torrent" });
+    };
+
+    let partial_seeds = tracker.get_torrent_partial_seeds(&info_hash).await.unwrap_or_default();
+
+    let countries = tracker
+        .get_torrent_countries(&info_hash)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(country, peers)| (country.to_string(), peers))
+        .collect();
+
+    let peers = tracker.get_all_torrent_peers(&info_hash).await;
+
+    let peer_resources = peers.into_iter().map(peer::Peer::from).collect();
+
+    Ok(Json(Torrent {
+        info_hash: info_hash.to_string(),
+        seeders,
+        completed,
+        leechers,
+        partial_seeds,
+        countries,
+        peers: Some(peer_resources),
+    }))
+}
+
+async fn remove_torrent(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    PathParam(info_hash): PathParam<InfoHash>,
+    QueryParams(remove_query): QueryParams<RemoveTorrentQuery>,
+) -> ActionResult {
+    tracker
+        .remove_torrent(&info_hash, remove_query.persistent_stat.unwrap_or(false))
+        .await
+        .map_err(|e| Error::database("remove torrent", "torrent", e))?;
+
+    if remove_query.whitelist.unwrap_or(false) && tracker.is_info_hash_whitelisted(&info_hash).await {
+        tracker
+            .remove_torrent_from_whitelist(&info_hash)
+            .await
+            .map_err(|e| Error::database("remove torrent from whitelist", "torrent", e))?;
+    }
+
+    Ok(Json(ActionStatus::Ok))
+}
+
+async fn remove_torrent_peer(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    Path((info_hash, peer_id)): Path<(String, String)>,
+) -> ActionResult {
+    let info_hash: InfoHash = parse_param("info_hash", info_hash)?;
+    let peer_id: tracker::peer::Id = parse_param("peer_id", peer_id)?;
+
+    tracker.remove_torrent_peer(&info_hash, &peer_id).await;
+
+    Ok(Json(ActionStatus::Ok))
+}
+
+async fn view_torrent_health(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    PathParam(info_hash): PathParam<InfoHash>,
+) -> Result<Json<SwarmHealth>, Error> {
+    let Some(health) = tracker.get_torrent_health(&info_hash).await else {
+        return Err(Error::NotFound { resource: "torrent" });
+    };
+
+    Ok(Json(SwarmHealth {
+        info_hash: info_hash.to_string(),
+        seeders: health.seeders,
+        leechers: health.leechers,
+        ratio: health.ratio,
+        churn: health.churn,
+        last_activity: health.last_activity,
+        score: health.score,
+    }))
+}
+
+async fn view_torrent_completed_history(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    PathParam(info_hash): PathParam<InfoHash>,
+    QueryParams(history_query): QueryParams<CompletedHistoryQuery>,
+) -> Result<Json<Vec<CompletedHistoryItem>>, Error> {
+    let since = Duration::from_secs(history_query.since.unwrap_or(0));
+    let interval = history_query.interval.unwrap_or(86_400).max(1);
+
+    let events = tracker
+        .get_completed_events(&info_hash, since)
+        .await
+        .map_err(|e| Error::database("load completed events", "torrent", e))?;
+
+    // The events are sorted, so the ones in the same interval are next to each other.
+    let mut results: Vec<CompletedHistoryItem> = vec![];
+    for completed_at in events {
+        let timestamp = completed_at.as_secs() - completed_at.as_secs() % interval;
+        match results.last_mut() {
+            Some(item) if item.timestamp == timestamp => item.completed += 1,
+            _ => results.push(CompletedHistoryItem { timestamp, completed: 1 }),
+        }
+    }
+
+    Ok(Json(results))
+}
+
+async fn view_whitelist(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    QueryParams(whitelist_query): QueryParams<WhitelistQuery>,
+) -> Json<Vec<String>> {
+    let query = torrent_query::Query {
+        offset: whitelist_query.offset.unwrap_or(0) as usize,
+        limit: min(whitelist_query.limit.unwrap_or(1000), 4000) as usize,
+        ..Default::default()
+    };
+
+    Json(
+        tracker
+            .get_whitelist_page(&query)
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    )
+}
+
+async fn view_whitelisted_torrent(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    PathParam(info_hash): PathParam<InfoHash>,
+) -> Result<Json<WhitelistEntry>, Error> {
+    match tracker.lookup_whitelisted_info_hash(&info_hash).await {
+        Ok(whitelisted) => Ok(Json(WhitelistEntry {
+            info_hash: info_hash.to_string(),
+            whitelisted,
+        })),
+        Err(e) => Err(Error::database("look up torrent in whitelist", "torrent", e)),
+    }
+}
+
+async fn delete_torrent(State(tracker): State<Arc<tracker::Tracker>>, PathParam(info_hash): PathParam<InfoHash>) -> ActionResult {
+    match tracker.remove_torrent_from_whitelist(&info_hash).await {
+        Ok(_) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("remove torrent from whitelist", "torrent", e)),
+    }
+}
+
+async fn add_torrent(State(tracker): State<Arc<tracker::Tracker>>, PathParam(info_hash): PathParam<InfoHash>) -> ActionResult {
+    match tracker.add_torrent_to_whitelist(&info_hash).await {
+        Ok(..) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("whitelist torrent", "torrent", e)),
+    }
+}
+
+async fn create_key(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    PathParam(seconds_valid): PathParam<u64>,
+    QueryParams(key_query): QueryParams<KeyQuery>,
+) -> Result<Json<AuthKey>, Error> {
+    match tracker
+        .generate_auth_key(Some(Duration::from_secs(seconds_valid)), key_query.label)
+        .await
+    {
+        Ok(auth_key) => Ok(Json(AuthKey::from(auth_key))),
+        Err(e) => Err(Error::database("generate key", "key", e)),
+    }
+}
+
+async fn generate_key(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    JsonBody(new_key): JsonBody<NewKey>,
+) -> Result<Json<AuthKey>, Error> {
+    match tracker.generate_auth_key(new_key.expiry.lifetime()?, new_key.label).await {
+        Ok(auth_key) => Ok(Json(AuthKey::from(auth_key))),
+        Err(e) => Err(Error::database("generate key", "key", e)),
+    }
+}
+
+async fn generate_keys(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    QueryParams(batch_query): QueryParams<KeyBatchQuery>,
+    JsonBody(batch): JsonBody<NewKeyBatch>,
+) -> Result<Response, Error> {
+    if batch.count == 0 || batch.count > MAX_KEYS_PER_BATCH {
+        return Err(Error::unprocessable(format!(
+            "count must be between 1 and {MAX_KEYS_PER_BATCH}"
+        )));
+    }
+
+    let lifetime = match (batch.expiry.seconds_valid, batch.expiry.never_expires) {
+        (None, false) => None,
+        _ => batch.expiry.lifetime()?,
+    };
+
+    let auth_keys: Vec<AuthKey> = match tracker
+        .generate_auth_keys(batch.count, lifetime, batch.label_prefix.as_deref())
+        .await
+    {
+        Ok(auth_keys) => auth_keys.into_iter().map(AuthKey::from).collect(),
+        Err(e) => return Err(Error::database("generate keys", "key", e)),
+    };
+
+    Ok(match batch_query.format {
+        KeyBatchFormat::Json => Json(auth_keys).into_response(),
+        KeyBatchFormat::Csv => (
+            [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+            auth_key::to_csv(&auth_keys),
+        )
+            .into_response(),
+    })
+}
+
+async fn update_key(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    Path(key): Path<String>,
+    JsonBody(expiry): JsonBody<KeyExpiry>,
+) -> Result<Json<AuthKey>, Error> {
+    let valid_until = match expiry.lifetime()? {
+        Some(lifetime) => Some(Current::add(&lifetime).ok_or_else(|| Error::unprocessable("seconds_valid is too large"))?),
+        None => None,
+    };
+
+    match tracker.update_auth_key_valid_until(&key, valid_until).await {
+        Ok(auth_key) => Ok(Json(AuthKey::from(auth_key))),
+        Err(e) => Err(Error::database("update key", "key", e)),
+    }
+}
+
+async fn delete_key(State(tracker): State<Arc<tracker::Tracker>>, Path(key): Path<String>) -> ActionResult {
+    match tracker.remove_auth_key(&key).await {
+        Ok(_) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("delete key", "key", e)),
+    }
+}
+
+async fn reload_whitelist(State(tracker): State<Arc<tracker::Tracker>>) -> ActionResult {
+    match tracker.load_whitelist().await {
+        Ok(_) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("reload whitelist", "torrent", e)),
+    }
+}
+
+async fn reload_keys(State(tracker): State<Arc<tracker::Tracker>>) -> ActionResult {
+    match tracker.load_keys().await {
+        Ok(_) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("reload keys", "key", e)),
+    }
+}
+
+async fn add_torrents(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    JsonBody(info_hashes): JsonBody<Vec<InfoHash>>,
+) -> ActionResult {
+    match tracker.add_torrents_to_whitelist(&info_hashes).await {
+        Ok(..) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("whitelist torrents", "torrent", e)),
+    }
+}
+
+async fn delete_torrents(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    JsonBody(info_hashes): JsonBody<Vec<InfoHash>>,
+) -> ActionResult {
+    match tracker.remove_torrents_from_whitelist(&info_hashes).await {
+        Ok(..) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("remove torrents from whitelist", "torrent", e)),
+    }
+}
+
+async fn add_blacklist_range(State(tracker): State<Arc<tracker::Tracker>>, Path(range): Path<String>) -> ActionResult {
+    let range = parse_blacklist_range(&range)?;
+
+    match tracker.add_range_to_blacklist(&range).await {
+        Ok(..) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("blacklist range", "ip range", e)),
+    }
+}
+
+async fn ban_ip(State(tracker): State<Arc<tracker::Tracker>>, PathParam(ip): PathParam<IpAddr>) -> ActionResult {
+    match tracker.ban_ip(&ip).await {
+        Ok(..) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("ban ip", "ip", e)),
+    }
+}
+
+async fn delete_blacklist_range(State(tracker): State<Arc<tracker::Tracker>>, Path(range): Path<String>) -> ActionResult {
+    let range = parse_blacklist_range(&range)?;
+
+    match tracker.remove_range_from_blacklist(&range).await {
+        Ok(..) => Ok(Json(ActionStatus::Ok)),
+        Err(e) => Err(Error::database("remove range from blacklist", "ip range", e)),
+    }
+}
 
-    // POST /api/key/:seconds_valid
-    // Generate new key
-    let t5 = tracker.clone();
-    let create_key = filters::method::post()
-        .and(filters::path::path("key"))
-        .and(filters::path::param())
-        .and(filters::path::end())
-        .map(move |seconds_valid: u64| {
-            let tracker = t5.clone();
-            (seconds_valid, tracker)
+async fn reload(State(tracker): State<Arc<tracker::Tracker>>) -> ActionResult {
+    tracker.reload().await?;
+    Ok(Json(ActionStatus::Ok))
+}
+
+async fn flush(State(tracker): State<Arc<tracker::Tracker>>) -> Result<Json<maintenance::Flush>, Error> {
+    match tracker.flush_and_cleanup().await {
+        Ok(report) => Ok(Json(maintenance::Flush::from(report))),
+        Err(e) => Err(Error::database("flush", "torrent", e)),
+    }
+}
+
+async fn view_log_level() -> Json<LogLevel> {
+    Json(LogLevel {
+        level: logging::level().to_string().to_lowercase(),
+    })
+}
+
+async fn update_log_level(JsonBody(log_level): JsonBody<LogLevel>) -> Result<Json<LogLevel>, Error> {
+    logging::set_level(parse_param("level", log_level.level)?);
+    Ok(view_log_level().await)
+}
+
+async fn start_maintenance(State(tracker): State<Arc<tracker::Tracker>>) -> Json<ActionStatus<'static>> {
+    tracker.set_maintenance(true);
+    Json(ActionStatus::Ok)
+}
+
+async fn stop_maintenance(State(tracker): State<Arc<tracker::Tracker>>) -> Json<ActionStatus<'static>> {
+    tracker.set_maintenance(false);
+    Json(ActionStatus::Ok)
+}
+
+async fn view_client_filter(State(tracker): State<Arc<tracker::Tracker>>) -> Json<ClientFilter> {
+    Json(ClientFilter::from(&*tracker.get_client_filter().await))
+}
+
+async fn add_client_filter_peer_id_prefix(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    Path(prefix): Path<String>,
+) -> Json<ActionStatus<'static>> {
+    // Already in the filter is not an error, like the blacklisted ranges
+    let _ = tracker.add_peer_id_prefix_to_client_filter(prefix).await;
+    Json(ActionStatus::Ok)
+}
+
+async fn delete_client_filter_peer_id_prefix(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    Path(prefix): Path<String>,
+) -> ActionResult {
+    if tracker.remove_peer_id_prefix_from_client_filter(&prefix).await {
+        Ok(Json(ActionStatus::Ok))
+    } else {
+        Err(Error::NotFound {
+            resource: "peer id prefix",
         })
-        .and_then(|(seconds_valid, tracker): (u64, Arc<tracker::Tracker>)| async move {
-            match tracker.generate_auth_key(Duration::from_secs(seconds_valid)).await {
-                Ok(auth_key) => Ok(warp::reply::json(&AuthKey::from(auth_key))),
-                Err(..) => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "failed to generate key".into(),
-                })),
-            }
-        });
+    }
+}
+
+async fn add_client_filter_user_agent(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    Path(pattern): Path<String>,
+) -> Json<ActionStatus<'static>> {
+    // Already in the filter is not an error, like the blacklisted ranges
+    let _ = tracker.add_user_agent_to_client_filter(pattern).await;
+    Json(ActionStatus::Ok)
+}
 
-    // DELETE /api/key/:key
-    // Delete key
-    let t6 = tracker.clone();
-    let delete_key = filters::method::delete()
-        .and(filters::path::path("key"))
-        .and(filters::path::param())
-        .and(filters::path::end())
-        .map(move |key: String| {
-            let tracker = t6.clone();
-            (key, tracker)
+async fn delete_client_filter_user_agent(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    Path(pattern): Path<String>,
+) -> ActionResult {
+    if tracker.remove_user_agent_from_client_filter(&pattern).await {
+        Ok(Json(ActionStatus::Ok))
+    } else {
+        Err(Error::NotFound {
+            resource: "user agent pattern",
         })
-        .and_then(|(key, tracker): (String, Arc<tracker::Tracker>)| async move {
-            match tracker.remove_auth_key(&key).await {
-                Ok(_) => Ok(warp::reply::json(&ActionStatus::Ok)),
-                Err(_) => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "failed to delete key".into(),
-                })),
-            }
-        });
+    }
+}
 
-    // GET /api/whitelist/reload
-    // Reload whitelist
-    let t7 = tracker.clone();
-    let reload_whitelist = filters::method::get()
-        .and(filters::path::path("whitelist"))
-        .and(filters::path::path("reload"))
-        .and(filters::path::end())
-        .map(move || t7.clone())
-        .and_then(|tracker: Arc<tracker::Tracker>| async move {
-            match tracker.load_whitelist().await {
-                Ok(_) => Ok(warp::reply::json(&ActionStatus::Ok)),
-                Err(_) => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "failed to reload whitelist".into(),
-                })),
-            }
-        });
+async fn view_audit_log(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    QueryParams(audit_log_query): QueryParams<AuditLogQuery>,
+) -> Result<Json<Vec<AuditLogEntry>>, Error> {
+    let query = audit::Query {
+        token_name: audit_log_query.token_name,
+        ip: audit_log_query.ip,
+        since: audit_log_query.since.map(Duration::from_secs),
+        until: audit_log_query.until.map(Duration::from_secs),
+        offset: audit_log_query.offset.unwrap_or(0) as usize,
+        limit: min(audit_log_query.limit.unwrap_or(1000), 4000) as usize,
+    };
 
-    // GET /api/keys/reload
-    // Reload whitelist
-    let t8 = tracker.clone();
-    let reload_keys = filters::method::get()
-        .and(filters::path::path("keys"))
-        .and(filters::path::path("reload"))
-        .and(filters::path::end())
-        .map(move || t8.clone())
-        .and_then(|tracker: Arc<tracker::Tracker>| async move {
-            match tracker.load_keys().await {
-                Ok(_) => Ok(warp::reply::json(&ActionStatus::Ok)),
-                Err(_) => Err(warp::reject::custom(ActionStatus::Err {
-                    reason: "failed to reload keys".into(),
-                })),
-            }
-        });
+    match tracker.get_audit_log(&query).await {
+        Ok(entries) => Ok(Json(entries.into_iter().map(AuditLogEntry::from).collect())),
+        Err(e) => Err(Error::database("load audit log", "audit log", e)),
+    }
+}
 
-    let api_routes = filters::path::path("api").and(
-        view_torrent_list
-            .or(delete_torrent)
-            .or(view_torrent_info)
-            .or(view_stats_list)
-            .or(add_torrent)
-            .or(create_key)
-            .or(delete_key)
-            .or(reload_whitelist)
-            .or(reload_keys),
-    );
-
-    let server = api_routes.and(authenticate(tracker.config.http_api.access_tokens.clone()));
-
-    let (_addr, api_server) = serve(server).bind_with_graceful_shutdown(socket_addr, async move {
-        tokio::signal::ctrl_c().await.expect("Failed to listen to shutdown signal.");
-    });
+async fn view_key_list(State(tracker): State<Arc<tracker::Tracker>>) -> Json<Vec<AuthKey>> {
+    Json(tracker.get_auth_keys().await.into_iter().map(AuthKey::from).collect())
+}
+
+async fn view_health(State(tracker): State<Arc<tracker::Tracker>>) -> (StatusCode, Json<Health>) {
+    let pool_state = tracker.get_database_pool_state();
+
+    // Pinging waits for a free connection, so it is skipped while the pool is exhausted.
+    let (status, error) = match pool_state {
+        Some(pool_state) if pool_state.is_exhausted() => {
+            (health::Status::Degraded, Some("connection pool exhausted".to_string()))
+        }
+        _ => match tracker.ping_database().await {
+            Ok(()) => (health::Status::Ok, None),
+            Err(e) => (health::Status::Unavailable, Some(e.to_string())),
+        },
+    };
+
+    let health = Health {
+        status,
+        components: health::Components {
+            database: health::Database {
+                status,
+                driver: format!("{:?}", tracker.config().db_driver),
+                error,
+                pool: pool_state.map(health::Pool::from),
+            },
+        },
+    };
+
+    let status_code = match health.status {
+        health::Status::Ok => StatusCode::OK,
+        health::Status::Degraded | health::Status::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    };
 
-    api_server
+    (status_code, Json(health))
 }
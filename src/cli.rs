@@ -0,0 +1,211 @@
+//! The command line of the tracker binary: its subcommands, and the settings of the configuration file which can be
+//! overridden when running it, so a quick change does not require editing the file.
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::template::Profile;
+use crate::config::{Format, Source};
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// The configuration file
+    #[arg(long, short, global = true, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: String,
+
+    /// `toml`, `yaml` or `json`, the one of the extension of the configuration file when not set
+    #[arg(long, global = true)]
+    pub config_format: Option<Format>,
+
+    /// Runs the tracker when not set
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Runs the tracker
+    Run(RunArgs),
+    /// Checks the configuration, with the same arguments as `run`, and connects to its database
+    ValidateConfig(RunArgs),
+    /// Writes the default configuration into the configuration file, with a comment above every setting in TOML
+    GenerateConfig {
+        /// `public-udp` or `private-http`, which pre-fill the mode, the intervals, the database and the trackers
+        #[arg(long)]
+        profile: Option<Profile>,
+        /// Overwrites the configuration file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Manages the database of the configuration
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum DbCommand {
+    /// Applies the pending migrations
+    Migrate,
+}
+
+/// The settings of the configuration file overridden for this run.
+#[derive(Args, Debug, Default, PartialEq, Eq)]
+pub struct RunArgs {
+    /// Enables the first UDP tracker of the configuration on this address, repeated for several ones
+    #[arg(long, value_name = "ADDRESS")]
+    pub udp_bind: Vec<String>,
+    /// Enables the first HTTP tracker of the configuration on this address
+    #[arg(long, value_name = "ADDRESS")]
+    pub http_bind: Option<String>,
+    /// Enables the API on this address
+    #[arg(long, value_name = "ADDRESS")]
+    pub api_bind: Option<String>,
+    #[arg(long, value_parser = ["public", "listed", "private", "private_listed"])]
+    pub mode: Option<String>,
+    /// Like `Sqlite3`, `MySQL`, `postgres`, `redis` or `memory`
+    #[arg(long)]
+    pub db_driver: Option<String>,
+    #[arg(long)]
+    pub db_path: Option<String>,
+    #[arg(long)]
+    pub log_level: Option<String>,
+}
+
+impl Cli {
+    /// The configuration file of the arguments, with the `overrides` of its settings.
+    #[must_use]
+    pub fn config_source(&self, overrides: Vec<(String, String)>) -> Source {
+        Source {
+            path: self.config.clone(),
+            format: self.config_format.unwrap_or_else(|| Format::from_path(&self.config)),
+            overrides,
+        }
+    }
+}
+
+impl RunArgs {
+    /// The settings overridden by the arguments, as their path in the configuration and their value, see
+    /// [`Source::load`].
+    #[must_use]
+    pub fn overrides(&self) -> Vec<(String, String)> {
+        let mut overrides = vec![];
+
+        if !self.udp_bind.is_empty() {
+            let addresses: Vec<String> = self.udp_bind.iter().map(|address| format!("{address:?}")).collect();
+            overrides.push(("udp_trackers[0].enabled".to_string(), "true".to_string()));
+            overrides.push((
+                "udp_trackers[0].bind_address".to_string(),
+                format!("[{}]", addresses.join(", ")),
+            ));
+        }
+
+        if let Some(http_bind) = &self.http_bind {
+            overrides.push(("http_trackers[0].enabled".to_string(), "true".to_string()));
+            overrides.push(("http_trackers[0].bind_address".to_string(), http_bind.clone()));
+        }
+
+        if let Some(api_bind) = &self.api_bind {
+            overrides.push(("http_api.enabled".to_string(), "true".to_string()));
+            overrides.push(("http_api.bind_address".to_string(), api_bind.clone()));
+        }
+
+        for (key, value) in [
+            ("mode", &self.mode),
+            ("db_driver", &self.db_driver),
+            ("db_path", &self.db_path),
+            ("log_level", &self.log_level),
+        ] {
+            if let Some(value) = value {
+                overrides.push((key.to_string(), value.clone()));
+            }
+        }
+
+        overrides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{Cli, Command, DbCommand, RunArgs, DEFAULT_CONFIG_PATH};
+    use crate::config::template::Profile;
+    use crate::config::Format;
+
+    #[test]
+    fn the_tracker_should_run_with_the_default_configuration_file_without_arguments() {
+        let cli = Cli::try_parse_from(["torrust-tracker"]).unwrap();
+
+        assert_eq!(cli.config, DEFAULT_CONFIG_PATH);
+        assert_eq!(cli.command, None);
+    }
+
+    #[test]
+    fn the_run_arguments_should_override_the_settings_of_the_configuration_file() {
+        let cli = Cli::try_parse_from([
+            "torrust-tracker",
+            "run",
+            "--config",
+            "/etc/torrust/tracker.toml",
+            "--udp-bind",
+            "0.0.0.0:6969",
+            "--udp-bind",
+            "[::]:6969",
+            "--mode",
+            "private",
+        ])
+        .unwrap();
+
+        let Some(Command::Run(args)) = cli.command else {
+            panic!("expected the run subcommand");
+        };
+
+        assert_eq!(cli.config, "/etc/torrust/tracker.toml");
+        assert_eq!(
+            args.overrides(),
+            [
+                ("udp_trackers[0].enabled", "true"),
+                ("udp_trackers[0].bind_address", r#"["0.0.0.0:6969", "[::]:6969"]"#),
+                ("mode", "private"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn the_subcommands_should_be_parsed() {
+        let command = |args: &[&str]| Cli::try_parse_from(args).unwrap().command;
+
+        assert_eq!(
+            command(&["torrust-tracker", "validate-config"]),
+            Some(Command::ValidateConfig(RunArgs::default()))
+        );
+        assert_eq!(
+            command(&["torrust-tracker", "generate-config", "--profile", "private-http", "--force"]),
+            Some(Command::GenerateConfig {
+                profile: Some(Profile::PrivateHttp),
+                force: true
+            })
+        );
+        assert_eq!(
+            command(&["torrust-tracker", "db", "migrate"]),
+            Some(Command::Db {
+                command: DbCommand::Migrate
+            })
+        );
+        assert!(Cli::try_parse_from(["torrust-tracker", "run", "--mode", "closed"]).is_err());
+    }
+
+    #[test]
+    fn the_format_of_the_configuration_file_could_be_set_for_any_extension() {
+        let cli = Cli::try_parse_from(["torrust-tracker", "--config", "tracker.conf", "--config-format", "yaml"]).unwrap();
+        assert_eq!(cli.config_source(vec![]).format, Format::Yaml);
+
+        let cli = Cli::try_parse_from(["torrust-tracker", "--config", "tracker.json"]).unwrap();
+        assert_eq!(cli.config_source(vec![]).format, Format::Json);
+    }
+}
@@ -0,0 +1,136 @@
+//! The durations of the configuration, kept in seconds, which can also be written like `"30m"`, `"2h"` or `"7d"` so
+//! the long ones are readable. The integers of the existing files are still seconds.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Visitor};
+use serde::Deserializer;
+
+/// Deserializes the seconds of an integer, or of a duration like `"1h 30m"`.
+///
+/// # Errors
+///
+/// Will return `Err` if the value is not a whole number of seconds, or too many of them for `T`.
+pub fn seconds<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    deserializer.deserialize_any(SecondsVisitor(PhantomData))
+}
+
+/// Deserializes the seconds of an optional setting, see [`seconds`].
+///
+/// # Errors
+///
+/// Will return `Err` if the value is not a whole number of seconds, or too many of them for `T`.
+pub fn optional_seconds<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    deserializer.deserialize_option(OptionalSecondsVisitor(PhantomData))
+}
+
+struct SecondsVisitor<T>(PhantomData<T>);
+
+impl<'de, T: TryFrom<u64>> Visitor<'de> for SecondsVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("seconds, or a duration like \"30m\", \"2h\" or \"7d\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        T::try_from(v).map_err(|_| E::custom(format!("{v} seconds is too long")))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        let v = u64::try_from(v).map_err(|_| E::custom(format!("{v} seconds is negative")))?;
+        self.visit_u64(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        // The environment variables are strings, even the integers
+        if let Ok(seconds) = v.trim().parse::<u64>() {
+            return self.visit_u64(seconds);
+        }
+
+        let duration = humantime::parse_duration(v).map_err(|e| E::custom(format!("invalid duration {v:?}, {e}")))?;
+        if duration.subsec_nanos() > 0 {
+            return Err(E::custom(format!(
+                "invalid duration {v:?}, it is not a whole number of seconds"
+            )));
+        }
+
+        self.visit_u64(duration.as_secs())
+    }
+}
+
+struct OptionalSecondsVisitor<T>(PhantomData<T>);
+
+impl<'de, T: TryFrom<u64>> Visitor<'de> for OptionalSecondsVisitor<T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("seconds, a duration like \"30m\", \"2h\" or \"7d\", or nothing")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+        seconds(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Settings {
+        #[serde(deserialize_with = "super::seconds")]
+        interval: u32,
+        #[serde(default, deserialize_with = "super::optional_seconds")]
+        timeout: Option<u64>,
+    }
+
+    fn settings(json: &str) -> Result<Settings, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn the_durations_should_be_read_as_seconds_or_as_human_durations() {
+        assert_eq!(
+            settings(r#"{"interval": 120}"#).unwrap(),
+            Settings {
+                interval: 120,
+                timeout: None
+            }
+        );
+        assert_eq!(
+            settings(r#"{"interval": "30m", "timeout": "7d"}"#).unwrap(),
+            Settings {
+                interval: 1800,
+                timeout: Some(604_800)
+            }
+        );
+        assert_eq!(settings(r#"{"interval": "1h 30m"}"#).unwrap().interval, 5400);
+        assert_eq!(settings(r#"{"interval": "600"}"#).unwrap().interval, 600);
+    }
+
+    #[test]
+    fn the_durations_should_be_whole_positive_numbers_of_seconds() {
+        assert!(settings(r#"{"interval": "500ms"}"#).is_err());
+        assert!(settings(r#"{"interval": -1}"#).is_err());
+        assert!(settings(r#"{"interval": "200y"}"#).is_err());
+        assert!(settings(r#"{"interval": "soon"}"#).is_err());
+    }
+}
@@ -1,7 +1,46 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub enum Driver {
     Sqlite3,
     MySQL,
+    #[serde(alias = "postgres")]
+    PostgreSQL,
+    #[serde(alias = "redis")]
+    Redis,
+    #[serde(alias = "memory", alias = "none")]
+    Memory,
+}
+
+impl FromStr for Driver {
+    type Err = String;
+
+    /// Accepts the same names as the configuration file, ignoring the case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sqlite3" => Ok(Driver::Sqlite3),
+            "mysql" => Ok(Driver::MySQL),
+            "postgresql" | "postgres" => Ok(Driver::PostgreSQL),
+            "redis" => Ok(Driver::Redis),
+            "memory" | "none" => Ok(Driver::Memory),
+            _ => Err(format!("unknown database driver: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Driver;
+
+    #[test]
+    fn it_should_be_parsed_from_the_configuration_names() {
+        assert_eq!(Driver::from_str("Sqlite3"), Ok(Driver::Sqlite3));
+        assert_eq!(Driver::from_str("MySQL"), Ok(Driver::MySQL));
+        assert_eq!(Driver::from_str("postgres"), Ok(Driver::PostgreSQL));
+        assert!(Driver::from_str("oracle").is_err());
+    }
 }
@@ -1,21 +1,174 @@
-use derive_more::{Display, Error};
+use std::error::Error as StdError;
 
-#[derive(Debug, Display, PartialEq, Eq, Error)]
-#[allow(dead_code)]
+use r2d2_mysql::mysql;
+use r2d2_postgres::postgres;
+use r2d2_sqlite::rusqlite;
+use thiserror::Error;
+
+type DynError = Box<dyn StdError + Send + Sync>;
+
+/// `SQLSTATE` class for integrity constraint violations, shared by `MySQL` and `PostgreSQL`.
+const INTEGRITY_CONSTRAINT_VIOLATION_CLASS: &str = "23";
+
+/// `SQLSTATE` class for connection exceptions, shared by `MySQL` and `PostgreSQL`.
+const CONNECTION_EXCEPTION_CLASS: &str = "08";
+
+#[derive(Error, Debug)]
 pub enum Error {
-    #[display(fmt = "Query returned no rows.")]
-    QueryReturnedNoRows,
-    #[display(fmt = "Invalid query.")]
-    InvalidQuery,
-    #[display(fmt = "Database error.")]
-    DatabaseError,
+    #[error("the record was not found")]
+    NotFound,
+
+    #[error("constraint violation: {source}")]
+    ConstraintViolation { source: DynError },
+
+    #[error("could not get a connection from the pool: {source}")]
+    ConnectionPool { source: r2d2::Error },
+
+    #[error("the connection to the database failed: {source}")]
+    Connection { source: DynError },
+
+    #[error("database driver error: {source}")]
+    Driver { source: DynError },
+}
+
+impl Error {
+    /// Whether the operation could succeed if it is retried, like when the connection to the
+    /// database was lost. Constraint violations and missing records are permanent.
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::ConnectionPool { .. } | Error::Connection { .. })
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Driver { source: Box::new(e) }
+    }
+}
+
+impl From<r2d2::Error> for Error {
+    fn from(e: r2d2::Error) -> Self {
+        Error::ConnectionPool { source: e }
+    }
 }
 
-impl From<r2d2_sqlite::rusqlite::Error> for Error {
-    fn from(e: r2d2_sqlite::rusqlite::Error) -> Self {
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
         match e {
-            r2d2_sqlite::rusqlite::Error::QueryReturnedNoRows => Error::QueryReturnedNoRows,
-            _ => Error::InvalidQuery,
+            rusqlite::Error::QueryReturnedNoRows => Error::NotFound,
+            rusqlite::Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation => {
+                Error::ConstraintViolation { source: Box::new(e) }
+            }
+            rusqlite::Error::SqliteFailure(failure, _)
+                if matches!(
+                    failure.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked | rusqlite::ErrorCode::CannotOpen
+                ) =>
+            {
+                Error::Connection { source: Box::new(e) }
+            }
+            _ => Error::Driver { source: Box::new(e) },
+        }
+    }
+}
+
+impl From<mysql::Error> for Error {
+    fn from(e: mysql::Error) -> Self {
+        match e {
+            mysql::Error::MySqlError(ref mysql_error) if mysql_error.state.starts_with(INTEGRITY_CONSTRAINT_VIOLATION_CLASS) => {
+                Error::ConstraintViolation { source: Box::new(e) }
+            }
+            mysql::Error::MySqlError(ref mysql_error) if mysql_error.state.starts_with(CONNECTION_EXCEPTION_CLASS) => {
+                Error::Connection { source: Box::new(e) }
+            }
+            mysql::Error::IoError(_) => Error::Connection { source: Box::new(e) },
+            _ => Error::Driver { source: Box::new(e) },
+        }
+    }
+}
+
+impl From<postgres::Error> for Error {
+    fn from(e: postgres::Error) -> Self {
+        match e.code() {
+            Some(state) if state.code().starts_with(INTEGRITY_CONSTRAINT_VIOLATION_CLASS) => {
+                Error::ConstraintViolation { source: Box::new(e) }
+            }
+            Some(state) if state.code().starts_with(CONNECTION_EXCEPTION_CLASS) => Error::Connection { source: Box::new(e) },
+            None if e.is_closed() => Error::Connection { source: Box::new(e) },
+            _ => Error::Driver { source: Box::new(e) },
         }
     }
 }
+
+impl From<redis::RedisError> for Error {
+    fn from(e: redis::RedisError) -> Self {
+        if e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() || e.is_timeout() {
+            Error::Connection { source: Box::new(e) }
+        } else {
+            Error::Driver { source: Box::new(e) }
+        }
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Error::Driver { source: Box::new(e) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::rusqlite::{self, Connection};
+
+    use super::Error;
+
+    #[test]
+    fn a_query_returning_no_rows_should_be_a_not_found_error() {
+        assert!(matches!(Error::from(rusqlite::Error::QueryReturnedNoRows), Error::NotFound));
+    }
+
+    #[test]
+    fn a_duplicated_unique_value_should_be_a_constraint_violation_error() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE whitelist (info_hash TEXT NOT NULL UNIQUE)", [])
+            .unwrap();
+        conn.execute("INSERT INTO whitelist (info_hash) VALUES ('a')", []).unwrap();
+
+        let error = conn
+            .execute("INSERT INTO whitelist (info_hash) VALUES ('a')", [])
+            .unwrap_err();
+
+        assert!(matches!(Error::from(error), Error::ConstraintViolation { .. }));
+    }
+
+    #[test]
+    fn any_other_driver_failure_should_keep_the_underlying_error_as_source() {
+        let conn = Connection::open_in_memory().unwrap();
+
+        let error = Error::from(conn.execute("SELECT * FROM missing_table", []).unwrap_err());
+
+        assert!(matches!(error, Error::Driver { .. }));
+        assert!(error.to_string().contains("no such table: missing_table"));
+    }
+
+    #[test]
+    fn a_locked_database_should_be_a_transient_error() {
+        let error = Error::from(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        ));
+
+        assert!(matches!(error, Error::Connection { .. }));
+        assert!(error.is_transient());
+    }
+
+    #[test]
+    fn a_constraint_violation_should_not_be_a_transient_error() {
+        let error = Error::ConstraintViolation {
+            source: "duplicated key".into(),
+        };
+
+        assert!(!error.is_transient());
+        assert!(!Error::NotFound.is_transient());
+    }
+}
@@ -0,0 +1,343 @@
+//! A `Database` kept in memory, for stateless deployments and tests.
+//!
+//! Nothing is persisted: all the data is lost when the tracker stops.
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+use tokio::sync::RwLock;
+
+use crate::databases::{Database, Error, PoolState};
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{audit, auth, peer};
+
+#[derive(Default)]
+pub struct Memory {
+    torrents: RwLock<HashMap<InfoHash, u32>>,
+    keys: RwLock<HashMap<String, auth::Key>>,
+    whitelist: RwLock<HashSet<InfoHash>>,
+    blacklist: RwLock<HashSet<IpNet>>,
+    peers: RwLock<Vec<(InfoHash, peer::Peer)>>,
+    completed_events: RwLock<HashMap<InfoHash, Vec<DurationSinceUnixEpoch>>>,
+    audit_log: RwLock<Vec<audit::Entry>>,
+}
+
+impl Memory {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Database for Memory {
+    fn create_database_tables(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn pool_state(&self) -> Option<PoolState> {
+        None
+    }
+
+    async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error> {
+        Ok(self
+            .torrents
+            .read()
+            .await
+            .iter()
+            .map(|(info_hash, completed)| (*info_hash, *completed))
+            .collect())
+    }
+
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error> {
+        Ok(self.torrents.read().await.get(info_hash).copied())
+    }
+
+    async fn load_keys(&self) -> Result<Vec<auth::Key>, Error> {
+        Ok(self.keys.read().await.values().cloned().collect())
+    }
+
+    async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error> {
+        Ok(self.whitelist.read().await.iter().copied().collect())
+    }
+
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error> {
+        Ok(self.blacklist.read().await.iter().copied().collect())
+    }
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error> {
+        Ok(self.peers.read().await.clone())
+    }
+
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error> {
+        *self.peers.write().await = peers.to_vec();
+        Ok(())
+    }
+
+    async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error> {
+        self.torrents.write().await.insert(*info_hash, completed);
+        Ok(())
+    }
+
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error> {
+        self.torrents.write().await.extend(torrents.iter().copied());
+        Ok(())
+    }
+
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error> {
+        Ok(usize::from(self.torrents.write().await.remove(info_hash).is_some()))
+    }
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        _peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error> {
+        self.completed_events
+            .write()
+            .await
+            .entry(*info_hash)
+            .or_default()
+            .push(completed_at);
+        Ok(())
+    }
+
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error> {
+        let mut events: Vec<DurationSinceUnixEpoch> = self
+            .completed_events
+            .read()
+            .await
+            .get(info_hash)
+            .map(|events| events.iter().copied().filter(|completed_at| *completed_at >= since).collect())
+            .unwrap_or_default();
+        events.sort();
+        Ok(events)
+    }
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error> {
+        self.audit_log.write().await.push(entry.clone());
+        Ok(())
+    }
+
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error> {
+        Ok(self
+            .audit_log
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|entry| query.matches(entry))
+            .skip(query.offset)
+            .take(query.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error> {
+        let info_hash = info_hash.parse::<InfoHash>().map_err(|_| Error::NotFound)?;
+
+        match self.whitelist.read().await.get(&info_hash) {
+            Some(info_hash) => Ok(*info_hash),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        if !self.whitelist.write().await.insert(info_hash) {
+            return Err(Error::ConstraintViolation {
+                source: format!("info_hash {info_hash} is already whitelisted").into(),
+            });
+        }
+        Ok(1)
+    }
+
+    async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        if !self.whitelist.write().await.remove(&info_hash) {
+            return Err(Error::NotFound);
+        }
+        Ok(1)
+    }
+
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        let mut whitelist = self.whitelist.write().await;
+        Ok(info_hashes.iter().filter(|info_hash| whitelist.insert(**info_hash)).count())
+    }
+
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        let mut whitelist = self.whitelist.write().await;
+        Ok(info_hashes.iter().filter(|info_hash| whitelist.remove(*info_hash)).count())
+    }
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        if !self.blacklist.write().await.insert(*range) {
+            return Err(Error::ConstraintViolation {
+                source: format!("range {range} is already blacklisted").into(),
+            });
+        }
+        Ok(1)
+    }
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        if !self.blacklist.write().await.remove(range) {
+            return Err(Error::NotFound);
+        }
+        Ok(1)
+    }
+
+    async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error> {
+        self.keys.read().await.get(key).cloned().ok_or(Error::NotFound)
+    }
+
+    async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error> {
+        let mut keys = self.keys.write().await;
+
+        if keys.contains_key(&auth_key.key) {
+            return Err(Error::ConstraintViolation {
+                source: format!("key {} already exists", auth_key.key).into(),
+            });
+        }
+
+        keys.insert(auth_key.key.clone(), auth_key.clone());
+        Ok(1)
+    }
+
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error> {
+        let mut keys = self.keys.write().await;
+
+        if let Some(auth_key) = auth_keys.iter().find(|auth_key| keys.contains_key(&auth_key.key)) {
+            return Err(Error::ConstraintViolation {
+                source: format!("key {} already exists", auth_key.key).into(),
+            });
+        }
+
+        keys.extend(auth_keys.iter().map(|auth_key| (auth_key.key.clone(), auth_key.clone())));
+        Ok(auth_keys.len())
+    }
+
+    async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error> {
+        match self.keys.write().await.remove(key) {
+            Some(_) => Ok(1),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error> {
+        match self.keys.write().await.get_mut(key) {
+            Some(auth_key) => {
+                auth_key.valid_until = valid_until;
+                Ok(1)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error> {
+        let mut keys = self.keys.write().await;
+
+        let before = keys.len();
+        keys.retain(|_, auth_key| auth_key.valid_until.is_none_or(|valid_until| valid_until >= now));
+
+        Ok(before - keys.len())
+    }
+
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error> {
+        let mut keys = self.keys.write().await;
+
+        for auth_key in auth_keys {
+            if let Some(key) = keys.get_mut(&auth_key.key) {
+                key.last_used = auth_key.last_used;
+                key.announces = auth_key.announces;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::Memory;
+    use crate::databases::{Database, Error};
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::auth;
+
+    fn sample_info_hash() -> InfoHash {
+        InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_should_keep_the_whitelisted_torrents() {
+        let database = Memory::new();
+
+        database.add_info_hash_to_whitelist(sample_info_hash()).await.unwrap();
+
+        assert!(database.is_info_hash_whitelisted(&sample_info_hash()).await.unwrap());
+        assert_eq!(database.load_whitelist().await.unwrap(), vec![sample_info_hash()]);
+    }
+
+    #[tokio::test]
+    async fn it_should_fail_whitelisting_a_torrent_twice() {
+        let database = Memory::new();
+
+        database.add_info_hash_to_whitelist(sample_info_hash()).await.unwrap();
+
+        assert!(matches!(
+            database.add_info_hash_to_whitelist(sample_info_hash()).await,
+            Err(Error::ConstraintViolation { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_fail_removing_a_torrent_that_is_not_whitelisted() {
+        let database = Memory::new();
+
+        assert!(matches!(
+            database.remove_info_hash_from_whitelist(sample_info_hash()).await,
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_keep_the_completed_stat_of_the_torrents() {
+        let database = Memory::new();
+
+        database.save_persistent_torrent(&sample_info_hash(), 1).await.unwrap();
+        database.save_persistent_torrents(&[(sample_info_hash(), 2)]).await.unwrap();
+
+        assert_eq!(
+            database.load_persistent_torrents().await.unwrap(),
+            vec![(sample_info_hash(), 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_keep_the_auth_keys() {
+        let database = Memory::new();
+        let auth_key = auth::generate(Duration::from_secs(60));
+
+        database.add_key_to_keys(&auth_key).await.unwrap();
+
+        assert_eq!(database.get_key_from_keys(&auth_key.key).await.unwrap(), auth_key);
+
+        database.remove_key_from_keys(&auth_key.key).await.unwrap();
+
+        assert!(matches!(
+            database.get_key_from_keys(&auth_key.key).await,
+            Err(Error::NotFound)
+        ));
+    }
+}
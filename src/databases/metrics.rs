@@ -0,0 +1,379 @@
+//! Query metrics, to diagnose slow databases.
+//!
+//! [`Instrumented`] wraps a `Database` and records, for every method, how many
+//! times it was called, how many calls failed and a latency histogram. The
+//! connection pools report through [`PoolEvents`] how long the queries waited
+//! for a free connection and how many gave up waiting.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+
+use crate::databases::{Database, Error, PoolState};
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{audit, auth, peer};
+
+/// Upper bounds of the latency histogram buckets, in milliseconds.
+pub const LATENCY_BUCKETS: [u64; 10] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 5000];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Cumulative, the Nth bucket counts the samples up to `LATENCY_BUCKETS[N]`.
+    pub buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+
+        for (bucket, upper_bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if duration <= Duration::from_millis(upper_bound) {
+                *bucket += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryMetrics {
+    pub errors: u64,
+    pub latency: Histogram,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolWaitMetrics {
+    pub timeouts: u64,
+    pub wait: Histogram,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub queries: BTreeMap<&'static str, QueryMetrics>,
+    pub pool_wait: PoolWaitMetrics,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    queries: Mutex<BTreeMap<&'static str, QueryMetrics>>,
+    pool_wait: Mutex<PoolWaitMetrics>,
+}
+
+impl Metrics {
+    /// # Panics
+    ///
+    /// Will panic if the metrics lock is poisoned.
+    pub fn record_query(&self, method: &'static str, latency: Duration, failed: bool) {
+        let mut queries = self.queries.lock().unwrap();
+        let query_metrics = queries.entry(method).or_default();
+
+        query_metrics.latency.record(latency);
+        if failed {
+            query_metrics.errors += 1;
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the metrics lock is poisoned.
+    pub fn record_pool_wait(&self, wait: Duration) {
+        self.pool_wait.lock().unwrap().wait.record(wait);
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the metrics lock is poisoned.
+    pub fn record_pool_timeout(&self) {
+        self.pool_wait.lock().unwrap().timeouts += 1;
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the metrics lock is poisoned.
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            queries: self.queries.lock().unwrap().clone(),
+            pool_wait: self.pool_wait.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Records the connection pool checkouts into the [`Metrics`].
+#[derive(Debug)]
+pub struct PoolEvents(pub Arc<Metrics>);
+
+impl r2d2::HandleEvent for PoolEvents {
+    fn handle_checkout(&self, event: r2d2::event::CheckoutEvent) {
+        self.0.record_pool_wait(event.duration());
+    }
+
+    fn handle_timeout(&self, _event: r2d2::event::TimeoutEvent) {
+        self.0.record_pool_timeout();
+    }
+}
+
+pub struct Instrumented {
+    database: Box<dyn Database>,
+    metrics: Arc<Metrics>,
+}
+
+impl Instrumented {
+    #[must_use]
+    pub fn new(database: Box<dyn Database>, metrics: Arc<Metrics>) -> Self {
+        Self { database, metrics }
+    }
+
+    async fn measure<T, Fut>(&self, method: &'static str, operation: Fut) -> Result<T, Error>
+    where
+        Fut: Future<Output = Result<T, Error>> + Send,
+    {
+        let start = Instant::now();
+        let result = operation.await;
+
+        // Not found is an answer, not a database failure.
+        let failed = matches!(&result, Err(e) if !matches!(e, Error::NotFound));
+        self.metrics.record_query(method, start.elapsed(), failed);
+
+        result
+    }
+}
+
+#[async_trait]
+impl Database for Instrumented {
+    fn create_database_tables(&self) -> Result<(), Error> {
+        self.database.create_database_tables()
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        self.measure("ping", self.database.ping()).await
+    }
+
+    fn pool_state(&self) -> Option<PoolState> {
+        self.database.pool_state()
+    }
+
+    fn metrics(&self) -> Option<Snapshot> {
+        Some(self.metrics.snapshot())
+    }
+
+    async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error> {
+        self.measure("load_persistent_torrents", self.database.load_persistent_torrents())
+            .await
+    }
+
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error> {
+        self.measure("load_persistent_torrent", self.database.load_persistent_torrent(info_hash))
+            .await
+    }
+
+    async fn load_keys(&self) -> Result<Vec<auth::Key>, Error> {
+        self.measure("load_keys", self.database.load_keys()).await
+    }
+
+    async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error> {
+        self.measure("load_whitelist", self.database.load_whitelist()).await
+    }
+
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error> {
+        self.measure("load_blacklist", self.database.load_blacklist()).await
+    }
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error> {
+        self.measure("load_peers", self.database.load_peers()).await
+    }
+
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error> {
+        self.measure("save_peers", self.database.save_peers(peers)).await
+    }
+
+    async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error> {
+        self.measure(
+            "save_persistent_torrent",
+            self.database.save_persistent_torrent(info_hash, completed),
+        )
+        .await
+    }
+
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error> {
+        self.measure("save_persistent_torrents", self.database.save_persistent_torrents(torrents))
+            .await
+    }
+
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error> {
+        self.measure(
+            "remove_persistent_torrent",
+            self.database.remove_persistent_torrent(info_hash),
+        )
+        .await
+    }
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error> {
+        self.measure(
+            "add_completed_event",
+            self.database.add_completed_event(info_hash, peer_id, completed_at),
+        )
+        .await
+    }
+
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error> {
+        self.measure("load_completed_events", self.database.load_completed_events(info_hash, since))
+            .await
+    }
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error> {
+        self.measure("add_audit_log_entry", self.database.add_audit_log_entry(entry))
+            .await
+    }
+
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error> {
+        self.measure("load_audit_log", self.database.load_audit_log(query)).await
+    }
+
+    async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error> {
+        self.measure(
+            "get_info_hash_from_whitelist",
+            self.database.get_info_hash_from_whitelist(info_hash),
+        )
+        .await
+    }
+
+    async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        self.measure(
+            "add_info_hash_to_whitelist",
+            self.database.add_info_hash_to_whitelist(info_hash),
+        )
+        .await
+    }
+
+    async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        self.measure(
+            "remove_info_hash_from_whitelist",
+            self.database.remove_info_hash_from_whitelist(info_hash),
+        )
+        .await
+    }
+
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        self.measure(
+            "add_info_hashes_to_whitelist",
+            self.database.add_info_hashes_to_whitelist(info_hashes),
+        )
+        .await
+    }
+
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        self.measure(
+            "remove_info_hashes_from_whitelist",
+            self.database.remove_info_hashes_from_whitelist(info_hashes),
+        )
+        .await
+    }
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        self.measure("add_range_to_blacklist", self.database.add_range_to_blacklist(range))
+            .await
+    }
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        self.measure(
+            "remove_range_from_blacklist",
+            self.database.remove_range_from_blacklist(range),
+        )
+        .await
+    }
+
+    async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error> {
+        self.measure("get_key_from_keys", self.database.get_key_from_keys(key)).await
+    }
+
+    async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error> {
+        self.measure("add_key_to_keys", self.database.add_key_to_keys(auth_key)).await
+    }
+
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error> {
+        self.measure("add_keys_to_keys", self.database.add_keys_to_keys(auth_keys))
+            .await
+    }
+
+    async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error> {
+        self.measure("remove_key_from_keys", self.database.remove_key_from_keys(key))
+            .await
+    }
+
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error> {
+        self.measure(
+            "update_key_valid_until",
+            self.database.update_key_valid_until(key, valid_until),
+        )
+        .await
+    }
+
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error> {
+        self.measure("remove_expired_keys", self.database.remove_expired_keys(now))
+            .await
+    }
+
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error> {
+        self.measure("save_keys_usage", self.database.save_keys_usage(auth_keys))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{Histogram, Instrumented, Metrics};
+    use crate::databases::memory::Memory;
+    use crate::databases::Database;
+
+    #[test]
+    fn the_histogram_buckets_should_be_cumulative() {
+        let mut histogram = Histogram::default();
+
+        histogram.record(Duration::from_millis(3));
+        histogram.record(Duration::from_millis(40));
+
+        assert_eq!(histogram.count, 2);
+        assert_eq!(histogram.total, Duration::from_millis(43));
+        assert_eq!(histogram.max, Duration::from_millis(40));
+        assert_eq!(histogram.buckets, [0, 1, 1, 1, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[tokio::test]
+    async fn it_should_count_the_queries_and_the_errors_per_method() {
+        let database = Instrumented::new(Box::new(Memory::new()), Arc::new(Metrics::default()));
+
+        database.load_keys().await.unwrap();
+        database.load_keys().await.unwrap();
+        assert!(database.get_key_from_keys("missing").await.is_err());
+
+        let metrics = database.metrics().unwrap();
+
+        assert_eq!(metrics.queries["load_keys"].latency.count, 2);
+        assert_eq!(metrics.queries["load_keys"].errors, 0);
+        assert_eq!(metrics.queries["get_key_from_keys"].latency.count, 1);
+        // Not found is not a database error
+        assert_eq!(metrics.queries["get_key_from_keys"].errors, 0);
+    }
+}
@@ -0,0 +1,100 @@
+//! Schema migrations.
+//!
+//! Every driver keeps an ordered list of [`Migration`]s. The versions already
+//! applied to a database are recorded in the [`TABLE`] table, so on start up
+//! only the pending ones are executed.
+//!
+//! New migrations must be appended at the end of each driver list with a
+//! version greater than the previous one. Applied migrations must never be
+//! edited, add a new one instead.
+
+/// The table used to track which migrations have already been applied.
+pub const TABLE: &str = "schema_migrations";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub statements: Vec<String>,
+}
+
+impl Migration {
+    #[must_use]
+    pub fn new(version: i64, description: &'static str, statements: Vec<String>) -> Self {
+        Self {
+            version,
+            description,
+            statements,
+        }
+    }
+}
+
+/// Returns the migrations that have not been applied yet, sorted by version.
+#[must_use]
+pub fn pending(mut migrations: Vec<Migration>, applied_versions: &[i64]) -> Vec<Migration> {
+    migrations.retain(|migration| !applied_versions.contains(&migration.version));
+    migrations.sort_by_key(|migration| migration.version);
+    migrations
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::sync::Arc;
+
+    use r2d2_sqlite::rusqlite::Connection;
+    use uuid::Uuid;
+
+    use super::{pending, Migration, TABLE};
+    use crate::config;
+    use crate::databases::sqlite::Sqlite;
+    use crate::databases::Database;
+
+    fn sample_migration(version: i64) -> Migration {
+        Migration::new(version, "sample", vec![])
+    }
+
+    #[test]
+    fn all_migrations_should_be_pending_for_a_new_database() {
+        let migrations = vec![sample_migration(1), sample_migration(2)];
+
+        assert_eq!(pending(migrations.clone(), &[]), migrations);
+    }
+
+    #[test]
+    fn applied_migrations_should_not_be_pending() {
+        let migrations = vec![sample_migration(1), sample_migration(2)];
+
+        assert_eq!(pending(migrations, &[1]), vec![sample_migration(2)]);
+    }
+
+    #[test]
+    fn pending_migrations_should_be_sorted_by_version() {
+        let migrations = vec![sample_migration(3), sample_migration(1), sample_migration(2)];
+
+        assert_eq!(
+            pending(migrations, &[]),
+            vec![sample_migration(1), sample_migration(2), sample_migration(3)]
+        );
+    }
+
+    #[test]
+    fn migrations_should_be_recorded_and_only_applied_once() {
+        let db_path = env::temp_dir().join(format!("data_{}.db", Uuid::new_v4()));
+        let db_path = db_path.to_str().unwrap();
+
+        let database = Sqlite::new(db_path, &config::Database::default(), &Arc::default()).unwrap();
+
+        database.create_database_tables().unwrap();
+        database.create_database_tables().unwrap();
+
+        let conn = Connection::open(db_path).unwrap();
+        let applied_versions: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM {TABLE} WHERE version = 1"), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+
+        assert_eq!(applied_versions, 1);
+    }
+}
@@ -1,33 +1,257 @@
 pub mod driver;
 pub mod error;
+pub mod memory;
+pub mod metrics;
+pub mod migrations;
 pub mod mysql;
+pub mod postgres;
+pub mod redis;
+pub mod retry;
 pub mod sqlite;
+pub mod transfer;
+pub mod write_behind;
 
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
 use async_trait::async_trait;
+use ipnet::IpNet;
 
 use self::driver::Driver;
 use self::error::Error;
+use crate::config;
+use crate::databases::memory::Memory;
+use crate::databases::metrics::{Instrumented, Metrics, PoolEvents};
 use crate::databases::mysql::Mysql;
+use crate::databases::postgres::Postgres;
+use crate::databases::redis::Redis;
+use crate::databases::retry::Retry;
 use crate::databases::sqlite::Sqlite;
+use crate::protocol::clock::DurationSinceUnixEpoch;
 use crate::protocol::info_hash::InfoHash;
-use crate::tracker::auth;
+use crate::tracker::{audit, auth, peer};
+
+/// A peer as stored in the `torrent_peers` table: `info_hash`, `peer_id`,
+/// `peer_addr`, `updated`, `uploaded`, `downloaded`, `left_bytes` and `event`.
+pub type PeerRow = (String, String, String, i64, i64, i64, i64, i32);
+
+/// The partial seeds are saved with the `paused` event of the UDP announces in BEP 21.
+const PAUSED_EVENT: i32 = 4;
+
+#[must_use]
+pub fn peer_into_row(info_hash: &InfoHash, peer: &peer::Peer) -> PeerRow {
+    (
+        info_hash.to_string(),
+        peer.peer_id.get_id().unwrap_or_default(),
+        peer.peer_addr.to_string(),
+        secs_into_column(peer.updated),
+        peer.uploaded.0,
+        peer.downloaded.0,
+        peer.left.0,
+        if peer.paused { PAUSED_EVENT } else { peer.event.to_i32() },
+    )
+}
+
+/// Returns `None` if the row does not contain a valid peer.
+#[must_use]
+pub fn peer_from_row(row: PeerRow) -> Option<(InfoHash, peer::Peer)> {
+    let (info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left, event) = row;
+
+    Some((
+        InfoHash::from_str(&info_hash).ok()?,
+        peer::Peer {
+            peer_id: peer::Id::from_str(&peer_id).ok()?,
+            peer_addr: SocketAddr::from_str(&peer_addr).ok()?,
+            updated: DurationSinceUnixEpoch::from_secs(updated.unsigned_abs()),
+            uploaded: NumberOfBytes(uploaded),
+            downloaded: NumberOfBytes(downloaded),
+            left: NumberOfBytes(left),
+            event: AnnounceEvent::from_i32(event),
+            paused: event == PAUSED_EVENT,
+            key: None,
+            country: None,
+        },
+    ))
+}
+
+/// A key as stored in the `keys` table: `key`, `valid_until`, `label`,
+/// `created_at`, `last_used` and `announces`. The keys never expiring have no `valid_until`.
+pub type KeyRow = (String, Option<i64>, Option<String>, Option<i64>, Option<i64>, i64);
+
+#[must_use]
+pub fn secs_into_column(duration: DurationSinceUnixEpoch) -> i64 {
+    i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+}
+
+#[must_use]
+pub fn key_into_row(auth_key: &auth::Key) -> KeyRow {
+    (
+        auth_key.key.clone(),
+        auth_key.valid_until.map(secs_into_column),
+        auth_key.label.clone(),
+        auth_key.created_at.map(secs_into_column),
+        auth_key.last_used.map(secs_into_column),
+        i64::try_from(auth_key.announces).unwrap_or(i64::MAX),
+    )
+}
+
+#[must_use]
+pub fn key_from_row(row: KeyRow) -> auth::Key {
+    let (key, valid_until, label, created_at, last_used, announces) = row;
+
+    auth::Key {
+        key,
+        valid_until: valid_until.map(|secs| DurationSinceUnixEpoch::from_secs(secs.unsigned_abs())),
+        label,
+        created_at: created_at.map(|secs| DurationSinceUnixEpoch::from_secs(secs.unsigned_abs())),
+        last_used: last_used.map(|secs| DurationSinceUnixEpoch::from_secs(secs.unsigned_abs())),
+        announces: announces.unsigned_abs(),
+    }
+}
+
+/// A completed event as stored in the `torrent_completed_events` table:
+/// `info_hash`, `peer_id_hash` and `completed_at`.
+pub type CompletedEventRow = (String, String, i64);
+
+/// Only a hash of the `peer_id` is stored, it is enough to tell peers apart.
+#[must_use]
+pub fn completed_event_into_row(
+    info_hash: &InfoHash,
+    peer_id: &peer::Id,
+    completed_at: DurationSinceUnixEpoch,
+) -> CompletedEventRow {
+    (
+        info_hash.to_string(),
+        hex::encode(openssl::sha::sha1(&peer_id.0)),
+        secs_into_column(completed_at),
+    )
+}
+
+/// An entry of the audit log as stored in the `audit_log` table: `created_at`, `token_name`, `ip`, `method`,
+/// `path` and `status`.
+pub type AuditLogRow = (i64, String, Option<String>, String, String, i64);
+
+#[must_use]
+pub fn audit_log_entry_into_row(entry: &audit::Entry) -> AuditLogRow {
+    (
+        secs_into_column(entry.created_at),
+        entry.token_name.clone(),
+        entry.ip.map(|ip| ip.to_string()),
+        entry.method.clone(),
+        entry.path.clone(),
+        i64::from(entry.status),
+    )
+}
+
+#[must_use]
+pub fn audit_log_entry_from_row(row: AuditLogRow) -> audit::Entry {
+    let (created_at, token_name, ip, method, path, status) = row;
+
+    audit::Entry {
+        created_at: DurationSinceUnixEpoch::from_secs(created_at.unsigned_abs()),
+        token_name,
+        ip: ip.and_then(|ip| ip.parse().ok()),
+        method,
+        path,
+        status: u16::try_from(status).unwrap_or_default(),
+    }
+}
+
+/// The `since` and `until` of the `query` as columns, the whole log without them.
+#[must_use]
+pub fn audit_log_query_range(query: &audit::Query) -> (i64, i64) {
+    (
+        query.since.map_or(0, secs_into_column),
+        query.until.map_or(i64::MAX, secs_into_column),
+    )
+}
+
+/// How many connections of a driver connection pool are in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolState {
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub max_connections: u32,
+}
+
+impl PoolState {
+    #[must_use]
+    pub fn of<M: r2d2::ManageConnection>(pool: &r2d2::Pool<M>) -> Self {
+        let state = pool.state();
+
+        Self {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_connections: pool.max_size(),
+        }
+    }
+
+    /// All the connections are open and in use, so new requests have to wait for one to be released.
+    #[must_use]
+    pub fn is_exhausted(&self) -> bool {
+        self.idle_connections == 0 && self.connections >= self.max_connections
+    }
+}
+
+/// Returns a connection pool builder with the pool `settings` from the configuration.
+///
+/// # Panics
+///
+/// Will panic if `max_connections` is zero.
+#[must_use]
+pub fn pool_builder<M: r2d2::ManageConnection>(settings: &config::Database, metrics: &Arc<Metrics>) -> r2d2::Builder<M> {
+    let max_lifetime = match settings.max_lifetime {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    };
+
+    r2d2::Pool::builder()
+        .max_size(settings.max_connections)
+        .min_idle(settings.min_idle)
+        .connection_timeout(Duration::from_secs(settings.connection_timeout))
+        .max_lifetime(max_lifetime)
+        .event_handler(Box::new(PoolEvents(metrics.clone())))
+}
 
 /// # Errors
 ///
-/// Will return `r2d2::Error` if `db_path` is not able to create a database.
-pub fn connect(db_driver: &Driver, db_path: &str) -> Result<Box<dyn Database>, r2d2::Error> {
+/// Will return `Error` if unable to connect to the database in `db_path` or to create its tables.
+pub fn connect(db_driver: &Driver, db_path: &str, settings: &config::Database) -> Result<Box<dyn Database>, Error> {
+    let metrics = Arc::new(Metrics::default());
+
     let database: Box<dyn Database> = match db_driver {
         Driver::Sqlite3 => {
-            let db = Sqlite::new(db_path)?;
+            let db = Sqlite::new(db_path, settings, &metrics)?;
             Box::new(db)
         }
         Driver::MySQL => {
-            let db = Mysql::new(db_path)?;
+            let db = Mysql::new(db_path, settings, &metrics)?;
+            Box::new(db)
+        }
+        Driver::PostgreSQL => {
+            let db = Postgres::new(db_path, settings, &metrics)?;
+            Box::new(db)
+        }
+        Driver::Redis => {
+            let db = Redis::new(db_path, settings, &metrics)?;
+            Box::new(db)
+        }
+        Driver::Memory => {
+            let db = Memory::new();
             Box::new(db)
         }
     };
 
-    database.create_database_tables().expect("Could not create database tables.");
+    database.create_database_tables()?;
+
+    let database: Box<dyn Database> = Box::new(Instrumented::new(database, metrics));
+
+    if settings.max_attempts > 1 {
+        return Ok(Box::new(Retry::new(database, settings)));
+    }
 
     Ok(database)
 }
@@ -36,38 +260,177 @@ pub fn connect(db_driver: &Driver, db_path: &str) -> Result<Box<dyn Database>, r
 pub trait Database: Sync + Send {
     /// # Errors
     ///
-    /// Will return `Error` if unable to create own tables.
+    /// Will return `Error` if unable to create own tables or to apply a pending schema migration.
     fn create_database_tables(&self) -> Result<(), Error>;
 
+    /// # Errors
+    ///
+    /// Will return `Error` if the database is not reachable.
+    async fn ping(&self) -> Result<(), Error>;
+
+    /// Returns `None` if the driver does not use a connection pool.
+    fn pool_state(&self) -> Option<PoolState>;
+
+    /// Returns `None` unless the database is [`Instrumented`].
+    fn metrics(&self) -> Option<metrics::Snapshot> {
+        None
+    }
+
     async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error>;
 
+    /// Returns `None` if the completed counter of the torrent was never saved.
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error>;
+
     async fn load_keys(&self) -> Result<Vec<auth::Key>, Error>;
 
     async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error>;
 
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error>;
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error>;
+
+    /// Replaces the previously saved peers with the given ones.
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error>;
+
     async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error>;
 
+    /// Saves all the `completed` counters in a single transaction.
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error>;
+
+    /// Removes the `completed` counter of the torrent.
+    /// Returns the number of removed counters, zero if it was never saved.
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error>;
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error>;
+
+    /// Returns when the torrent was completed since `since`, sorted from the oldest.
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error>;
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error>;
+
+    /// Returns the page of the audit log entries matching the `query`, from the newest.
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error>;
+
     async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error>;
 
     async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error>;
 
     async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error>;
 
+    /// Adds all the `info_hashes` in a single transaction, skipping the ones already whitelisted.
+    /// Returns the number of added `info_hashes`.
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error>;
+
+    /// Removes all the `info_hashes` in a single transaction.
+    /// Returns the number of removed `info_hashes`.
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error>;
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error>;
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error>;
+
     async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error>;
 
     async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error>;
 
+    /// Adds all the `auth_keys` in a single transaction, failing if any of them already exists.
+    /// Returns the number of added `auth_keys`.
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error>;
+
     async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error>;
 
+    /// Sets when the `key` expires, `None` for it to never expire.
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error>;
+
+    /// Removes the keys that expired before `now`.
+    /// Returns the number of removed keys.
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error>;
+
+    /// Saves the `last_used` and `announces` usage counters of the given keys in a single transaction.
+    /// Keys no longer in the database are ignored.
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error>;
+
     async fn is_info_hash_whitelisted(&self, info_hash: &InfoHash) -> Result<bool, Error> {
         self.get_info_hash_from_whitelist(&info_hash.clone().to_string())
             .await
             .map_or_else(
                 |e| match e {
-                    Error::QueryReturnedNoRows => Ok(false),
+                    Error::NotFound => Ok(false),
                     e => Err(e),
                 },
                 |_| Ok(true),
             )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::{pool_builder, PoolState};
+    use crate::config;
+
+    #[test]
+    fn the_connection_pool_should_be_built_with_the_configured_settings() {
+        let settings = config::Database {
+            max_connections: 3,
+            min_idle: Some(1),
+            connection_timeout: 5,
+            max_lifetime: 60,
+            ..Default::default()
+        };
+
+        let pool = pool_builder(&settings, &Arc::default())
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+
+        assert_eq!(pool.max_size(), 3);
+        assert_eq!(pool.min_idle(), Some(1));
+        assert_eq!(pool.connection_timeout(), Duration::from_secs(5));
+        assert_eq!(pool.max_lifetime(), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn a_zero_max_lifetime_should_disable_recycling_connections() {
+        let settings = config::Database {
+            max_lifetime: 0,
+            ..Default::default()
+        };
+
+        let pool = pool_builder(&settings, &Arc::default())
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+
+        assert_eq!(pool.max_lifetime(), None);
+    }
+
+    #[test]
+    fn the_pool_should_be_exhausted_when_all_its_connections_are_in_use() {
+        let settings = config::Database {
+            max_connections: 1,
+            ..Default::default()
+        };
+
+        let pool = pool_builder(&settings, &Arc::default())
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+
+        assert!(!PoolState::of(&pool).is_exhausted());
+
+        let _conn = pool.get().unwrap();
+
+        assert!(PoolState::of(&pool).is_exhausted());
+    }
+}
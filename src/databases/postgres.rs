@@ -0,0 +1,718 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+use log::{debug, info};
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::postgres::{Config, NoTls, Row};
+use r2d2_postgres::PostgresConnectionManager;
+
+use crate::config;
+use crate::databases::metrics::Metrics;
+use crate::databases::migrations::{self, Migration};
+use crate::databases::{self, Database, Error, KeyRow, PeerRow, PoolState};
+use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Time};
+use crate::protocol::common::AUTH_KEY_LENGTH;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{audit, auth, peer};
+
+type Manager = PostgresConnectionManager<NoTls>;
+
+pub struct Postgres {
+    // It is only `None` while the driver is being dropped.
+    pool: Option<Pool<Manager>>,
+}
+
+impl Postgres {
+    /// # Errors
+    ///
+    /// Will return `r2d2::Error` if `db_path` is not able to create `PostgreSQL` database.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `db_path` is not a valid `PostgreSQL` connection string.
+    pub fn new(db_path: &str, settings: &config::Database, metrics: &Arc<Metrics>) -> Result<Self, r2d2::Error> {
+        let config = Config::from_str(db_path).expect("Failed to parse PostgreSQL connection string.");
+        let manager = PostgresConnectionManager::new(config, NoTls);
+        let pool = databases::pool_builder(settings, metrics).build(manager)?;
+
+        Ok(Self { pool: Some(pool) })
+    }
+
+    fn migrations() -> Vec<Migration> {
+        vec![
+            Migration::new(
+                1,
+                "create whitelist, keys and torrents tables",
+                vec![
+                    "
+        CREATE TABLE IF NOT EXISTS whitelist (
+            id SERIAL PRIMARY KEY,
+            info_hash VARCHAR(40) NOT NULL UNIQUE
+        );"
+                    .to_string(),
+                    format!(
+                        "
+        CREATE TABLE IF NOT EXISTS keys (
+            id SERIAL PRIMARY KEY,
+            key VARCHAR({}) NOT NULL UNIQUE,
+            valid_until BIGINT NOT NULL
+        );",
+                        i8::try_from(AUTH_KEY_LENGTH).expect("auth::Auth Key Length Should fit within a i8!")
+                    ),
+                    "
+        CREATE TABLE IF NOT EXISTS torrents (
+            id SERIAL PRIMARY KEY,
+            info_hash VARCHAR(40) NOT NULL UNIQUE,
+            completed BIGINT DEFAULT 0 NOT NULL
+        );"
+                    .to_string(),
+                ],
+            ),
+            Migration::new(
+                2,
+                "create torrent_peers table",
+                vec!["
+        CREATE TABLE IF NOT EXISTS torrent_peers (
+            info_hash VARCHAR(40) NOT NULL,
+            peer_id VARCHAR(40) NOT NULL,
+            peer_addr VARCHAR(64) NOT NULL,
+            updated BIGINT NOT NULL,
+            uploaded BIGINT NOT NULL,
+            downloaded BIGINT NOT NULL,
+            left_bytes BIGINT NOT NULL,
+            event INTEGER NOT NULL,
+            PRIMARY KEY (info_hash, peer_id)
+        );"
+                .to_string()],
+            ),
+            Migration::new(
+                3,
+                "add label and usage columns to keys table",
+                vec!["
+        ALTER TABLE keys
+            ADD COLUMN label VARCHAR(255),
+            ADD COLUMN created_at BIGINT,
+            ADD COLUMN last_used BIGINT,
+            ADD COLUMN announces BIGINT NOT NULL DEFAULT 0;"
+                    .to_string()],
+            ),
+            Migration::new(
+                4,
+                "create torrent_completed_events table",
+                vec![
+                    "
+        CREATE TABLE IF NOT EXISTS torrent_completed_events (
+            id BIGSERIAL PRIMARY KEY,
+            info_hash VARCHAR(40) NOT NULL,
+            peer_id_hash VARCHAR(40) NOT NULL,
+            completed_at BIGINT NOT NULL
+        );"
+                    .to_string(),
+                    "CREATE INDEX IF NOT EXISTS torrent_completed_events_info_hash ON torrent_completed_events (info_hash, completed_at);"
+                        .to_string(),
+                ],
+            ),
+            Migration::new(
+                5,
+                "create blacklist table",
+                vec!["
+        CREATE TABLE IF NOT EXISTS blacklist (
+            id SERIAL PRIMARY KEY,
+            ip_range VARCHAR(43) NOT NULL UNIQUE
+        );"
+                .to_string()],
+            ),
+            Migration::new(
+                6,
+                "allow keys never expiring",
+                vec!["ALTER TABLE keys ALTER COLUMN valid_until DROP NOT NULL;".to_string()],
+            ),
+            Migration::new(
+                7,
+                "create audit_log table",
+                vec![
+                    "
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id BIGSERIAL PRIMARY KEY,
+            created_at BIGINT NOT NULL,
+            token_name VARCHAR(255) NOT NULL,
+            ip VARCHAR(39),
+            method VARCHAR(16) NOT NULL,
+            path TEXT NOT NULL,
+            status BIGINT NOT NULL
+        );"
+                    .to_string(),
+                    "CREATE INDEX IF NOT EXISTS audit_log_created_at ON audit_log (created_at);".to_string(),
+                ],
+            ),
+        ]
+    }
+
+    fn pool(&self) -> Pool<Manager> {
+        self.pool.clone().expect("PostgreSQL connection pool already dropped.")
+    }
+
+    /// The synchronous `postgres` client drives its own runtime internally, so
+    /// it must never be called from a thread running the tracker's runtime.
+    /// Every query is moved onto tokio's blocking thread pool instead.
+    async fn with_connection<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut PooledConnection<Manager>) -> Result<T, Error> + Send + 'static,
+    {
+        let pool = self.pool();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+/// Reads a row selected with the `keys` columns in the [`KeyRow`] order.
+fn key_row(row: &Row) -> KeyRow {
+    (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4), row.get(5))
+}
+
+impl Drop for Postgres {
+    fn drop(&mut self) {
+        // Closing the pooled clients also blocks, so it can not happen on a runtime thread either.
+        if let Some(pool) = self.pool.take() {
+            let _ = std::thread::spawn(move || drop(pool)).join();
+        }
+    }
+}
+
+#[async_trait]
+impl Database for Postgres {
+    fn create_database_tables(&self) -> Result<(), Error> {
+        let create_migrations_table = format!(
+            "
+        CREATE TABLE IF NOT EXISTS {} (
+            version BIGINT PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at BIGINT NOT NULL
+        );",
+            migrations::TABLE
+        );
+
+        let pool = self.pool();
+
+        // This method is not async but it is called from within the runtime
+        // while the tracker is being built, so run it on a plain thread.
+        std::thread::spawn(move || {
+            let mut conn = pool.get()?;
+
+            conn.batch_execute(&create_migrations_table)?;
+
+            let applied_versions: Vec<i64> = conn
+                .query(&format!("SELECT version FROM {}", migrations::TABLE), &[])?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+
+            for migration in migrations::pending(Self::migrations(), &applied_versions) {
+                let applied_at = i64::try_from(Current::now().as_secs()).unwrap_or(i64::MAX);
+
+                let mut tx = conn.transaction()?;
+
+                tx.batch_execute(&migration.statements.concat())?;
+
+                tx.execute(
+                    &format!(
+                        "INSERT INTO {} (version, description, applied_at) VALUES ($1, $2, $3)",
+                        migrations::TABLE
+                    ),
+                    &[&migration.version, &migration.description, &applied_at],
+                )?;
+
+                tx.commit()?;
+
+                info!("Applied database migration {}: {}", migration.version, migration.description);
+            }
+
+            Ok(())
+        })
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        self.with_connection(|conn| {
+            conn.simple_query("SELECT 1")?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    fn pool_state(&self) -> Option<PoolState> {
+        self.pool.as_ref().map(PoolState::of)
+    }
+
+    async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error> {
+        self.with_connection(|conn| {
+            let rows = conn.query("SELECT info_hash, completed FROM torrents", &[])?;
+
+            let torrents: Vec<(InfoHash, u32)> = rows
+                .iter()
+                .map(|row| {
+                    let info_hash = InfoHash::from_str(row.get::<_, &str>(0)).unwrap();
+                    let completed: i64 = row.get(1);
+                    (info_hash, u32::try_from(completed).unwrap_or_default())
+                })
+                .collect();
+
+            Ok(torrents)
+        })
+        .await
+    }
+
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error> {
+        let info_hash_str = info_hash.to_string();
+
+        self.with_connection(move |conn| {
+            let row = conn.query_opt("SELECT completed FROM torrents WHERE info_hash = $1", &[&info_hash_str])?;
+
+            Ok(row.map(|row| u32::try_from(row.get::<_, i64>(0)).unwrap_or_default()))
+        })
+        .await
+    }
+
+    async fn load_keys(&self) -> Result<Vec<auth::Key>, Error> {
+        self.with_connection(|conn| {
+            let rows = conn.query(
+                "SELECT key, valid_until, label, created_at, last_used, announces FROM keys",
+                &[],
+            )?;
+
+            let keys: Vec<auth::Key> = rows.iter().map(|row| databases::key_from_row(key_row(row))).collect();
+
+            Ok(keys)
+        })
+        .await
+    }
+
+    async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error> {
+        self.with_connection(|conn| {
+            let rows = conn.query("SELECT info_hash FROM whitelist", &[])?;
+
+            let info_hashes: Vec<InfoHash> = rows
+                .iter()
+                .map(|row| InfoHash::from_str(row.get::<_, &str>(0)).unwrap())
+                .collect();
+
+            Ok(info_hashes)
+        })
+        .await
+    }
+
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error> {
+        self.with_connection(|conn| {
+            let rows = conn.query("SELECT ip_range FROM blacklist", &[])?;
+
+            Ok(rows
+                .iter()
+                .filter_map(|row| IpNet::from_str(row.get::<_, &str>(0)).ok())
+                .collect())
+        })
+        .await
+    }
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error> {
+        self.with_connection(|conn| {
+            let rows = conn.query(
+                "SELECT info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left_bytes, event FROM torrent_peers",
+                &[],
+            )?;
+
+            let peers: Vec<(InfoHash, peer::Peer)> = rows
+                .iter()
+                .filter_map(|row| {
+                    databases::peer_from_row((
+                        row.get(0),
+                        row.get(1),
+                        row.get(2),
+                        row.get(3),
+                        row.get(4),
+                        row.get(5),
+                        row.get(6),
+                        row.get(7),
+                    ))
+                })
+                .collect();
+
+            Ok(peers)
+        })
+        .await
+    }
+
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error> {
+        let rows: Vec<PeerRow> = peers
+            .iter()
+            .map(|(info_hash, peer)| databases::peer_into_row(info_hash, peer))
+            .collect();
+
+        self.with_connection(move |conn| {
+            let mut tx = conn.transaction()?;
+
+            tx.execute("DELETE FROM torrent_peers", &[])?;
+
+            let stmt = tx.prepare(
+                "INSERT INTO torrent_peers (info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left_bytes, event) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            )?;
+
+            for (info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left, event) in &rows {
+                tx.execute(&stmt, &[info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left, event])?;
+            }
+
+            tx.commit().map_err(Error::from)
+        })
+        .await
+    }
+
+    async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error> {
+        let info_hash_str = info_hash.to_string();
+
+        debug!("{}", info_hash_str);
+
+        self.with_connection(move |conn| {
+            match conn.execute(
+                "INSERT INTO torrents (info_hash, completed) VALUES ($1, $2) ON CONFLICT (info_hash) DO UPDATE SET completed = EXCLUDED.completed",
+                &[&info_hash_str, &i64::from(completed)],
+            ) {
+                Ok(updated) => {
+                    if updated > 0 {
+                        return Ok(());
+                    }
+                    Err(Error::NotFound)
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await
+    }
+
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error> {
+        let info_hash = info_hash.to_string();
+
+        self.with_connection(move |conn| {
+            let removed = conn.execute("DELETE FROM torrents WHERE info_hash = $1", &[&info_hash])?;
+            Ok(usize::try_from(removed).unwrap_or(usize::MAX))
+        })
+        .await
+    }
+
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error> {
+        let (info_hashes, completed): (Vec<String>, Vec<i64>) = torrents
+            .iter()
+            .map(|(info_hash, completed)| (info_hash.to_string(), i64::from(*completed)))
+            .unzip();
+
+        self.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO torrents (info_hash, completed) SELECT * FROM UNNEST($1::VARCHAR[], $2::BIGINT[]) ON CONFLICT (info_hash) DO UPDATE SET completed = EXCLUDED.completed",
+                &[&info_hashes, &completed],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error> {
+        let (info_hash, peer_id_hash, completed_at) = databases::completed_event_into_row(info_hash, peer_id, completed_at);
+
+        self.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO torrent_completed_events (info_hash, peer_id_hash, completed_at) VALUES ($1, $2, $3)",
+                &[&info_hash, &peer_id_hash, &completed_at],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error> {
+        let info_hash = info_hash.to_string();
+        let since = databases::secs_into_column(since);
+
+        self.with_connection(move |conn| {
+            let rows = conn.query(
+                "SELECT completed_at FROM torrent_completed_events WHERE info_hash = $1 AND completed_at >= $2 ORDER BY completed_at",
+                &[&info_hash, &since],
+            )?;
+
+            Ok(rows
+                .iter()
+                .map(|row| DurationSinceUnixEpoch::from_secs(row.get::<_, i64>(0).unsigned_abs()))
+                .collect())
+        })
+        .await
+    }
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error> {
+        let (created_at, token_name, ip, method, path, status) = databases::audit_log_entry_into_row(entry);
+
+        self.with_connection(move |conn| {
+            conn.execute(
+                "INSERT INTO audit_log (created_at, token_name, ip, method, path, status) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[&created_at, &token_name, &ip, &method, &path, &status],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error> {
+        let token_name = query.token_name.clone();
+        let ip = query.ip.map(|ip| ip.to_string());
+        let (since, until) = databases::audit_log_query_range(query);
+        let limit = i64::try_from(query.limit).unwrap_or(i64::MAX);
+        let offset = i64::try_from(query.offset).unwrap_or(i64::MAX);
+
+        self.with_connection(move |conn| {
+            let rows = conn.query(
+                "SELECT created_at, token_name, ip, method, path, status FROM audit_log
+                WHERE ($1::TEXT IS NULL OR token_name = $1) AND ($2::TEXT IS NULL OR ip = $2)
+                AND created_at >= $3 AND created_at <= $4
+                ORDER BY id DESC LIMIT $5 OFFSET $6",
+                &[&token_name, &ip, &since, &until, &limit, &offset],
+            )?;
+
+            Ok(rows
+                .iter()
+                .map(|row| {
+                    databases::audit_log_entry_from_row((row.get(0), row.get(1), row.get(2), row.get(3), row.get(4), row.get(5)))
+                })
+                .collect())
+        })
+        .await
+    }
+
+    async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error> {
+        let info_hash = info_hash.to_string();
+
+        self.with_connection(move |conn| {
+            match conn.query_opt("SELECT info_hash FROM whitelist WHERE info_hash = $1", &[&info_hash])? {
+                Some(row) => Ok(InfoHash::from_str(row.get::<_, &str>(0)).unwrap()),
+                None => Err(Error::NotFound),
+            }
+        })
+        .await
+    }
+
+    async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        let info_hash_str = info_hash.to_string();
+
+        self.with_connection(
+            move |conn| match conn.execute("INSERT INTO whitelist (info_hash) VALUES ($1)", &[&info_hash_str]) {
+                Ok(updated) => {
+                    if updated > 0 {
+                        return Ok(usize::try_from(updated).unwrap_or(usize::MAX));
+                    }
+                    Err(Error::NotFound)
+                }
+                Err(e) => Err(e.into()),
+            },
+        )
+        .await
+    }
+
+    async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        let info_hash = info_hash.to_string();
+
+        self.with_connection(
+            move |conn| match conn.execute("DELETE FROM whitelist WHERE info_hash = $1", &[&info_hash]) {
+                Ok(updated) => {
+                    if updated > 0 {
+                        return Ok(usize::try_from(updated).unwrap_or(usize::MAX));
+                    }
+                    Err(Error::NotFound)
+                }
+                Err(e) => Err(e.into()),
+            },
+        )
+        .await
+    }
+
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        let info_hashes: Vec<String> = info_hashes.iter().map(ToString::to_string).collect();
+
+        self.with_connection(move |conn| {
+            let inserted = conn.execute(
+                "INSERT INTO whitelist (info_hash) SELECT UNNEST($1::VARCHAR[]) ON CONFLICT (info_hash) DO NOTHING",
+                &[&info_hashes],
+            )?;
+
+            Ok(usize::try_from(inserted).unwrap_or(usize::MAX))
+        })
+        .await
+    }
+
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        let info_hashes: Vec<String> = info_hashes.iter().map(ToString::to_string).collect();
+
+        self.with_connection(move |conn| {
+            let removed = conn.execute("DELETE FROM whitelist WHERE info_hash = ANY($1)", &[&info_hashes])?;
+
+            Ok(usize::try_from(removed).unwrap_or(usize::MAX))
+        })
+        .await
+    }
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        let ip_range = range.to_string();
+
+        self.with_connection(move |conn| {
+            let inserted = conn.execute("INSERT INTO blacklist (ip_range) VALUES ($1)", &[&ip_range])?;
+
+            Ok(usize::try_from(inserted).unwrap_or(usize::MAX))
+        })
+        .await
+    }
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        let ip_range = range.to_string();
+
+        self.with_connection(
+            move |conn| match conn.execute("DELETE FROM blacklist WHERE ip_range = $1", &[&ip_range])? {
+                0 => Err(Error::NotFound),
+                removed => Ok(usize::try_from(removed).unwrap_or(usize::MAX)),
+            },
+        )
+        .await
+    }
+
+    async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error> {
+        let key = key.to_string();
+
+        self.with_connection(move |conn| {
+            match conn.query_opt(
+                "SELECT key, valid_until, label, created_at, last_used, announces FROM keys WHERE key = $1",
+                &[&key],
+            )? {
+                Some(row) => Ok(databases::key_from_row(key_row(&row))),
+                None => Err(Error::NotFound),
+            }
+        })
+        .await
+    }
+
+    async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error> {
+        let (key, valid_until, label, created_at, last_used, announces) = databases::key_into_row(auth_key);
+
+        self.with_connection(move |conn| {
+            match conn.execute(
+                "INSERT INTO keys (key, valid_until, label, created_at, last_used, announces) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[&key, &valid_until, &label, &created_at, &last_used, &announces],
+            ) {
+                Ok(updated) => {
+                    if updated > 0 {
+                        return Ok(usize::try_from(updated).unwrap_or(usize::MAX));
+                    }
+                    Err(Error::NotFound)
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await
+    }
+
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error> {
+        let rows: Vec<KeyRow> = auth_keys.iter().map(databases::key_into_row).collect();
+
+        self.with_connection(move |conn| {
+            let mut tx = conn.transaction()?;
+            let mut inserted = 0;
+
+            for (key, valid_until, label, created_at, last_used, announces) in &rows {
+                inserted += tx.execute(
+                    "INSERT INTO keys (key, valid_until, label, created_at, last_used, announces) VALUES ($1, $2, $3, $4, $5, $6)",
+                    &[key, valid_until, label, created_at, last_used, announces],
+                )?;
+            }
+
+            tx.commit()?;
+
+            Ok(usize::try_from(inserted).unwrap_or(usize::MAX))
+        })
+        .await
+    }
+
+    async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error> {
+        let key = key.to_string();
+
+        self.with_connection(move |conn| match conn.execute("DELETE FROM keys WHERE key = $1", &[&key]) {
+            Ok(updated) => {
+                if updated > 0 {
+                    return Ok(usize::try_from(updated).unwrap_or(usize::MAX));
+                }
+                Err(Error::NotFound)
+            }
+            Err(e) => Err(e.into()),
+        })
+        .await
+    }
+
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error> {
+        let key = key.to_string();
+        let valid_until = valid_until.map(databases::secs_into_column);
+
+        self.with_connection(move |conn| {
+            match conn.execute("UPDATE keys SET valid_until = $1 WHERE key = $2", &[&valid_until, &key])? {
+                0 => Err(Error::NotFound),
+                updated => Ok(usize::try_from(updated).unwrap_or(usize::MAX)),
+            }
+        })
+        .await
+    }
+
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error> {
+        let now = i64::try_from(now.as_secs()).unwrap_or(i64::MAX);
+
+        self.with_connection(move |conn| {
+            let removed = conn.execute("DELETE FROM keys WHERE valid_until < $1", &[&now])?;
+
+            Ok(usize::try_from(removed).unwrap_or(usize::MAX))
+        })
+        .await
+    }
+
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error> {
+        let mut keys = Vec::with_capacity(auth_keys.len());
+        let mut last_used = Vec::with_capacity(auth_keys.len());
+        let mut announces = Vec::with_capacity(auth_keys.len());
+
+        for auth_key in auth_keys {
+            let (key, _, _, _, key_last_used, key_announces) = databases::key_into_row(auth_key);
+            keys.push(key);
+            last_used.push(key_last_used);
+            announces.push(key_announces);
+        }
+
+        self.with_connection(move |conn| {
+            conn.execute(
+                "UPDATE keys SET last_used = usage.last_used, announces = usage.announces FROM UNNEST($1::VARCHAR[], $2::BIGINT[], $3::BIGINT[]) AS usage (key, last_used, announces) WHERE keys.key = usage.key",
+                &[&keys, &last_used, &announces],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+}
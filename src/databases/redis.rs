@@ -0,0 +1,505 @@
+//! A `Database` backed by `Redis`, so several tracker instances can share
+//! the whitelist, the blacklist, the keys and the torrents stats.
+//!
+//! Other instances' changes to the lists and the keys are picked up when
+//! they are reloaded through the API. The peers snapshot replaces the stored
+//! one, so it should only be enabled in one of the instances.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+use r2d2::Pool;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::databases::metrics::Metrics;
+use crate::databases::{self, Database, Error, PeerRow, PoolState};
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{audit, auth, peer};
+
+const TORRENTS: &str = "torrust:torrents";
+const KEYS: &str = "torrust:keys";
+const WHITELIST: &str = "torrust:whitelist";
+const BLACKLIST: &str = "torrust:blacklist";
+const PEERS: &str = "torrust:peers";
+/// Prefix of the sorted sets, one per torrent, holding the completed events
+/// scored by their timestamp.
+const COMPLETED_EVENTS: &str = "torrust:completed_events";
+/// A list of the audit log entries, from the oldest, each one a JSON array of
+/// the [`databases::AuditLogRow`] columns.
+const AUDIT_LOG: &str = "torrust:audit_log";
+
+/// The value of a key in the [`KEYS`] hash. Keys added before the metadata
+/// was introduced only stored the `valid_until` timestamp. The keys never
+/// expiring have no `valid_until`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredKey {
+    Metadata {
+        valid_until: Option<u64>,
+        label: Option<String>,
+        created_at: Option<u64>,
+        last_used: Option<u64>,
+        announces: u64,
+    },
+    ValidUntil(u64),
+}
+
+impl StoredKey {
+    fn into_key(self, key: String) -> auth::Key {
+        match self {
+            StoredKey::Metadata {
+                valid_until,
+                label,
+                created_at,
+                last_used,
+                announces,
+            } => auth::Key {
+                key,
+                valid_until: valid_until.map(Duration::from_secs),
+                label,
+                created_at: created_at.map(Duration::from_secs),
+                last_used: last_used.map(Duration::from_secs),
+                announces,
+            },
+            StoredKey::ValidUntil(valid_until) => auth::Key {
+                key,
+                valid_until: Some(Duration::from_secs(valid_until)),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<&auth::Key> for StoredKey {
+    fn from(auth_key: &auth::Key) -> Self {
+        StoredKey::Metadata {
+            valid_until: auth_key.valid_until.map(|valid_until| valid_until.as_secs()),
+            label: auth_key.label.clone(),
+            created_at: auth_key.created_at.map(|created_at| created_at.as_secs()),
+            last_used: auth_key.last_used.map(|last_used| last_used.as_secs()),
+            announces: auth_key.announces,
+        }
+    }
+}
+
+pub struct Redis {
+    pool: Pool<redis::Client>,
+}
+
+impl Redis {
+    /// # Errors
+    ///
+    /// Will return `r2d2::Error` if `db_path` is not able to create `Redis` connection pool.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `db_path` is not a valid `Redis` connection URL.
+    pub fn new(db_path: &str, settings: &config::Database, metrics: &Arc<Metrics>) -> Result<Self, r2d2::Error> {
+        let client = redis::Client::open(db_path).expect("Failed to parse Redis connection URL.");
+        let pool = databases::pool_builder(settings, metrics).build(client)?;
+
+        Ok(Self { pool })
+    }
+
+    fn key_from_value(key: String, value: &str) -> Option<auth::Key> {
+        serde_json::from_str::<StoredKey>(value)
+            .ok()
+            .map(|stored_key| stored_key.into_key(key))
+    }
+
+    fn key_into_value(auth_key: &auth::Key) -> String {
+        serde_json::to_string(&StoredKey::from(auth_key)).unwrap_or_default()
+    }
+
+    fn peer_field(info_hash: &InfoHash, peer: &peer::Peer) -> String {
+        format!("{}:{}", info_hash, peer.peer_id.get_id().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl Database for Redis {
+    /// There is no schema in `Redis`, it only checks the server is reachable.
+    fn create_database_tables(&self) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        redis::cmd("PING").query::<String>(&mut *conn)?;
+
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        redis::cmd("PING").query::<String>(&mut *conn)?;
+
+        Ok(())
+    }
+
+    fn pool_state(&self) -> Option<PoolState> {
+        Some(PoolState::of(&self.pool))
+    }
+
+    async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let torrents: Vec<(String, u32)> = conn.hgetall(TORRENTS)?;
+
+        Ok(torrents
+            .into_iter()
+            .filter_map(|(info_hash, completed)| Some((InfoHash::from_str(&info_hash).ok()?, completed)))
+            .collect())
+    }
+
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error> {
+        let mut conn = self.pool.get()?;
+
+        Ok(conn.hget(TORRENTS, info_hash.to_string())?)
+    }
+
+    async fn load_keys(&self) -> Result<Vec<auth::Key>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let keys: Vec<(String, String)> = conn.hgetall(KEYS)?;
+
+        Ok(keys
+            .into_iter()
+            .filter_map(|(key, value)| Self::key_from_value(key, &value))
+            .collect())
+    }
+
+    async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let info_hashes: Vec<String> = conn.smembers(WHITELIST)?;
+
+        Ok(info_hashes
+            .iter()
+            .filter_map(|info_hash| InfoHash::from_str(info_hash).ok())
+            .collect())
+    }
+
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let ranges: Vec<String> = conn.smembers(BLACKLIST)?;
+
+        Ok(ranges.iter().filter_map(|range| IpNet::from_str(range).ok()).collect())
+    }
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let rows: Vec<String> = conn.hvals(PEERS)?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| serde_json::from_str::<PeerRow>(row).ok())
+            .filter_map(databases::peer_from_row)
+            .collect())
+    }
+
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let fields: Vec<(String, String)> = peers
+            .iter()
+            .map(|(info_hash, peer)| {
+                (
+                    Self::peer_field(info_hash, peer),
+                    serde_json::to_string(&databases::peer_into_row(info_hash, peer)).unwrap_or_default(),
+                )
+            })
+            .collect();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(PEERS).ignore();
+        if !fields.is_empty() {
+            pipe.hset_multiple(PEERS, &fields).ignore();
+        }
+        pipe.query::<()>(&mut *conn)?;
+
+        Ok(())
+    }
+
+    async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        conn.hset::<_, _, _, ()>(TORRENTS, info_hash.to_string(), completed)?;
+
+        Ok(())
+    }
+
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        Ok(conn.hdel(TORRENTS, info_hash.to_string())?)
+    }
+
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error> {
+        if torrents.is_empty() {
+            return Ok(());
+        }
+
+        let mut conn = self.pool.get()?;
+
+        let fields: Vec<(String, u32)> = torrents
+            .iter()
+            .map(|(info_hash, completed)| (info_hash.to_string(), *completed))
+            .collect();
+
+        conn.hset_multiple::<_, _, _, ()>(TORRENTS, &fields)?;
+
+        Ok(())
+    }
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let (info_hash, peer_id_hash, completed_at) = databases::completed_event_into_row(info_hash, peer_id, completed_at);
+
+        conn.zadd::<_, _, _, ()>(
+            format!("{COMPLETED_EVENTS}:{info_hash}"),
+            format!("{completed_at}:{peer_id_hash}"),
+            completed_at,
+        )?;
+
+        Ok(())
+    }
+
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let events: Vec<(String, u64)> =
+            conn.zrangebyscore_withscores(format!("{COMPLETED_EVENTS}:{info_hash}"), since.as_secs(), "+inf")?;
+
+        Ok(events
+            .into_iter()
+            .map(|(_, completed_at)| DurationSinceUnixEpoch::from_secs(completed_at))
+            .collect())
+    }
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let value = serde_json::to_string(&databases::audit_log_entry_into_row(entry)).unwrap_or_default();
+
+        conn.rpush::<_, _, ()>(AUDIT_LOG, value)?;
+
+        Ok(())
+    }
+
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error> {
+        let mut conn = self.pool.get()?;
+
+        let values: Vec<String> = conn.lrange(AUDIT_LOG, 0, -1)?;
+
+        Ok(values
+            .iter()
+            .rev()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .map(databases::audit_log_entry_from_row)
+            .filter(|entry| query.matches(entry))
+            .skip(query.offset)
+            .take(query.limit)
+            .collect())
+    }
+
+    async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error> {
+        let mut conn = self.pool.get()?;
+
+        if conn.sismember(WHITELIST, info_hash)? {
+            InfoHash::from_str(info_hash).map_err(|_| Error::NotFound)
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        match conn.sadd(WHITELIST, info_hash.to_string())? {
+            0 => Err(Error::ConstraintViolation {
+                source: format!("info_hash {info_hash} is already whitelisted").into(),
+            }),
+            added => Ok(added),
+        }
+    }
+
+    async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        match conn.srem(WHITELIST, info_hash.to_string())? {
+            0 => Err(Error::NotFound),
+            removed => Ok(removed),
+        }
+    }
+
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        if info_hashes.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.pool.get()?;
+
+        let info_hashes: Vec<String> = info_hashes.iter().map(ToString::to_string).collect();
+
+        Ok(conn.sadd(WHITELIST, info_hashes)?)
+    }
+
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        if info_hashes.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.pool.get()?;
+
+        let info_hashes: Vec<String> = info_hashes.iter().map(ToString::to_string).collect();
+
+        Ok(conn.srem(WHITELIST, info_hashes)?)
+    }
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        match conn.sadd(BLACKLIST, range.to_string())? {
+            0 => Err(Error::ConstraintViolation {
+                source: format!("range {range} is already blacklisted").into(),
+            }),
+            added => Ok(added),
+        }
+    }
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        match conn.srem(BLACKLIST, range.to_string())? {
+            0 => Err(Error::NotFound),
+            removed => Ok(removed),
+        }
+    }
+
+    async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error> {
+        let mut conn = self.pool.get()?;
+
+        conn.hget::<_, _, Option<String>>(KEYS, key)?
+            .and_then(|value| Self::key_from_value(key.to_string(), &value))
+            .ok_or(Error::NotFound)
+    }
+
+    async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        if conn.hset_nx(KEYS, &auth_key.key, Self::key_into_value(auth_key))? {
+            Ok(1)
+        } else {
+            Err(Error::ConstraintViolation {
+                source: format!("key {} already exists", auth_key.key).into(),
+            })
+        }
+    }
+
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error> {
+        if auth_keys.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.pool.get()?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for auth_key in auth_keys {
+            pipe.hset_nx(KEYS, &auth_key.key, Self::key_into_value(auth_key));
+        }
+        let added: Vec<bool> = pipe.query(&mut *conn)?;
+
+        match added.iter().position(|added| !added) {
+            Some(index) => Err(Error::ConstraintViolation {
+                source: format!("key {} already exists", auth_keys[index].key).into(),
+            }),
+            None => Ok(added.len()),
+        }
+    }
+
+    async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        match conn.hdel(KEYS, key)? {
+            0 => Err(Error::NotFound),
+            removed => Ok(removed),
+        }
+    }
+
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        let mut auth_key = conn
+            .hget::<_, _, Option<String>>(KEYS, key)?
+            .and_then(|value| Self::key_from_value(key.to_string(), &value))
+            .ok_or(Error::NotFound)?;
+
+        auth_key.valid_until = valid_until;
+        conn.hset::<_, _, _, ()>(KEYS, key, Self::key_into_value(&auth_key))?;
+
+        Ok(1)
+    }
+
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        let keys: Vec<(String, String)> = conn.hgetall(KEYS)?;
+
+        let expired_keys: Vec<String> = keys
+            .into_iter()
+            .filter_map(|(key, value)| Self::key_from_value(key, &value))
+            .filter(|auth_key| auth_key.valid_until.is_some_and(|valid_until| valid_until < now))
+            .map(|auth_key| auth_key.key)
+            .collect();
+
+        if expired_keys.is_empty() {
+            return Ok(0);
+        }
+
+        Ok(conn.hdel(KEYS, expired_keys)?)
+    }
+
+    /// The usage counters are stored with the rest of the key metadata, so each stored key is
+    /// read and written back. Keys removed in the meantime are not added back.
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let mut fields = Vec::with_capacity(auth_keys.len());
+
+        for auth_key in auth_keys {
+            let stored_key = conn
+                .hget::<_, _, Option<String>>(KEYS, &auth_key.key)?
+                .and_then(|value| Self::key_from_value(auth_key.key.clone(), &value));
+
+            if let Some(mut stored_key) = stored_key {
+                stored_key.last_used = auth_key.last_used;
+                stored_key.announces = auth_key.announces;
+                fields.push((stored_key.key.clone(), Self::key_into_value(&stored_key)));
+            }
+        }
+
+        if !fields.is_empty() {
+            conn.hset_multiple::<_, _, _, ()>(KEYS, &fields)?;
+        }
+
+        Ok(())
+    }
+}
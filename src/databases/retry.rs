@@ -0,0 +1,274 @@
+//! A `Database` wrapper retrying the operations that fail with a transient
+//! error, like a lost connection, with an exponential backoff.
+//!
+//! Permanent errors, like constraint violations or missing records, are
+//! returned right away.
+
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ipnet::IpNet;
+use log::warn;
+
+use crate::config;
+use crate::databases::{metrics, Database, Error, PoolState};
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{audit, auth, peer};
+
+pub struct Retry {
+    database: Box<dyn Database>,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl Retry {
+    #[must_use]
+    pub fn new(database: Box<dyn Database>, settings: &config::Database) -> Self {
+        Self {
+            database,
+            max_attempts: settings.max_attempts,
+            backoff: Duration::from_millis(settings.retry_backoff),
+        }
+    }
+
+    async fn retry<T, F, Fut>(&self, operation: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<T, Error>> + Send,
+    {
+        retry(self.max_attempts, self.backoff, operation).await
+    }
+}
+
+/// Runs the `operation` up to `max_attempts` times while it fails with a transient error,
+/// waiting `backoff` before the first retry and doubling it before every following one.
+async fn retry<T, F, Fut>(max_attempts: u32, mut backoff: Duration, mut operation: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match operation().await {
+            Err(e) if e.is_transient() && attempt < max_attempts => {
+                warn!(
+                    "Database operation failed (attempt {} of {}), retrying in {:?}: {}",
+                    attempt, max_attempts, backoff, e
+                );
+
+                tokio::time::sleep(backoff).await;
+
+                backoff = backoff.saturating_mul(2);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[async_trait]
+impl Database for Retry {
+    fn create_database_tables(&self) -> Result<(), Error> {
+        self.database.create_database_tables()
+    }
+
+    /// It is not retried, so health checks report the failure right away.
+    async fn ping(&self) -> Result<(), Error> {
+        self.database.ping().await
+    }
+
+    fn pool_state(&self) -> Option<PoolState> {
+        self.database.pool_state()
+    }
+
+    fn metrics(&self) -> Option<metrics::Snapshot> {
+        self.database.metrics()
+    }
+
+    async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error> {
+        self.retry(|| self.database.load_persistent_torrents()).await
+    }
+
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error> {
+        self.retry(|| self.database.load_persistent_torrent(info_hash)).await
+    }
+
+    async fn load_keys(&self) -> Result<Vec<auth::Key>, Error> {
+        self.retry(|| self.database.load_keys()).await
+    }
+
+    async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error> {
+        self.retry(|| self.database.load_whitelist()).await
+    }
+
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error> {
+        self.retry(|| self.database.load_blacklist()).await
+    }
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error> {
+        self.retry(|| self.database.load_peers()).await
+    }
+
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error> {
+        self.retry(|| self.database.save_peers(peers)).await
+    }
+
+    async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error> {
+        self.retry(|| self.database.save_persistent_torrent(info_hash, completed))
+            .await
+    }
+
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error> {
+        self.retry(|| self.database.save_persistent_torrents(torrents)).await
+    }
+
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error> {
+        self.retry(|| self.database.remove_persistent_torrent(info_hash)).await
+    }
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error> {
+        self.retry(|| self.database.add_completed_event(info_hash, peer_id, completed_at))
+            .await
+    }
+
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error> {
+        self.retry(|| self.database.load_completed_events(info_hash, since)).await
+    }
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error> {
+        self.retry(|| self.database.add_audit_log_entry(entry)).await
+    }
+
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error> {
+        self.retry(|| self.database.load_audit_log(query)).await
+    }
+
+    async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error> {
+        self.retry(|| self.database.get_info_hash_from_whitelist(info_hash)).await
+    }
+
+    async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        self.retry(|| self.database.add_info_hash_to_whitelist(info_hash)).await
+    }
+
+    async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
+        self.retry(|| self.database.remove_info_hash_from_whitelist(info_hash)).await
+    }
+
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        self.retry(|| self.database.add_info_hashes_to_whitelist(info_hashes)).await
+    }
+
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        self.retry(|| self.database.remove_info_hashes_from_whitelist(info_hashes))
+            .await
+    }
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        self.retry(|| self.database.add_range_to_blacklist(range)).await
+    }
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        self.retry(|| self.database.remove_range_from_blacklist(range)).await
+    }
+
+    async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error> {
+        self.retry(|| self.database.get_key_from_keys(key)).await
+    }
+
+    async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error> {
+        self.retry(|| self.database.add_key_to_keys(auth_key)).await
+    }
+
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error> {
+        self.retry(|| self.database.add_keys_to_keys(auth_keys)).await
+    }
+
+    async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error> {
+        self.retry(|| self.database.remove_key_from_keys(key)).await
+    }
+
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error> {
+        self.retry(|| self.database.update_key_valid_until(key, valid_until)).await
+    }
+
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error> {
+        self.retry(|| self.database.remove_expired_keys(now)).await
+    }
+
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error> {
+        self.retry(|| self.database.save_keys_usage(auth_keys)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use super::retry;
+    use crate::databases::Error;
+
+    fn transient_error() -> Error {
+        Error::Connection {
+            source: "connection reset".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_retry_an_operation_failing_with_a_transient_error() {
+        let attempts = AtomicU32::new(0);
+
+        let result = retry(3, Duration::ZERO, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(transient_error())
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn it_should_give_up_after_the_max_attempts() {
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), Error> = retry(3, Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(transient_error())
+        })
+        .await;
+
+        assert!(matches!(result, Err(Error::Connection { .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn it_should_not_retry_an_operation_failing_with_a_permanent_error() {
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), Error> = retry(3, Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Error::NotFound)
+        })
+        .await;
+
+        assert!(matches!(result, Err(Error::NotFound)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
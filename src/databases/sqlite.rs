@@ -1,14 +1,23 @@
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use async_trait::async_trait;
-use log::debug;
+use chrono::Utc;
+use ipnet::IpNet;
+use log::info;
 use r2d2::Pool;
+use r2d2_sqlite::rusqlite::{params, Connection, DatabaseName, OpenFlags};
 use r2d2_sqlite::SqliteConnectionManager;
 
-use crate::databases::{Database, Error};
-use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::config::{self, JournalMode, Synchronous};
+use crate::databases::metrics::Metrics;
+use crate::databases::migrations::{self, Migration};
+use crate::databases::{self, Database, Error, PoolState};
+use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Time};
 use crate::protocol::info_hash::InfoHash;
-use crate::tracker::auth;
+use crate::tracker::{audit, auth, peer};
 
 pub struct Sqlite {
     pool: Pool<SqliteConnectionManager>,
@@ -18,50 +27,288 @@ impl Sqlite {
     /// # Errors
     ///
     /// Will return `r2d2::Error` if `db_path` is not able to create `SqLite` database.
-    pub fn new(db_path: &str) -> Result<Sqlite, r2d2::Error> {
-        let cm = SqliteConnectionManager::file(db_path);
-        let pool = Pool::new(cm).expect("Failed to create r2d2 SQLite connection pool.");
+    pub fn new(db_path: &str, settings: &config::Database, metrics: &Arc<Metrics>) -> Result<Sqlite, r2d2::Error> {
+        let pragmas = Self::pragmas(&settings.sqlite);
+        let cm = SqliteConnectionManager::file(db_path).with_init(move |conn| conn.execute_batch(&pragmas));
+        let pool = databases::pool_builder(settings, metrics)
+            .build(cm)
+            .expect("Failed to create r2d2 SQLite connection pool.");
         Ok(Sqlite { pool })
     }
-}
 
-#[async_trait]
-impl Database for Sqlite {
-    fn create_database_tables(&self) -> Result<(), Error> {
-        let create_whitelist_table = "
+    /// Pragmas applied on every new pooled connection.
+    fn pragmas(settings: &config::Sqlite) -> String {
+        let journal_mode = match settings.journal_mode {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        };
+
+        let synchronous = match settings.synchronous {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        };
+
+        format!(
+            "PRAGMA journal_mode = {journal_mode};
+            PRAGMA synchronous = {synchronous};
+            PRAGMA busy_timeout = {};
+            PRAGMA cache_size = {};",
+            settings.busy_timeout, settings.cache_size
+        )
+    }
+
+    fn migrations() -> Vec<Migration> {
+        vec![
+            Migration::new(
+                1,
+                "create whitelist, keys and torrents tables",
+                vec![
+                    "
         CREATE TABLE IF NOT EXISTS whitelist (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             info_hash TEXT NOT NULL UNIQUE
         );"
-        .to_string();
-
-        let create_torrents_table = "
+                    .to_string(),
+                    "
+        CREATE TABLE IF NOT EXISTS keys (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            key TEXT NOT NULL UNIQUE,
+            valid_until INTEGER NOT NULL
+         );"
+                    .to_string(),
+                    "
         CREATE TABLE IF NOT EXISTS torrents (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             info_hash TEXT NOT NULL UNIQUE,
             completed INTEGER DEFAULT 0 NOT NULL
         );"
-        .to_string();
-
-        let create_keys_table = "
-        CREATE TABLE IF NOT EXISTS keys (
+                    .to_string(),
+                ],
+            ),
+            Migration::new(
+                2,
+                "create torrent_peers table",
+                vec!["
+        CREATE TABLE IF NOT EXISTS torrent_peers (
+            info_hash TEXT NOT NULL,
+            peer_id TEXT NOT NULL,
+            peer_addr TEXT NOT NULL,
+            updated INTEGER NOT NULL,
+            uploaded INTEGER NOT NULL,
+            downloaded INTEGER NOT NULL,
+            left_bytes INTEGER NOT NULL,
+            event INTEGER NOT NULL,
+            PRIMARY KEY (info_hash, peer_id)
+        );"
+                .to_string()],
+            ),
+            Migration::new(
+                3,
+                "add label and usage columns to keys table",
+                vec![
+                    "ALTER TABLE keys ADD COLUMN label TEXT;".to_string(),
+                    "ALTER TABLE keys ADD COLUMN created_at INTEGER;".to_string(),
+                    "ALTER TABLE keys ADD COLUMN last_used INTEGER;".to_string(),
+                    "ALTER TABLE keys ADD COLUMN announces INTEGER DEFAULT 0 NOT NULL;".to_string(),
+                ],
+            ),
+            Migration::new(
+                4,
+                "create torrent_completed_events table",
+                vec![
+                    "CREATE TABLE IF NOT EXISTS torrent_completed_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            info_hash TEXT NOT NULL,
+            peer_id_hash TEXT NOT NULL,
+            completed_at INTEGER NOT NULL
+        );"
+                    .to_string(),
+                    "CREATE INDEX IF NOT EXISTS torrent_completed_events_info_hash ON torrent_completed_events (info_hash, completed_at);"
+                        .to_string(),
+                ],
+            ),
+            Migration::new(
+                5,
+                "create blacklist table",
+                vec!["
+        CREATE TABLE IF NOT EXISTS blacklist (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ip_range TEXT NOT NULL UNIQUE
+        );"
+                .to_string()],
+            ),
+            // SQLite can not drop the `NOT NULL` of a column, so the table is copied into a new one.
+            Migration::new(
+                6,
+                "allow keys never expiring",
+                vec![
+                    "
+        CREATE TABLE keys_new (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             key TEXT NOT NULL UNIQUE,
-            valid_until INTEGER NOT NULL
-         );"
-        .to_string();
+            valid_until INTEGER,
+            label TEXT,
+            created_at INTEGER,
+            last_used INTEGER,
+            announces INTEGER DEFAULT 0 NOT NULL
+        );"
+                    .to_string(),
+                    "INSERT INTO keys_new SELECT id, key, valid_until, label, created_at, last_used, announces FROM keys;"
+                        .to_string(),
+                    "DROP TABLE keys;".to_string(),
+                    "ALTER TABLE keys_new RENAME TO keys;".to_string(),
+                ],
+            ),
+            Migration::new(
+                7,
+                "create audit_log table",
+                vec![
+                    "
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at INTEGER NOT NULL,
+            token_name TEXT NOT NULL,
+            ip TEXT,
+            method TEXT NOT NULL,
+            path TEXT NOT NULL,
+            status INTEGER NOT NULL
+        );"
+                    .to_string(),
+                    "CREATE INDEX IF NOT EXISTS audit_log_created_at ON audit_log (created_at);".to_string(),
+                ],
+            ),
+        ]
+    }
+}
+
+/// Timestamp appended to the database file name to name its backups.
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// Copies the database in `db_path` into a new timestamped file in the `backup_path` directory,
+/// then removes the oldest backups beyond `backup_retention`.
+///
+/// It uses `SQLite`'s online backup API, so the tracker keeps using the database meanwhile.
+///
+/// # Errors
+///
+/// Will return `Error` if unable to read the database or to write the backup.
+pub fn backup(db_path: &str, settings: &config::Sqlite) -> Result<PathBuf, Error> {
+    let backup_dir = Path::new(&settings.backup_path);
+    fs::create_dir_all(backup_dir)?;
+
+    let (stem, extension) = backup_file_name_parts(Path::new(db_path));
+    let backup_path = backup_dir.join(format!("{stem}_{}.{extension}", Utc::now().format(BACKUP_TIMESTAMP_FORMAT)));
+
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    conn.backup(DatabaseName::Main, &backup_path, None)?;
+
+    if settings.backup_retention > 0 {
+        remove_old_backups(backup_dir, &stem, &extension, settings.backup_retention)?;
+    }
+
+    Ok(backup_path)
+}
+
+fn backup_file_name_parts(db_path: &Path) -> (String, String) {
+    let stem = db_path
+        .file_stem()
+        .map_or_else(|| "data".to_string(), |stem| stem.to_string_lossy().into_owned());
+    let extension = db_path
+        .extension()
+        .map_or_else(|| "db".to_string(), |extension| extension.to_string_lossy().into_owned());
+    (stem, extension)
+}
+
+/// Only the files named like the backups of this database are removed.
+fn remove_old_backups(backup_dir: &Path, stem: &str, extension: &str, retention: usize) -> Result<(), Error> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_dir)?
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(|file_name| file_name.strip_prefix(&format!("{stem}_")))
+                .and_then(|file_name| file_name.strip_suffix(&format!(".{extension}")))
+                .is_some_and(|timestamp| timestamp.len() == 14 && timestamp.chars().all(|c| c.is_ascii_digit()))
+        })
+        .collect();
+
+    // The timestamps sort chronologically
+    backups.sort();
+
+    for old_backup in backups.iter().take(backups.len().saturating_sub(retention)) {
+        fs::remove_file(old_backup)?;
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl Database for Sqlite {
+    fn create_database_tables(&self) -> Result<(), Error> {
+        let create_migrations_table = format!(
+            "
+        CREATE TABLE IF NOT EXISTS {} (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        );",
+            migrations::TABLE
+        );
+
+        let mut conn = self.pool.get()?;
+
+        conn.execute(&create_migrations_table, [])?;
+
+        let applied_versions: Vec<i64> = {
+            let mut stmt = conn.prepare(&format!("SELECT version FROM {}", migrations::TABLE))?;
+            let version_iter = stmt.query_map([], |row| row.get(0))?;
+            version_iter.collect::<Result<Vec<_>, _>>()?
+        };
+
+        for migration in migrations::pending(Self::migrations(), &applied_versions) {
+            let tx = conn.transaction()?;
+
+            for statement in &migration.statements {
+                tx.execute(statement, [])?;
+            }
+
+            tx.execute(
+                &format!(
+                    "INSERT INTO {} (version, description, applied_at) VALUES (?1, ?2, ?3)",
+                    migrations::TABLE
+                ),
+                params![migration.version, migration.description, Current::now().as_secs()],
+            )?;
+
+            tx.commit()?;
+
+            info!("Applied database migration {}: {}", migration.version, migration.description);
+        }
+
+        Ok(())
+    }
 
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+    async fn ping(&self) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+
+        conn.query_row("SELECT 1", [], |_| Ok(()))?;
+
+        Ok(())
+    }
 
-        conn.execute(&create_whitelist_table, [])
-            .and_then(|_| conn.execute(&create_keys_table, []))
-            .and_then(|_| conn.execute(&create_torrents_table, []))
-            .map_err(|_| Error::InvalidQuery)
-            .map(|_| ())
+    fn pool_state(&self) -> Option<PoolState> {
+        Some(PoolState::of(&self.pool))
     }
 
     async fn load_persistent_torrents(&self) -> Result<Vec<(InfoHash, u32)>, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         let mut stmt = conn.prepare("SELECT info_hash, completed FROM torrents")?;
 
@@ -77,28 +324,37 @@ impl Database for Sqlite {
         Ok(torrents)
     }
 
+    async fn load_persistent_torrent(&self, info_hash: &InfoHash) -> Result<Option<u32>, Error> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT completed FROM torrents WHERE info_hash = ?")?;
+        let mut rows = stmt.query([info_hash.to_string()])?;
+
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
     async fn load_keys(&self) -> Result<Vec<auth::Key>, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
-        let mut stmt = conn.prepare("SELECT key, valid_until FROM keys")?;
+        let mut stmt = conn.prepare("SELECT key, valid_until, label, created_at, last_used, announces FROM keys")?;
 
         let keys_iter = stmt.query_map([], |row| {
-            let key = row.get(0)?;
-            let valid_until: i64 = row.get(1)?;
-
-            Ok(auth::Key {
-                key,
-                valid_until: Some(DurationSinceUnixEpoch::from_secs(valid_until.unsigned_abs())),
-            })
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
         })?;
 
-        let keys: Vec<auth::Key> = keys_iter.filter_map(std::result::Result::ok).collect();
+        let keys: Vec<auth::Key> = keys_iter
+            .filter_map(std::result::Result::ok)
+            .map(databases::key_from_row)
+            .collect();
 
         Ok(keys)
     }
 
     async fn load_whitelist(&self) -> Result<Vec<InfoHash>, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         let mut stmt = conn.prepare("SELECT info_hash FROM whitelist")?;
 
@@ -113,8 +369,78 @@ impl Database for Sqlite {
         Ok(info_hashes)
     }
 
+    async fn load_blacklist(&self) -> Result<Vec<IpNet>, Error> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT ip_range FROM blacklist")?;
+
+        let range_iter = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let ranges: Vec<IpNet> = range_iter
+            .filter_map(std::result::Result::ok)
+            .filter_map(|range| IpNet::from_str(&range).ok())
+            .collect();
+
+        Ok(ranges)
+    }
+
+    async fn load_peers(&self) -> Result<Vec<(InfoHash, peer::Peer)>, Error> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left_bytes, event FROM torrent_peers",
+        )?;
+
+        let peer_iter = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
+            ))
+        })?;
+
+        let peers: Vec<(InfoHash, peer::Peer)> = peer_iter
+            .filter_map(std::result::Result::ok)
+            .filter_map(databases::peer_from_row)
+            .collect();
+
+        Ok(peers)
+    }
+
+    async fn save_peers(&self, peers: &[(InfoHash, peer::Peer)]) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM torrent_peers", [])?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO torrent_peers (info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left_bytes, event) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+
+            for (info_hash, peer) in peers {
+                let (info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left, event) =
+                    databases::peer_into_row(info_hash, peer);
+
+                stmt.execute(params![
+                    info_hash, peer_id, peer_addr, updated, uploaded, downloaded, left, event
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
     async fn save_persistent_torrent(&self, info_hash: &InfoHash, completed: u32) -> Result<(), Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         match conn.execute(
             "INSERT INTO torrents (info_hash, completed) VALUES (?1, ?2) ON CONFLICT(info_hash) DO UPDATE SET completed = ?2",
@@ -124,17 +450,129 @@ impl Database for Sqlite {
                 if updated > 0 {
                     return Ok(());
                 }
-                Err(Error::QueryReturnedNoRows)
+                Err(Error::NotFound)
             }
-            Err(e) => {
-                debug!("{:?}", e);
-                Err(Error::InvalidQuery)
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn remove_persistent_torrent(&self, info_hash: &InfoHash) -> Result<usize, Error> {
+        let conn = self.pool.get()?;
+
+        Ok(conn.execute("DELETE FROM torrents WHERE info_hash = ?", [info_hash.to_string()])?)
+    }
+
+    async fn save_persistent_torrents(&self, torrents: &[(InfoHash, u32)]) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO torrents (info_hash, completed) VALUES (?1, ?2) ON CONFLICT(info_hash) DO UPDATE SET completed = ?2",
+            )?;
+
+            for (info_hash, completed) in torrents {
+                stmt.execute(params![info_hash.to_string(), completed])?;
             }
         }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    async fn add_completed_event(
+        &self,
+        info_hash: &InfoHash,
+        peer_id: &peer::Id,
+        completed_at: DurationSinceUnixEpoch,
+    ) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+
+        let (info_hash, peer_id_hash, completed_at) = databases::completed_event_into_row(info_hash, peer_id, completed_at);
+
+        conn.execute(
+            "INSERT INTO torrent_completed_events (info_hash, peer_id_hash, completed_at) VALUES (?1, ?2, ?3)",
+            params![info_hash, peer_id_hash, completed_at],
+        )?;
+
+        Ok(())
+    }
+
+    async fn load_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, Error> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT completed_at FROM torrent_completed_events WHERE info_hash = ?1 AND completed_at >= ?2 ORDER BY completed_at",
+        )?;
+
+        let completed_at_iter = stmt.query_map(params![info_hash.to_string(), databases::secs_into_column(since)], |row| {
+            row.get::<_, i64>(0)
+        })?;
+
+        let events = completed_at_iter
+            .filter_map(std::result::Result::ok)
+            .map(|secs| DurationSinceUnixEpoch::from_secs(secs.unsigned_abs()))
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn add_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), Error> {
+        let conn = self.pool.get()?;
+
+        let (created_at, token_name, ip, method, path, status) = databases::audit_log_entry_into_row(entry);
+
+        conn.execute(
+            "INSERT INTO audit_log (created_at, token_name, ip, method, path, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![created_at, token_name, ip, method, path, status],
+        )?;
+
+        Ok(())
+    }
+
+    async fn load_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, Error> {
+        let conn = self.pool.get()?;
+
+        let (since, until) = databases::audit_log_query_range(query);
+
+        let mut stmt = conn.prepare(
+            "SELECT created_at, token_name, ip, method, path, status FROM audit_log
+            WHERE (?1 IS NULL OR token_name = ?1) AND (?2 IS NULL OR ip = ?2) AND created_at >= ?3 AND created_at <= ?4
+            ORDER BY id DESC LIMIT ?5 OFFSET ?6",
+        )?;
+
+        let entry_iter = stmt.query_map(
+            params![
+                query.token_name,
+                query.ip.map(|ip| ip.to_string()),
+                since,
+                until,
+                i64::try_from(query.limit).unwrap_or(i64::MAX),
+                i64::try_from(query.offset).unwrap_or(i64::MAX)
+            ],
+            |row| {
+                Ok(databases::audit_log_entry_from_row((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                )))
+            },
+        )?;
+
+        Ok(entry_iter.filter_map(std::result::Result::ok).collect())
     }
 
     async fn get_info_hash_from_whitelist(&self, info_hash: &str) -> Result<InfoHash, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         let mut stmt = conn.prepare("SELECT info_hash FROM whitelist WHERE info_hash = ?")?;
         let mut rows = stmt.query([info_hash])?;
@@ -142,102 +580,305 @@ impl Database for Sqlite {
         match rows.next() {
             Ok(row) => match row {
                 Some(row) => Ok(InfoHash::from_str(&row.get_unwrap::<_, String>(0)).unwrap()),
-                None => Err(Error::QueryReturnedNoRows),
+                None => Err(Error::NotFound),
             },
-            Err(e) => {
-                debug!("{:?}", e);
-                Err(Error::InvalidQuery)
-            }
+            Err(e) => Err(e.into()),
         }
     }
 
     async fn add_info_hash_to_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         match conn.execute("INSERT INTO whitelist (info_hash) VALUES (?)", [info_hash.to_string()]) {
             Ok(updated) => {
                 if updated > 0 {
                     return Ok(updated);
                 }
-                Err(Error::QueryReturnedNoRows)
-            }
-            Err(e) => {
-                debug!("{:?}", e);
-                Err(Error::InvalidQuery)
+                Err(Error::NotFound)
             }
+            Err(e) => Err(e.into()),
         }
     }
 
     async fn remove_info_hash_from_whitelist(&self, info_hash: InfoHash) -> Result<usize, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         match conn.execute("DELETE FROM whitelist WHERE info_hash = ?", [info_hash.to_string()]) {
             Ok(updated) => {
                 if updated > 0 {
                     return Ok(updated);
                 }
-                Err(Error::QueryReturnedNoRows)
+                Err(Error::NotFound)
             }
-            Err(e) => {
-                debug!("{:?}", e);
-                Err(Error::InvalidQuery)
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn add_info_hashes_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        let tx = conn.transaction()?;
+        let mut inserted = 0;
+
+        {
+            let mut stmt = tx.prepare("INSERT OR IGNORE INTO whitelist (info_hash) VALUES (?)")?;
+
+            for info_hash in info_hashes {
+                inserted += stmt.execute([info_hash.to_string()])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(inserted)
+    }
+
+    async fn remove_info_hashes_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+
+        {
+            let mut stmt = tx.prepare("DELETE FROM whitelist WHERE info_hash = ?")?;
+
+            for info_hash in info_hashes {
+                removed += stmt.execute([info_hash.to_string()])?;
             }
         }
+
+        tx.commit()?;
+
+        Ok(removed)
+    }
+
+    async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        let conn = self.pool.get()?;
+
+        Ok(conn.execute("INSERT INTO blacklist (ip_range) VALUES (?)", [range.to_string()])?)
+    }
+
+    async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<usize, Error> {
+        let conn = self.pool.get()?;
+
+        match conn.execute("DELETE FROM blacklist WHERE ip_range = ?", [range.to_string()])? {
+            0 => Err(Error::NotFound),
+            removed => Ok(removed),
+        }
     }
 
     async fn get_key_from_keys(&self, key: &str) -> Result<auth::Key, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
-        let mut stmt = conn.prepare("SELECT key, valid_until FROM keys WHERE key = ?")?;
+        let mut stmt =
+            conn.prepare("SELECT key, valid_until, label, created_at, last_used, announces FROM keys WHERE key = ?")?;
         let mut rows = stmt.query([key.to_string()])?;
 
         if let Some(row) = rows.next()? {
-            let key: String = row.get(0).unwrap();
-            let valid_until: i64 = row.get(1).unwrap();
-
-            Ok(auth::Key {
-                key,
-                valid_until: Some(DurationSinceUnixEpoch::from_secs(valid_until.unsigned_abs())),
-            })
+            Ok(databases::key_from_row((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            )))
         } else {
-            Err(Error::QueryReturnedNoRows)
+            Err(Error::NotFound)
         }
     }
 
     async fn add_key_to_keys(&self, auth_key: &auth::Key) -> Result<usize, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
+
+        let (key, valid_until, label, created_at, last_used, announces) = databases::key_into_row(auth_key);
 
         match conn.execute(
-            "INSERT INTO keys (key, valid_until) VALUES (?1, ?2)",
-            [auth_key.key.to_string(), auth_key.valid_until.unwrap().as_secs().to_string()],
+            "INSERT INTO keys (key, valid_until, label, created_at, last_used, announces) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![key, valid_until, label, created_at, last_used, announces],
         ) {
             Ok(updated) => {
                 if updated > 0 {
                     return Ok(updated);
                 }
-                Err(Error::QueryReturnedNoRows)
+                Err(Error::NotFound)
             }
-            Err(e) => {
-                debug!("{:?}", e);
-                Err(Error::InvalidQuery)
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn add_keys_to_keys(&self, auth_keys: &[auth::Key]) -> Result<usize, Error> {
+        let mut conn = self.pool.get()?;
+
+        let tx = conn.transaction()?;
+        let mut inserted = 0;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO keys (key, valid_until, label, created_at, last_used, announces) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+
+            for auth_key in auth_keys {
+                let (key, valid_until, label, created_at, last_used, announces) = databases::key_into_row(auth_key);
+                inserted += stmt.execute(params![key, valid_until, label, created_at, last_used, announces])?;
             }
         }
+
+        tx.commit()?;
+
+        Ok(inserted)
     }
 
     async fn remove_key_from_keys(&self, key: &str) -> Result<usize, Error> {
-        let conn = self.pool.get().map_err(|_| Error::DatabaseError)?;
+        let conn = self.pool.get()?;
 
         match conn.execute("DELETE FROM keys WHERE key = ?", [key]) {
             Ok(updated) => {
                 if updated > 0 {
                     return Ok(updated);
                 }
-                Err(Error::QueryReturnedNoRows)
+                Err(Error::NotFound)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update_key_valid_until(&self, key: &str, valid_until: Option<DurationSinceUnixEpoch>) -> Result<usize, Error> {
+        let conn = self.pool.get()?;
+
+        match conn.execute(
+            "UPDATE keys SET valid_until = ?1 WHERE key = ?2",
+            params![valid_until.map(databases::secs_into_column), key],
+        ) {
+            Ok(updated) => {
+                if updated > 0 {
+                    return Ok(updated);
+                }
+                Err(Error::NotFound)
             }
-            Err(e) => {
-                debug!("{:?}", e);
-                Err(Error::InvalidQuery)
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn remove_expired_keys(&self, now: DurationSinceUnixEpoch) -> Result<usize, Error> {
+        let conn = self.pool.get()?;
+
+        let removed = conn.execute("DELETE FROM keys WHERE valid_until < ?", [now.as_secs()])?;
+
+        Ok(removed)
+    }
+
+    async fn save_keys_usage(&self, auth_keys: &[auth::Key]) -> Result<(), Error> {
+        let mut conn = self.pool.get()?;
+
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare("UPDATE keys SET last_used = ?1, announces = ?2 WHERE key = ?3")?;
+
+            for auth_key in auth_keys {
+                let (key, _, _, _, last_used, announces) = databases::key_into_row(auth_key);
+
+                stmt.execute(params![last_used, announces, key])?;
             }
         }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use r2d2_sqlite::rusqlite;
+    use uuid::Uuid;
+
+    use super::{backup, backup_file_name_parts, Sqlite};
+    use crate::config::{self, JournalMode, Synchronous};
+    use crate::databases::Database;
+    use crate::protocol::info_hash::InfoHash;
+
+    #[test]
+    fn the_configured_pragmas_should_be_applied_on_every_pooled_connection() {
+        let db_path = env::temp_dir().join(format!("data_{}.db", Uuid::new_v4()));
+
+        let settings = config::Database {
+            sqlite: config::Sqlite {
+                journal_mode: JournalMode::Wal,
+                synchronous: Synchronous::Full,
+                busy_timeout: 1234,
+                cache_size: -4000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let database = Sqlite::new(db_path.to_str().unwrap(), &settings, &Arc::default()).unwrap();
+        let conn = database.pool.get().unwrap();
+
+        let pragma = |name: &str| -> String {
+            conn.query_row(&format!("PRAGMA {name}"), [], |row| row.get::<_, rusqlite::types::Value>(0))
+                .map(|value| match value {
+                    rusqlite::types::Value::Integer(value) => value.to_string(),
+                    rusqlite::types::Value::Text(value) => value,
+                    _ => String::new(),
+                })
+                .unwrap()
+        };
+
+        assert_eq!(pragma("journal_mode"), "wal");
+        assert_eq!(pragma("synchronous"), "2");
+        assert_eq!(pragma("busy_timeout"), "1234");
+        assert_eq!(pragma("cache_size"), "-4000");
+    }
+
+    #[tokio::test]
+    async fn it_should_back_up_the_database_and_keep_only_the_latest_backups() {
+        let db_path = env::temp_dir().join(format!("data_{}.db", Uuid::new_v4()));
+        let db_path = db_path.to_str().unwrap();
+        let backup_path = env::temp_dir().join(format!("backups_{}", Uuid::new_v4()));
+
+        let database = Sqlite::new(db_path, &config::Database::default(), &Arc::default()).unwrap();
+        database.create_database_tables().unwrap();
+        let info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+        database.add_info_hash_to_whitelist(info_hash).await.unwrap();
+
+        let (stem, _) = backup_file_name_parts(Path::new(db_path));
+        fs::create_dir_all(&backup_path).unwrap();
+        fs::write(backup_path.join(format!("{stem}_20200101000000.db")), "").unwrap();
+        fs::write(backup_path.join(format!("{stem}_20210101000000.db")), "").unwrap();
+        fs::write(backup_path.join("unrelated.db"), "").unwrap();
+
+        let settings = config::Sqlite {
+            backup_path: backup_path.to_str().unwrap().to_string(),
+            backup_retention: 2,
+            ..Default::default()
+        };
+
+        let backup = backup(db_path, &settings).unwrap();
+
+        let mut remaining: Vec<String> = fs::read_dir(&backup_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                format!("{stem}_20210101000000.db"),
+                backup.file_name().unwrap().to_string_lossy().into_owned(),
+                "unrelated.db".to_string()
+            ]
+        );
+
+        let backup_database = Sqlite::new(backup.to_str().unwrap(), &config::Database::default(), &Arc::default()).unwrap();
+        assert_eq!(backup_database.load_whitelist().await.unwrap(), vec![info_hash]);
     }
 }
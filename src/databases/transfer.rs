@@ -0,0 +1,99 @@
+//! Copies the tracker data from one `Database` into another, to switch the
+//! database driver without losing the whitelist, the keys or the torrents
+//! stats. The peers are not copied, they are announced again after a restart.
+
+use log::info;
+
+use crate::databases::{Database, Error};
+
+/// The number of records written into the target database.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub whitelist: usize,
+    pub keys: usize,
+    pub torrents: usize,
+}
+
+/// Records already in the `target` database are kept: whitelisted info hashes and keys
+/// are skipped, and the torrents stats are overwritten with the `source` ones.
+///
+/// # Errors
+///
+/// Will return `Error` if unable to read from the `source` database or to write into the `target` one.
+pub async fn transfer(source: &dyn Database, target: &dyn Database) -> Result<Summary, Error> {
+    let mut summary = Summary::default();
+
+    let whitelist = source.load_whitelist().await?;
+    summary.whitelist = target.add_info_hashes_to_whitelist(&whitelist).await?;
+    info!("Copied {} of {} whitelisted torrents", summary.whitelist, whitelist.len());
+
+    let keys = source.load_keys().await?;
+    for auth_key in &keys {
+        match target.add_key_to_keys(auth_key).await {
+            Ok(_) => summary.keys += 1,
+            Err(Error::ConstraintViolation { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    info!("Copied {} of {} keys", summary.keys, keys.len());
+
+    let torrents = source.load_persistent_torrents().await?;
+    target.save_persistent_torrents(&torrents).await?;
+    summary.torrents = torrents.len();
+    info!("Copied {} torrents stats", summary.torrents);
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::{transfer, Summary};
+    use crate::databases::memory::Memory;
+    use crate::databases::Database;
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::auth;
+
+    #[tokio::test]
+    async fn it_should_copy_the_whitelist_the_keys_and_the_torrents_stats() {
+        let info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+        let auth_key = auth::generate(Duration::from_secs(60));
+
+        let source = Memory::new();
+        source.add_info_hash_to_whitelist(info_hash).await.unwrap();
+        source.add_key_to_keys(&auth_key).await.unwrap();
+        source.save_persistent_torrent(&info_hash, 7).await.unwrap();
+
+        let target = Memory::new();
+
+        assert_eq!(
+            transfer(&source, &target).await.unwrap(),
+            Summary {
+                whitelist: 1,
+                keys: 1,
+                torrents: 1
+            }
+        );
+        assert_eq!(target.load_whitelist().await.unwrap(), vec![info_hash]);
+        assert_eq!(target.load_keys().await.unwrap(), vec![auth_key]);
+        assert_eq!(target.load_persistent_torrents().await.unwrap(), vec![(info_hash, 7)]);
+    }
+
+    #[tokio::test]
+    async fn it_should_skip_the_records_already_in_the_target_database() {
+        let info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+        let auth_key = auth::generate(Duration::from_secs(60));
+
+        let source = Memory::new();
+        source.add_info_hash_to_whitelist(info_hash).await.unwrap();
+        source.add_key_to_keys(&auth_key).await.unwrap();
+
+        let target = Memory::new();
+        target.add_info_hash_to_whitelist(info_hash).await.unwrap();
+        target.add_key_to_keys(&auth_key).await.unwrap();
+
+        assert_eq!(transfer(&source, &target).await.unwrap(), Summary::default());
+    }
+}
@@ -0,0 +1,111 @@
+//! Write-behind buffer for the torrents `completed` counters.
+//!
+//! Instead of issuing one upsert per `completed` announce, the counters are
+//! coalesced in memory and flushed to the database in a single transaction.
+
+use std::collections::HashMap;
+
+use tokio::sync::Mutex;
+
+use crate::protocol::info_hash::InfoHash;
+
+pub struct Buffer {
+    pending: Mutex<HashMap<InfoHash, u32>>,
+    flush_threshold: usize,
+}
+
+impl Buffer {
+    #[must_use]
+    pub fn new(flush_threshold: usize) -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            flush_threshold,
+        }
+    }
+
+    /// Buffers the latest `completed` counter for the torrent.
+    ///
+    /// Returns `true` when the buffer has reached the flush threshold.
+    pub async fn push(&self, info_hash: InfoHash, completed: u32) -> bool {
+        let mut pending = self.pending.lock().await;
+
+        pending
+            .entry(info_hash)
+            .and_modify(|buffered| *buffered = (*buffered).max(completed))
+            .or_insert(completed);
+
+        pending.len() >= self.flush_threshold
+    }
+
+    /// Takes all the buffered counters, leaving the buffer empty.
+    pub async fn take(&self) -> Vec<(InfoHash, u32)> {
+        self.pending.lock().await.drain().collect()
+    }
+
+    /// Puts back counters that could not be flushed, unless a newer value was buffered meanwhile.
+    pub async fn restore(&self, counters: Vec<(InfoHash, u32)>) {
+        let mut pending = self.pending.lock().await;
+
+        for (info_hash, completed) in counters {
+            pending.entry(info_hash).or_insert(completed);
+        }
+    }
+
+    /// Drops the buffered counter of a removed torrent, so it is not saved again.
+    pub async fn remove(&self, info_hash: &InfoHash) {
+        self.pending.lock().await.remove(info_hash);
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.pending.lock().await.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Buffer;
+    use crate::protocol::info_hash::InfoHash;
+
+    fn sample_info_hash() -> InfoHash {
+        InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_should_coalesce_the_updates_for_the_same_torrent_keeping_the_highest_counter() {
+        let buffer = Buffer::new(10);
+
+        buffer.push(sample_info_hash(), 2).await;
+        buffer.push(sample_info_hash(), 1).await;
+
+        assert_eq!(buffer.take().await, vec![(sample_info_hash(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn it_should_ask_for_a_flush_when_the_threshold_is_reached() {
+        let buffer = Buffer::new(1);
+
+        assert!(buffer.push(sample_info_hash(), 1).await);
+    }
+
+    #[tokio::test]
+    async fn it_should_be_empty_after_taking_the_buffered_counters() {
+        let buffer = Buffer::new(10);
+
+        buffer.push(sample_info_hash(), 1).await;
+        let _ = buffer.take().await;
+
+        assert!(buffer.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn restoring_counters_should_not_overwrite_newer_buffered_values() {
+        let buffer = Buffer::new(10);
+
+        buffer.push(sample_info_hash(), 3).await;
+        buffer.restore(vec![(sample_info_hash(), 2)]).await;
+
+        assert_eq!(buffer.take().await, vec![(sample_info_hash(), 3)]);
+    }
+}
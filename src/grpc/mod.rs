@@ -0,0 +1,9 @@
+//! The management operations of the REST API over gRPC, for the integrators wanting typed clients and the stats
+//! streamed instead of polled. The service is defined in `proto/tracker.proto`.
+
+pub mod server;
+
+#[allow(clippy::pedantic)]
+pub mod proto {
+    tonic::include_proto!("torrust.tracker.v1");
+}
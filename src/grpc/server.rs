@@ -0,0 +1,498 @@
+use std::cmp::min;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{Future, Stream};
+use log::error;
+use tonic::{Code, Request, Response, Status};
+
+use super::proto::management_server::{Management, ManagementServer};
+use super::proto::{
+    BanIpRequest, BanIpResponse, BlacklistRequest, DeleteKeyRequest, Empty, GenerateKeyRequest, GetStatsRequest, InfoHashRequest,
+    Key, ListKeysRequest, ListKeysResponse, ListWhitelistRequest, ListWhitelistResponse, Stats, StreamStatsRequest,
+};
+use crate::api::rate_limit;
+use crate::databases;
+use crate::protocol::clock::{Current, Time};
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{self, audit, auth, blacklist, torrent_query};
+
+/// The management operations, with the requests authenticated by an `authorization: Bearer <access token>` metadata
+/// against the access tokens of the current configuration, so they can be reloaded. The tokens are checked like the
+/// ones of the REST API: the requests are rate limited, the IP addresses sending invalid tokens are locked out, and
+/// the changes are rejected when the API is read-only and recorded in the audit log.
+pub struct Service {
+    tracker: Arc<tracker::Tracker>,
+    guard: rate_limit::Guard,
+}
+
+// The errors are the `Status` the RPCs have to answer with
+#[allow(clippy::result_large_err)]
+impl Service {
+    #[must_use]
+    pub fn new(tracker: Arc<tracker::Tracker>) -> Self {
+        let guard = rate_limit::Guard::new(&tracker.config().http_api.rate_limit);
+        Self { tracker, guard }
+    }
+
+    /// The name of the access token of the `request`.
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<String, Status> {
+        let now = Current::now();
+        let ip = remote_ip(request);
+
+        if let Some(ip) = ip {
+            self.guard.check_ip(ip, now).await.map_err(rate_limited)?;
+        }
+
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let token_name = token.and_then(|token| {
+            self.tracker
+                .config()
+                .grpc_api
+                .access_tokens
+                .iter()
+                .find(|(_, access_token)| *access_token == token)
+                .map(|(name, _)| name.clone())
+        });
+
+        match (token, token_name) {
+            (Some(token), Some(token_name)) => {
+                if let Some(ip) = ip {
+                    self.guard.record_successful_attempt(ip).await;
+                }
+                self.guard.check_token(token, now).await.map_err(rate_limited)?;
+
+                Ok(token_name)
+            }
+            (Some(_), None) => {
+                if let Some(ip) = ip {
+                    self.guard.record_failed_attempt(ip, now).await;
+                }
+
+                Err(Status::unauthenticated("invalid token"))
+            }
+            (None, _) => Err(Status::unauthenticated("unauthorized")),
+        }
+    }
+
+    /// Makes the `change` of the `rpc` with the message of the authenticated `request`, unless the API is read-only,
+    /// and records it in the audit log.
+    async fn change<T, R, F>(&self, rpc: &str, request: Request<T>, change: impl FnOnce(T) -> F) -> Result<Response<R>, Status>
+    where
+        F: Future<Output = Result<R, Status>>,
+    {
+        let token_name = self.authenticate(&request).await?;
+        let ip = remote_ip(&request);
+
+        let result = if self.tracker.config().http_api.read_only {
+            Err(Status::permission_denied("the API is read-only"))
+        } else {
+            change(request.into_inner()).await
+        };
+
+        let entry = audit::Entry {
+            created_at: Current::now(),
+            token_name,
+            ip,
+            method: "POST".to_string(),
+            path: format!("/torrust.tracker.v1.Management/{rpc}"),
+            status: audit_status(&result),
+        };
+        if let Err(e) = self.tracker.record_audit_log_entry(&entry).await {
+            error!("Could not record {} {} in the audit log: {}", entry.method, entry.path, e);
+        }
+
+        result.map(Response::new)
+    }
+}
+
+/// The remote address is set by the `Server` of tonic.
+fn remote_ip<T>(request: &Request<T>) -> Option<IpAddr> {
+    request.remote_addr().map(|remote_addr| remote_addr.ip())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn rate_limited(e: rate_limit::Error) -> Status {
+    Status::resource_exhausted(e.to_string())
+}
+
+/// The status the REST API would answer with for the `result` of an RPC, for the audit log.
+fn audit_status<R>(result: &Result<R, Status>) -> u16 {
+    match result.as_ref().map_err(Status::code) {
+        Ok(_) => 200,
+        Err(Code::InvalidArgument) => 400,
+        Err(Code::Unauthenticated) => 401,
+        Err(Code::PermissionDenied) => 403,
+        Err(Code::NotFound) => 404,
+        Err(Code::AlreadyExists) => 409,
+        Err(Code::ResourceExhausted) => 429,
+        Err(Code::Unavailable) => 503,
+        Err(_) => 500,
+    }
+}
+
+/// Serves the gRPC API until the tracker is stopped.
+pub async fn start(socket_addr: SocketAddr, tracker: Arc<tracker::Tracker>) {
+    let service = ManagementServer::new(Service::new(tracker));
+
+    let server = tonic::transport::Server::builder()
+        .add_service(service)
+        .serve_with_shutdown(socket_addr, async move {
+            tokio::signal::ctrl_c().await.expect("Failed to listen to shutdown signal.");
+        });
+
+    if let Err(e) = server.await {
+        error!("Could not serve the gRPC API on {}: {}", socket_addr, e);
+    }
+}
+
+async fn stats(tracker: &tracker::Tracker) -> Stats {
+    let mut results = Stats::default();
+
+    for (_, (seeders, completed, leechers)) in tracker.get_torrents_stats().await {
+        results.seeders += u64::from(seeders);
+        results.completed += u64::from(completed);
+        results.leechers += u64::from(leechers);
+        results.torrents += 1;
+    }
+
+    let metrics = tracker.get_stats().await;
+
+    Stats {
+        tcp4_connections_handled: metrics.tcp4_connections_handled,
+        tcp4_announces_handled: metrics.tcp4_announces_handled,
+        tcp4_scrapes_handled: metrics.tcp4_scrapes_handled,
+        tcp6_connections_handled: metrics.tcp6_connections_handled,
+        tcp6_announces_handled: metrics.tcp6_announces_handled,
+        tcp6_scrapes_handled: metrics.tcp6_scrapes_handled,
+        udp4_connections_handled: metrics.udp4_connections_handled,
+        udp4_announces_handled: metrics.udp4_announces_handled,
+        udp4_scrapes_handled: metrics.udp4_scrapes_handled,
+        udp6_connections_handled: metrics.udp6_connections_handled,
+        udp6_announces_handled: metrics.udp6_announces_handled,
+        udp6_scrapes_handled: metrics.udp6_scrapes_handled,
+        torrents_evicted: metrics.torrents_evicted,
+        peers_evicted: metrics.peers_evicted,
+        udp_packets_dropped: metrics.udp_packets_dropped,
+        ..results
+    }
+}
+
+/// The status of the database `action`, like the REST API answers it.
+fn database_status(action: &str, e: &databases::error::Error) -> Status {
+    match e {
+        databases::error::Error::NotFound => Status::not_found(format!("could not {action}: not found")),
+        databases::error::Error::ConstraintViolation { .. } => {
+            Status::already_exists(format!("could not {action}: already exists"))
+        }
+        e if e.is_transient() => Status::unavailable(format!("could not {action}: {e}")),
+        e => Status::internal(format!("could not {action}: {e}")),
+    }
+}
+
+// The error is the `Status` the RPCs have to answer with
+#[allow(clippy::result_large_err)]
+fn parse_info_hash(info_hash: &str) -> Result<InfoHash, Status> {
+    match InfoHash::from_str(info_hash) {
+        Ok(parsed) if info_hash.len() == 40 => Ok(parsed),
+        _ => Err(Status::invalid_argument(format!("invalid info_hash: {info_hash}"))),
+    }
+}
+
+impl From<auth::Key> for Key {
+    fn from(auth_key: auth::Key) -> Self {
+        Key {
+            key: auth_key.key,
+            valid_until: auth_key.valid_until.map(|valid_until| valid_until.as_secs()),
+            label: auth_key.label,
+            created_at: auth_key.created_at.map(|created_at| created_at.as_secs()),
+            last_used: auth_key.last_used.map(|last_used| last_used.as_secs()),
+            announces: auth_key.announces,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Management for Service {
+    type StreamStatsStream = Pin<Box<dyn Stream<Item = Result<Stats, Status>> + Send>>;
+
+    async fn get_stats(&self, request: Request<GetStatsRequest>) -> Result<Response<Stats>, Status> {
+        self.authenticate(&request).await?;
+
+        Ok(Response::new(stats(&self.tracker).await))
+    }
+
+    async fn stream_stats(&self, request: Request<StreamStatsRequest>) -> Result<Response<Self::StreamStatsStream>, Status> {
+        self.authenticate(&request).await?;
+
+        let period = Duration::from_secs(u64::from(request.into_inner().interval_secs.max(1)));
+        let tracker = self.tracker.clone();
+
+        // The stream is dropped, stopping the interval, when the client cancels the call
+        let stream = futures::stream::unfold(tokio::time::interval(period), move |mut interval| {
+            let tracker = tracker.clone();
+            async move {
+                interval.tick().await;
+                Some((Ok(stats(&tracker).await), interval))
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn list_whitelist(&self, request: Request<ListWhitelistRequest>) -> Result<Response<ListWhitelistResponse>, Status> {
+        self.authenticate(&request).await?;
+
+        let request = request.into_inner();
+        let limit = if request.limit == 0 { 1000 } else { min(request.limit, 4000) };
+        let query = torrent_query::Query {
+            offset: request.offset as usize,
+            limit: limit as usize,
+            ..Default::default()
+        };
+
+        let info_hashes = self
+            .tracker
+            .get_whitelist_page(&query)
+            .await
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        Ok(Response::new(ListWhitelistResponse { info_hashes }))
+    }
+
+    async fn add_to_whitelist(&self, request: Request<InfoHashRequest>) -> Result<Response<Empty>, Status> {
+        self.change("AddToWhitelist", request, |request| async move {
+            let info_hash = parse_info_hash(&request.info_hash)?;
+
+            match self.tracker.add_torrent_to_whitelist(&info_hash).await {
+                Ok(..) => Ok(Empty {}),
+                Err(e) => Err(database_status("whitelist torrent", &e)),
+            }
+        })
+        .await
+    }
+
+    async fn remove_from_whitelist(&self, request: Request<InfoHashRequest>) -> Result<Response<Empty>, Status> {
+        self.change("RemoveFromWhitelist", request, |request| async move {
+            let info_hash = parse_info_hash(&request.info_hash)?;
+
+            match self.tracker.remove_torrent_from_whitelist(&info_hash).await {
+                Ok(..) => Ok(Empty {}),
+                Err(e) => Err(database_status("remove torrent from whitelist", &e)),
+            }
+        })
+        .await
+    }
+
+    async fn list_keys(&self, request: Request<ListKeysRequest>) -> Result<Response<ListKeysResponse>, Status> {
+        self.authenticate(&request).await?;
+
+        let keys = self.tracker.get_auth_keys().await.into_iter().map(Key::from).collect();
+
+        Ok(Response::new(ListKeysResponse { keys }))
+    }
+
+    async fn generate_key(&self, request: Request<GenerateKeyRequest>) -> Result<Response<Key>, Status> {
+        self.change("GenerateKey", request, |request| async move {
+            let lifetime = request.seconds_valid.map(Duration::from_secs);
+
+            match self.tracker.generate_auth_key(lifetime, request.label).await {
+                Ok(auth_key) => Ok(Key::from(auth_key)),
+                Err(e) => Err(database_status("generate key", &e)),
+            }
+        })
+        .await
+    }
+
+    async fn delete_key(&self, request: Request<DeleteKeyRequest>) -> Result<Response<Empty>, Status> {
+        self.change("DeleteKey", request, |request| async move {
+            match self.tracker.remove_auth_key(&request.key).await {
+                Ok(()) => Ok(Empty {}),
+                Err(e) => Err(database_status("delete key", &e)),
+            }
+        })
+        .await
+    }
+
+    async fn ban_ip(&self, request: Request<BanIpRequest>) -> Result<Response<BanIpResponse>, Status> {
+        self.change("BanIp", request, |request| async move {
+            let ip = request.ip;
+            let ip = IpAddr::from_str(&ip).map_err(|_| Status::invalid_argument(format!("invalid ip: {ip}")))?;
+
+            match self.tracker.ban_ip(&ip).await {
+                Ok(peers_removed) => Ok(BanIpResponse {
+                    peers_removed: peers_removed as u64,
+                }),
+                Err(e) => Err(database_status("ban ip", &e)),
+            }
+        })
+        .await
+    }
+
+    async fn add_to_blacklist(&self, request: Request<BlacklistRequest>) -> Result<Response<Empty>, Status> {
+        self.change("AddToBlacklist", request, |request| async move {
+            let cidr = request.cidr;
+            let range = blacklist::parse_range(&cidr).map_err(|_| Status::invalid_argument(format!("invalid cidr: {cidr}")))?;
+
+            match self.tracker.add_range_to_blacklist(&range).await {
+                Ok(()) => Ok(Empty {}),
+                Err(e) => Err(database_status("add range to blacklist", &e)),
+            }
+        })
+        .await
+    }
+
+    async fn remove_from_blacklist(&self, request: Request<BlacklistRequest>) -> Result<Response<Empty>, Status> {
+        self.change("RemoveFromBlacklist", request, |request| async move {
+            let cidr = request.cidr;
+            let range = blacklist::parse_range(&cidr).map_err(|_| Status::invalid_argument(format!("invalid cidr: {cidr}")))?;
+
+            match self.tracker.remove_range_from_blacklist(&range).await {
+                Ok(()) => Ok(Empty {}),
+                Err(e) => Err(database_status("remove range from blacklist", &e)),
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+    use tonic::{Code, Request};
+
+    use super::Service;
+    use crate::config::Configuration;
+    use crate::databases::driver::Driver;
+    use crate::grpc::proto::management_server::Management;
+    use crate::grpc::proto::{
+        GenerateKeyRequest, GetStatsRequest, InfoHashRequest, ListKeysRequest, ListWhitelistRequest, StreamStatsRequest,
+    };
+    use crate::tracker::{audit, statistics, Tracker};
+
+    fn tracker(configure: impl FnOnce(&mut Configuration)) -> Arc<Tracker> {
+        let mut config = Configuration::default();
+        config.db_driver = Driver::Memory;
+        configure(&mut config);
+
+        Arc::new(Tracker::new(&Arc::new(config), None, statistics::Repo::new()).unwrap())
+    }
+
+    fn service() -> Service {
+        Service::new(tracker(|_| {}))
+    }
+
+    fn request_with_authorization<T>(message: T, authorization: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", authorization.parse().unwrap());
+        request
+    }
+
+    fn request<T>(message: T) -> Request<T> {
+        request_with_authorization(message, "Bearer MyAccessToken")
+    }
+
+    #[tokio::test]
+    async fn only_the_requests_with_an_access_token_should_be_authenticated() {
+        let service = service();
+
+        assert!(service.get_stats(request(GetStatsRequest {})).await.is_ok());
+        assert_eq!(
+            service
+                .get_stats(request_with_authorization(GetStatsRequest {}, "Bearer OtherToken"))
+                .await
+                .unwrap_err()
+                .code(),
+            Code::Unauthenticated
+        );
+        assert_eq!(
+            service.get_stats(Request::new(GetStatsRequest {})).await.unwrap_err().code(),
+            Code::Unauthenticated
+        );
+    }
+
+    #[tokio::test]
+    async fn the_changes_should_be_rejected_when_the_api_is_read_only_and_recorded_in_the_audit_log() {
+        let tracker = tracker(|config| config.http_api.read_only = true);
+        let service = Service::new(tracker.clone());
+
+        let status = service
+            .add_to_whitelist(request(InfoHashRequest {
+                info_hash: String::from("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d"),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        let query = audit::Query {
+            limit: 10,
+            ..Default::default()
+        };
+        let entries = tracker.get_audit_log(&query).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "/torrust.tracker.v1.Management/AddToWhitelist");
+        assert_eq!(entries[0].status, 403);
+    }
+
+    #[tokio::test]
+    async fn the_whitelist_and_the_keys_should_be_managed() {
+        let service = service();
+        let info_hash = String::from("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d");
+
+        service
+            .add_to_whitelist(request(InfoHashRequest {
+                info_hash: info_hash.clone(),
+            }))
+            .await
+            .unwrap();
+        let whitelist = service
+            .list_whitelist(request(ListWhitelistRequest::default()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(whitelist.info_hashes, [info_hash]);
+
+        let status = service
+            .add_to_whitelist(request(InfoHashRequest {
+                info_hash: String::from("not an info hash"),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let key = service
+            .generate_key(request(GenerateKeyRequest {
+                seconds_valid: None,
+                label: Some(String::from("ci")),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let keys = service.list_keys(request(ListKeysRequest {})).await.unwrap().into_inner();
+        assert_eq!(keys.keys, [key]);
+    }
+
+    #[tokio::test]
+    async fn the_stats_should_be_streamed() {
+        let service = service();
+
+        let mut stream = service
+            .stream_stats(request(StreamStatsRequest { interval_secs: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().torrents, 0);
+    }
+}
@@ -0,0 +1,93 @@
+//! The tuning of the connections of an HTTP tracker, so the index sites and the proxies making many scrapes
+//! multiplex them in a few HTTP/2 connections, or reuse their HTTP/1.1 ones, instead of opening a socket for each.
+
+use std::time::Duration;
+
+use crate::config::HttpTracker;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Connections {
+    pub http2: bool,
+    pub keep_alive: bool,
+    // `None` keeps the idle connections open until their client closes them
+    pub keep_alive_timeout: Option<Duration>,
+    pub max_concurrent_streams: u32,
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        Self {
+            http2: true,
+            keep_alive: true,
+            keep_alive_timeout: Some(Duration::from_secs(60)),
+            max_concurrent_streams: 100,
+        }
+    }
+}
+
+impl Connections {
+    #[must_use]
+    pub fn new(config: &HttpTracker) -> Self {
+        Self {
+            http2: config.http2_enabled,
+            keep_alive: config.keep_alive,
+            keep_alive_timeout: Some(Duration::from_secs(config.keep_alive_timeout)).filter(|timeout| !timeout.is_zero()),
+            max_concurrent_streams: config.http2_max_concurrent_streams,
+        }
+    }
+
+    /// The HTTP of the connections accepted one by one, like the TLS and the Unix domain socket ones, which
+    /// detects the HTTP/2 clients by their preface, with prior knowledge or after negotiating `h2` with ALPN.
+    #[must_use]
+    pub fn http(&self) -> hyper::server::conn::Http {
+        let mut http = hyper::server::conn::Http::new();
+
+        http.http1_only(!self.http2)
+            .http1_keep_alive(self.keep_alive)
+            .http2_max_concurrent_streams(self.max_concurrent_streams)
+            .http2_keep_alive_interval(self.keep_alive_timeout);
+        if let Some(keep_alive_timeout) = self.keep_alive_timeout {
+            http.http1_header_read_timeout(keep_alive_timeout)
+                .http2_keep_alive_timeout(keep_alive_timeout);
+        }
+
+        http
+    }
+
+    /// The `builder` of a server with the same tuning as the `http` of the connections accepted one by one.
+    #[must_use]
+    pub fn server<I>(&self, builder: hyper::server::Builder<I>) -> hyper::server::Builder<I> {
+        let builder = builder
+            .http1_only(!self.http2)
+            .http1_keepalive(self.keep_alive)
+            .http2_max_concurrent_streams(self.max_concurrent_streams)
+            .http2_keep_alive_interval(self.keep_alive_timeout);
+
+        match self.keep_alive_timeout {
+            Some(keep_alive_timeout) => builder
+                .http1_header_read_timeout(keep_alive_timeout)
+                .http2_keep_alive_timeout(keep_alive_timeout),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Connections;
+    use crate::config::Configuration;
+
+    #[test]
+    fn a_zero_keep_alive_timeout_should_keep_the_idle_connections_open() {
+        let mut config = Configuration::default().http_trackers.remove(0);
+
+        assert_eq!(Connections::new(&config), Connections::default());
+        assert_eq!(Connections::new(&config).keep_alive_timeout, Some(Duration::from_secs(60)));
+
+        config.keep_alive_timeout = 0;
+
+        assert_eq!(Connections::new(&config).keep_alive_timeout, None);
+    }
+}
@@ -1,11 +1,27 @@
+use axum::response::{IntoResponse, Response};
+use log::debug;
 use thiserror::Error;
-use warp::reject::Reject;
+
+use super::response;
+use crate::tracker::torrent;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("internal server error")]
     InternalServer,
 
+    #[error("invalid request")]
+    InvalidRequest,
+
+    #[error("query string too long")]
+    QueryTooLong,
+
+    #[error("malformed query string")]
+    MalformedQuery,
+
+    #[error("{param} is invalid")]
+    InvalidParam { param: &'static str },
+
     #[error("info_hash is either missing or invalid")]
     InvalidInfo,
 
@@ -21,6 +37,12 @@ pub enum Error {
     #[error("torrent not on whitelist")]
     TorrentNotWhitelisted,
 
+    #[error("peer ip is blacklisted")]
+    PeerBlacklisted,
+
+    #[error("network not allowed")]
+    NetworkNotAllowed,
+
     #[error("peer not authenticated")]
     PeerNotAuthenticated,
 
@@ -29,6 +51,64 @@ pub enum Error {
 
     #[error("exceeded info_hash limit")]
     ExceededInfoHashLimit,
+
+    #[error("scrape is disabled")]
+    ScrapeDisabled,
+
+    #[error("announcing too often, retry in {retry_in} seconds")]
+    AnnounceRateLimited { retry_in: u64 },
+
+    #[error("tracker under maintenance, retry in {retry_in} seconds")]
+    UnderMaintenance { retry_in: u64 },
+
+    #[error("client not allowed")]
+    ClientNotAllowed,
+
+    #[error("announced port {port} is not allowed")]
+    PortNotAllowed { port: u16 },
+
+    #[error("peer id announced by another client")]
+    PeerIdTaken,
 }
 
-impl Reject for Error {}
+/// Marks the responses with a failure reason, which have the same status as the other ones.
+#[derive(Debug, Clone, Copy)]
+pub struct Failure;
+
+/// The trackers answer the errors with a bencoded failure reason instead of an HTTP error status.
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        debug!("{:?}", self);
+        let retry_in = match self {
+            Error::AnnounceRateLimited { retry_in } | Error::UnderMaintenance { retry_in } => Some(retry_in.div_ceil(60)),
+            _ => None,
+        };
+
+        let mut response = response::Error {
+            failure_reason: self.to_string(),
+            retry_in,
+        }
+        .write()
+        .into_response();
+        response.extensions_mut().insert(Failure);
+        response
+    }
+}
+
+impl From<torrent::Error> for Error {
+    fn from(e: torrent::Error) -> Self {
+        match e {
+            torrent::Error::TorrentNotWhitelisted => Error::TorrentNotWhitelisted,
+            torrent::Error::PeerNotAuthenticated => Error::PeerNotAuthenticated,
+            torrent::Error::PeerKeyNotValid => Error::PeerKeyNotValid,
+            torrent::Error::NoPeersFound => Error::NoPeersFound,
+            torrent::Error::CouldNotSendResponse => Error::InternalServer,
+            torrent::Error::InvalidInfoHash => Error::InvalidInfo,
+            torrent::Error::AnnounceRateLimited { retry_in } => Error::AnnounceRateLimited { retry_in },
+            torrent::Error::UnderMaintenance { retry_in } => Error::UnderMaintenance { retry_in },
+            torrent::Error::ClientNotAllowed => Error::ClientNotAllowed,
+            torrent::Error::PortNotAllowed { port } => Error::PortNotAllowed { port },
+            torrent::Error::PeerIdTaken => Error::PeerIdTaken,
+        }
+    }
+}
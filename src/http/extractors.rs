@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts, Path, Query};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap};
+
+use super::error::Error;
+use super::reverse_proxy::ReverseProxy;
+use super::{request, WebResult};
+use crate::protocol::common::MAX_SCRAPE_TORRENTS;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{self, auth, peer};
+
+/// The optional `auth::Key` at the end of the path, like in `/announce/<key>`, or else in the first of the
+/// `http_auth_key_params` of the query, like in `/announce?info_hash=...&key=<key>`
+pub struct AuthKey(pub Option<auth::Key>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthKey
+where
+    Arc<tracker::Tracker>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Ok(Path(key)) = Path::<String>::from_request_parts(parts, state).await {
+            return Ok(AuthKey(auth::Key::from_string(&key)));
+        }
+
+        let tracker = Arc::<tracker::Tracker>::from_ref(state);
+        let raw_query = parts.uri.query().unwrap_or_default();
+        let key = tracker
+            .config()
+            .http_auth_key_params
+            .iter()
+            .find_map(|name| query_param(raw_query, name));
+
+        Ok(AuthKey(key.and_then(|key| auth::Key::from_string(&key))))
+    }
+}
+
+/// The IP of the peer, from the remote address or the headers of the reverse proxy
+pub struct PeerAddr(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PeerAddr
+where
+    Option<Arc<ReverseProxy>>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // `ConnectInfo` is set by the `Server` of axum, and by the HTTPS trackers for each of their connections
+        let remote_addr = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(remote_addr)| *remote_addr);
+
+        peer_addr(Option::from_ref(state), remote_addr, &parts.headers).map(PeerAddr)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for request::Announce
+where
+    Option<Arc<ReverseProxy>>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(announce_request_query) =
+            Query::<request::AnnounceQuery>::try_from_uri(&parts.uri).map_err(|_| Error::InternalServer)?;
+        let raw_query = parts.uri.query().unwrap_or_default();
+        let info_hashes = info_hashes(raw_query)?;
+        let peer_id = peer_id(raw_query)?;
+        let PeerAddr(peer_addr) = PeerAddr::from_request_parts(parts, state).await?;
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(ToString::to_string);
+
+        Ok(announce_request(
+            announce_request_query,
+            &info_hashes,
+            peer_id,
+            peer_addr,
+            user_agent,
+        ))
+    }
+}
+
+/// A `request::Scrape` of the `info_hash` params, or a `request::FullScrape` without any
+pub enum ScrapeRequest {
+    Scrape(request::Scrape),
+    FullScrape(request::FullScrape),
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ScrapeRequest
+where
+    Option<Arc<ReverseProxy>>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let raw_query = parts.uri.query().unwrap_or_default();
+
+        if raw_query.split('&').any(|param| param.contains("info_hash")) {
+            let info_hashes = info_hashes(raw_query)?;
+            let PeerAddr(peer_addr) = PeerAddr::from_request_parts(parts, state).await?;
+
+            Ok(ScrapeRequest::Scrape(request::Scrape { info_hashes, peer_addr }))
+        } else {
+            let PeerAddr(peer_addr) = PeerAddr::from_request_parts(parts, state).await?;
+
+            Ok(ScrapeRequest::FullScrape(request::FullScrape { peer_addr }))
+        }
+    }
+}
+
+/// Parse `InfoHash` from raw query string
+fn info_hashes(raw_query: &str) -> WebResult<Vec<InfoHash>> {
+    let split_raw_query: Vec<&str> = raw_query.split('&').collect();
+    let mut info_hashes: Vec<InfoHash> = Vec::new();
+
+    for v in split_raw_query {
+        if v.contains("info_hash") {
+            let raw_info_hash = v.split('=').collect::<Vec<&str>>()[1];
+            let info_hash_bytes = percent_encoding::percent_decode_str(raw_info_hash).collect::<Vec<u8>>();
+            let info_hash = InfoHash::from_str(&hex::encode(info_hash_bytes));
+            if let Ok(ih) = info_hash {
+                info_hashes.push(ih);
+            }
+        }
+    }
+
+    if info_hashes.len() > MAX_SCRAPE_TORRENTS as usize {
+        Err(Error::ExceededInfoHashLimit)
+    } else if info_hashes.is_empty() {
+        Err(Error::InvalidInfo)
+    } else {
+        Ok(info_hashes)
+    }
+}
+
+/// Parse `PeerId` from raw query string
+fn peer_id(raw_query: &str) -> WebResult<peer::Id> {
+    // put all query params in a vec
+    let split_raw_query: Vec<&str> = raw_query.split('&').collect();
+
+    let mut peer_id: Option<peer::Id> = None;
+
+    for v in split_raw_query {
+        // look for the peer_id param
+        if v.contains("peer_id") {
+            // get raw percent_encoded peer_id
+            let raw_peer_id = v.split('=').collect::<Vec<&str>>()[1];
+
+            // decode peer_id
+            let peer_id_bytes = percent_encoding::percent_decode_str(raw_peer_id).collect::<Vec<u8>>();
+
+            // peer_id must be 20 bytes
+            if peer_id_bytes.len() != 20 {
+                return Err(Error::InvalidPeerId);
+            }
+
+            // clone peer_id_bytes into fixed length array
+            let mut byte_arr: [u8; 20] = Default::default();
+            byte_arr.clone_from_slice(peer_id_bytes.as_slice());
+
+            peer_id = Some(peer::Id(byte_arr));
+            break;
+        }
+    }
+
+    match peer_id {
+        Some(id) => Ok(id),
+        None => Err(Error::InvalidPeerId),
+    }
+}
+
+/// The percent decoded value of the first `name` param of the raw query string
+fn query_param(raw_query: &str, name: &str) -> Option<String> {
+    raw_query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(param_name, _)| *param_name == name)
+        .and_then(|(_, value)| percent_encoding::percent_decode_str(value).decode_utf8().ok())
+        .map(Cow::into_owned)
+}
+
+/// Get `PeerAddress` from `RemoteAddress` or the headers of the reverse proxy
+fn peer_addr(
+    reverse_proxy: Option<Arc<ReverseProxy>>,
+    remote_addr: Option<SocketAddr>,
+    headers: &HeaderMap,
+) -> WebResult<IpAddr> {
+    let remote_ip = remote_addr.ok_or(Error::AddressNotFound)?.ip();
+
+    match reverse_proxy {
+        Some(reverse_proxy) => reverse_proxy.client_ip(remote_ip, headers).ok_or(Error::AddressNotFound),
+        None => Ok(remote_ip),
+    }
+}
+
+/// Parse `AnnounceRequest` from raw `AnnounceRequestQuery`, `InfoHash` and Option<SocketAddr>
+#[allow(clippy::ptr_arg)]
+fn announce_request(
+    announce_request_query: request::AnnounceQuery,
+    info_hashes: &Vec<InfoHash>,
+    peer_id: peer::Id,
+    peer_addr: IpAddr,
+    user_agent: Option<String>,
+) -> request::Announce {
+    request::Announce {
+        info_hash: info_hashes[0],
+        peer_addr,
+        downloaded: announce_request_query.downloaded.unwrap_or(0),
+        uploaded: announce_request_query.uploaded.unwrap_or(0),
+        peer_id,
+        port: announce_request_query.port,
+        left: announce_request_query.left.unwrap_or(0),
+        event: announce_request_query.event,
+        compact: announce_request_query.compact,
+        numwant: announce_request_query.numwant,
+        user_agent,
+        tracker_id: announce_request_query.trackerid,
+    }
+}
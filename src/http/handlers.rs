@@ -1,16 +1,20 @@
-use std::collections::HashMap;
-use std::convert::Infallible;
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 use std::sync::Arc;
 
+use aquatic_udp_protocol::AnnounceEvent;
+use axum::body::StreamBody;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
 use log::debug;
-use warp::http::Response;
-use warp::{reject, Rejection, Reply};
 
 use super::error::Error;
-use super::{request, response, WebResult};
+use super::extractors::{AuthKey, ScrapeRequest};
+use super::{request, response, tracker_id, WebResult};
+use crate::config::Configuration;
 use crate::protocol::info_hash::InfoHash;
-use crate::tracker::{self, auth, peer, statistics, torrent};
+use crate::tracker::announce_interval::{self, Enforcement};
+use crate::tracker::{self, auth, events, peer, statistics, torrent};
 
 /// Authenticate `InfoHash` using optional `auth::Key`
 ///
@@ -22,41 +26,107 @@ pub async fn authenticate(
     auth_key: &Option<auth::Key>,
     tracker: Arc<tracker::Tracker>,
 ) -> Result<(), Error> {
-    tracker.authenticate_request(info_hash, auth_key).await.map_err(|e| match e {
-        torrent::Error::TorrentNotWhitelisted => Error::TorrentNotWhitelisted,
-        torrent::Error::PeerNotAuthenticated => Error::PeerNotAuthenticated,
-        torrent::Error::PeerKeyNotValid => Error::PeerKeyNotValid,
-        torrent::Error::NoPeersFound => Error::NoPeersFound,
-        torrent::Error::CouldNotSendResponse => Error::InternalServer,
-        torrent::Error::InvalidInfoHash => Error::InvalidInfo,
-    })
+    tracker.authenticate_request(info_hash, auth_key).await.map_err(Error::from)
 }
 
 /// Handle announce request
 ///
 /// # Errors
 ///
-/// Will return `warp::Rejection` that wraps the `ServerError` if unable to `send_scrape_response`.
+/// Will return the `Error` of the announce if the peer is not allowed to announce, or if unable to `send_announce_response`.
 pub async fn handle_announce(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    State(intervals): State<announce_interval::Overrides>,
+    AuthKey(auth_key): AuthKey,
     announce_request: request::Announce,
-    auth_key: Option<auth::Key>,
-    tracker: Arc<tracker::Tracker>,
-) -> WebResult<impl Reply> {
-    authenticate(&announce_request.info_hash, &auth_key, tracker.clone())
-        .await
-        .map_err(reject::custom)?;
+) -> WebResult<Response> {
+    tracker.check_maintenance()?;
+
+    if tracker.is_ip_blacklisted(&announce_request.peer_addr).await {
+        return Err(Error::PeerBlacklisted);
+    }
+
+    // Without the header the client is checked against an empty User-Agent
+    tracker
+        .check_client(
+            &announce_request.peer_id,
+            Some(announce_request.user_agent.as_deref().unwrap_or_default()),
+        )
+        .await?;
+
+    tracker.check_port(announce_request.port)?;
+
+    authenticate(&announce_request.info_hash, &auth_key, tracker.clone()).await?;
 
     debug!("{:?}", announce_request);
 
-    let peer = peer::Peer::from_http_announce_request(&announce_request, announce_request.peer_addr, tracker.config.get_ext_ip());
-    let torrent_stats = tracker
-        .update_torrent_with_peer_and_get_stats(&announce_request.info_hash, &peer)
-        .await;
+    let config = tracker.config();
+
+    let mut peer = peer::Peer::from_http_announce_request(&announce_request, announce_request.peer_addr, config.get_ext_ip());
 
-    // get all torrent peers excluding the peer_addr
-    let peers = tracker.get_torrent_peers(&announce_request.info_hash, &peer.peer_addr).await;
+    let tracker_id = if config.issue_tracker_id {
+        match announce_request
+            .tracker_id
+            .as_deref()
+            .and_then(|id| tracker_id::check(&announce_request.info_hash, &announce_request.peer_id, id))
+        {
+            Some(key) => peer.key = Some(key),
+            // the client was given its id in the response to its first announce
+            None if peer.event != AnnounceEvent::Started => tracker.increase_http_tracker_ids_ignored().await,
+            None => {}
+        }
+        Some(tracker_id::make(&announce_request.info_hash, &announce_request.peer_id))
+    } else {
+        None
+    };
 
-    let announce_interval = tracker.config.announce_interval;
+    tracker
+        .check_announce_rate_limit(&announce_request.peer_addr, &announce_request.info_hash, peer.event)
+        .await?;
+
+    tracker.check_peer_id(&announce_request.info_hash, &peer).await?;
+
+    let min_interval = intervals.min_interval(&config);
+    let too_early = match config.min_announce_interval_enforcement {
+        Enforcement::Off => false,
+        enforcement => match tracker
+            .get_min_interval_wait(&announce_request.info_hash, &peer, min_interval)
+            .await
+        {
+            Some(wait) if enforcement == Enforcement::Reject => {
+                return Err(Error::AnnounceRateLimited {
+                    retry_in: wait.as_secs() + 1,
+                })
+            }
+            wait => wait.is_some(),
+        },
+    };
+
+    if let Some(auth_key) = &auth_key {
+        tracker.update_auth_key_usage(&auth_key.key).await;
+    }
+    let torrent_stats = if too_early {
+        // the peer is answered with the swarm as it is, like on its last announce
+        let (seeders, completed, leechers) = tracker
+            .get_torrent_stats(&announce_request.info_hash)
+            .await
+            .unwrap_or_default();
+        torrent::SwamStats {
+            completed,
+            seeders,
+            leechers,
+        }
+    } else {
+        tracker
+            .update_torrent_with_peer_and_get_stats(&announce_request.info_hash, &peer)
+            .await
+    };
+
+    let peers = tracker
+        .get_torrent_peers(&announce_request.info_hash, &peer, announce_request.numwant)
+        .await;
+
+    let announce_interval = intervals.interval(tracker.announce_interval(&torrent_stats), min_interval);
 
     // send stats event
     match announce_request.peer_addr {
@@ -72,38 +142,69 @@ pub async fn handle_announce(
         &announce_request,
         &torrent_stats,
         &peers,
-        announce_interval,
-        tracker.config.min_announce_interval,
+        Reannounce {
+            interval: announce_interval,
+            interval_min: min_interval,
+            tracker_id,
+        },
+        &config,
+        config.announce_external_ip.then_some(peer.peer_addr.ip()),
     )
 }
 
-/// Handle scrape request
+/// Handle scrape request, or full scrape request if it has no `info_hash`
 ///
 /// # Errors
 ///
-/// Will return `warp::Rejection` that wraps the `ServerError` if unable to `send_scrape_response`.
+/// Will return the `Error` of the scrape if the scrapes are disabled, or if unable to `send_scrape_response`.
 pub async fn handle_scrape(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    AuthKey(auth_key): AuthKey,
+    scrape_request: ScrapeRequest,
+) -> WebResult<Response> {
+    if !tracker.config().scrape_enabled {
+        return Err(Error::ScrapeDisabled);
+    }
+
+    match scrape_request {
+        ScrapeRequest::Scrape(scrape_request) => scrape(scrape_request, auth_key, tracker).await,
+        ScrapeRequest::FullScrape(full_scrape_request) => full_scrape(full_scrape_request.peer_addr, auth_key, tracker).await,
+    }
+}
+
+/// Handle the requests of any other route or method, answered with a failure reason like the errors
+pub async fn handle_not_found() -> Error {
+    Error::InternalServer
+}
+
+async fn scrape(
     scrape_request: request::Scrape,
     auth_key: Option<auth::Key>,
     tracker: Arc<tracker::Tracker>,
-) -> WebResult<impl Reply> {
-    let mut files: HashMap<InfoHash, response::ScrapeEntry> = HashMap::new();
-    let db = tracker.get_torrents().await;
+) -> WebResult<Response> {
+    if tracker.is_ip_blacklisted(&scrape_request.peer_addr).await {
+        return Err(Error::PeerBlacklisted);
+    }
+
+    let mut files: BTreeMap<InfoHash, response::ScrapeEntry> = BTreeMap::new();
 
     for info_hash in &scrape_request.info_hashes {
-        let scrape_entry = match db.get(info_hash) {
-            Some(torrent_info) => {
+        let scrape_entry = match tracker.get_torrent_stats(info_hash).await {
+            Some((seeders, completed, leechers)) => {
                 if authenticate(info_hash, &auth_key, tracker.clone()).await.is_ok() {
-                    let (seeders, completed, leechers) = torrent_info.get_stats();
+                    let partial_seeds = tracker.get_torrent_partial_seeds(info_hash).await.unwrap_or_default();
+
                     response::ScrapeEntry {
                         complete: seeders,
                         downloaded: completed,
+                        downloaders: leechers.saturating_sub(partial_seeds),
                         incomplete: leechers,
                     }
                 } else {
                     response::ScrapeEntry {
                         complete: 0,
                         downloaded: 0,
+                        downloaders: 0,
                         incomplete: 0,
                     }
                 }
@@ -111,11 +212,14 @@ pub async fn handle_scrape(
             None => response::ScrapeEntry {
                 complete: 0,
                 downloaded: 0,
+                downloaders: 0,
                 incomplete: 0,
             },
         };
 
         files.insert(*info_hash, scrape_entry);
+
+        tracker.emit_event(events::Event::TorrentScraped { info_hash: *info_hash });
     }
 
     // send stats event
@@ -131,71 +235,92 @@ pub async fn handle_scrape(
     send_scrape_response(files)
 }
 
+/// The full scrape, if enabled and allowed for the client
+async fn full_scrape(peer_addr: IpAddr, auth_key: Option<auth::Key>, tracker: Arc<tracker::Tracker>) -> WebResult<Response> {
+    // Without the full scrapes it is a scrape missing its info hashes
+    if !tracker.config().full_scrape {
+        return Err(Error::InvalidInfo);
+    }
+
+    if tracker.is_ip_blacklisted(&peer_addr).await {
+        return Err(Error::PeerBlacklisted);
+    }
+
+    if tracker.is_private() {
+        let auth_key = auth_key.ok_or(Error::PeerNotAuthenticated)?;
+        tracker.verify_auth_key(&auth_key).await.map_err(|_| Error::PeerKeyNotValid)?;
+    }
+
+    // send stats event
+    match peer_addr {
+        IpAddr::V4(_) => {
+            tracker.send_stats_event(statistics::Event::Tcp4Scrape).await;
+        }
+        IpAddr::V6(_) => {
+            tracker.send_stats_event(statistics::Event::Tcp6Scrape).await;
+        }
+    }
+
+    let full_scrape = response::FullScrape {
+        entries: tracker.get_full_scrape().await,
+    };
+
+    Ok(StreamBody::new(full_scrape.write()).into_response())
+}
+
+/// What the peer is told to send on its next announce
+struct Reannounce {
+    interval: u32,
+    interval_min: u32,
+    tracker_id: Option<String>,
+}
+
 /// Send announce response
 #[allow(clippy::ptr_arg)]
 fn send_announce_response(
     announce_request: &request::Announce,
     torrent_stats: &torrent::SwamStats,
     peers: &Vec<peer::Peer>,
-    interval: u32,
-    interval_min: u32,
-) -> WebResult<impl Reply> {
+    reannounce: Reannounce,
+    config: &Configuration,
+    external_ip: Option<IpAddr>,
+) -> WebResult<Response> {
     let http_peers: Vec<response::Peer> = peers
         .iter()
         .map(|peer| response::Peer {
-            peer_id: peer.peer_id.to_string(),
+            peer_id: peer.peer_id,
             ip: peer.peer_addr.ip(),
             port: peer.peer_addr.port(),
         })
         .collect();
 
     let res = response::Announce {
-        interval,
-        interval_min,
+        interval: reannounce.interval,
+        interval_min: reannounce.interval_min,
         complete: torrent_stats.seeders,
         incomplete: torrent_stats.leechers,
         peers: http_peers,
+        external_ip,
+        tracker_id: reannounce.tracker_id,
     };
 
-    // check for compact response request
-    if let Some(1) = announce_request.compact {
+    // the dictionary peer list only for the clients asking for it with `compact=0`
+    if config.http_compact_peers_only || announce_request.compact != Some(0) {
         match res.write_compact() {
-            Ok(body) => Ok(Response::new(body)),
-            Err(_) => Err(reject::custom(Error::InternalServer)),
+            Ok(body) => Ok(body.into_response()),
+            Err(_) => Err(Error::InternalServer),
         }
     } else {
-        Ok(Response::new(res.write().into()))
+        Ok(res.write().into_response())
     }
 }
 
 /// Send scrape response
-fn send_scrape_response(files: HashMap<InfoHash, response::ScrapeEntry>) -> WebResult<impl Reply> {
+fn send_scrape_response(files: BTreeMap<InfoHash, response::ScrapeEntry>) -> WebResult<Response> {
     let res = response::Scrape { files };
 
     match res.write() {
-        Ok(body) => Ok(Response::new(body)),
-        Err(_) => Err(reject::custom(Error::InternalServer)),
+        Ok(body) => Ok(body.into_response()),
+        Err(_) => Err(Error::InternalServer),
     }
 }
-
-/// Handle all server errors and send error reply
-///
-/// # Errors
-///
-/// Will not return a error, `Infallible`, but instead  convert the `ServerError` into a `Response`.
-pub fn send_error(r: &Rejection) -> std::result::Result<impl Reply, Infallible> {
-    let body = if let Some(server_error) = r.find::<Error>() {
-        debug!("{:?}", server_error);
-        response::Error {
-            failure_reason: server_error.to_string(),
-        }
-        .write()
-    } else {
-        response::Error {
-            failure_reason: Error::InternalServer.to_string(),
-        }
-        .write()
-    };
-
-    Ok(Response::new(body))
-}
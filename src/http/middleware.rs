@@ -0,0 +1,103 @@
+//! The middleware shared by the HTTP trackers and the API.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::{MatchedPath, State};
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use log::debug;
+
+use super::error::{Error, Failure};
+use super::extractors::PeerAddr;
+use super::routes::Listener;
+use super::validation;
+use crate::protocol::clock::{Current, Time};
+use crate::tracker::{self, statistics};
+use crate::udp::network_filter::NetworkFilter;
+
+/// Logs the method, route and status of each request, with the time it took to answer it.
+///
+/// It has to be a route layer to know the matched route, like `/announce/:key`, which is logged instead of
+/// the path so the keys in the paths are not.
+pub async fn log_request<B>(request: Request<B>, next: Next<B>) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|route| route.as_str().to_string())
+        .unwrap_or_default();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    debug!("{} {} {} in {:?}", method, route, response.status(), start.elapsed());
+
+    response
+}
+
+/// Records the status and the response time of each request of the HTTP tracker, by its route, and whether it
+/// was answered with a failure reason, in the stats of the tracker and of its listener.
+pub async fn record_metrics<B>(
+    State(tracker): State<Arc<tracker::Tracker>>,
+    State(Listener(listener)): State<Listener>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let kind = request.extensions().get::<MatchedPath>().and_then(|route| {
+        if route.as_str().starts_with("/announce") {
+            Some(statistics::HttpRequestKind::Announce)
+        } else if route.as_str().starts_with("/scrape") {
+            Some(statistics::HttpRequestKind::Scrape)
+        } else {
+            None
+        }
+    });
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+    let is_error = response.extensions().get::<Failure>().is_some();
+
+    tracker
+        .record_http_response(kind, response.status().as_u16(), start.elapsed(), is_error)
+        .await;
+
+    if let Some(listener) = listener {
+        let listener_kind = match kind {
+            Some(statistics::HttpRequestKind::Announce) => statistics::ListenerRequestKind::Announce,
+            Some(statistics::HttpRequestKind::Scrape) => statistics::ListenerRequestKind::Scrape,
+            None => statistics::ListenerRequestKind::Other,
+        };
+        listener.record(listener_kind, is_error, Current::now());
+    }
+
+    response
+}
+
+/// Answers the announces and the scrapes with an oversized or malformed query string with a failure reason,
+/// before they are handled.
+pub async fn validate_query<B>(State(tracker): State<Arc<tracker::Tracker>>, request: Request<B>, next: Next<B>) -> Response {
+    match validation::validate_query(
+        request.uri().query().unwrap_or_default(),
+        tracker.config().http_max_query_length,
+    ) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Answers the requests of the clients outside the `allowed_networks` of the HTTP tracker with a
+/// failure reason, before they are handled.
+pub async fn filter_networks<B>(
+    State(network_filter): State<Option<Arc<NetworkFilter>>>,
+    PeerAddr(peer_addr): PeerAddr,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if network_filter.is_none_or(|network_filter| network_filter.accepts(&peer_addr)) {
+        next.run(request).await
+    } else {
+        Error::NetworkNotAllowed.into_response()
+    }
+}
@@ -1,10 +1,17 @@
+pub mod connections;
 pub mod error;
-pub mod filters;
+pub mod extractors;
 pub mod handlers;
+pub mod middleware;
 pub mod request;
 pub mod response;
+pub mod reverse_proxy;
 pub mod routes;
 pub mod server;
+pub mod tls;
+pub mod tracker_id;
+pub mod unix;
+pub mod validation;
 
 pub type Bytes = u64;
-pub type WebResult<T> = std::result::Result<T, warp::Rejection>;
+pub type WebResult<T> = std::result::Result<T, error::Error>;
@@ -15,6 +15,8 @@ pub struct AnnounceQuery {
     pub left: Option<Bytes>,
     pub event: Option<String>,
     pub compact: Option<u8>,
+    pub numwant: Option<u32>,
+    pub trackerid: Option<String>,
 }
 
 #[derive(Debug)]
@@ -28,9 +30,17 @@ pub struct Announce {
     pub left: Bytes,
     pub event: Option<String>,
     pub compact: Option<u8>,
+    pub numwant: Option<u32>,
+    pub user_agent: Option<String>,
+    pub tracker_id: Option<String>,
 }
 
 pub struct Scrape {
     pub info_hashes: Vec<InfoHash>,
     pub peer_addr: IpAddr,
 }
+
+/// A scrape without any `info_hash`, of every torrent
+pub struct FullScrape {
+    pub peer_addr: IpAddr,
+}
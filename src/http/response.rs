@@ -1,15 +1,20 @@
-use std::collections::HashMap;
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
 use std::net::IpAddr;
+use std::sync::Arc;
 
+use futures::{future, stream, Stream, StreamExt};
 use serde;
 use serde::Serialize;
 
 use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{full_scrape, peer};
 
+/// A peer of the BEP 3 dictionary peer lists, for the announces with `compact=0`.
 #[derive(Serialize)]
 pub struct Peer {
-    pub peer_id: String,
+    #[serde(rename = "peer id", serialize_with = "serialize_peer_id")]
+    pub peer_id: peer::Id,
     pub ip: IpAddr,
     pub port: u16,
 }
@@ -19,19 +24,48 @@ pub struct Announce {
     pub interval: u32,
     #[serde(rename = "min interval")]
     pub interval_min: u32,
-    //pub tracker_id: String,
     pub complete: u32,
     pub incomplete: u32,
     pub peers: Vec<Peer>,
+    // BEP 24, the IP of the client as seen by the other peers, unless `announce_external_ip` is off
+    #[serde(
+        rename = "external ip",
+        serialize_with = "serialize_ip_octets",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub external_ip: Option<IpAddr>,
+    // The id to send back in the next announces, unless `issue_tracker_id` is off
+    #[serde(rename = "tracker id", skip_serializing_if = "Option::is_none")]
+    pub tracker_id: Option<String>,
+}
+
+fn serialize_peer_id<S: serde::Serializer>(peer_id: &peer::Id, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(&peer_id.0)
+}
+
+fn serialize_ip_octets<S: serde::Serializer>(ip: &Option<IpAddr>, serializer: S) -> Result<S::Ok, S::Error> {
+    match ip {
+        Some(ip) => serializer.serialize_bytes(&ip_octets(ip)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn ip_octets(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
 }
 
 impl Announce {
+    /// The response with the BEP 3 dictionary peer list.
+    ///
     /// # Panics
     ///
     /// It would panic if the `Announce` struct would contain an inappropriate type.
     #[must_use]
-    pub fn write(&self) -> String {
-        serde_bencode::to_string(&self).unwrap()
+    pub fn write(&self) -> Vec<u8> {
+        serde_bencode::to_bytes(&self).unwrap()
     }
 
     /// # Errors
@@ -54,23 +88,38 @@ impl Announce {
             }
         }
 
+        // The keys of a bencoded dictionary are sorted, as `serde_bencode` does for `write`
         let mut bytes: Vec<u8> = Vec::new();
-        bytes.write_all(b"d8:intervali")?;
+        bytes.write_all(b"d8:completei")?;
+        bytes.write_all(self.complete.to_string().as_bytes())?;
+        bytes.write_all(b"e")?;
+        if let Some(external_ip) = &self.external_ip {
+            let external_ip = ip_octets(external_ip);
+            bytes.write_all(b"11:external ip")?;
+            bytes.write_all(external_ip.len().to_string().as_bytes())?;
+            bytes.write_all(b":")?;
+            bytes.write_all(external_ip.as_slice())?;
+        }
+        bytes.write_all(b"10:incompletei")?;
+        bytes.write_all(self.incomplete.to_string().as_bytes())?;
+        bytes.write_all(b"e8:intervali")?;
         bytes.write_all(self.interval.to_string().as_bytes())?;
         bytes.write_all(b"e12:min intervali")?;
         bytes.write_all(self.interval_min.to_string().as_bytes())?;
-        bytes.write_all(b"e8:completei")?;
-        bytes.write_all(self.complete.to_string().as_bytes())?;
-        bytes.write_all(b"e10:incompletei")?;
-        bytes.write_all(self.incomplete.to_string().as_bytes())?;
         bytes.write_all(b"e5:peers")?;
         bytes.write_all(peers_v4.len().to_string().as_bytes())?;
         bytes.write_all(b":")?;
         bytes.write_all(peers_v4.as_slice())?;
-        bytes.write_all(b"e6:peers6")?;
+        bytes.write_all(b"6:peers6")?;
         bytes.write_all(peers_v6.len().to_string().as_bytes())?;
         bytes.write_all(b":")?;
         bytes.write_all(peers_v6.as_slice())?;
+        if let Some(tracker_id) = &self.tracker_id {
+            bytes.write_all(b"10:tracker id")?;
+            bytes.write_all(tracker_id.len().to_string().as_bytes())?;
+            bytes.write_all(b":")?;
+            bytes.write_all(tracker_id.as_bytes())?;
+        }
         bytes.write_all(b"e")?;
 
         Ok(bytes)
@@ -81,12 +130,42 @@ impl Announce {
 pub struct ScrapeEntry {
     pub complete: u32,
     pub downloaded: u32,
+    // The leechers that are not BEP 21 partial seeds
+    pub downloaders: u32,
     pub incomplete: u32,
 }
 
+impl ScrapeEntry {
+    fn write(&self, info_hash: &InfoHash, bytes: &mut Vec<u8>) -> io::Result<()> {
+        bytes.write_all(b"20:")?;
+        bytes.write_all(&info_hash.0)?;
+        bytes.write_all(b"d8:completei")?;
+        bytes.write_all(self.complete.to_string().as_bytes())?;
+        bytes.write_all(b"e10:downloadedi")?;
+        bytes.write_all(self.downloaded.to_string().as_bytes())?;
+        bytes.write_all(b"e11:downloadersi")?;
+        bytes.write_all(self.downloaders.to_string().as_bytes())?;
+        bytes.write_all(b"e10:incompletei")?;
+        bytes.write_all(self.incomplete.to_string().as_bytes())?;
+        bytes.write_all(b"ee")
+    }
+}
+
+impl From<&full_scrape::Entry> for ScrapeEntry {
+    fn from(entry: &full_scrape::Entry) -> Self {
+        Self {
+            complete: entry.seeders,
+            downloaded: entry.completed,
+            downloaders: entry.leechers.saturating_sub(entry.partial_seeds),
+            incomplete: entry.leechers,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct Scrape {
-    pub files: HashMap<InfoHash, ScrapeEntry>,
+    // Sorted by info hash, as the keys of a bencoded dictionary
+    pub files: BTreeMap<InfoHash, ScrapeEntry>,
 }
 
 impl Scrape {
@@ -99,15 +178,7 @@ impl Scrape {
         bytes.write_all(b"d5:filesd")?;
 
         for (info_hash, scrape_response_entry) in &self.files {
-            bytes.write_all(b"20:")?;
-            bytes.write_all(&info_hash.0)?;
-            bytes.write_all(b"d8:completei")?;
-            bytes.write_all(scrape_response_entry.complete.to_string().as_bytes())?;
-            bytes.write_all(b"e10:downloadedi")?;
-            bytes.write_all(scrape_response_entry.downloaded.to_string().as_bytes())?;
-            bytes.write_all(b"e10:incompletei")?;
-            bytes.write_all(scrape_response_entry.incomplete.to_string().as_bytes())?;
-            bytes.write_all(b"ee")?;
+            scrape_response_entry.write(info_hash, &mut bytes)?;
         }
 
         bytes.write_all(b"ee")?;
@@ -116,10 +187,48 @@ impl Scrape {
     }
 }
 
+/// Torrents bencoded in each chunk of the full scrape
+const FULL_SCRAPE_CHUNK_TORRENTS: usize = 1024;
+
+/// The scrape of every torrent, as of the last refresh of their stats.
+pub struct FullScrape {
+    pub entries: Arc<Vec<full_scrape::Entry>>,
+}
+
+impl FullScrape {
+    /// The same bencoded dictionary as the one of a `Scrape`, in chunks of `FULL_SCRAPE_CHUNK_TORRENTS` torrents,
+    /// so the response is streamed while it is bencoded.
+    pub fn write(self) -> impl Stream<Item = io::Result<Vec<u8>>> {
+        let entries = self.entries;
+        let chunks = entries.len().div_ceil(FULL_SCRAPE_CHUNK_TORRENTS);
+
+        let files = stream::iter(0..chunks).map(move |chunk| {
+            let mut bytes: Vec<u8> = Vec::new();
+
+            for entry in entries
+                .iter()
+                .skip(chunk * FULL_SCRAPE_CHUNK_TORRENTS)
+                .take(FULL_SCRAPE_CHUNK_TORRENTS)
+            {
+                ScrapeEntry::from(entry).write(&entry.info_hash, &mut bytes)?;
+            }
+
+            Ok(bytes)
+        });
+
+        stream::once(future::ready(Ok(b"d5:filesd".to_vec())))
+            .chain(files)
+            .chain(stream::once(future::ready(Ok(b"ee".to_vec()))))
+    }
+}
+
 #[derive(Serialize)]
 pub struct Error {
     #[serde(rename = "failure reason")]
     pub failure_reason: String,
+    /// Minutes the client should wait before retrying, as in BEP 31.
+    #[serde(rename = "retry in", skip_serializing_if = "Option::is_none")]
+    pub retry_in: Option<u64>,
 }
 
 impl Error {
@@ -131,3 +240,179 @@ impl Error {
         serde_bencode::to_string(&self).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use std::sync::Arc;
+
+    use futures::TryStreamExt;
+    use serde_bencode::value::Value;
+
+    use super::{Announce, FullScrape, Peer, Scrape, ScrapeEntry};
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::{full_scrape, peer};
+
+    fn announce() -> Announce {
+        Announce {
+            interval: 120,
+            interval_min: 120,
+            complete: 1,
+            incomplete: 0,
+            peers: vec![Peer {
+                peer_id: peer::Id(*b"-qB00000000000000\xff\xfe\x01"),
+                ip: IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)),
+                port: 6881,
+            }],
+            external_ip: Some(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1))),
+            tracker_id: Some(String::from("0123456789abcdef")),
+        }
+    }
+
+    fn contains(body: &[u8], bytes: &[u8]) -> bool {
+        body.windows(bytes.len()).any(|window| window == bytes)
+    }
+
+    #[test]
+    fn it_should_return_the_dictionary_peer_list_with_the_raw_peer_ids() {
+        let body = announce().write();
+
+        assert!(contains(
+            &body,
+            b"5:peersld2:ip9:126.0.0.27:peer id20:-qB00000000000000\xff\xfe\x014:porti6881eee"
+        ));
+    }
+
+    #[test]
+    fn it_should_return_the_external_ip_of_the_client_in_the_announce_response() {
+        assert!(contains(&announce().write(), b"11:external ip4:\x7e\0\0\x01"));
+    }
+
+    fn decode(body: &[u8]) -> HashMap<Vec<u8>, Value> {
+        match serde_bencode::from_bytes(body).unwrap() {
+            Value::Dict(dict) => dict,
+            value => panic!("the response should be a dictionary, got {value:?}"),
+        }
+    }
+
+    fn bytes(value: &[u8]) -> Value {
+        Value::Bytes(value.to_vec())
+    }
+
+    #[test]
+    fn it_should_return_the_compact_announce_response_as_a_bencoded_dictionary() {
+        let response = decode(&announce().write_compact().unwrap());
+
+        assert_eq!(response[b"complete".as_slice()], Value::Int(1));
+        assert_eq!(response[b"incomplete".as_slice()], Value::Int(0));
+        assert_eq!(response[b"interval".as_slice()], Value::Int(120));
+        assert_eq!(response[b"min interval".as_slice()], Value::Int(120));
+        assert_eq!(response[b"peers".as_slice()], bytes(b"\x7e\0\0\x02\x1a\xe1"));
+        assert_eq!(response[&b"peers6".to_vec()], bytes(b""));
+    }
+
+    #[test]
+    fn it_should_sort_the_keys_of_the_compact_announce_response() {
+        let body = announce().write_compact().unwrap();
+
+        assert_eq!(
+            serde_bencode::to_bytes(&serde_bencode::from_bytes::<Value>(&body).unwrap()).unwrap(),
+            body
+        );
+    }
+
+    #[test]
+    fn it_should_return_the_external_ip_of_the_client_in_the_compact_announce_response() {
+        let response = decode(&announce().write_compact().unwrap());
+
+        assert_eq!(response[b"external ip".as_slice()], bytes(b"\x7e\0\0\x01"));
+    }
+
+    #[test]
+    fn it_should_not_return_the_external_ip_of_the_client_when_it_is_not_set() {
+        let announce = Announce {
+            external_ip: None,
+            ..announce()
+        };
+
+        assert!(!decode(&announce.write()).contains_key(b"external ip".as_slice()));
+        assert!(!decode(&announce.write_compact().unwrap()).contains_key(b"external ip".as_slice()));
+    }
+
+    #[test]
+    fn it_should_return_the_tracker_id_in_the_announce_responses() {
+        let tracker_id = bytes(b"0123456789abcdef");
+
+        assert_eq!(decode(&announce().write())[b"tracker id".as_slice()], tracker_id);
+        assert_eq!(
+            decode(&announce().write_compact().unwrap())[b"tracker id".as_slice()],
+            tracker_id
+        );
+    }
+
+    #[test]
+    fn it_should_not_return_the_tracker_id_when_it_is_not_issued() {
+        let announce = Announce {
+            tracker_id: None,
+            ..announce()
+        };
+
+        assert!(!decode(&announce.write()).contains_key(b"tracker id".as_slice()));
+        assert!(!decode(&announce.write_compact().unwrap()).contains_key(b"tracker id".as_slice()));
+    }
+
+    #[test]
+    fn it_should_return_the_scraped_torrents_sorted_by_info_hash() {
+        let entry = || ScrapeEntry {
+            complete: 1,
+            downloaded: 2,
+            downloaders: 0,
+            incomplete: 3,
+        };
+        let scrape = Scrape {
+            files: BTreeMap::from([(InfoHash([2u8; 20]), entry()), (InfoHash([1u8; 20]), entry())]),
+        };
+
+        let stats = b"d8:completei1e10:downloadedi2e11:downloadersi0e10:incompletei3ee";
+        let expected = [&b"d5:filesd20:"[..], &[1u8; 20], stats, b"20:", &[2u8; 20], stats, b"ee"].concat();
+        assert_eq!(scrape.write().unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn it_should_stream_the_full_scrape_as_the_scrape_of_every_torrent() {
+        let entries: Vec<full_scrape::Entry> = (0..3000u32)
+            .map(|i| {
+                let mut info_hash = [0u8; 20];
+                info_hash[16..].copy_from_slice(&i.to_be_bytes());
+
+                full_scrape::Entry {
+                    info_hash: InfoHash(info_hash),
+                    seeders: i,
+                    completed: 1,
+                    leechers: 2,
+                    partial_seeds: 1,
+                }
+            })
+            .collect();
+        let scrape = Scrape {
+            files: entries
+                .iter()
+                .map(|entry| (entry.info_hash, ScrapeEntry::from(entry)))
+                .collect(),
+        };
+
+        let chunks: Vec<Vec<u8>> = FullScrape {
+            entries: Arc::new(entries),
+        }
+        .write()
+        .try_collect()
+        .await
+        .unwrap();
+
+        // The opening, the three chunks of torrents and the closing of the dictionary
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks.concat(), scrape.write().unwrap());
+    }
+}
@@ -0,0 +1,149 @@
+//! The IP of the clients of an HTTP tracker behind reverse proxies, taken from the headers the
+//! proxies set only when the request comes from one of the trusted ones.
+
+use std::net::IpAddr;
+
+use axum::http::HeaderMap;
+
+use crate::config::Configuration;
+use crate::udp::network_filter::Network;
+
+#[derive(Debug)]
+pub struct ReverseProxy {
+    headers: Vec<String>,
+    trusted_proxies: Vec<Network>,
+}
+
+impl ReverseProxy {
+    /// `None` unless the tracker is `on_reverse_proxy`.
+    #[must_use]
+    pub fn new(config: &Configuration) -> Option<Self> {
+        config.on_reverse_proxy.then(|| Self {
+            headers: config.reverse_proxy_headers.clone(),
+            trusted_proxies: config.trusted_proxies.clone(),
+        })
+    }
+
+    /// The IP of the client in the first of the `headers` present, or the `remote_ip` if it is not a trusted proxy.
+    ///
+    /// The addresses appended by the trusted proxies to a list like the one of `X-Forwarded-For` are skipped,
+    /// so the one appended by the first trusted proxy is returned.
+    /// `None` if the request came through a trusted proxy without any of the `headers`, or with an invalid one.
+    #[must_use]
+    pub fn client_ip(&self, remote_ip: IpAddr, headers: &HeaderMap) -> Option<IpAddr> {
+        if !self.trusted_proxies.is_empty() && !self.is_trusted_proxy(&remote_ip) {
+            return Some(remote_ip);
+        }
+
+        let forwarded = self
+            .headers
+            .iter()
+            .find_map(|header| headers.get(header.as_str()))?
+            .to_str()
+            .ok()?
+            .split(',')
+            .map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .collect::<Option<Vec<IpAddr>>>()?;
+
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted_proxy(ip))
+            .or_else(|| forwarded.first())
+            .copied()
+    }
+
+    /// The IPv4 proxies of a dual-stack socket are seen as IPv4-mapped IPv6 addresses, like `::ffff:10.0.0.1`.
+    fn is_trusted_proxy(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.trusted_proxies.iter().any(|network| network.0.contains(&ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use axum::http::HeaderMap;
+
+    use super::ReverseProxy;
+    use crate::config::Configuration;
+
+    fn reverse_proxy(trusted_proxies: &[&str]) -> ReverseProxy {
+        let mut config = Configuration::default();
+        config.on_reverse_proxy = true;
+        config.trusted_proxies = trusted_proxies.iter().map(|network| network.parse().unwrap()).collect();
+        ReverseProxy::new(&config).unwrap()
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn it_should_not_be_used_when_the_tracker_is_not_on_a_reverse_proxy() {
+        assert!(ReverseProxy::new(&Configuration::default()).is_none());
+    }
+
+    #[test]
+    fn it_should_take_the_last_forwarded_ip_when_every_proxy_is_trusted() {
+        let reverse_proxy = reverse_proxy(&[]);
+
+        let client_ip = reverse_proxy.client_ip(ip("127.0.0.1"), &headers("X-Forwarded-For", "203.0.113.7, 198.51.100.2"));
+
+        assert_eq!(client_ip, Some(ip("198.51.100.2")));
+    }
+
+    #[test]
+    fn it_should_skip_the_forwarded_ips_of_the_trusted_proxies() {
+        let reverse_proxy = reverse_proxy(&["10.0.0.0/8"]);
+
+        let client_ip = reverse_proxy.client_ip(ip("10.0.0.1"), &headers("X-Forwarded-For", "203.0.113.7, 10.0.0.2"));
+
+        assert_eq!(client_ip, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn it_should_trust_the_ipv4_proxies_seen_as_mapped_addresses_on_a_dual_stack_socket() {
+        let reverse_proxy = reverse_proxy(&["10.0.0.0/8"]);
+
+        let client_ip = reverse_proxy.client_ip(ip("::ffff:10.0.0.1"), &headers("X-Forwarded-For", "203.0.113.7"));
+
+        assert_eq!(client_ip, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn it_should_ignore_the_headers_of_the_requests_which_do_not_come_from_a_trusted_proxy() {
+        let reverse_proxy = reverse_proxy(&["10.0.0.0/8"]);
+
+        let client_ip = reverse_proxy.client_ip(ip("198.51.100.2"), &headers("X-Forwarded-For", "203.0.113.7"));
+
+        assert_eq!(client_ip, Some(ip("198.51.100.2")));
+    }
+
+    #[test]
+    fn it_should_take_the_ip_of_the_x_real_ip_header() {
+        let reverse_proxy = reverse_proxy(&[]);
+
+        let client_ip = reverse_proxy.client_ip(ip("127.0.0.1"), &headers("X-Real-IP", "2001:db8::1"));
+
+        assert_eq!(client_ip, Some(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn it_should_not_find_the_ip_of_a_proxied_request_without_a_valid_header() {
+        let reverse_proxy = reverse_proxy(&[]);
+
+        assert_eq!(reverse_proxy.client_ip(ip("127.0.0.1"), &HeaderMap::new()), None);
+        assert_eq!(
+            reverse_proxy.client_ip(ip("127.0.0.1"), &headers("X-Forwarded-For", "unknown")),
+            None
+        );
+    }
+}
@@ -1,36 +1,262 @@
-use std::convert::Infallible;
 use std::sync::Arc;
 
-use warp::{Filter, Rejection};
-
-use super::filters::{with_announce_request, with_auth_key, with_scrape_request, with_tracker};
-use super::handlers::{handle_announce, handle_scrape, send_error};
-use crate::tracker;
-
-/// All routes
-#[must_use]
-pub fn routes(tracker: Arc<tracker::Tracker>) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
-    announce(tracker.clone())
-        .or(scrape(tracker))
-        .recover(|q| async move { send_error(&q) })
-}
-
-/// GET /announce or /announce/<key>
-fn announce(tracker: Arc<tracker::Tracker>) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
-    warp::path::path("announce")
-        .and(warp::filters::method::get())
-        .and(with_announce_request(tracker.config.on_reverse_proxy))
-        .and(with_auth_key())
-        .and(with_tracker(tracker))
-        .and_then(handle_announce)
-}
-
-/// GET /scrape/<key>
-fn scrape(tracker: Arc<tracker::Tracker>) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
-    warp::path::path("scrape")
-        .and(warp::filters::method::get())
-        .and(with_scrape_request(tracker.config.on_reverse_proxy))
-        .and(with_auth_key())
-        .and(with_tracker(tracker))
-        .and_then(handle_scrape)
+use axum::extract::FromRef;
+use axum::routing::get;
+use axum::{middleware, Router};
+use tower_http::compression::predicate::SizeAbove;
+use tower_http::compression::CompressionLayer;
+
+use super::handlers::{handle_announce, handle_not_found, handle_scrape};
+use super::middleware::{filter_networks, log_request, record_metrics, validate_query};
+use super::reverse_proxy::ReverseProxy;
+use crate::tracker::{self, announce_interval, statistics};
+use crate::udp::network_filter::NetworkFilter;
+
+/// The state shared by the routes, taken apart by their extractors
+#[derive(Clone)]
+pub struct AppState {
+    pub tracker: Arc<tracker::Tracker>,
+    pub reverse_proxy: Option<Arc<ReverseProxy>>,
+    pub network_filter: Option<Arc<NetworkFilter>>,
+    pub intervals: announce_interval::Overrides,
+    pub listener: Listener,
+}
+
+/// The counters of the bind address of the HTTP tracker, like `http://0.0.0.0:7070`, unless the usage statistics are off
+#[derive(Clone)]
+pub struct Listener(pub Option<Arc<statistics::ListenerCounters>>);
+
+impl FromRef<AppState> for Arc<tracker::Tracker> {
+    fn from_ref(state: &AppState) -> Self {
+        state.tracker.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<ReverseProxy>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.reverse_proxy.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<NetworkFilter>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.network_filter.clone()
+    }
+}
+
+impl FromRef<AppState> for Listener {
+    fn from_ref(state: &AppState) -> Self {
+        state.listener.clone()
+    }
+}
+
+impl FromRef<AppState> for announce_interval::Overrides {
+    fn from_ref(state: &AppState) -> Self {
+        state.intervals
+    }
+}
+
+/// All routes, only answered to the networks accepted by the `network_filter`, announcing the `intervals`
+/// of the `listener`
+pub fn routes(
+    tracker: Arc<tracker::Tracker>,
+    network_filter: Option<Arc<NetworkFilter>>,
+    intervals: announce_interval::Overrides,
+    listener: Arc<str>,
+) -> Router {
+    let compression = tracker.config().http_compression.then(|| {
+        CompressionLayer::new()
+            .gzip(true)
+            .deflate(true)
+            .compress_when(SizeAbove::new(tracker.config().http_compression_min_size))
+    });
+    let listener = Listener(tracker.register_listener(&listener));
+    let state = AppState {
+        reverse_proxy: ReverseProxy::new(&tracker.config()).map(Arc::new),
+        tracker,
+        network_filter,
+        intervals,
+        listener,
+    };
+
+    let router = Router::new()
+        // GET /announce or /announce/<key>
+        .route("/announce", get(handle_announce).fallback(handle_not_found))
+        .route("/announce/:key", get(handle_announce).fallback(handle_not_found))
+        // GET /scrape or /scrape/<key>, a full scrape without any `info_hash`
+        .route("/scrape", get(handle_scrape).fallback(handle_not_found))
+        .route("/scrape/:key", get(handle_scrape).fallback(handle_not_found))
+        .route_layer(middleware::from_fn_with_state(state.clone(), validate_query))
+        .route_layer(middleware::from_fn_with_state(state.clone(), filter_networks))
+        .route_layer(middleware::from_fn_with_state(state.clone(), record_metrics))
+        .route_layer(middleware::from_fn(log_request))
+        .fallback(handle_not_found)
+        .with_state(state);
+
+    match compression {
+        Some(compression) => router.layer(compression),
+        None => router,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::Request;
+    use hyper::service::Service;
+
+    use super::routes;
+    use crate::config::Configuration;
+    use crate::databases::driver::Driver;
+    use crate::tracker::{self, announce_interval, mode, statistics};
+    use crate::udp::network_filter::{Network, NetworkFilter};
+
+    const LISTENER: &str = "http://127.0.0.1:7070";
+
+    const QUERY: &str =
+        "info_hash=%9e%02%17%d0%fa%71%c8%73%32%cd%8b%f9%db%ea%bc%b2%c2%cf%3c%4d&peer_id=-qB00000000000000000&port=6881";
+
+    async fn get(uri: &str) -> Vec<u8> {
+        get_from_networks(&[], uri).await
+    }
+
+    async fn get_from_networks(allowed_networks: &[&str], uri: &str) -> Vec<u8> {
+        let allowed_networks: Vec<Network> = allowed_networks.iter().map(|network| network.parse().unwrap()).collect();
+        let network_filter = Some(Arc::new(NetworkFilter::new(&allowed_networks, &[])));
+        let mut config = Configuration::default();
+        config.db_driver = Driver::Memory;
+        let tracker = Arc::new(tracker::Tracker::new(&Arc::new(config), None, statistics::Repo::new()).unwrap());
+
+        let mut request = Request::get(uri).body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo("126.0.0.1:6881".parse::<SocketAddr>().unwrap()));
+
+        let response = routes(
+            tracker,
+            network_filter,
+            announce_interval::Overrides::default(),
+            LISTENER.into(),
+        )
+        .call(request)
+        .await
+        .unwrap();
+
+        hyper::body::to_bytes(response.into_body()).await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn it_should_route_the_announces_and_the_scrapes_with_or_without_a_key() {
+        assert!(get(&format!("/announce?{QUERY}")).await.starts_with(b"d8:completei"));
+        assert!(get(&format!("/announce/key?{QUERY}")).await.starts_with(b"d8:completei"));
+        assert!(get(&format!("/scrape?{QUERY}")).await.starts_with(b"d5:filesd"));
+        assert!(get(&format!("/scrape/key?{QUERY}")).await.starts_with(b"d5:filesd"));
+    }
+
+    #[tokio::test]
+    async fn it_should_take_the_key_of_the_private_announces_from_the_query_when_not_in_the_path() {
+        let mut config = Configuration::default();
+        config.db_driver = Driver::Memory;
+        config.mode = mode::Mode::Private;
+        let (stats_event_sender, stats_repository) = statistics::Keeper::new_active_instance();
+        let tracker = Arc::new(tracker::Tracker::new(&Arc::new(config), Some(stats_event_sender), stats_repository).unwrap());
+        let key = tracker
+            .generate_auth_key(Some(Duration::from_secs(3600)), None)
+            .await
+            .unwrap()
+            .key;
+
+        let announce = |uri: String| {
+            let mut request = Request::get(uri).body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo("126.0.0.1:6881".parse::<SocketAddr>().unwrap()));
+            routes(
+                tracker.clone(),
+                None,
+                announce_interval::Overrides::default(),
+                LISTENER.into(),
+            )
+            .call(request)
+        };
+
+        for uri in [
+            format!("/announce/{key}?{QUERY}"),
+            format!("/announce?{QUERY}&key={key}"),
+            format!("/announce?{QUERY}&auth_key={key}"),
+        ] {
+            let response = announce(uri).await.unwrap();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert!(body.starts_with(b"d8:completei"));
+        }
+
+        let response = announce(format!("/announce?{QUERY}&passkey={key}")).await.unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.starts_with(b"d14:failure reason"));
+
+        let listener_stats = tracker.get_listener_stats()[LISTENER];
+        assert_eq!((listener_stats.announces, listener_stats.errors), (4, 1));
+    }
+
+    #[tokio::test]
+    async fn it_should_only_answer_the_allowed_networks_when_there_are_any() {
+        let announce = format!("/announce?{QUERY}");
+
+        assert!(get_from_networks(&["126.0.0.0/8"], &announce)
+            .await
+            .starts_with(b"d8:completei"));
+        assert_eq!(
+            get_from_networks(&["10.0.0.0/8"], &announce).await,
+            b"d14:failure reason19:network not allowede"
+        );
+        assert_eq!(
+            get_from_networks(&["10.0.0.0/8"], &format!("/scrape?{QUERY}")).await,
+            b"d14:failure reason19:network not allowede"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_compress_the_responses_for_the_clients_accepting_it() {
+        let mut config = Configuration::default();
+        config.db_driver = Driver::Memory;
+        config.http_compression_min_size = 0;
+        let tracker = Arc::new(tracker::Tracker::new(&Arc::new(config), None, statistics::Repo::new()).unwrap());
+
+        let mut request = Request::get(format!("/scrape?{QUERY}"))
+            .header("Accept-Encoding", "gzip")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo("126.0.0.1:6881".parse::<SocketAddr>().unwrap()));
+
+        let response = routes(tracker, None, announce_interval::Overrides::default(), LISTENER.into())
+            .call(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+    }
+
+    #[tokio::test]
+    async fn it_should_answer_the_malformed_queries_with_a_failure_reason() {
+        assert_eq!(
+            get(&format!("/announce?{QUERY}&numwant=many")).await,
+            b"d14:failure reason18:numwant is invalide"
+        );
+        assert_eq!(
+            get(&format!("/scrape?{QUERY}&info_hash=%zz")).await,
+            b"d14:failure reason22:malformed query stringe"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_answer_the_unknown_routes_with_a_failure_reason() {
+        assert_eq!(get("/unknown").await, b"d14:failure reason21:internal server errore");
+    }
 }
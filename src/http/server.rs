@@ -1,46 +1,169 @@
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use axum::extract::ConnectInfo;
+use axum::Router;
+use hyper::service::{service_fn, Service};
+use log::{debug, error};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+use super::connections::Connections;
 use super::routes;
-use crate::tracker;
+use super::tls::Certificates;
+use crate::config::HttpTracker;
+use crate::tracker::{self, announce_interval};
+use crate::udp::network_filter::NetworkFilter;
 
 /// Server that listens on HTTP, needs a `tracker::TorrentTracker`
 #[derive(Clone)]
 pub struct Http {
     tracker: Arc<tracker::Tracker>,
+    // The bind address with its protocol, like `http://0.0.0.0:7070`, to count its requests apart
+    listener: Arc<str>,
+    network_filter: Option<Arc<NetworkFilter>>,
+    intervals: announce_interval::Overrides,
+    connections: Connections,
 }
 
 impl Http {
     #[must_use]
-    pub fn new(tracker: Arc<tracker::Tracker>) -> Http {
-        Http { tracker }
+    pub fn new(tracker: Arc<tracker::Tracker>, config: &HttpTracker) -> Http {
+        let network_filter = Some(NetworkFilter::new(&config.allowed_networks, &[]))
+            .filter(|network_filter| !network_filter.is_empty())
+            .map(Arc::new);
+
+        let scheme = if config.ssl_enabled && super::unix::socket_path(&config.bind_address).is_none() {
+            "https"
+        } else {
+            "http"
+        };
+
+        Http {
+            tracker,
+            listener: format!("{scheme}://{}", config.bind_address).into(),
+            network_filter,
+            intervals: announce_interval::Overrides::new(config),
+            connections: Connections::new(config),
+        }
     }
 
     /// Start the `HttpServer`
-    pub fn start(&self, socket_addr: SocketAddr) -> impl warp::Future<Output = ()> {
-        let (_addr, server) =
-            warp::serve(routes::routes(self.tracker.clone())).bind_with_graceful_shutdown(socket_addr, async move {
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the `socket_addr` cannot be bound.
+    pub fn start(&self, socket_addr: SocketAddr) -> impl Future<Output = ()> {
+        let server = self
+            .connections
+            .server(axum::Server::bind(&socket_addr))
+            .serve(
+                routes::routes(
+                    self.tracker.clone(),
+                    self.network_filter.clone(),
+                    self.intervals,
+                    self.listener.clone(),
+                )
+                .into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(async move {
                 tokio::signal::ctrl_c().await.expect("Failed to listen to shutdown signal.");
             });
 
-        server
+        async move {
+            if let Err(e) = server.await {
+                error!("Could not serve the HTTP tracker on {}: {}", socket_addr, e);
+            }
+        }
     }
 
-    /// Start the `HttpServer` in TLS mode
-    pub fn start_tls(
-        &self,
-        socket_addr: SocketAddr,
-        ssl_cert_path: String,
-        ssl_key_path: String,
-    ) -> impl warp::Future<Output = ()> {
-        let (_addr, server) = warp::serve(routes::routes(self.tracker.clone()))
-            .tls()
-            .cert_path(ssl_cert_path)
-            .key_path(ssl_key_path)
-            .bind_with_graceful_shutdown(socket_addr, async move {
-                tokio::signal::ctrl_c().await.expect("Failed to listen to shutdown signal.");
+    /// Start the `HttpServer` on the Unix domain socket at `path`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the `path` cannot be bound.
+    #[cfg(unix)]
+    pub async fn start_unix(&self, path: &std::path::Path) {
+        super::unix::serve(
+            path,
+            routes::routes(
+                self.tracker.clone(),
+                self.network_filter.clone(),
+                self.intervals,
+                self.listener.clone(),
+            ),
+            self.connections,
+        )
+        .await;
+    }
+
+    /// Start the `HttpServer` in TLS mode, serving the current certificate of `certificates` to each new connection
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the `socket_addr` cannot be bound.
+    pub async fn start_tls(&self, socket_addr: SocketAddr, certificates: Arc<Certificates>) {
+        serve_tls(
+            socket_addr,
+            certificates,
+            routes::routes(
+                self.tracker.clone(),
+                self.network_filter.clone(),
+                self.intervals,
+                self.listener.clone(),
+            ),
+            self.connections,
+        )
+        .await;
+    }
+}
+
+/// Serves the `routes` on `socket_addr` in TLS mode until the process is stopped, with the current certificate
+/// of `certificates` for each new connection
+///
+/// # Panics
+///
+/// Will panic if the `socket_addr` cannot be bound.
+pub async fn serve_tls(socket_addr: SocketAddr, certificates: Arc<Certificates>, routes: Router, connections: Connections) {
+    let listener = TcpListener::bind(socket_addr)
+        .await
+        .unwrap_or_else(|e| panic!("Could not bind the TLS server to {socket_addr}: {e}"));
+    let acceptor = TlsAcceptor::from(Arc::new(certificates.server_config()));
+
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    debug!("Could not accept a connection on {}: {}", socket_addr, e);
+                    continue;
+                }
+            },
+        };
+        let acceptor = acceptor.clone();
+        let routes = routes.clone();
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with {} failed: {}", remote_addr, e);
+                    return;
+                }
+            };
+
+            // `ConnectInfo` is only set by the `Server` of axum, so it is inserted for each request of the connection
+            let service = service_fn(move |mut request| {
+                request.extensions_mut().insert(ConnectInfo(remote_addr));
+                routes.clone().call(request)
             });
 
-        server
+            // The upgrades are needed by the WebSocket connections
+            if let Err(e) = connections.http().serve_connection(stream, service).with_upgrades().await {
+                debug!("Could not serve the connection of {}: {}", remote_addr, e);
+            }
+        });
     }
 }
@@ -0,0 +1,210 @@
+//! The TLS certificate of an HTTPS tracker, read from its PEM files and reloaded from them
+//! without restarting the server, so the renewed certificates are served to the new connections.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::{Arc, RwLock};
+
+use thiserror::Error;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::{self, CertifiedKey};
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("could not read {path}: {source}")]
+    Io { path: String, source: io::Error },
+
+    #[error("no certificate in {path}")]
+    MissingCertificate { path: String },
+
+    #[error("no PKCS#8 or RSA private key in {path}")]
+    MissingPrivateKey { path: String },
+
+    #[error("unsupported private key in {path}")]
+    UnsupportedPrivateKey { path: String },
+}
+
+pub struct Certificates {
+    cert_path: String,
+    key_path: String,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl Certificates {
+    /// # Errors
+    ///
+    /// Will return `Err` if the files cannot be read or have no certificate or supported private key.
+    pub fn load(cert_path: &str, key_path: &str) -> Result<Self, Error> {
+        Ok(Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            certified_key: RwLock::new(Arc::new(certified_key(cert_path, key_path)?)),
+        })
+    }
+
+    /// Reads the files again, keeping the current certificate if they are not valid.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the files cannot be read or have no certificate or supported private key.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the lock of the certificate is poisoned.
+    pub fn reload(&self) -> Result<(), Error> {
+        let certified_key = certified_key(&self.cert_path, &self.key_path)?;
+
+        *self.certified_key.write().unwrap() = Arc::new(certified_key);
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn server_config(self: &Arc<Self>) -> ServerConfig {
+        let mut server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        server_config
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.certified_key.read().unwrap().clone())
+    }
+}
+
+fn certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, Error> {
+    let certs = rustls_pemfile::certs(&mut reader(cert_path)?).map_err(|source| Error::Io {
+        path: cert_path.to_string(),
+        source,
+    })?;
+    if certs.is_empty() {
+        return Err(Error::MissingCertificate {
+            path: cert_path.to_string(),
+        });
+    }
+
+    let key = rustls_pemfile::read_all(&mut reader(key_path)?)
+        .map_err(|source| Error::Io {
+            path: key_path.to_string(),
+            source,
+        })?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) => Some(PrivateKey(key)),
+            rustls_pemfile::Item::X509Certificate(_) => None,
+        })
+        .ok_or_else(|| Error::MissingPrivateKey {
+            path: key_path.to_string(),
+        })?;
+    let signing_key = sign::any_supported_type(&key).map_err(|_| Error::UnsupportedPrivateKey {
+        path: key_path.to_string(),
+    })?;
+
+    Ok(CertifiedKey::new(certs.into_iter().map(Certificate).collect(), signing_key))
+}
+
+fn reader(path: &str) -> Result<BufReader<File>, Error> {
+    File::open(path).map(BufReader::new).map_err(|source| Error::Io {
+        path: path.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509NameBuilder, X509};
+
+    use super::{Certificates, Error};
+
+    /// Writes a self-signed certificate and its key in `dir`, returning their paths.
+    fn write_self_signed_certificate(dir: &Path) -> (String, String) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        let name = name.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        fs::write(&cert_path, cert.build().to_pem().unwrap()).unwrap();
+        fs::write(&key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+        (cert_path.to_string_lossy().into(), key_path.to_string_lossy().into())
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("torrust-tls-{}", uuid::Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn served_certificate(certificates: &Certificates) -> Vec<u8> {
+        certificates.certified_key.read().unwrap().cert[0].0.clone()
+    }
+
+    #[test]
+    fn it_should_load_a_certificate_and_its_private_key() {
+        let dir = temp_dir();
+        let (cert_path, key_path) = write_self_signed_certificate(&dir);
+
+        assert!(Certificates::load(&cert_path, &key_path).is_ok());
+    }
+
+    #[test]
+    fn it_should_not_load_a_file_without_a_private_key() {
+        let dir = temp_dir();
+        let (cert_path, _key_path) = write_self_signed_certificate(&dir);
+
+        assert!(matches!(
+            Certificates::load(&cert_path, &cert_path),
+            Err(Error::MissingPrivateKey { .. })
+        ));
+    }
+
+    #[test]
+    fn it_should_serve_the_renewed_certificate_after_reloading_it() {
+        let dir = temp_dir();
+        let (cert_path, key_path) = write_self_signed_certificate(&dir);
+        let certificates = Certificates::load(&cert_path, &key_path).unwrap();
+        let previous = served_certificate(&certificates);
+
+        write_self_signed_certificate(&dir);
+        certificates.reload().unwrap();
+
+        assert_ne!(served_certificate(&certificates), previous);
+    }
+
+    #[test]
+    fn it_should_keep_the_current_certificate_when_the_renewed_one_is_invalid() {
+        let dir = temp_dir();
+        let (cert_path, key_path) = write_self_signed_certificate(&dir);
+        let certificates = Certificates::load(&cert_path, &key_path).unwrap();
+        let previous = served_certificate(&certificates);
+
+        fs::write(&key_path, "").unwrap();
+
+        assert!(certificates.reload().is_err());
+        assert_eq!(served_certificate(&certificates), previous);
+    }
+}
@@ -0,0 +1,106 @@
+//! The `tracker id` of the HTTP announce responses, derived from an HMAC of the torrent and the
+//! peer id with the secret of the instance, so the tracker can verify the ids sent back without
+//! keeping them.
+//!
+//! A client sending back its id proves it is the same peer when its IP address changes, like the
+//! `key` of the UDP announces, and the first bytes of the HMAC are used as the key of the peer.
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+use crate::protocol::crypto::keys::seeds::{Current, Keeper};
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::peer;
+
+pub type TrackerId = [u8; 8];
+
+/// The hex encoded `tracker id` of the peer in the torrent.
+#[must_use]
+pub fn make(info_hash: &InfoHash, peer_id: &peer::Id) -> String {
+    hex::encode(build(info_hash, peer_id))
+}
+
+/// The key of the peer, if the `tracker id` it sent back is the one it was given for the torrent.
+#[must_use]
+pub fn check(info_hash: &InfoHash, peer_id: &peer::Id, tracker_id: &str) -> Option<u32> {
+    let tracker_id = hex::decode(tracker_id).ok()?;
+    let expected = build(info_hash, peer_id);
+
+    if tracker_id.len() == expected.len() && openssl::memcmp::eq(&tracker_id, &expected) {
+        Some(u32::from_be_bytes([expected[0], expected[1], expected[2], expected[3]]))
+    } else {
+        None
+    }
+}
+
+/// The first bytes of the HMAC-SHA256 of the info hash and the peer id.
+///
+/// # Panics
+///
+/// It would panic if `OpenSSL` fails to compute the HMAC.
+fn build(info_hash: &InfoHash, peer_id: &peer::Id) -> TrackerId {
+    let key = PKey::hmac(Current::get_seed()).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+
+    signer.update(&info_hash.0).unwrap();
+    signer.update(&peer_id.0).unwrap();
+
+    let hmac = signer.sign_to_vec().unwrap();
+
+    let mut tracker_id = TrackerId::default();
+    let length = tracker_id.len();
+    tracker_id.copy_from_slice(&hmac[..length]);
+    tracker_id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, make};
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::peer;
+
+    const INFO_HASH: InfoHash = InfoHash([0u8; 20]);
+    const PEER_ID: peer::Id = peer::Id(*b"-qB00000000000000000");
+
+    #[test]
+    fn it_should_make_a_tracker_id_of_16_hex_digits() {
+        let tracker_id = make(&INFO_HASH, &PEER_ID);
+
+        assert_eq!(tracker_id.len(), 16);
+        assert!(tracker_id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn it_should_make_a_different_tracker_id_for_each_peer_of_the_torrent() {
+        assert_ne!(
+            make(&INFO_HASH, &PEER_ID),
+            make(&INFO_HASH, &peer::Id(*b"-qB00000000000000001"))
+        );
+    }
+
+    #[test]
+    fn it_should_accept_the_tracker_id_given_to_the_peer_with_the_same_key_each_time() {
+        let tracker_id = make(&INFO_HASH, &PEER_ID);
+
+        let key = check(&INFO_HASH, &PEER_ID, &tracker_id);
+
+        assert!(key.is_some());
+        assert_eq!(key, check(&INFO_HASH, &PEER_ID, &tracker_id));
+    }
+
+    #[test]
+    fn it_should_not_accept_the_tracker_id_given_to_another_peer() {
+        let tracker_id = make(&INFO_HASH, &peer::Id(*b"-qB00000000000000001"));
+
+        assert_eq!(check(&INFO_HASH, &PEER_ID, &tracker_id), None);
+    }
+
+    #[test]
+    fn it_should_not_accept_a_malformed_tracker_id() {
+        let tracker_id = make(&INFO_HASH, &PEER_ID);
+
+        assert_eq!(check(&INFO_HASH, &PEER_ID, "not hex"), None);
+        assert_eq!(check(&INFO_HASH, &PEER_ID, &tracker_id[..8]), None);
+    }
+}
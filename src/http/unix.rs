@@ -0,0 +1,85 @@
+//! The HTTP trackers and the API bound to a Unix domain socket with a `unix:/path/to.sock` bind address,
+//! for the deployments behind a local reverse proxy, where the permissions of the socket file control who
+//! can connect.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+
+/// The clients of a Unix domain socket have no IP address, they are taken as local ones like the reverse
+/// proxies in front of it, which send the IP of the clients in their headers.
+pub const REMOTE_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+/// The path of the socket of a `unix:/path/to.sock` bind address, `None` for the other addresses.
+#[must_use]
+pub fn socket_path(bind_address: &str) -> Option<&Path> {
+    bind_address.strip_prefix("unix:").map(Path::new)
+}
+
+/// Serves the `routes` on the Unix domain socket at `path` until the process is stopped, removing the socket
+/// left by a previous run before binding it and the new one afterwards.
+///
+/// # Panics
+///
+/// Will panic if the `path` cannot be bound.
+#[cfg(unix)]
+pub async fn serve(path: &Path, routes: axum::Router, connections: super::connections::Connections) {
+    use std::os::unix::fs::FileTypeExt;
+
+    use axum::extract::ConnectInfo;
+    use hyper::service::{service_fn, Service};
+    use log::debug;
+    use tokio::net::UnixListener;
+
+    let remove_socket = || {
+        if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            let _ = std::fs::remove_file(path);
+        }
+    };
+
+    remove_socket();
+
+    let listener = UnixListener::bind(path).unwrap_or_else(|e| panic!("Could not bind the socket {}: {e}", path.display()));
+
+    loop {
+        let stream = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    debug!("Could not accept a connection on {}: {}", path.display(), e);
+                    continue;
+                }
+            },
+        };
+        let routes = routes.clone();
+
+        tokio::spawn(async move {
+            let service = service_fn(move |mut request| {
+                request.extensions_mut().insert(ConnectInfo(REMOTE_ADDR));
+                routes.clone().call(request)
+            });
+
+            if let Err(e) = connections.http().serve_connection(stream, service).with_upgrades().await {
+                debug!("Could not serve a connection of the Unix domain socket: {}", e);
+            }
+        });
+    }
+
+    remove_socket();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::socket_path;
+
+    #[test]
+    fn the_socket_path_should_only_be_taken_from_the_unix_bind_addresses() {
+        assert_eq!(
+            socket_path("unix:/run/torrust/http.sock"),
+            Some(Path::new("/run/torrust/http.sock"))
+        );
+        assert_eq!(socket_path("0.0.0.0:6969"), None);
+    }
+}
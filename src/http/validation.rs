@@ -0,0 +1,109 @@
+//! The checks of the query strings of the announces and the scrapes, answering the malformed ones with a
+//! failure reason before they reach the tracker.
+
+use super::error::Error;
+
+/// The params which have to be numbers, with their bounds
+const NUMERIC_PARAMS: [(&str, u64); 6] = [
+    ("port", u16::MAX as u64),
+    ("uploaded", u64::MAX),
+    ("downloaded", u64::MAX),
+    ("left", u64::MAX),
+    ("numwant", u32::MAX as u64),
+    ("compact", 1),
+];
+
+/// Checks the `raw_query` is at most `max_length` bytes, with its percent escapes well formed, the `info_hash`
+/// and `peer_id` params of 20 bytes when decoded, and the numeric params within their bounds.
+///
+/// # Errors
+///
+/// Will return the `Error` of the first check failing.
+pub fn validate_query(raw_query: &str, max_length: usize) -> Result<(), Error> {
+    if raw_query.len() > max_length {
+        return Err(Error::QueryTooLong);
+    }
+
+    for param in raw_query.split('&').filter(|param| !param.is_empty()) {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = decode(value).ok_or(Error::MalformedQuery)?;
+
+        match name {
+            "info_hash" if value.len() != 20 => return Err(Error::InvalidInfo),
+            "peer_id" if value.len() != 20 => return Err(Error::InvalidPeerId),
+            _ => {}
+        }
+
+        if let Some((param, max)) = NUMERIC_PARAMS.iter().find(|(numeric_param, _)| *numeric_param == name) {
+            let number = std::str::from_utf8(&value)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|number| number <= max);
+            if number.is_none() {
+                return Err(Error::InvalidParam { param });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The bytes of a percent encoded value, `None` if a `%` is not followed by two hex digits
+fn decode(value: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2)?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(if byte == b'+' { b' ' } else { byte });
+            rest = tail;
+        }
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_query;
+    use crate::http::error::Error;
+
+    const INFO_HASH: &str = "%9e%02%17%d0%fa%71%c8%73%32%cd%8b%f9%db%ea%bc%b2%c2%cf%3c%4d";
+
+    #[test]
+    fn the_well_formed_queries_should_be_valid() {
+        assert!(validate_query(
+            &format!("info_hash={INFO_HASH}&peer_id=-qB00000000000000000&port=6881&left=0&compact=1"),
+            1024
+        )
+        .is_ok());
+        assert!(validate_query(&format!("info_hash={INFO_HASH}&info_hash={INFO_HASH}"), 1024).is_ok());
+        assert!(validate_query("", 1024).is_ok());
+    }
+
+    #[test]
+    fn the_malformed_queries_should_be_rejected() {
+        assert!(matches!(
+            validate_query(&format!("info_hash={INFO_HASH}"), 16),
+            Err(Error::QueryTooLong)
+        ));
+        assert!(matches!(validate_query("info_hash=%9e%0", 1024), Err(Error::MalformedQuery)));
+        assert!(matches!(validate_query("info_hash=%9e%02", 1024), Err(Error::InvalidInfo)));
+        assert!(matches!(
+            validate_query("peer_id=-qB0000000000000000000", 1024),
+            Err(Error::InvalidPeerId)
+        ));
+        assert!(matches!(
+            validate_query("port=65536", 1024),
+            Err(Error::InvalidParam { param: "port" })
+        ));
+        assert!(matches!(
+            validate_query("left=-1", 1024),
+            Err(Error::InvalidParam { param: "left" })
+        ));
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let interval = config.completed_stat_flush_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping completed stats flush job..");
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        flush_completed_stats(&tracker).await;
+                    }
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        flush_completed_stats(&tracker).await;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn flush_completed_stats(tracker: &tracker::Tracker) {
+    if let Err(e) = tracker.flush_completed_stats().await {
+        error!("Could not save the completed stats: {}", e);
+    }
+}
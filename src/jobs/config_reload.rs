@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinHandle;
+
+use crate::tracker;
+
+/// Reloads the lists, the keys and the runtime settings of the configuration file each time the process receives a
+/// `SIGHUP`, see [`tracker::Tracker::reload`].
+#[must_use]
+pub fn start_job(tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+
+    tokio::spawn(async move {
+        let mut hangups = signal(SignalKind::hangup()).expect("Failed to listen to the SIGHUP signal.");
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping configuration reload job..");
+                    break;
+                }
+                _ = hangups.recv() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        if let Err(e) = tracker.reload().await {
+                            error!("Could not reload on SIGHUP, keeping the current configuration: {}", e);
+                        }
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
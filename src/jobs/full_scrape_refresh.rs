@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use log::{debug, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let interval = config.full_scrape_refresh_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+
+        // The first tick completes right away, so the full scrapes have the stats since the start
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping full scrape refresh job..");
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        let start_time = Utc::now().time();
+                        tracker.refresh_full_scrape().await;
+                        debug!("Refreshed the full scrape in: {}ms", (Utc::now().time() - start_time).num_milliseconds());
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,25 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use log::info;
+use tokio::task::JoinHandle;
+
+use crate::config::GrpcApi;
+use crate::grpc::server;
+use crate::tracker;
+
+/// # Panics
+///
+/// It would panic if the `config::GrpcApi` struct would contain an invalid `bind_address`.
+#[must_use]
+pub fn start_job(config: &GrpcApi, tracker: Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let bind_addr = config
+        .bind_address
+        .parse::<SocketAddr>()
+        .expect("Tracker gRPC API bind_address invalid.");
+
+    tokio::spawn(async move {
+        info!("Starting Torrust gRPC API server on: {}", bind_addr);
+        server::start(bind_addr, tracker).await;
+    })
+}
@@ -1,11 +1,13 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use log::{info, warn};
+use log::{error, info, warn};
 use tokio::task::JoinHandle;
 
 use crate::config::HttpTracker;
 use crate::http::server::Http;
+use crate::http::tls::Certificates;
+use crate::http::unix;
 use crate::tracker;
 
 /// # Panics
@@ -13,24 +15,72 @@ use crate::tracker;
 /// It would panic if the `config::HttpTracker` struct would contain an inappropriate values.
 #[must_use]
 pub fn start_job(config: &HttpTracker, tracker: Arc<tracker::Tracker>) -> JoinHandle<()> {
+    // The TLS of a Unix domain socket is up to the reverse proxy in front of it
+    if let Some(socket_path) = unix::socket_path(&config.bind_address) {
+        let socket_path = socket_path.to_path_buf();
+        let http_tracker = Http::new(tracker, config);
+
+        return tokio::spawn(async move {
+            #[cfg(unix)]
+            {
+                info!("Starting HTTP server on: unix:{}", socket_path.display());
+                http_tracker.start_unix(&socket_path).await;
+            }
+            #[cfg(not(unix))]
+            {
+                drop(http_tracker);
+                warn!(
+                    "Could not start HTTP tracker on: unix:{}, no Unix domain sockets!",
+                    socket_path.display()
+                );
+            }
+        });
+    }
+
     let bind_addr = config.bind_address.parse::<SocketAddr>().unwrap();
     let ssl_enabled = config.ssl_enabled;
     let ssl_cert_path = config.ssl_cert_path.clone();
     let ssl_key_path = config.ssl_key_path.clone();
+    let http_tracker = Http::new(tracker, config);
 
     tokio::spawn(async move {
-        let http_tracker = Http::new(tracker);
-
         if !ssl_enabled {
             info!("Starting HTTP server on: {}", bind_addr);
             http_tracker.start(bind_addr).await;
-        } else if ssl_enabled && ssl_cert_path.is_some() && ssl_key_path.is_some() {
+        } else if let (Some(ssl_cert_path), Some(ssl_key_path)) = (ssl_cert_path, ssl_key_path) {
+            let certificates = match Certificates::load(&ssl_cert_path, &ssl_key_path) {
+                Ok(certificates) => Arc::new(certificates),
+                Err(e) => {
+                    warn!("Could not start HTTP tracker on: {}, {}", bind_addr, e);
+                    return;
+                }
+            };
+
+            #[cfg(unix)]
+            tokio::spawn(reload_on_hangup(bind_addr, certificates.clone()));
+
             info!("Starting HTTPS server on: {} (TLS)", bind_addr);
-            http_tracker
-                .start_tls(bind_addr, ssl_cert_path.unwrap(), ssl_key_path.unwrap())
-                .await;
+            http_tracker.start_tls(bind_addr, certificates).await;
         } else {
             warn!("Could not start HTTP tracker on: {}, missing SSL Cert or Key!", bind_addr);
         }
     })
 }
+
+/// Reloads the certificate of the TLS server on `bind_addr` each time the process receives a `SIGHUP`.
+#[cfg(unix)]
+pub async fn reload_on_hangup(bind_addr: SocketAddr, certificates: Arc<Certificates>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup()).expect("Failed to listen to the SIGHUP signal.");
+
+    while hangups.recv().await.is_some() {
+        match certificates.reload() {
+            Ok(()) => info!("Reloaded the TLS certificate of the server on: {}", bind_addr),
+            Err(e) => error!(
+                "Could not reload the TLS certificate of the server on: {}, {}, keeping the current one",
+                bind_addr, e
+            ),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let interval = config.expired_key_cleanup_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping expired keys cleanup job..");
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        match tracker.remove_expired_auth_keys().await {
+                            Ok(removed) => info!("Removed {} expired keys", removed),
+                            Err(e) => error!("Could not remove the expired keys: {}", e),
+                        }
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
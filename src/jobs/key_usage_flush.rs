@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let interval = config.key_usage_flush_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping key usage flush job..");
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        flush_auth_keys_usage(&tracker).await;
+                    }
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        flush_auth_keys_usage(&tracker).await;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn flush_auth_keys_usage(tracker: &tracker::Tracker) {
+    if let Err(e) = tracker.flush_auth_keys_usage().await {
+        error!("Could not save the keys usage: {}", e);
+    }
+}
@@ -1,4 +1,18 @@
+pub mod completed_stat_flush;
+#[cfg(unix)]
+pub mod config_reload;
+pub mod full_scrape_refresh;
+pub mod grpc_api;
 pub mod http_tracker;
+pub mod key_cleanup;
+pub mod key_usage_flush;
+pub mod peer_persistence;
+pub mod sqlite_backup;
+pub mod state_snapshot;
+pub mod stats_history;
 pub mod torrent_cleanup;
+pub mod torrent_memory_limit;
+pub mod torrent_retention;
 pub mod tracker_api;
 pub mod udp_tracker;
+pub mod ws_tracker;
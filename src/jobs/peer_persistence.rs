@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let interval = config.persist_peers_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping peer persistence job..");
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        save_peers(&tracker).await;
+                    }
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        save_peers(&tracker).await;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn save_peers(tracker: &tracker::Tracker) {
+    let start_time = Utc::now().time();
+    info!("Saving peers..");
+    match tracker.save_persistent_peers().await {
+        Ok(peers) => info!(
+            "Saved {} peers in: {}ms",
+            peers,
+            (Utc::now().time() - start_time).num_milliseconds()
+        ),
+        Err(e) => error!("Could not save peers: {:?}", e),
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::Utc;
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::databases::sqlite;
+
+#[must_use]
+pub fn start_job(config: &Configuration) -> JoinHandle<()> {
+    let db_path = config.db_path.clone();
+    let settings = config.database.sqlite.clone();
+    let interval = settings.backup_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping SQLite backup job..");
+                    break;
+                }
+                _ = interval.tick() => {
+                    let start_time = Utc::now().time();
+                    info!("Backing up the database..");
+
+                    let db_path = db_path.clone();
+                    let settings = settings.clone();
+
+                    match tokio::task::spawn_blocking(move || sqlite::backup(&db_path, &settings)).await {
+                        Ok(Ok(backup_path)) => info!(
+                            "Backed up the database into {} in: {}ms",
+                            backup_path.display(),
+                            (Utc::now().time() - start_time).num_milliseconds()
+                        ),
+                        Ok(Err(e)) => error!("Could not back up the database: {}", e),
+                        Err(e) => error!("Could not back up the database: {}", e),
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::Utc;
+use log::{error, info};
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let path = PathBuf::from(&config.state_snapshot_path);
+    let periodic = config.state_snapshot_interval > 0;
+    let interval = config.state_snapshot_interval.max(1);
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping state snapshot job..");
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        save_snapshot(&tracker, &path).await;
+                    }
+                    break;
+                }
+                _ = interval.tick(), if periodic => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        save_snapshot(&tracker, &path).await;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn save_snapshot(tracker: &tracker::Tracker, path: &Path) {
+    let start_time = Utc::now().time();
+    info!("Saving state snapshot..");
+    match tracker.save_snapshot(path).await {
+        Ok(()) => info!(
+            "Saved state snapshot into {} in: {}ms",
+            path.display(),
+            (Utc::now().time() - start_time).num_milliseconds()
+        ),
+        Err(e) => error!("Could not save state snapshot: {}", e),
+    }
+}
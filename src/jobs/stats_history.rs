@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use log::info;
+use tokio::task::JoinHandle;
+
+use crate::tracker;
+
+/// How often the peers and torrents are sampled, the last sample of each bucket is kept.
+const SAMPLE_INTERVAL_SECS: u64 = 60;
+
+#[must_use]
+pub fn start_job(tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(SAMPLE_INTERVAL_SECS));
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping statistics history job..");
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        tracker.sample_stats_history().await;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
@@ -27,8 +27,13 @@ pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> Joi
                     if let Some(tracker) = weak_tracker.upgrade() {
                         let start_time = Utc::now().time();
                         info!("Cleaning up torrents..");
-                        tracker.cleanup_torrents().await;
-                        info!("Cleaned up torrents in: {}ms", (Utc::now().time() - start_time).num_milliseconds());
+                        let (torrents_removed, peers_removed) = tracker.cleanup_torrents().await;
+                        info!(
+                            "Cleaned up {} torrents and {} peers in: {}ms",
+                            torrents_removed,
+                            peers_removed,
+                            (Utc::now().time() - start_time).num_milliseconds()
+                        );
                     } else {
                         break;
                     }
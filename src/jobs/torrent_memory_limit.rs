@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use log::info;
+use tokio::task::JoinHandle;
+
+use crate::config::Configuration;
+use crate::tracker;
+
+#[must_use]
+pub fn start_job(config: &Configuration, tracker: &Arc<tracker::Tracker>) -> JoinHandle<()> {
+    let weak_tracker = std::sync::Arc::downgrade(tracker);
+    let interval = config.torrent_memory_limit_interval;
+
+    tokio::spawn(async move {
+        let interval = std::time::Duration::from_secs(interval);
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping torrent memory limit job..");
+                    break;
+                }
+                _ = interval.tick() => {
+                    if let Some(tracker) = weak_tracker.upgrade() {
+                        let start_time = Utc::now().time();
+                        info!("Checking the torrents memory limit..");
+                        let evicted = tracker.enforce_torrent_memory_limit().await;
+                        info!(
+                            "Evicted {} least active torrents in: {}ms",
+                            evicted,
+                            (Utc::now().time() - start_time).num_milliseconds()
+                        );
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
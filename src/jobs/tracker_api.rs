@@ -6,6 +6,8 @@ use tokio::task::JoinHandle;
 
 use crate::api::server;
 use crate::config::Configuration;
+use crate::http::tls::Certificates;
+use crate::http::unix;
 use crate::tracker;
 
 #[derive(Debug)]
@@ -13,26 +15,65 @@ pub struct ApiServerJobStarted();
 
 /// # Panics
 ///
-/// It would panic if unable to send the  `ApiServerJobStarted` notice.
+/// It would panic if unable to send the  `ApiServerJobStarted` notice, or to load the TLS certificate.
 pub async fn start_job(config: &Configuration, tracker: Arc<tracker::Tracker>) -> JoinHandle<()> {
-    let bind_addr = config
-        .http_api
-        .bind_address
-        .parse::<std::net::SocketAddr>()
-        .expect("Tracker API bind_address invalid.");
-
-    info!("Starting Torrust API server on: {}", bind_addr);
-
     let (tx, rx) = oneshot::channel::<ApiServerJobStarted>();
 
     // Run the API server
-    let join_handle = tokio::spawn(async move {
-        let handel = server::start(bind_addr, &tracker);
+    let join_handle = if let Some(socket_path) = unix::socket_path(&config.http_api.bind_address) {
+        let socket_path = socket_path.to_path_buf();
+
+        info!("Starting Torrust API server on: unix:{}", socket_path.display());
+
+        tokio::spawn(async move {
+            tx.send(ApiServerJobStarted()).expect("the start job dropped");
+
+            #[cfg(unix)]
+            server::start_unix(&socket_path, &tracker).await;
+            #[cfg(not(unix))]
+            panic!(
+                "Could not start the API server on: unix:{}, no Unix domain sockets!",
+                socket_path.display()
+            );
+        })
+    } else {
+        let bind_addr = config
+            .http_api
+            .bind_address
+            .parse::<std::net::SocketAddr>()
+            .expect("Tracker API bind_address invalid.");
+
+        if config.http_api.ssl_enabled {
+            let (Some(ssl_cert_path), Some(ssl_key_path)) = (&config.http_api.ssl_cert_path, &config.http_api.ssl_key_path)
+            else {
+                panic!("Could not start the API server on: {bind_addr}, missing SSL Cert or Key!");
+            };
+            let certificates = Certificates::load(ssl_cert_path, ssl_key_path)
+                .map(Arc::new)
+                .unwrap_or_else(|e| panic!("Could not start the API server on: {bind_addr}, {e}"));
+
+            info!("Starting Torrust API server on: {} (TLS)", bind_addr);
+
+            tokio::spawn(async move {
+                #[cfg(unix)]
+                tokio::spawn(super::http_tracker::reload_on_hangup(bind_addr, certificates.clone()));
+
+                tx.send(ApiServerJobStarted()).expect("the start job dropped");
+
+                server::start_tls(bind_addr, certificates, &tracker).await;
+            })
+        } else {
+            info!("Starting Torrust API server on: {}", bind_addr);
+
+            tokio::spawn(async move {
+                let handel = server::start(bind_addr, &tracker);
 
-        tx.send(ApiServerJobStarted()).expect("the start job dropped");
+                tx.send(ApiServerJobStarted()).expect("the start job dropped");
 
-        handel.await;
-    });
+                handel.await;
+            })
+        }
+    };
 
     // Wait until the API server job is running
     match rx.await {
@@ -1,21 +1,32 @@
 use std::sync::Arc;
 
+use futures::future::join_all;
 use log::{error, info, warn};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 
 use crate::config::UdpTracker;
 use crate::tracker;
 use crate::udp::server::Udp;
 
+/// Starts the workers of the UDP tracker on each of its addresses, the job finishes once all of them
+/// have stopped on the `shutdown` signal.
 #[must_use]
-pub fn start_job(config: &UdpTracker, tracker: Arc<tracker::Tracker>) -> JoinHandle<()> {
-    let bind_addr = config.bind_address.clone();
+pub fn start_job(config: &UdpTracker, tracker: Arc<tracker::Tracker>, shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+    let bind_addr = config.bind_address.to_string();
+    let config = config.clone();
 
     tokio::spawn(async move {
-        match Udp::new(tracker, &bind_addr).await {
-            Ok(udp_server) => {
-                info!("Starting UDP server on: {}", bind_addr);
-                udp_server.start().await;
+        match Udp::new_workers(tracker, &config).await {
+            Ok(udp_servers) => {
+                info!("Starting UDP server on: {} with {} workers", bind_addr, udp_servers.len());
+                // Each worker runs its own receive loop, in parallel with the others
+                join_all(udp_servers.into_iter().map(|udp_server| {
+                    let shutdown = shutdown.clone();
+                    tokio::spawn(async move { udp_server.start(shutdown).await })
+                }))
+                .await;
+                info!("Stopped UDP server on: {}", bind_addr);
             }
             Err(e) => {
                 warn!("Could not start UDP tracker on: {}", bind_addr);
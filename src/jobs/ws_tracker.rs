@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use log::{info, warn};
+use tokio::task::JoinHandle;
+
+use crate::config::WsTracker;
+use crate::http::tls::Certificates;
+use crate::tracker;
+use crate::ws::server::Ws;
+use crate::ws::swarms::Swarms;
+
+/// # Panics
+///
+/// It would panic if the `config::WsTracker` struct would contain an inappropriate values.
+#[must_use]
+pub fn start_job(config: &WsTracker, tracker: Arc<tracker::Tracker>, swarms: Arc<Swarms>) -> JoinHandle<()> {
+    let bind_addr = config.bind_address.parse::<SocketAddr>().unwrap();
+    let ssl_enabled = config.ssl_enabled;
+    let ssl_cert_path = config.ssl_cert_path.clone();
+    let ssl_key_path = config.ssl_key_path.clone();
+    let ws_tracker = Ws::new(tracker, swarms);
+
+    tokio::spawn(async move {
+        if !ssl_enabled {
+            info!("Starting WebSocket server on: {}", bind_addr);
+            ws_tracker.start(bind_addr).await;
+        } else if let (Some(ssl_cert_path), Some(ssl_key_path)) = (ssl_cert_path, ssl_key_path) {
+            let certificates = match Certificates::load(&ssl_cert_path, &ssl_key_path) {
+                Ok(certificates) => Arc::new(certificates),
+                Err(e) => {
+                    warn!("Could not start WebSocket tracker on: {}, {}", bind_addr, e);
+                    return;
+                }
+            };
+
+            #[cfg(unix)]
+            tokio::spawn(super::http_tracker::reload_on_hangup(bind_addr, certificates.clone()));
+
+            info!("Starting secure WebSocket server on: {} (TLS)", bind_addr);
+            ws_tracker.start_tls(bind_addr, certificates).await;
+        } else {
+            warn!(
+                "Could not start WebSocket tracker on: {}, missing SSL Cert or Key!",
+                bind_addr
+            );
+        }
+    })
+}
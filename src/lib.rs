@@ -1,6 +1,8 @@
 pub mod api;
+pub mod cli;
 pub mod config;
 pub mod databases;
+pub mod grpc;
 pub mod http;
 pub mod jobs;
 pub mod logging;
@@ -9,6 +11,7 @@ pub mod setup;
 pub mod stats;
 pub mod tracker;
 pub mod udp;
+pub mod ws;
 
 #[macro_use]
 extern crate lazy_static;
@@ -7,18 +7,25 @@ use crate::config::Configuration;
 
 static INIT: Once = Once::new();
 
+/// The logger is set up even when the logs are off, so they can be turned on while the tracker runs.
 pub fn setup(cfg: &Configuration) {
     let level = config_level_or_default(&cfg.log_level);
 
-    if level == log::LevelFilter::Off {
-        return;
-    }
-
     INIT.call_once(|| {
         stdout_config(level);
     });
 }
 
+/// Changes the verbosity of the logs while the tracker runs.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+#[must_use]
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
+
 fn config_level_or_default(log_level: &Option<String>) -> LevelFilter {
     match log_level {
         None => log::LevelFilter::Info,
@@ -26,6 +33,7 @@ fn config_level_or_default(log_level: &Option<String>) -> LevelFilter {
     }
 }
 
+/// The logs are only filtered by the max level of the `log` crate, so it can be changed.
 fn stdout_config(level: LevelFilter) {
     if let Err(_err) = fern::Dispatch::new()
         .format(|out, message, record| {
@@ -37,12 +45,14 @@ fn stdout_config(level: LevelFilter) {
                 message
             ));
         })
-        .level(level)
+        .level(LevelFilter::Trace)
         .chain(std::io::stdout())
         .apply()
     {
         panic!("Failed to initialize logging.")
     }
 
+    set_level(level);
+
     info!("logging initialized.");
 }
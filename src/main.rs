@@ -1,14 +1,31 @@
+use std::path::Path;
 use std::sync::Arc;
+use std::{fs, process};
 
+use clap::Parser;
 use log::info;
-use torrust_tracker::config::Configuration;
+use tokio::sync::watch;
+use torrust_tracker::cli::{Cli, Command, DbCommand, RunArgs};
+use torrust_tracker::config::template::{self, Profile};
+use torrust_tracker::config::{self, Configuration, Format};
 use torrust_tracker::stats::setup_statistics;
-use torrust_tracker::{ephemeral_instance_keys, logging, setup, static_time, tracker};
+use torrust_tracker::{databases, ephemeral_instance_keys, logging, setup, static_time, tracker};
 
 #[tokio::main]
 async fn main() {
-    const CONFIG_PATH: &str = "config.toml";
+    let mut cli = Cli::parse();
 
+    match cli.command.take().unwrap_or(Command::Run(RunArgs::default())) {
+        Command::Run(args) => run(cli.config_source(args.overrides())).await,
+        Command::ValidateConfig(args) => validate_config(&cli.config_source(args.overrides())),
+        Command::GenerateConfig { profile, force } => generate_config(&cli.config_source(vec![]), profile, force),
+        Command::Db {
+            command: DbCommand::Migrate,
+        } => migrate_database(&cli.config_source(vec![])),
+    }
+}
+
+async fn run(config_source: config::Source) {
     // Set the time of Torrust app starting
     lazy_static::initialize(&static_time::TIME_AT_APP_START);
 
@@ -16,34 +33,42 @@ async fn main() {
     lazy_static::initialize(&ephemeral_instance_keys::RANDOM_SEED);
 
     // Initialize Torrust config
-    let config = match Configuration::load_from_file(CONFIG_PATH) {
-        Ok(config) => Arc::new(config),
-        Err(error) => {
-            panic!("{}", error)
-        }
-    };
+    let config = Arc::new(load_config(&config_source));
 
     // Initialize statistics
     let (stats_event_sender, stats_repository) = setup_statistics(config.tracker_usage_statistics);
 
-    // Initialize Torrust tracker
+    // Initialize Torrust tracker, which connects to the database, then stop with all the problems of the config
+    let mut problems = config.problems();
     let tracker = match tracker::Tracker::new(&config.clone(), stats_event_sender, stats_repository) {
-        Ok(tracker) => Arc::new(tracker),
+        Ok(tracker) => Some(Arc::new(tracker.with_config_source(config_source))),
         Err(error) => {
-            panic!("{}", error)
+            problems.push(format!("db_path: could not connect to the database, {error}"));
+            None
+        }
+    };
+    let tracker = match tracker {
+        Some(tracker) if problems.is_empty() => tracker,
+        _ => {
+            eprintln!("{}", config::Error::Invalid(problems));
+            process::exit(1);
         }
     };
 
     // Initialize logging
     logging::setup(&config);
 
+    // Signals the jobs to stop on shutdown
+    let (shutdown_sender, shutdown) = watch::channel(false);
+
     // Run jobs
-    let jobs = setup::setup(&config, tracker.clone()).await;
+    let jobs = setup::setup(&config, tracker.clone(), &shutdown).await;
 
     // handle the signals here
     tokio::select! {
         _ = tokio::signal::ctrl_c() => {
             info!("Torrust shutting down..");
+            let _ = shutdown_sender.send(true);
 
             // Await for all jobs to shutdown
             futures::future::join_all(jobs).await;
@@ -51,3 +76,64 @@ async fn main() {
         }
     }
 }
+
+/// Checks the configuration like `run` does before starting the tracker, connecting to the database.
+fn validate_config(config_source: &config::Source) {
+    let config = load_config(config_source);
+
+    let mut problems = config.problems();
+    if let Err(error) = databases::connect(&config.db_driver, &config.db_path, &config.database) {
+        problems.push(format!("db_path: could not connect to the database, {error}"));
+    }
+
+    if !problems.is_empty() {
+        eprintln!("{}", config::Error::Invalid(problems));
+        process::exit(1);
+    }
+
+    println!("The configuration of {} is valid.", config_source.path);
+}
+
+/// Writes the default configuration, or the one of the `profile`, in the format of the file, commented in TOML.
+fn generate_config(config_source: &config::Source, profile: Option<Profile>, force: bool) {
+    let config_path = &config_source.path;
+
+    if Path::new(config_path).exists() && !force {
+        eprintln!("{config_path} already exists, overwrite it with --force.");
+        process::exit(1);
+    }
+
+    let config = profile.map_or_else(Configuration::default, Profile::configuration);
+    let contents = match config_source.format {
+        Format::Toml => template::commented_toml(&config),
+        format => format.encode(&config),
+    };
+
+    let written = contents.and_then(|contents| fs::write(config_path, contents).map_err(config::Error::IOError));
+    if let Err(error) = written {
+        eprintln!("Could not write {config_path}: {error}");
+        process::exit(1);
+    }
+
+    println!("The default configuration was written into {config_path}.");
+}
+
+/// Connects to the database of the configuration, which applies its pending migrations.
+fn migrate_database(config_source: &config::Source) {
+    let config = load_config(config_source);
+    logging::setup(&config);
+
+    if let Err(error) = databases::connect(&config.db_driver, &config.db_path, &config.database) {
+        eprintln!("Could not migrate the database: {error}");
+        process::exit(1);
+    }
+
+    println!("The database is up to date.");
+}
+
+fn load_config(config_source: &config::Source) -> Configuration {
+    config_source.load().unwrap_or_else(|error| {
+        eprintln!("{error}");
+        process::exit(1);
+    })
+}
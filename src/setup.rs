@@ -1,15 +1,33 @@
+use std::path::Path;
 use std::sync::Arc;
 
-use log::warn;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 
 use crate::config::Configuration;
-use crate::jobs::{http_tracker, torrent_cleanup, tracker_api, udp_tracker};
-use crate::tracker;
+use crate::databases::driver::Driver;
+use crate::jobs::{
+    completed_stat_flush, full_scrape_refresh, grpc_api, http_tracker, key_cleanup, key_usage_flush, peer_persistence,
+    sqlite_backup, state_snapshot, stats_history, torrent_cleanup, torrent_memory_limit, torrent_retention, tracker_api,
+    udp_tracker, ws_tracker,
+};
+use crate::{tracker, ws};
 
-pub async fn setup(config: &Configuration, tracker: Arc<tracker::Tracker>) -> Vec<JoinHandle<()>> {
+/// Starts the jobs of the `config`, the UDP trackers stop once `shutdown` changes to `true`.
+pub async fn setup(
+    config: &Configuration,
+    tracker: Arc<tracker::Tracker>,
+    shutdown: &watch::Receiver<bool>,
+) -> Vec<JoinHandle<()>> {
     let mut jobs: Vec<JoinHandle<()>> = Vec::new();
 
+    // Load the GeoIP database first, so the peers restored below are located too
+    if config.geoip {
+        tracker
+            .load_geoip_database(Path::new(&config.geoip_db_path))
+            .expect("Could not load the GeoIP database.");
+    }
+
     // Load peer keys
     if tracker.is_private() {
         tracker.load_keys().await.expect("Could not retrieve keys from database.");
@@ -23,20 +41,35 @@ pub async fn setup(config: &Configuration, tracker: Arc<tracker::Tracker>) -> Ve
             .expect("Could not load whitelist from database.");
     }
 
+    // Load blacklisted IP ranges
+    tracker
+        .load_blacklist()
+        .await
+        .expect("Could not load blacklist from database.");
+
+    // Load the peers saved before the last shutdown
+    if config.persist_peers {
+        tracker
+            .load_persistent_peers()
+            .await
+            .expect("Could not load peers from database.");
+    }
+
+    // Restore the torrents and peers of the snapshot saved on the last shutdown
+    if config.state_snapshot {
+        tracker
+            .load_snapshot(Path::new(&config.state_snapshot_path))
+            .await
+            .expect("Could not load the tracker state snapshot.");
+    }
+
     // Start the UDP blocks
     for udp_tracker_config in &config.udp_trackers {
         if !udp_tracker_config.enabled {
             continue;
         }
 
-        if tracker.is_private() {
-            warn!(
-                "Could not start UDP tracker on: {} while in {:?}. UDP is not safe for private trackers!",
-                udp_tracker_config.bind_address, config.mode
-            );
-        } else {
-            jobs.push(udp_tracker::start_job(udp_tracker_config, tracker.clone()));
-        }
+        jobs.push(udp_tracker::start_job(udp_tracker_config, tracker.clone(), shutdown.clone()));
     }
 
     // Start the HTTP blocks
@@ -47,15 +80,81 @@ pub async fn setup(config: &Configuration, tracker: Arc<tracker::Tracker>) -> Ve
         jobs.push(http_tracker::start_job(http_tracker_config, tracker.clone()));
     }
 
+    // Start the WebSocket blocks, with the offers relayed between the peers of all of them
+    let ws_swarms = Arc::new(ws::swarms::Swarms::default());
+    for ws_tracker_config in &config.ws_trackers {
+        if !ws_tracker_config.enabled {
+            continue;
+        }
+        jobs.push(ws_tracker::start_job(ws_tracker_config, tracker.clone(), ws_swarms.clone()));
+    }
+
     // Start HTTP API server
     if config.http_api.enabled {
         jobs.push(tracker_api::start_job(config, tracker.clone()).await);
     }
 
+    // Start the gRPC API server
+    if config.grpc_api.enabled {
+        jobs.push(grpc_api::start_job(&config.grpc_api, tracker.clone()));
+    }
+
+    // Reload the lists, the keys and the runtime settings of the configuration, on SIGHUP
+    #[cfg(unix)]
+    jobs.push(crate::jobs::config_reload::start_job(&tracker));
+
+    // Sample the peers and torrents for the statistics history, every minute
+    jobs.push(stats_history::start_job(&tracker));
+
     // Remove torrents without peers, every interval
     if config.inactive_peer_cleanup_interval > 0 {
         jobs.push(torrent_cleanup::start_job(config, &tracker));
     }
 
+    // Remove the torrents without announces for the retention days of the mode, every interval
+    if config.torrent_retention.days(config.mode) > 0 && config.torrent_retention.interval > 0 {
+        jobs.push(torrent_retention::start_job(config, &tracker));
+    }
+
+    // Evict the least active torrents while they use more memory than the limit, every interval
+    if config.torrent_memory_limit > 0 && config.torrent_memory_limit_interval > 0 {
+        jobs.push(torrent_memory_limit::start_job(config, &tracker));
+    }
+
+    // Refresh the stats of the full scrapes, every interval
+    if config.scrape_enabled && config.full_scrape && config.full_scrape_refresh_interval > 0 {
+        jobs.push(full_scrape_refresh::start_job(config, &tracker));
+    }
+
+    // Save the buffered completed stats, every interval
+    if config.persistent_torrent_completed_stat && config.completed_stat_flush_interval > 0 {
+        jobs.push(completed_stat_flush::start_job(config, &tracker));
+    }
+
+    // Save a snapshot of the peers, every interval
+    if config.persist_peers && config.persist_peers_interval > 0 {
+        jobs.push(peer_persistence::start_job(config, &tracker));
+    }
+
+    // Save a snapshot of the torrents and peers, on shutdown and every interval
+    if config.state_snapshot {
+        jobs.push(state_snapshot::start_job(config, &tracker));
+    }
+
+    // Save the usage of the keys, every interval
+    if tracker.is_private() && config.key_usage_flush_interval > 0 {
+        jobs.push(key_usage_flush::start_job(config, &tracker));
+    }
+
+    // Remove the expired keys, every interval
+    if tracker.is_private() && config.expired_key_cleanup_interval > 0 {
+        jobs.push(key_cleanup::start_job(config, &tracker));
+    }
+
+    // Back up the SQLite database, every interval
+    if config.db_driver == Driver::Sqlite3 && config.database.sqlite.backup_interval > 0 {
+        jobs.push(sqlite_backup::start_job(config));
+    }
+
     jobs
 }
@@ -0,0 +1,208 @@
+//! Announce interval adjusted to the swarm size and to the tracker load.
+//!
+//! Big swarms and busy trackers get longer intervals, so the number of
+//! announces the tracker handles grows slower than the number of peers.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Configuration, HttpTracker};
+use crate::protocol::clock::DurationSinceUnixEpoch;
+
+/// Seconds the announces are counted for before the rate is updated.
+const RATE_WINDOW_SECS: u64 = 10;
+
+/// Measures the announces per second handled by the tracker.
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    window_start: AtomicU64,
+    announces_in_window: AtomicU64,
+    last_rate: AtomicU64,
+}
+
+impl RateMeter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// It does not lock, concurrent announces at the window boundary may be counted in
+    /// the next window, which is good enough for a load estimation.
+    pub fn record(&self, now: DurationSinceUnixEpoch) {
+        let now = now.as_secs();
+        let window_start = self.window_start.load(Ordering::Relaxed);
+
+        if now >= window_start + RATE_WINDOW_SECS
+            && self
+                .window_start
+                .compare_exchange(window_start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            let announces = self.announces_in_window.swap(0, Ordering::Relaxed);
+            let elapsed = now - window_start;
+            // A window that ended long ago does not tell the current load
+            let rate = if elapsed < 2 * RATE_WINDOW_SECS {
+                announces / elapsed
+            } else {
+                0
+            };
+            self.last_rate.store(rate, Ordering::Relaxed);
+        }
+
+        self.announces_in_window.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Announces per second in the last complete window.
+    #[must_use]
+    pub fn rate(&self) -> u64 {
+        self.last_rate.load(Ordering::Relaxed)
+    }
+}
+
+/// What is done with the announces of a peer arriving before the `min interval` since its last one.
+/// The started, stopped and completed announces are always accepted, they change the torrent stats.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Enforcement {
+    // They are handled like the other ones
+    Off,
+    // They are answered with an error asking the client to retry later
+    Reject,
+    // They are answered with the swarm as it is, without updating the peer
+    Ignore,
+}
+
+/// The intervals of an HTTP tracker replacing the ones of the tracker, like longer ones for a public
+/// listener than for a private one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub announce_interval: Option<u32>,
+    pub min_announce_interval: Option<u32>,
+}
+
+impl Overrides {
+    #[must_use]
+    pub fn new(config: &HttpTracker) -> Self {
+        Self {
+            announce_interval: config.announce_interval,
+            min_announce_interval: config.min_announce_interval,
+        }
+    }
+
+    /// The `announce_interval` of the listener, or the `interval` of the tracker, never below the `min_interval`.
+    #[must_use]
+    pub fn interval(&self, interval: u32, min_interval: u32) -> u32 {
+        self.announce_interval.unwrap_or(interval).max(min_interval)
+    }
+
+    /// The `min_announce_interval` of the listener, or the one of the tracker.
+    #[must_use]
+    pub fn min_interval(&self, config: &Configuration) -> u32 {
+        self.min_announce_interval.unwrap_or(config.min_announce_interval)
+    }
+}
+
+/// The configured `announce_interval` is multiplied by how many times the swarm exceeds
+/// `announce_interval_swarm_size` and the announce rate exceeds `announce_interval_max_rate`,
+/// then bounded by `min_announce_interval` and `max_announce_interval`.
+///
+/// The configured interval is returned as is if `dynamic_announce_interval` is disabled.
+#[must_use]
+pub fn compute(config: &Configuration, swarm_size: u32, announce_rate: u64) -> u32 {
+    if !config.dynamic_announce_interval {
+        return config.announce_interval;
+    }
+
+    let swarm_factor = factor(u64::from(swarm_size), u64::from(config.announce_interval_swarm_size));
+    let load_factor = factor(announce_rate, u64::from(config.announce_interval_max_rate));
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let interval = (f64::from(config.announce_interval) * swarm_factor * load_factor).min(f64::from(u32::MAX)) as u32;
+
+    interval.clamp(
+        config.min_announce_interval,
+        config.max_announce_interval.max(config.min_announce_interval),
+    )
+}
+
+/// How many times `value` exceeds `threshold`, at least one. A zero `threshold` disables it.
+#[allow(clippy::cast_precision_loss)]
+fn factor(value: u64, threshold: u64) -> f64 {
+    if threshold == 0 {
+        return 1.0;
+    }
+    (value as f64 / threshold as f64).max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{compute, Overrides, RateMeter};
+    use crate::config::Configuration;
+
+    fn dynamic_configuration() -> Configuration {
+        let mut config = Configuration::default();
+        config.dynamic_announce_interval = true;
+        config.announce_interval = 120;
+        config.min_announce_interval = 60;
+        config.max_announce_interval = 1800;
+        config.announce_interval_swarm_size = 1000;
+        config.announce_interval_max_rate = 100;
+        config
+    }
+
+    #[test]
+    fn the_configured_interval_should_be_used_when_it_is_not_dynamic() {
+        let config = Configuration::default();
+
+        assert_eq!(compute(&config, 1_000_000, 1_000_000), config.announce_interval);
+    }
+
+    #[test]
+    fn small_swarms_on_an_idle_tracker_should_get_the_configured_interval() {
+        assert_eq!(compute(&dynamic_configuration(), 10, 0), 120);
+    }
+
+    #[test]
+    fn the_interval_should_grow_with_the_swarm_size_and_the_load() {
+        let config = dynamic_configuration();
+
+        assert_eq!(compute(&config, 3000, 0), 360);
+        assert_eq!(compute(&config, 3000, 200), 720);
+    }
+
+    #[test]
+    fn the_interval_should_not_exceed_the_maximum() {
+        assert_eq!(compute(&dynamic_configuration(), 1_000_000, 0), 1800);
+    }
+
+    #[test]
+    fn the_intervals_of_a_listener_should_replace_the_ones_of_the_tracker() {
+        let config = Configuration::default();
+        let overrides = Overrides {
+            announce_interval: Some(1800),
+            min_announce_interval: Some(900),
+        };
+
+        assert_eq!(overrides.interval(120, overrides.min_interval(&config)), 1800);
+        assert_eq!(overrides.min_interval(&config), 900);
+        assert_eq!(Overrides::default().interval(120, 300), 300);
+        assert_eq!(Overrides::default().min_interval(&config), config.min_announce_interval);
+    }
+
+    #[test]
+    fn the_rate_meter_should_measure_the_announces_per_second_of_the_last_window() {
+        let meter = RateMeter::new();
+        let start = Duration::from_secs(1_000);
+
+        meter.record(start);
+        for _ in 0..49 {
+            meter.record(start + Duration::from_secs(5));
+        }
+        meter.record(start + Duration::from_secs(10));
+
+        assert_eq!(meter.rate(), 5);
+    }
+}
@@ -0,0 +1,87 @@
+//! The audit log of the changes made with the API: what they were, when, with which access token and from which
+//! IP address, so the admins sharing a tracker know who made them.
+
+use std::net::IpAddr;
+
+use crate::protocol::clock::DurationSinceUnixEpoch;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub created_at: DurationSinceUnixEpoch,
+    // The name of the access token in the `access_tokens` of the API
+    pub token_name: String,
+    // `None` for the requests of the Unix domain socket
+    pub ip: Option<IpAddr>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+}
+
+/// The filters of the entries listed by the API, from the newest
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    pub token_name: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub since: Option<DurationSinceUnixEpoch>,
+    pub until: Option<DurationSinceUnixEpoch>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Query {
+    /// Whether the entry is kept by the filters of the query
+    #[must_use]
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.token_name
+            .as_ref()
+            .is_none_or(|token_name| *token_name == entry.token_name)
+            && self.ip.is_none_or(|ip| Some(ip) == entry.ip)
+            && self.since.is_none_or(|since| entry.created_at >= since)
+            && self.until.is_none_or(|until| entry.created_at <= until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use super::{Entry, Query};
+
+    fn sample_entry() -> Entry {
+        Entry {
+            created_at: Duration::from_secs(100),
+            token_name: "admin".to_string(),
+            ip: Some(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1))),
+            method: "POST".to_string(),
+            path: "/api/v1/whitelist/9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".to_string(),
+            status: 200,
+        }
+    }
+
+    #[test]
+    fn the_entries_should_be_filtered_by_token_name_ip_and_time() {
+        assert!(Query::default().matches(&sample_entry()));
+
+        let query = Query {
+            token_name: Some("admin".to_string()),
+            ip: Some(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1))),
+            since: Some(Duration::from_secs(100)),
+            until: Some(Duration::from_secs(100)),
+            ..Default::default()
+        };
+        assert!(query.matches(&sample_entry()));
+
+        let query = Query {
+            token_name: Some("other".to_string()),
+            ..Default::default()
+        };
+        assert!(!query.matches(&sample_entry()));
+
+        let query = Query {
+            since: Some(Duration::from_secs(101)),
+            ..Default::default()
+        };
+        assert!(!query.matches(&sample_entry()));
+    }
+}
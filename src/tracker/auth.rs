@@ -14,17 +14,29 @@ use crate::protocol::common::AUTH_KEY_LENGTH;
 ///
 /// It would panic if the `lifetime: Duration` + Duration is more than `Duration::MAX`.
 pub fn generate(lifetime: Duration) -> Key {
+    let auth_key = generate_never_expiring();
+
+    debug!("Generated key: {}, valid for: {:?} seconds", auth_key.key, lifetime);
+
+    Key {
+        valid_until: Some(Current::add(&lifetime).unwrap()),
+        ..auth_key
+    }
+}
+
+/// Generates a key without `valid_until`, for the services which are given a key once and for all.
+#[must_use]
+pub fn generate_never_expiring() -> Key {
     let key: String = thread_rng()
         .sample_iter(&Alphanumeric)
         .take(AUTH_KEY_LENGTH)
         .map(char::from)
         .collect();
 
-    debug!("Generated key: {}, valid for: {:?} seconds", key, lifetime);
-
     Key {
         key,
-        valid_until: Some(Current::add(&lifetime).unwrap()),
+        created_at: Some(Current::now()),
+        ..Default::default()
     }
 }
 
@@ -32,33 +44,36 @@ pub fn generate(lifetime: Duration) -> Key {
 ///
 /// Will return `Error::KeyExpired` if `auth_key.valid_until` is past the `current_time`.
 ///
-/// Will return `Error::KeyInvalid` if `auth_key.valid_until` is past the `None`.
+/// The keys without `valid_until` never expire.
 pub fn verify(auth_key: &Key) -> Result<(), Error> {
     let current_time: DurationSinceUnixEpoch = Current::now();
 
     match auth_key.valid_until {
-        Some(valid_untill) => {
-            if valid_untill < current_time {
-                Err(Error::KeyExpired)
-            } else {
-                Ok(())
-            }
-        }
-        None => Err(Error::KeyInvalid),
+        Some(valid_untill) if valid_untill < current_time => Err(Error::KeyExpired),
+        _ => Ok(()),
     }
 }
 
-#[derive(Serialize, Debug, Eq, PartialEq, Clone)]
+#[derive(Serialize, Debug, Default, Eq, PartialEq, Clone)]
 pub struct Key {
     pub key: String,
     pub valid_until: Option<DurationSinceUnixEpoch>,
+    /// A human-readable name to know who the key was given to.
+    pub label: Option<String>,
+    pub created_at: Option<DurationSinceUnixEpoch>,
+    pub last_used: Option<DurationSinceUnixEpoch>,
+    /// Number of announces authenticated with the key.
+    pub announces: u64,
 }
 
 impl Key {
     #[must_use]
     pub fn from_buffer(key_buffer: [u8; AUTH_KEY_LENGTH]) -> Option<Key> {
         if let Ok(key) = String::from_utf8(Vec::from(key_buffer)) {
-            Some(Key { key, valid_until: None })
+            Some(Key {
+                key,
+                ..Default::default()
+            })
         } else {
             None
         }
@@ -69,7 +84,7 @@ impl Key {
         if key.len() == AUTH_KEY_LENGTH {
             Some(Key {
                 key: key.to_string(),
-                valid_until: None,
+                ..Default::default()
             })
         } else {
             None
@@ -147,4 +162,13 @@ mod tests {
 
         assert!(auth::verify(&auth_key).is_err());
     }
+
+    #[test]
+    fn a_key_without_valid_until_should_never_expire() {
+        let auth_key = auth::generate_never_expiring();
+
+        Current::local_add(&Duration::from_secs(u64::from(u32::MAX))).unwrap();
+
+        assert!(auth::verify(&auth_key).is_ok());
+    }
 }
@@ -0,0 +1,85 @@
+//! IP addresses and ranges whose announces and scrapes are rejected.
+
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ipnet::IpNet;
+
+#[derive(Debug, Default)]
+pub struct Blacklist {
+    ranges: BTreeSet<IpNet>,
+}
+
+impl Blacklist {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `false` if the range was already blacklisted.
+    pub fn insert(&mut self, range: IpNet) -> bool {
+        self.ranges.insert(range.trunc())
+    }
+
+    /// Returns `false` if the range was not blacklisted.
+    pub fn remove(&mut self, range: &IpNet) -> bool {
+        self.ranges.remove(&range.trunc())
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    /// IPv4-mapped IPv6 addresses are also checked against the IPv4 ranges.
+    #[must_use]
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.ranges.iter().any(|range| range.contains(&ip))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+/// Parses a CIDR range, like `10.0.0.0/8`, or a single IP address.
+/// The host bits of the range are cleared.
+///
+/// # Errors
+///
+/// Will return an `ipnet::AddrParseError` if `range` is neither a CIDR range nor an IP address.
+pub fn parse_range(range: &str) -> Result<IpNet, ipnet::AddrParseError> {
+    match IpAddr::from_str(range) {
+        Ok(ip) => Ok(IpNet::from(ip)),
+        Err(_) => IpNet::from_str(range).map(|range| range.trunc()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    use super::{parse_range, Blacklist};
+
+    #[test]
+    fn it_should_parse_single_addresses_and_cidr_ranges() {
+        assert_eq!(parse_range("126.0.0.1").unwrap().to_string(), "126.0.0.1/32");
+        assert_eq!(parse_range("10.1.2.3/8").unwrap().to_string(), "10.0.0.0/8");
+        assert_eq!(parse_range("2001:db8::1/32").unwrap().to_string(), "2001:db8::/32");
+        assert!(parse_range("10.0.0.0/33").is_err());
+        assert!(parse_range("not an ip").is_err());
+    }
+
+    #[test]
+    fn it_should_contain_the_addresses_in_the_blacklisted_ranges() {
+        let mut blacklist = Blacklist::new();
+        blacklist.insert(parse_range("10.0.0.0/8").unwrap());
+
+        assert!(blacklist.contains(&IpAddr::from_str("10.20.30.40").unwrap()));
+        assert!(blacklist.contains(&IpAddr::from_str("::ffff:10.20.30.40").unwrap()));
+        assert!(!blacklist.contains(&IpAddr::from_str("11.0.0.1").unwrap()));
+    }
+}
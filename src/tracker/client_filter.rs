@@ -0,0 +1,160 @@
+//! Clients whose announces are rejected, or the only ones served, recognized
+//! by their peer id prefix, like `-XL` for Xunlei, and for HTTP by their
+//! User-Agent.
+
+use std::collections::BTreeSet;
+
+use serde;
+use serde::{Deserialize, Serialize};
+
+use super::peer;
+use crate::config;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mode {
+    // The matching clients are rejected
+    #[serde(rename = "block")]
+    Block,
+    // Only the matching clients are served
+    #[serde(rename = "allow")]
+    Allow,
+}
+
+#[derive(Debug)]
+pub struct ClientFilter {
+    mode: Mode,
+    peer_id_prefixes: BTreeSet<String>,
+    user_agents: BTreeSet<String>,
+}
+
+impl ClientFilter {
+    #[must_use]
+    pub fn new(config: &config::ClientFilter) -> Self {
+        Self {
+            mode: config.mode,
+            peer_id_prefixes: config.peer_id_prefixes.iter().cloned().collect(),
+            user_agents: config.user_agents.iter().cloned().collect(),
+        }
+    }
+
+    /// `user_agent` is `None` for the protocols without one, like UDP, then only the peer id is checked.
+    #[must_use]
+    pub fn is_allowed(&self, peer_id: &peer::Id, user_agent: Option<&str>) -> bool {
+        let peer_id_matches = self
+            .peer_id_prefixes
+            .iter()
+            .any(|prefix| peer_id.0.starts_with(prefix.as_bytes()));
+        let user_agent_matches =
+            user_agent.is_some_and(|user_agent| self.user_agents.iter().any(|pattern| matches(pattern, user_agent)));
+
+        match self.mode {
+            Mode::Block => !peer_id_matches && !user_agent_matches,
+            Mode::Allow => {
+                (self.peer_id_prefixes.is_empty() || peer_id_matches)
+                    && (self.user_agents.is_empty() || user_agent.is_none() || user_agent_matches)
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn peer_id_prefixes(&self) -> impl Iterator<Item = &String> {
+        self.peer_id_prefixes.iter()
+    }
+
+    pub fn user_agents(&self) -> impl Iterator<Item = &String> {
+        self.user_agents.iter()
+    }
+
+    /// Returns `false` if the prefix was already in the filter.
+    pub fn add_peer_id_prefix(&mut self, prefix: String) -> bool {
+        self.peer_id_prefixes.insert(prefix)
+    }
+
+    /// Returns `false` if the prefix was not in the filter.
+    pub fn remove_peer_id_prefix(&mut self, prefix: &str) -> bool {
+        self.peer_id_prefixes.remove(prefix)
+    }
+
+    /// Returns `false` if the pattern was already in the filter.
+    pub fn add_user_agent(&mut self, pattern: String) -> bool {
+        self.user_agents.insert(pattern)
+    }
+
+    /// Returns `false` if the pattern was not in the filter.
+    pub fn remove_user_agent(&mut self, pattern: &str) -> bool {
+        self.user_agents.remove(pattern)
+    }
+}
+
+/// Case insensitive match of the whole `text`, where a `*` in the `pattern` matches any text.
+fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+
+    let mut parts = pattern.split('*');
+    // There is always a first part, empty if the pattern starts with `*`
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    // There was no `*`
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, ClientFilter, Mode};
+    use crate::config;
+    use crate::tracker::peer;
+
+    fn filter(mode: Mode) -> ClientFilter {
+        ClientFilter::new(&config::ClientFilter {
+            mode,
+            peer_id_prefixes: vec!["-XL".to_string()],
+            user_agents: vec!["BadClient/*".to_string()],
+        })
+    }
+
+    #[test]
+    fn user_agent_patterns_should_match_the_whole_text_case_insensitively() {
+        assert!(matches("BadClient/*", "badclient/1.0"));
+        assert!(matches("*torrent*", "uTorrent/3.5"));
+        assert!(matches("Exact", "exact"));
+        assert!(!matches("Exact", "Exactly"));
+        assert!(!matches("BadClient/*", "GoodClient/1.0"));
+    }
+
+    #[test]
+    fn it_should_block_the_matching_clients() {
+        let filter = filter(Mode::Block);
+
+        assert!(!filter.is_allowed(&peer::Id(*b"-XL0012-000000000000"), None));
+        assert!(!filter.is_allowed(&peer::Id(*b"-qB00000000000000000"), Some("BadClient/2.1")));
+        assert!(filter.is_allowed(&peer::Id(*b"-qB00000000000000000"), Some("qBittorrent/4.5")));
+    }
+
+    #[test]
+    fn it_should_only_allow_the_matching_clients() {
+        let mut filter = filter(Mode::Allow);
+        filter.remove_user_agent("BadClient/*");
+
+        assert!(filter.is_allowed(&peer::Id(*b"-XL0012-000000000000"), Some("Xunlei")));
+        assert!(!filter.is_allowed(&peer::Id(*b"-qB00000000000000000"), None));
+    }
+}
@@ -0,0 +1,134 @@
+//! Announce, scrape and key events, for integrations like webhooks, statistics
+//! exporters or indexes, without changing the announce path.
+//!
+//! The [`EventListener`]s subscribed to the [`Bus`] receive the events from a
+//! background task, so a slow listener does not delay the tracker responses.
+//! If the listeners fall behind and the channel fills up, the new events are
+//! dropped rather than blocking the announces.
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use async_trait::async_trait;
+use log::warn;
+use tokio::sync::mpsc;
+
+use super::{auth, peer};
+use crate::protocol::info_hash::InfoHash;
+
+const CHANNEL_BUFFER_SIZE: usize = 65_535;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The first announce of a torrent not in the repository, or removed from it since its last one.
+    TorrentAdded {
+        info_hash: InfoHash,
+    },
+    /// Every accepted announce, but the stopped ones.
+    PeerAnnounced {
+        info_hash: InfoHash,
+        peer: peer::Peer,
+    },
+    PeerStopped {
+        info_hash: InfoHash,
+        peer: peer::Peer,
+    },
+    /// A known peer announced it finished downloading the torrent.
+    TorrentCompleted {
+        info_hash: InfoHash,
+        peer: peer::Peer,
+    },
+    TorrentScraped {
+        info_hash: InfoHash,
+    },
+    KeyCreated {
+        key: auth::Key,
+    },
+}
+
+#[async_trait]
+pub trait EventListener: Sync + Send {
+    async fn on_event(&self, event: &Event);
+}
+
+type Listeners = Arc<RwLock<Vec<Arc<dyn EventListener>>>>;
+
+#[derive(Default)]
+pub struct Bus {
+    listeners: Listeners,
+    // Only set once there is a listener, so emitting without listeners costs nothing
+    sender: OnceLock<mpsc::Sender<Event>>,
+}
+
+impl Bus {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first subscription starts the task delivering the events, so it has to be done
+    /// within a Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the listeners lock is poisoned.
+    pub fn subscribe(&self, listener: Arc<dyn EventListener>) {
+        self.listeners.write().unwrap().push(listener);
+
+        self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER_SIZE);
+            tokio::spawn(dispatch(receiver, self.listeners.clone()));
+            sender
+        });
+    }
+
+    pub fn emit(&self, event: Event) {
+        if let Some(sender) = self.sender.get() {
+            if let Err(e) = sender.try_send(event) {
+                warn!("Dropped tracker event, the listeners are falling behind: {}", e);
+            }
+        }
+    }
+}
+
+async fn dispatch(mut receiver: mpsc::Receiver<Event>, listeners: Listeners) {
+    while let Some(event) = receiver.recv().await {
+        let listeners = listeners.read().unwrap().clone();
+
+        for listener in listeners {
+            listener.on_event(&event).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use tokio::sync::mpsc;
+
+    use super::{Bus, Event, EventListener};
+    use crate::protocol::info_hash::InfoHash;
+
+    struct Forwarder(mpsc::UnboundedSender<Event>);
+
+    #[async_trait]
+    impl EventListener for Forwarder {
+        async fn on_event(&self, event: &Event) {
+            self.0.send(event.clone()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn the_subscribed_listeners_should_receive_the_emitted_events() {
+        let bus = Bus::new();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        bus.subscribe(Arc::new(Forwarder(sender)));
+
+        let info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+        bus.emit(Event::TorrentScraped { info_hash });
+
+        assert_eq!(receiver.recv().await, Some(Event::TorrentScraped { info_hash }));
+    }
+}
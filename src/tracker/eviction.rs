@@ -0,0 +1,21 @@
+use serde;
+use serde::{Deserialize, Serialize};
+
+use super::peer;
+
+/// Which peer is removed from a torrent that reached `max_peers_per_torrent` when a new one announces.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Policy {
+    // The peer that has not announced for the longest time, it is the most likely to be gone.
+    #[serde(rename = "oldest_announce_first")]
+    OldestAnnounceFirst,
+}
+
+impl Policy {
+    /// Returns the peer to evict, `None` if there are no peers.
+    pub fn select<'a>(self, peers: impl Iterator<Item = &'a peer::Peer>) -> Option<&'a peer::Peer> {
+        match self {
+            Policy::OldestAnnounceFirst => peers.min_by_key(|peer| peer.updated),
+        }
+    }
+}
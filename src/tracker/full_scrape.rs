@@ -0,0 +1,52 @@
+//! The stats of all the torrents for the full scrapes, refreshed every interval so the scrapes
+//! read the last snapshot instead of locking every shard of the torrents.
+
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+
+use super::torrent;
+use crate::protocol::info_hash::InfoHash;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Entry {
+    pub info_hash: InfoHash,
+    pub seeders: u32,
+    pub completed: u32,
+    pub leechers: u32,
+    // The leechers that are BEP 21 partial seeds
+    pub partial_seeds: u32,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn new(info_hash: InfoHash, torrent_entry: &torrent::Entry) -> Self {
+        let (seeders, completed, leechers) = torrent_entry.get_stats();
+
+        Self {
+            info_hash,
+            seeders,
+            completed,
+            leechers,
+            partial_seeds: torrent_entry.get_partial_seeds(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Cache {
+    entries: RwLock<Arc<Vec<Entry>>>,
+}
+
+impl Cache {
+    /// The entries of the last refresh, sorted by info hash.
+    pub async fn get(&self) -> Arc<Vec<Entry>> {
+        self.entries.read().await.clone()
+    }
+
+    pub async fn set(&self, mut entries: Vec<Entry>) {
+        entries.sort_unstable_by_key(|entry| entry.info_hash);
+
+        *self.entries.write().await = Arc::new(entries);
+    }
+}
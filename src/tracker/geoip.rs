@@ -0,0 +1,92 @@
+//! Country of the peers, looked up in a `MaxMind` GeoIP2 or GeoLite2 country database,
+//! for the operators who need regional insight.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde::{Serialize, Serializer};
+
+/// ISO 3166-1 alpha-2 country code, like `US`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Country(pub [u8; 2]);
+
+impl FromStr for Country {
+    type Err = ();
+
+    fn from_str(iso_code: &str) -> Result<Self, Self::Err> {
+        match iso_code.as_bytes() {
+            [first, second] if first.is_ascii_alphabetic() && second.is_ascii_alphabetic() => {
+                Ok(Country([first.to_ascii_uppercase(), second.to_ascii_uppercase()]))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", char::from(self.0[0]), char::from(self.0[1]))
+    }
+}
+
+impl Serialize for Country {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+pub struct Locator {
+    reader: Reader<Vec<u8>>,
+}
+
+impl Locator {
+    /// The whole database is loaded in memory.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `MaxMindDBError` if unable to read the database at `path`.
+    pub fn open(path: &Path) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    /// Falls back to the country where the network is registered, `None` for the
+    /// private addresses and the ones not in the database.
+    #[must_use]
+    pub fn country(&self, ip: IpAddr) -> Option<Country> {
+        let record = self.reader.lookup::<geoip2::Country>(ip).ok()?;
+
+        record
+            .country
+            .or(record.registered_country)
+            .and_then(|country| country.iso_code)
+            .and_then(|iso_code| iso_code.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Country, Locator};
+
+    #[test]
+    fn a_country_should_be_parsed_from_its_iso_code() {
+        assert_eq!("us".parse::<Country>(), Ok(Country(*b"US")));
+        assert_eq!(Country(*b"ES").to_string(), "ES");
+        assert!("USA".parse::<Country>().is_err());
+        assert!("1A".parse::<Country>().is_err());
+    }
+
+    #[test]
+    fn it_should_fail_to_open_a_missing_database() {
+        assert!(Locator::open(Path::new("missing-geoip-database.mmdb")).is_err());
+    }
+}
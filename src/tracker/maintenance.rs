@@ -0,0 +1,120 @@
+//! Runs the persistence and the cleanup of the periodic jobs right away, so the operators can make a checkpoint
+//! before a planned restart.
+
+use super::Tracker;
+use crate::databases;
+
+/// What was saved into the database and removed from memory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    pub torrents_saved: usize,
+    pub keys_saved: usize,
+    pub torrents_removed: usize,
+    pub peers_removed: usize,
+    pub peers_saved: usize,
+}
+
+impl Tracker {
+    /// Saves the buffered completed stats and key usage, removes the inactive peers and the peerless torrents, then
+    /// saves the remaining peers if they are persisted.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to save into the database, the buffers are kept to be retried.
+    pub async fn flush_and_cleanup(&self) -> Result<Report, databases::error::Error> {
+        let torrents_saved = self.flush_completed_stats().await?;
+        let keys_saved = self.flush_auth_keys_usage().await?;
+
+        let (torrents_removed, peers_removed) = self.cleanup_torrents().await;
+
+        let peers_saved = if self.config().persist_peers {
+            self.save_persistent_peers().await?
+        } else {
+            0
+        };
+
+        Ok(Report {
+            torrents_saved,
+            keys_saved,
+            torrents_removed,
+            peers_removed,
+            peers_saved,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+
+    use super::Report;
+    use crate::config::Configuration;
+    use crate::databases::driver::Driver;
+    use crate::protocol::clock::{Current, StoppedTime, Time, TimeNow};
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::{peer, statistics, Tracker};
+
+    fn configuration() -> Configuration {
+        let mut config = Configuration::default();
+        config.db_driver = Driver::Memory;
+        config.persistent_torrent_completed_stat = true;
+        config.completed_stat_flush_interval = 10;
+        config
+    }
+
+    fn peer(peer_id: &[u8; 20], updated: Duration, event: AnnounceEvent) -> peer::Peer {
+        peer::Peer {
+            peer_id: peer::Id(*peer_id),
+            peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)), 8080),
+            updated,
+            uploaded: NumberOfBytes(0),
+            downloaded: NumberOfBytes(0),
+            left: NumberOfBytes(0),
+            event,
+            paused: false,
+            key: None,
+            country: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_save_the_completed_stats_and_remove_the_inactive_peers_right_away() {
+        Current::local_set_to_system_time_now();
+
+        let tracker = Tracker::new(&Arc::new(configuration()), None, statistics::Repo::new()).unwrap();
+
+        let completed_torrent = InfoHash([0; 20]);
+        let inactive_torrent = InfoHash([1; 20]);
+        let inactive_since = Current::sub(&Duration::from_secs(3600)).unwrap();
+
+        // The completed announce of a peer is only counted after it started
+        for event in [AnnounceEvent::Started, AnnounceEvent::Completed] {
+            tracker
+                .update_torrent_with_peer_and_get_stats(&completed_torrent, &peer(b"-qB00000000000000001", Current::now(), event))
+                .await;
+        }
+        tracker
+            .update_torrent_with_peer_and_get_stats(
+                &inactive_torrent,
+                &peer(b"-qB00000000000000002", inactive_since, AnnounceEvent::Started),
+            )
+            .await;
+
+        assert_eq!(
+            tracker.flush_and_cleanup().await.unwrap(),
+            Report {
+                torrents_saved: 1,
+                keys_saved: 0,
+                torrents_removed: 1,
+                peers_removed: 1,
+                peers_saved: 0,
+            }
+        );
+        assert!(tracker.get_torrent_stats(&inactive_torrent).await.is_none());
+        assert_eq!(tracker.get_torrent_stats(&completed_torrent).await, Some((1, 1, 0)));
+    }
+}
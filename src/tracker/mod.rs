@@ -1,78 +1,180 @@
+pub mod announce_interval;
+pub mod audit;
 pub mod auth;
+pub mod blacklist;
+pub mod client_filter;
+pub mod events;
+pub mod eviction;
+pub mod full_scrape;
+pub mod geoip;
+pub mod maintenance;
 pub mod mode;
 pub mod peer;
+pub mod peer_selection;
+pub mod port_range;
+pub mod rate_limit;
+pub mod reload;
+pub mod repository;
+pub mod snapshot;
 pub mod statistics;
+pub mod swarm_health;
 pub mod torrent;
+pub mod torrent_query;
 
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock as StdRwLock};
 use std::time::Duration;
 
+use aquatic_udp_protocol::AnnounceEvent;
+use ipnet::IpNet;
+use log::error;
 use tokio::sync::mpsc::error::SendError;
-use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
 
 use crate::config::Configuration;
-use crate::databases::{self, Database};
+use crate::databases::{self, write_behind, Database};
+use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Time};
+use crate::protocol::common::MAX_SCRAPE_TORRENTS;
 use crate::protocol::info_hash::InfoHash;
 
 pub struct Tracker {
-    pub config: Arc<Configuration>,
-    mode: mode::Mode,
+    // Replaced by the reloaded one, with the settings which can change while the tracker runs, see `reload`
+    config: StdRwLock<Arc<Configuration>>,
+    // Where the configuration was loaded from, to reload it
+    config_source: Option<crate::config::Source>,
     keys: RwLock<std::collections::HashMap<String, auth::Key>>,
+    // Keys used since their usage was last saved
+    used_keys: Mutex<std::collections::HashSet<String>>,
     whitelist: RwLock<std::collections::HashSet<InfoHash>>,
-    torrents: RwLock<std::collections::BTreeMap<InfoHash, torrent::Entry>>,
+    blacklist: RwLock<blacklist::Blacklist>,
+    client_filter: RwLock<client_filter::ClientFilter>,
+    geoip: OnceLock<geoip::Locator>,
+    announce_rate_limiter: Mutex<rate_limit::RateLimiter>,
+    announce_rate: announce_interval::RateMeter,
+    under_maintenance: AtomicBool,
+    events: events::Bus,
+    torrents: repository::Repository,
     stats_event_sender: Option<Box<dyn statistics::EventSender>>,
     stats_repository: statistics::Repo,
     database: Box<dyn Database>,
+    completed_stats: write_behind::Buffer,
+    full_scrape: full_scrape::Cache,
 }
 
 impl Tracker {
     /// # Errors
     ///
-    /// Will return a `r2d2::Error` if unable to connect to database.
+    /// Will return a `database::Error` if unable to connect to database.
     pub fn new(
         config: &Arc<Configuration>,
         stats_event_sender: Option<Box<dyn statistics::EventSender>>,
         stats_repository: statistics::Repo,
-    ) -> Result<Tracker, r2d2::Error> {
-        let database = databases::connect(&config.db_driver, &config.db_path)?;
+    ) -> Result<Tracker, databases::error::Error> {
+        let database = databases::connect(&config.db_driver, &config.db_path, &config.database)?;
 
         Ok(Tracker {
-            config: config.clone(),
-            mode: config.mode,
+            config: StdRwLock::new(config.clone()),
+            config_source: None,
             keys: RwLock::new(std::collections::HashMap::new()),
+            used_keys: Mutex::new(std::collections::HashSet::new()),
             whitelist: RwLock::new(std::collections::HashSet::new()),
-            torrents: RwLock::new(std::collections::BTreeMap::new()),
+            blacklist: RwLock::new(blacklist::Blacklist::new()),
+            client_filter: RwLock::new(client_filter::ClientFilter::new(&config.client_filter)),
+            geoip: OnceLock::new(),
+            announce_rate_limiter: Mutex::new(rate_limit::RateLimiter::new(Duration::from_secs(u64::from(
+                config.announce_rate_limit_interval,
+            )))),
+            announce_rate: announce_interval::RateMeter::new(),
+            under_maintenance: AtomicBool::new(config.maintenance_mode),
+            events: events::Bus::new(),
+            torrents: repository::Repository::new(config.torrent_shards),
             stats_event_sender,
             stats_repository,
             database,
+            completed_stats: write_behind::Buffer::new(config.completed_stat_flush_threshold),
+            full_scrape: full_scrape::Cache::default(),
         })
     }
 
+    /// The current configuration, the one of the last reload if any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the configuration lock is poisoned.
+    #[must_use]
+    pub fn config(&self) -> Arc<Configuration> {
+        self.config.read().unwrap().clone()
+    }
+
     pub fn is_public(&self) -> bool {
-        self.mode == mode::Mode::Public
+        self.config().mode == mode::Mode::Public
     }
 
     pub fn is_private(&self) -> bool {
-        self.mode == mode::Mode::Private || self.mode == mode::Mode::PrivateListed
+        matches!(self.config().mode, mode::Mode::Private | mode::Mode::PrivateListed)
     }
 
     pub fn is_whitelisted(&self) -> bool {
-        self.mode == mode::Mode::Listed || self.mode == mode::Mode::PrivateListed
+        matches!(self.config().mode, mode::Mode::Listed | mode::Mode::PrivateListed)
     }
 
+    /// The key never expires without a `lifetime`.
+    ///
     /// # Errors
     ///
     /// Will return a `database::Error` if unable to add the `auth_key` to the database.
-    pub async fn generate_auth_key(&self, lifetime: Duration) -> Result<auth::Key, databases::error::Error> {
-        let auth_key = auth::generate(lifetime);
+    pub async fn generate_auth_key(
+        &self,
+        lifetime: Option<Duration>,
+        label: Option<String>,
+    ) -> Result<auth::Key, databases::error::Error> {
+        let auth_key = auth::Key {
+            label,
+            ..lifetime.map_or_else(auth::generate_never_expiring, auth::generate)
+        };
         self.database.add_key_to_keys(&auth_key).await?;
         self.keys.write().await.insert(auth_key.key.clone(), auth_key.clone());
+        self.events.emit(events::Event::KeyCreated { key: auth_key.clone() });
         Ok(auth_key)
     }
 
+    /// Generates `count` keys with the same `lifetime`, labeled with the `label_prefix` followed by their
+    /// number from 1, and adds all of them to the database at once.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to add the keys to the database, then none of them is added.
+    pub async fn generate_auth_keys(
+        &self,
+        count: usize,
+        lifetime: Option<Duration>,
+        label_prefix: Option<&str>,
+    ) -> Result<Vec<auth::Key>, databases::error::Error> {
+        let auth_keys: Vec<auth::Key> = (1..=count)
+            .map(|number| auth::Key {
+                label: label_prefix.map(|label_prefix| format!("{label_prefix}{number}")),
+                ..lifetime.map_or_else(auth::generate_never_expiring, auth::generate)
+            })
+            .collect();
+
+        self.database.add_keys_to_keys(&auth_keys).await?;
+
+        self.keys
+            .write()
+            .await
+            .extend(auth_keys.iter().map(|auth_key| (auth_key.key.clone(), auth_key.clone())));
+        for auth_key in &auth_keys {
+            self.events.emit(events::Event::KeyCreated { key: auth_key.clone() });
+        }
+
+        Ok(auth_keys)
+    }
+
     /// # Errors
     ///
     /// Will return a `database::Error` if unable to remove the `key` to the database.
@@ -82,6 +184,29 @@ impl Tracker {
         Ok(())
     }
 
+    /// Sets when the `key` expires, `None` for it to never expire, and returns the updated key.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error::NotFound` if there is no such `key`, or another one if unable to update it
+    /// in the database.
+    pub async fn update_auth_key_valid_until(
+        &self,
+        key: &str,
+        valid_until: Option<DurationSinceUnixEpoch>,
+    ) -> Result<auth::Key, databases::error::Error> {
+        if !self.keys.read().await.contains_key(key) {
+            return Err(databases::error::Error::NotFound);
+        }
+
+        self.database.update_key_valid_until(key, valid_until).await?;
+
+        let mut keys = self.keys.write().await;
+        let auth_key = keys.get_mut(key).ok_or(databases::error::Error::NotFound)?;
+        auth_key.valid_until = valid_until;
+        Ok(auth_key.clone())
+    }
+
     /// # Errors
     ///
     /// Will return a `key::Error` if unable to get any `auth_key`.
@@ -96,6 +221,9 @@ impl Tracker {
     ///
     /// Will return a `database::Error` if unable to `load_keys` from the database.
     pub async fn load_keys(&self) -> Result<(), databases::error::Error> {
+        // Otherwise the usage not saved yet would be lost
+        self.flush_auth_keys_usage().await?;
+
         let keys_from_database = self.database.load_keys().await?;
         let mut keys = self.keys.write().await;
 
@@ -108,6 +236,84 @@ impl Tracker {
         Ok(())
     }
 
+    /// Removing the expired keys from the database and from memory
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to remove the expired keys from the database.
+    pub async fn remove_expired_auth_keys(&self) -> Result<usize, databases::error::Error> {
+        let now = Current::now();
+
+        let removed = self.database.remove_expired_keys(now).await?;
+
+        self.keys
+            .write()
+            .await
+            .retain(|_, auth_key| auth_key.valid_until.is_none_or(|valid_until| valid_until >= now));
+
+        Ok(removed)
+    }
+
+    pub async fn get_auth_keys(&self) -> Vec<auth::Key> {
+        self.keys.read().await.values().cloned().collect()
+    }
+
+    /// Records an announce authenticated with the `key`. Keys are only used in private modes.
+    ///
+    /// The usage is saved right away if `key_usage_flush_interval` is zero, otherwise it is
+    /// saved by the key usage flush job.
+    pub async fn update_auth_key_usage(&self, key: &str) {
+        if !self.is_private() {
+            return;
+        }
+
+        let auth_key = {
+            let mut keys = self.keys.write().await;
+
+            let Some(auth_key) = keys.get_mut(key) else {
+                return;
+            };
+
+            auth_key.last_used = Some(Current::now());
+            auth_key.announces += 1;
+            auth_key.clone()
+        };
+
+        if self.config().key_usage_flush_interval == 0 {
+            if let Err(e) = self.database.save_keys_usage(&[auth_key]).await {
+                error!("Could not save the usage of the key: {}", e);
+            }
+        } else {
+            self.used_keys.lock().await.insert(auth_key.key);
+        }
+    }
+
+    /// Saving the usage of the keys used since the last flush into the database in a single transaction,
+    /// returning how many keys were saved
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to save the usage. The keys are kept to be retried.
+    pub async fn flush_auth_keys_usage(&self) -> Result<usize, databases::error::Error> {
+        let used_keys = std::mem::take(&mut *self.used_keys.lock().await);
+
+        if used_keys.is_empty() {
+            return Ok(0);
+        }
+
+        let auth_keys: Vec<auth::Key> = {
+            let keys = self.keys.read().await;
+            used_keys.iter().filter_map(|key| keys.get(key).cloned()).collect()
+        };
+
+        if let Err(e) = self.database.save_keys_usage(&auth_keys).await {
+            self.used_keys.lock().await.extend(used_keys);
+            return Err(e);
+        }
+
+        Ok(auth_keys.len())
+    }
+
     /// Adding torrents is not relevant to public trackers.
     ///
     /// # Errors
@@ -121,7 +327,7 @@ impl Tracker {
 
     /// It adds a torrent to the whitelist if it has not been whitelisted previously
     async fn add_torrent_to_database_whitelist(&self, info_hash: &InfoHash) -> Result<(), databases::error::Error> {
-        if self.database.is_info_hash_whitelisted(info_hash).await.unwrap() {
+        if self.database.is_info_hash_whitelisted(info_hash).await? {
             return Ok(());
         }
 
@@ -145,10 +351,119 @@ impl Tracker {
         Ok(())
     }
 
+    /// Adding torrents is not relevant to public trackers.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to add the `info_hashes` into the whitelist database.
+    pub async fn add_torrents_to_whitelist(&self, info_hashes: &[InfoHash]) -> Result<(), databases::error::Error> {
+        self.database.add_info_hashes_to_whitelist(info_hashes).await?;
+        self.whitelist.write().await.extend(info_hashes.iter().copied());
+        Ok(())
+    }
+
+    /// Removing torrents is not relevant to public trackers.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to remove the `info_hashes` from the whitelist database.
+    pub async fn remove_torrents_from_whitelist(&self, info_hashes: &[InfoHash]) -> Result<(), databases::error::Error> {
+        self.database.remove_info_hashes_from_whitelist(info_hashes).await?;
+
+        let mut whitelist = self.whitelist.write().await;
+
+        for info_hash in info_hashes {
+            whitelist.remove(info_hash);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the torrent with its peers, and its buffered `completed` stat, returning whether it was known.
+    /// With `remove_persistent_stat`, the saved `completed` stat is removed from the database too, otherwise
+    /// the torrent gets it back on its next announce.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to remove the `completed` stat of the torrent from the database.
+    pub async fn remove_torrent(
+        &self,
+        info_hash: &InfoHash,
+        remove_persistent_stat: bool,
+    ) -> Result<bool, databases::error::Error> {
+        let removed = {
+            let mut torrents = self.torrents.shard(info_hash).write().await;
+            let removed_torrent = torrents.remove(info_hash);
+            if let Some(torrent_entry) = &removed_torrent {
+                self.torrents.unindex_torrent(info_hash, torrent_entry);
+            }
+            removed_torrent.is_some()
+        };
+
+        self.completed_stats.remove(info_hash).await;
+
+        if remove_persistent_stat {
+            self.database.remove_persistent_torrent(info_hash).await?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Removes the peer with the `peer_id` from the swarm of the torrent, returns whether it was in it.
+    pub async fn remove_torrent_peer(&self, info_hash: &InfoHash, peer_id: &peer::Id) -> bool {
+        let mut torrents = self.torrents.shard(info_hash).write().await;
+
+        let Some(torrent_entry) = torrents.get_mut(info_hash) else {
+            return false;
+        };
+
+        let ips: Vec<IpAddr> = torrent_entry
+            .all_peers()
+            .filter(|peer| peer.peer_id == *peer_id)
+            .map(|peer| peer.peer_addr.ip())
+            .collect();
+        let removed = torrent_entry.remove_peer(peer_id);
+        for ip in &ips {
+            self.torrents.reindex_ip(info_hash, ip, torrent_entry);
+        }
+
+        removed
+    }
+
     pub async fn is_info_hash_whitelisted(&self, info_hash: &InfoHash) -> bool {
         self.whitelist.read().await.contains(info_hash)
     }
 
+    /// Looks the `info_hash` up in the whitelist kept in memory, then in the database for the ones whitelisted
+    /// since it was loaded, like by another tracker sharing the database, which are added to the one in memory.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to look the `info_hash` up in the database.
+    pub async fn lookup_whitelisted_info_hash(&self, info_hash: &InfoHash) -> Result<bool, databases::error::Error> {
+        if self.is_info_hash_whitelisted(info_hash).await {
+            return Ok(true);
+        }
+
+        if self.database.is_info_hash_whitelisted(info_hash).await? {
+            self.add_torrent_to_memory_whitelist(info_hash).await;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Get the page of the whitelisted info hashes of the `query`, sorted like the torrents
+    pub async fn get_whitelist_page(&self, query: &torrent_query::Query) -> Vec<InfoHash> {
+        let mut page = torrent_query::Page::new(query);
+
+        for info_hash in self.whitelist.read().await.iter() {
+            page.push(*info_hash, (0, 0, 0));
+        }
+
+        page.into_torrents().into_iter().map(|(info_hash, _)| info_hash).collect()
+    }
+
     /// # Errors
     ///
     /// Will return a `database::Error` if unable to load the list whitelisted `info_hash`s from the database.
@@ -165,6 +480,209 @@ impl Tracker {
         Ok(())
     }
 
+    /// Adds an IP range to the blacklist, ranges already blacklisted are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to add the `range` into the blacklist database.
+    pub async fn add_range_to_blacklist(&self, range: &IpNet) -> Result<(), databases::error::Error> {
+        let range = range.trunc();
+
+        match self.database.add_range_to_blacklist(&range).await {
+            Ok(_) | Err(databases::error::Error::ConstraintViolation { .. }) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.blacklist.write().await.insert(range);
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to remove the `range` from the blacklist database.
+    pub async fn remove_range_from_blacklist(&self, range: &IpNet) -> Result<(), databases::error::Error> {
+        let range = range.trunc();
+
+        self.database.remove_range_from_blacklist(&range).await?;
+        self.blacklist.write().await.remove(&range);
+
+        Ok(())
+    }
+
+    /// Blacklists the `ip` and removes its peers from all the swarms right away, instead of waiting for them to
+    /// time out. Returns how many peers were removed.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to add the `ip` into the blacklist database.
+    pub async fn ban_ip(&self, ip: &IpAddr) -> Result<usize, databases::error::Error> {
+        self.add_range_to_blacklist(&IpNet::from(*ip)).await?;
+
+        let mut removed = 0;
+
+        // One shard is locked at a time, so announces to the other shards are not blocked
+        for (shard_index, shard) in self.torrents.shards().iter().enumerate() {
+            let mut torrents_lock = shard.write().await;
+            for torrent_entry in torrents_lock.values_mut() {
+                removed += torrent_entry.remove_peers_with_ip(ip);
+            }
+            self.torrents.unindex_ip(shard_index, ip);
+        }
+
+        Ok(removed)
+    }
+
+    pub async fn is_ip_blacklisted(&self, ip: &IpAddr) -> bool {
+        self.blacklist.read().await.contains(ip)
+    }
+
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to load the blacklisted ranges from the database.
+    pub async fn load_blacklist(&self) -> Result<(), databases::error::Error> {
+        let ranges = self.database.load_blacklist().await?;
+        let mut blacklist = self.blacklist.write().await;
+
+        blacklist.clear();
+
+        for range in ranges {
+            blacklist.insert(range);
+        }
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Will return a `torrent::Error::PortNotAllowed` if the `port` is 0 or in the `blocked_ports`.
+    pub fn check_port(&self, port: u16) -> Result<(), torrent::Error> {
+        if port == 0 || self.config().blocked_ports.iter().any(|range| range.contains(port)) {
+            return Err(torrent::Error::PortNotAllowed { port });
+        }
+        Ok(())
+    }
+
+    /// `user_agent` is `None` for the protocols without one, like UDP.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `torrent::Error::ClientNotAllowed` if the client filter rejects the client.
+    pub async fn check_client(&self, peer_id: &peer::Id, user_agent: Option<&str>) -> Result<(), torrent::Error> {
+        if self.client_filter.read().await.is_allowed(peer_id, user_agent) {
+            Ok(())
+        } else {
+            Err(torrent::Error::ClientNotAllowed)
+        }
+    }
+
+    /// The client filter changes are kept in memory only, the config file is not updated.
+    pub async fn get_client_filter(&self) -> RwLockReadGuard<'_, client_filter::ClientFilter> {
+        self.client_filter.read().await
+    }
+
+    /// Returns `false` if the prefix was already in the client filter.
+    pub async fn add_peer_id_prefix_to_client_filter(&self, prefix: String) -> bool {
+        self.client_filter.write().await.add_peer_id_prefix(prefix)
+    }
+
+    /// Returns `false` if the prefix was not in the client filter.
+    pub async fn remove_peer_id_prefix_from_client_filter(&self, prefix: &str) -> bool {
+        self.client_filter.write().await.remove_peer_id_prefix(prefix)
+    }
+
+    /// Returns `false` if the pattern was already in the client filter.
+    pub async fn add_user_agent_to_client_filter(&self, pattern: String) -> bool {
+        self.client_filter.write().await.add_user_agent(pattern)
+    }
+
+    /// Returns `false` if the pattern was not in the client filter.
+    pub async fn remove_user_agent_from_client_filter(&self, pattern: &str) -> bool {
+        self.client_filter.write().await.remove_user_agent(pattern)
+    }
+
+    /// Stopped and completed announces are never limited, they change the torrent stats.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `torrent::Error::AnnounceRateLimited` if the `ip` announced the torrent
+    /// less than `announce_rate_limit_interval` seconds ago.
+    pub async fn check_announce_rate_limit(
+        &self,
+        ip: &IpAddr,
+        info_hash: &InfoHash,
+        event: AnnounceEvent,
+    ) -> Result<(), torrent::Error> {
+        if self.config().announce_rate_limit_interval == 0 || matches!(event, AnnounceEvent::Stopped | AnnounceEvent::Completed) {
+            return Ok(());
+        }
+
+        self.announce_rate_limiter
+            .lock()
+            .await
+            .check(*ip, *info_hash, Current::now())
+            .map_err(|retry_in| torrent::Error::AnnounceRateLimited {
+                retry_in: retry_in.as_secs() + u64::from(retry_in.subsec_nanos() > 0),
+            })
+    }
+
+    /// # Errors
+    ///
+    /// Will return a `torrent::Error::PeerIdTaken` if the peer id of the `peer` is in the swarm with another
+    /// IP address and another `key`, see [`peer::Peer::is_announced_by`].
+    pub async fn check_peer_id(&self, info_hash: &InfoHash, peer: &peer::Peer) -> Result<(), torrent::Error> {
+        let torrents = self.torrents.shard(info_hash).read().await;
+
+        match torrents.get(info_hash).and_then(|torrent| torrent.get_peer(peer)) {
+            Some(known_peer) if !known_peer.is_announced_by(peer) => Err(torrent::Error::PeerIdTaken),
+            _ => Ok(()),
+        }
+    }
+
+    /// How long the `peer` has to wait before announcing the torrent again, if its last announce was less
+    /// than `min_interval` seconds ago. The started, stopped and completed announces never have to wait.
+    pub async fn get_min_interval_wait(&self, info_hash: &InfoHash, peer: &peer::Peer, min_interval: u32) -> Option<Duration> {
+        if peer.event != AnnounceEvent::None {
+            return None;
+        }
+
+        let last_announce = self
+            .torrents
+            .shard(info_hash)
+            .read()
+            .await
+            .get(info_hash)?
+            .get_peer(peer)?
+            .updated;
+        let next_allowed = last_announce + Duration::from_secs(u64::from(min_interval));
+        let now = Current::now();
+
+        (now < next_allowed).then(|| next_allowed - now)
+    }
+
+    #[must_use]
+    pub fn is_under_maintenance(&self) -> bool {
+        self.under_maintenance.load(Ordering::Relaxed)
+    }
+
+    /// While under maintenance the announces are rejected, so the clients move to other trackers
+    /// or wait, but the scrapes and the API keep working.
+    pub fn set_maintenance(&self, under_maintenance: bool) {
+        self.under_maintenance.store(under_maintenance, Ordering::Relaxed);
+    }
+
+    /// # Errors
+    ///
+    /// Will return a `torrent::Error::UnderMaintenance` if the tracker is under maintenance.
+    pub fn check_maintenance(&self) -> Result<(), torrent::Error> {
+        if self.is_under_maintenance() {
+            return Err(torrent::Error::UnderMaintenance {
+                retry_in: u64::from(self.config().maintenance_retry_interval),
+            });
+        }
+        Ok(())
+    }
+
     /// # Errors
     ///
     /// Will return a `torrent::Error::PeerKeyNotValid` if the `key` is not valid.
@@ -207,17 +725,18 @@ impl Tracker {
     /// Will return a `database::Error` if unable to load the list of `persistent_torrents` from the database.
     pub async fn load_persistent_torrents(&self) -> Result<(), databases::error::Error> {
         let persistent_torrents = self.database.load_persistent_torrents().await?;
-        let mut torrents = self.torrents.write().await;
 
         for (info_hash, completed) in persistent_torrents {
+            let mut torrents = self.torrents.shard(&info_hash).write().await;
+
             // Skip if torrent entry already exists
             if torrents.contains_key(&info_hash) {
                 continue;
             }
 
             let torrent_entry = torrent::Entry {
-                peers: BTreeMap::default(),
                 completed,
+                ..torrent::Entry::new()
             };
 
             torrents.insert(info_hash, torrent_entry);
@@ -226,87 +745,1447 @@ impl Tracker {
         Ok(())
     }
 
-    /// Get all torrent peers for a given torrent filtering out the peer with the client address
-    pub async fn get_torrent_peers(&self, info_hash: &InfoHash, client_addr: &SocketAddr) -> Vec<peer::Peer> {
-        let read_lock = self.torrents.read().await;
+    /// Loading the peers saved before the last shutdown into memory
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to load the list of peers from the database.
+    pub async fn load_persistent_peers(&self) -> Result<(), databases::error::Error> {
+        let persistent_peers = self.database.load_peers().await?;
 
-        match read_lock.get(info_hash) {
-            None => vec![],
-            Some(entry) => entry.get_peers(Some(client_addr)).into_iter().copied().collect(),
+        for (info_hash, peer) in persistent_peers {
+            let mut torrents = self.torrents.shard(&info_hash).write().await;
+
+            let torrent_entry = match torrents.entry(info_hash) {
+                Entry::Vacant(vacant) => vacant.insert(torrent::Entry::new()),
+                Entry::Occupied(entry) => entry.into_mut(),
+            };
+
+            // Skip if the peer has already announced since the tracker started
+            torrent_entry.add_peer_if_absent(self.locate(peer));
+            self.torrents.reindex_ip(&info_hash, &peer.peer_addr.ip(), torrent_entry);
         }
+
+        Ok(())
     }
 
-    /// Get all torrent peers for a given torrent
-    pub async fn get_all_torrent_peers(&self, info_hash: &InfoHash) -> Vec<peer::Peer> {
-        let read_lock = self.torrents.read().await;
+    /// Saving a snapshot of all the peers in memory into the database, returning how many peers were saved
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to save the list of peers into the database.
+    pub async fn save_persistent_peers(&self) -> Result<usize, databases::error::Error> {
+        let mut peers: Vec<(InfoHash, peer::Peer)> = vec![];
 
-        match read_lock.get(info_hash) {
-            None => vec![],
-            Some(entry) => entry.get_peers(None).into_iter().copied().collect(),
+        for shard in self.torrents.shards() {
+            peers.extend(
+                shard
+                    .read()
+                    .await
+                    .iter()
+                    .flat_map(|(info_hash, torrent_entry)| torrent_entry.all_peers().map(|peer| (*info_hash, *peer))),
+            );
         }
+
+        self.database.save_peers(&peers).await?;
+
+        Ok(peers.len())
     }
 
-    pub async fn update_torrent_with_peer_and_get_stats(&self, info_hash: &InfoHash, peer: &peer::Peer) -> torrent::SwamStats {
-        let mut torrents = self.torrents.write().await;
+    /// Restoring the torrents and peers of the snapshot file at `path`, if there is one.
+    /// The peers that already announced since the tracker started are kept.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `snapshot::Error` if unable to read the snapshot, or it is not valid.
+    pub async fn load_snapshot(&self, path: &Path) -> Result<(), snapshot::Error> {
+        let path = path.to_path_buf();
+        let torrents = tokio::task::spawn_blocking(move || snapshot::load(&path))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e).into()))?;
 
-        let torrent_entry = match torrents.entry(*info_hash) {
-            Entry::Vacant(vacant) => vacant.insert(torrent::Entry::new()),
-            Entry::Occupied(entry) => entry.into_mut(),
-        };
+        for snapshot_torrent in torrents.unwrap_or_default() {
+            let mut torrents = self.torrents.shard(&snapshot_torrent.info_hash).write().await;
 
-        let stats_updated = torrent_entry.update_peer(peer);
+            let torrent_entry = match torrents.entry(snapshot_torrent.info_hash) {
+                Entry::Vacant(vacant) => vacant.insert(torrent::Entry::new()),
+                Entry::Occupied(entry) => entry.into_mut(),
+            };
 
-        // todo: move this action to a separate worker
-        if self.config.persistent_torrent_completed_stat && stats_updated {
-            let _ = self
-                .database
-                .save_persistent_torrent(info_hash, torrent_entry.completed)
-                .await;
+            torrent_entry.completed = torrent_entry.completed.max(snapshot_torrent.completed);
+            for peer in snapshot_torrent.peers {
+                torrent_entry.add_peer_if_absent(self.locate(peer));
+                self.torrents
+                    .reindex_ip(&snapshot_torrent.info_hash, &peer.peer_addr.ip(), torrent_entry);
+            }
         }
 
-        let (seeders, completed, leechers) = torrent_entry.get_stats();
+        Ok(())
+    }
 
-        torrent::SwamStats {
-            completed,
-            seeders,
-            leechers,
+    /// Saving all the torrents and their peers into the snapshot file at `path`
+    ///
+    /// # Errors
+    ///
+    /// Will return a `snapshot::Error` if unable to write the snapshot.
+    pub async fn save_snapshot(&self, path: &Path) -> Result<(), snapshot::Error> {
+        let mut torrents: Vec<snapshot::Torrent> = vec![];
+
+        for shard in self.torrents.shards() {
+            torrents.extend(shard.read().await.iter().map(|(info_hash, torrent_entry)| snapshot::Torrent {
+                info_hash: *info_hash,
+                completed: torrent_entry.completed,
+                peers: torrent_entry.all_peers().copied().collect(),
+            }));
         }
-    }
 
-    pub async fn get_torrents(&self) -> RwLockReadGuard<'_, BTreeMap<InfoHash, torrent::Entry>> {
-        self.torrents.read().await
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || snapshot::save(&path, &torrents))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e).into()))
     }
 
-    pub async fn get_stats(&self) -> RwLockReadGuard<'_, statistics::Metrics> {
-        self.stats_repository.get_stats().await
-    }
+    /// Get the torrent peers for the `peer` that announced, chosen with the configured `peer_selection_strategy`.
+    /// At most `numwant` peers are returned, and never more than `MAX_SCRAPE_TORRENTS`.
+    pub async fn get_torrent_peers(&self, info_hash: &InfoHash, peer: &peer::Peer, numwant: Option<u32>) -> Vec<peer::Peer> {
+        let numwant = numwant.map_or(MAX_SCRAPE_TORRENTS as usize, |numwant| {
+            numwant.min(u32::from(MAX_SCRAPE_TORRENTS)) as usize
+        });
 
-    pub async fn send_stats_event(&self, event: statistics::Event) -> Option<Result<(), SendError<statistics::Event>>> {
-        match &self.stats_event_sender {
-            None => None,
-            Some(stats_event_sender) => stats_event_sender.send_event(event).await,
+        let read_lock = self.torrents.shard(info_hash).read().await;
+
+        match read_lock.get(info_hash) {
+            None => vec![],
+            Some(entry) => self
+                .config()
+                .peer_selection_strategy
+                .select(peer, entry.all_peers(), numwant)
+                .into_iter()
+                .copied()
+                .collect(),
         }
     }
 
-    // Remove inactive peers and (optionally) peerless torrents
-    pub async fn cleanup_torrents(&self) {
-        let mut torrents_lock = self.torrents.write().await;
+    /// Get all torrent peers for a given torrent
+    pub async fn get_all_torrent_peers(&self, info_hash: &InfoHash) -> Vec<peer::Peer> {
+        let read_lock = self.torrents.shard(info_hash).read().await;
 
-        // If we don't need to remove torrents we will use the faster iter
-        if self.config.remove_peerless_torrents {
-            torrents_lock.retain(|_, torrent_entry| {
-                torrent_entry.remove_inactive_peers(self.config.max_peer_timeout);
+        match read_lock.get(info_hash) {
+            None => vec![],
+            Some(entry) => entry.all_peers().copied().collect(),
+        }
+    }
+
+    /// The peers with the IP in any torrent, with the info hash of the torrent, sorted by info hash
+    pub async fn get_peers_by_ip(&self, ip: &IpAddr) -> Vec<(InfoHash, peer::Peer)> {
+        self.torrents.get_peers_by_ip(ip).await
+    }
+
+    /// The listener receives the events of all the torrents from now on, see [`events::Bus::subscribe`].
+    pub fn subscribe(&self, listener: Arc<dyn events::EventListener>) {
+        self.events.subscribe(listener);
+    }
+
+    pub fn emit_event(&self, event: events::Event) {
+        self.events.emit(event);
+    }
+
+    /// The interval sent in the announce responses for a swarm with the given `stats`.
+    #[must_use]
+    pub fn announce_interval(&self, stats: &torrent::SwamStats) -> u32 {
+        announce_interval::compute(
+            &self.config(),
+            stats.seeders.saturating_add(stats.leechers),
+            self.announce_rate.rate(),
+        )
+    }
+
+    pub async fn update_torrent_with_peer_and_get_stats(&self, info_hash: &InfoHash, peer: &peer::Peer) -> torrent::SwamStats {
+        let peer = &self.locate(*peer);
+
+        self.announce_rate.record(Current::now());
+
+        self.send_stats_event(statistics::Event::PeerAnnounced(peer.peer_id)).await;
+
+        let config = self.config();
+        let mut torrents = self.torrents.shard(info_hash).write().await;
+
+        // The IPv6 peers are not tracked, but they still get the IPv4 ones
+        if peer.peer_addr.is_ipv6() && !config.ipv6_peers {
+            let (seeders, completed, leechers) = torrents.get(info_hash).map(torrent::Entry::get_stats).unwrap_or_default();
+
+            return torrent::SwamStats {
+                completed,
+                seeders,
+                leechers,
+            };
+        }
+
+        // The completed stat of a torrent which may have been evicted is loaded without holding the lock of the
+        // shard, and only used if the torrent is still not there once it is locked again
+        let mut evicted_completed = 0;
+        if config.torrent_memory_limit > 0 && config.persistent_torrent_completed_stat && !torrents.contains_key(info_hash) {
+            drop(torrents);
+            evicted_completed = self.load_evicted_completed_stat(info_hash).await;
+            torrents = self.torrents.shard(info_hash).write().await;
+        }
+
+        let (torrent_entry, added) = match torrents.entry(*info_hash) {
+            Entry::Vacant(vacant) => (
+                vacant.insert(torrent::Entry {
+                    completed: evicted_completed,
+                    ..torrent::Entry::new()
+                }),
+                true,
+            ),
+            Entry::Occupied(entry) => (entry.into_mut(), false),
+        };
 
-                if self.config.persistent_torrent_completed_stat {
-                    torrent_entry.completed > 0 || !torrent_entry.peers.is_empty()
-                } else {
-                    !torrent_entry.peers.is_empty()
+        let evicted = if config.max_peers_per_torrent > 0 {
+            torrent_entry.make_room_for(peer, config.max_peers_per_torrent, config.peer_eviction_policy)
+        } else {
+            vec![]
+        };
+        if !evicted.is_empty() {
+            self.stats_repository.add_peers_evicted(evicted.len() as u64).await;
+        }
+
+        // The addresses the peer may be moving from, with its `key`
+        let previous_ips: Vec<IpAddr> = torrent_entry
+            .all_peers()
+            .filter(|known_peer| known_peer.peer_id == peer.peer_id && known_peer.peer_addr.ip() != peer.peer_addr.ip())
+            .map(|known_peer| known_peer.peer_addr.ip())
+            .collect();
+
+        let stats_updated = torrent_entry.update_peer(peer);
+
+        // The IP of the peer is not indexed when it is not in the swarm, like after a `stopped` or an ignored announce
+        self.torrents.reindex_ip(info_hash, &peer.peer_addr.ip(), torrent_entry);
+        for previous_ip in &previous_ips {
+            self.torrents.reindex_ip(info_hash, previous_ip, torrent_entry);
+        }
+        for evicted_peer in &evicted {
+            self.torrents
+                .reindex_ip(info_hash, &evicted_peer.peer_addr.ip(), torrent_entry);
+        }
+
+        let mut flush_completed_stats = false;
+
+        if config.persistent_torrent_completed_stat && stats_updated {
+            if config.completed_stat_flush_interval == 0 {
+                if let Err(e) = self
+                    .database
+                    .save_persistent_torrent(info_hash, torrent_entry.completed)
+                    .await
+                {
+                    error!("Could not save the completed stat for torrent {}: {}", info_hash, e);
                 }
-            });
+            } else {
+                flush_completed_stats = self.completed_stats.push(*info_hash, torrent_entry.completed).await;
+            }
+        }
+
+        let (seeders, completed, leechers) = torrent_entry.get_stats();
+
+        drop(torrents);
+
+        if flush_completed_stats {
+            if let Err(e) = self.flush_completed_stats().await {
+                error!("Could not save the completed stats: {}", e);
+            }
+        }
+
+        if config.record_completed_events && stats_updated {
+            if let Err(e) = self
+                .database
+                .add_completed_event(info_hash, &peer.peer_id, Current::now())
+                .await
+            {
+                error!("Could not save the completed event for torrent {}: {}", info_hash, e);
+            }
+        }
+
+        let info_hash = *info_hash;
+        let peer = *peer;
+        if added {
+            self.events.emit(events::Event::TorrentAdded { info_hash });
+        }
+        if peer.event == AnnounceEvent::Stopped {
+            self.events.emit(events::Event::PeerStopped { info_hash, peer });
         } else {
-            for (_, torrent_entry) in torrents_lock.iter_mut() {
-                torrent_entry.remove_inactive_peers(self.config.max_peer_timeout);
+            self.events.emit(events::Event::PeerAnnounced { info_hash, peer });
+            if stats_updated {
+                self.events.emit(events::Event::TorrentCompleted { info_hash, peer });
+            }
+        }
+
+        torrent::SwamStats {
+            completed,
+            seeders,
+            leechers,
+        }
+    }
+
+    /// Returns when the torrent was completed since `since`, sorted from the oldest.
+    /// Only the events recorded while `record_completed_events` was enabled are returned.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to load the events from the database.
+    pub async fn get_completed_events(
+        &self,
+        info_hash: &InfoHash,
+        since: DurationSinceUnixEpoch,
+    ) -> Result<Vec<DurationSinceUnixEpoch>, databases::error::Error> {
+        self.database.load_completed_events(info_hash, since).await
+    }
+
+    /// Records a change made with the API in the audit log.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to save the `entry` into the database.
+    pub async fn record_audit_log_entry(&self, entry: &audit::Entry) -> Result<(), databases::error::Error> {
+        self.database.add_audit_log_entry(entry).await
+    }
+
+    /// Returns the page of the audit log entries matching the `query`, from the newest.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to load the entries from the database.
+    pub async fn get_audit_log(&self, query: &audit::Query) -> Result<Vec<audit::Entry>, databases::error::Error> {
+        self.database.load_audit_log(query).await
+    }
+
+    /// Saving the buffered completed stats into the database in a single transaction, returning how many
+    /// torrents were saved
+    ///
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if unable to save the stats. They are kept in the buffer to be retried.
+    pub async fn flush_completed_stats(&self) -> Result<usize, databases::error::Error> {
+        let completed_stats = self.completed_stats.take().await;
+
+        if completed_stats.is_empty() {
+            return Ok(0);
+        }
+
+        if let Err(e) = self.database.save_persistent_torrents(&completed_stats).await {
+            self.completed_stats.restore(completed_stats).await;
+            return Err(e);
+        }
+
+        Ok(completed_stats.len())
+    }
+
+    /// # Errors
+    ///
+    /// Will return a `database::Error` if the database is not reachable.
+    pub async fn ping_database(&self) -> Result<(), databases::error::Error> {
+        self.database.ping().await
+    }
+
+    pub fn get_database_pool_state(&self) -> Option<databases::PoolState> {
+        self.database.pool_state()
+    }
+
+    pub fn get_database_metrics(&self) -> Option<databases::metrics::Snapshot> {
+        self.database.metrics()
+    }
+
+    /// Get the `(seeders, completed, leechers)` stats of a torrent
+    pub async fn get_torrent_stats(&self, info_hash: &InfoHash) -> Option<(u32, u32, u32)> {
+        self.torrents
+            .shard(info_hash)
+            .read()
+            .await
+            .get(info_hash)
+            .map(torrent::Entry::get_stats)
+    }
+
+    /// Get how many of the torrent leechers are BEP 21 partial seeds
+    pub async fn get_torrent_partial_seeds(&self, info_hash: &InfoHash) -> Option<u32> {
+        self.torrents
+            .shard(info_hash)
+            .read()
+            .await
+            .get(info_hash)
+            .map(torrent::Entry::get_partial_seeds)
+    }
+
+    /// Get the swarm health of the torrent, scored from its seed/leech ratio, churn and last announce
+    pub async fn get_torrent_health(&self, info_hash: &InfoHash) -> Option<swarm_health::Health> {
+        self.torrents
+            .shard(info_hash)
+            .read()
+            .await
+            .get(info_hash)
+            .map(torrent::Entry::get_health)
+    }
+
+    /// Get the `(seeders, completed, leechers)` stats of all the torrents, sorted by info hash
+    pub async fn get_torrents_stats(&self) -> Vec<(InfoHash, (u32, u32, u32))> {
+        let mut torrents_stats = vec![];
+
+        for shard in self.torrents.shards() {
+            torrents_stats.extend(
+                shard
+                    .read()
+                    .await
+                    .iter()
+                    .map(|(info_hash, torrent_entry)| (*info_hash, torrent_entry.get_stats())),
+            );
+        }
+
+        torrents_stats.sort_unstable_by_key(|(info_hash, _)| *info_hash);
+        torrents_stats
+    }
+
+    /// Get the `(seeders, completed, leechers)` stats of the page of torrents of the `query`
+    pub async fn get_torrents_page(&self, query: &torrent_query::Query) -> Vec<(InfoHash, (u32, u32, u32))> {
+        let mut page = torrent_query::Page::new(query);
+
+        for shard in self.torrents.shards() {
+            for (info_hash, torrent_entry) in shard.read().await.range(query.info_hash_range()) {
+                page.push(*info_hash, torrent_entry.get_stats());
+            }
+        }
+
+        page.into_torrents()
+    }
+
+    /// Takes a new snapshot of the stats of the full scrapes, without the torrents not whitelisted in the listed modes
+    pub async fn refresh_full_scrape(&self) {
+        let mut entries = vec![];
+
+        for shard in self.torrents.shards() {
+            entries.extend(
+                shard
+                    .read()
+                    .await
+                    .iter()
+                    .map(|(info_hash, torrent_entry)| full_scrape::Entry::new(*info_hash, torrent_entry)),
+            );
+        }
+
+        if self.is_whitelisted() {
+            let whitelist = self.whitelist.read().await;
+            entries.retain(|entry| whitelist.contains(&entry.info_hash));
+        }
+
+        self.full_scrape.set(entries).await;
+    }
+
+    /// Get the stats of the full scrapes, sorted by info hash, as of their last refresh
+    pub async fn get_full_scrape(&self) -> Arc<Vec<full_scrape::Entry>> {
+        self.full_scrape.get().await
+    }
+
+    /// Get how many peers of the torrent are in each country, it requires the GeoIP lookup
+    pub async fn get_torrent_countries(&self, info_hash: &InfoHash) -> Option<BTreeMap<geoip::Country, u32>> {
+        let torrents = self.torrents.shard(info_hash).read().await;
+        let torrent_entry = torrents.get(info_hash)?;
+
+        let mut countries = BTreeMap::new();
+        torrent_entry.count_countries(&mut countries);
+        Some(countries)
+    }
+
+    /// Get how many peers of all the torrents are in each country, it requires the GeoIP lookup
+    pub async fn get_countries(&self) -> BTreeMap<geoip::Country, u32> {
+        let mut countries = BTreeMap::new();
+
+        if self.geoip.get().is_some() {
+            for shard in self.torrents.shards() {
+                for torrent_entry in shard.read().await.values() {
+                    torrent_entry.count_countries(&mut countries);
+                }
+            }
+        }
+
+        countries
+    }
+
+    /// Loads the `MaxMind` country database at `path`, to look up the country of the announcing peers.
+    /// A database already loaded is kept.
+    ///
+    /// # Errors
+    ///
+    /// Will return a `MaxMindDBError` if unable to read the database.
+    pub fn load_geoip_database(&self, path: &Path) -> Result<(), maxminddb::MaxMindDBError> {
+        let locator = geoip::Locator::open(path)?;
+        let _ = self.geoip.set(locator);
+        Ok(())
+    }
+
+    /// The `peer` with its country, when the GeoIP database is loaded.
+    fn locate(&self, peer: peer::Peer) -> peer::Peer {
+        match self.geoip.get() {
+            Some(locator) => peer::Peer {
+                country: locator.country(peer.peer_addr.ip()),
+                ..peer
+            },
+            None => peer,
+        }
+    }
+
+    pub async fn get_stats(&self) -> RwLockReadGuard<'_, statistics::Metrics> {
+        self.stats_repository.get_stats().await
+    }
+
+    pub async fn add_udp_packets_dropped(&self, dropped: u64) {
+        self.stats_repository.add_udp_packets_dropped(dropped).await;
+    }
+
+    pub async fn add_udp_connects_shed(&self, shed: u64) {
+        self.stats_repository.add_udp_connects_shed(shed).await;
+    }
+
+    pub async fn increase_udp_requests_malformed(&self) {
+        self.stats_repository.increase_udp_requests_malformed().await;
+    }
+
+    pub async fn increase_http_tracker_ids_ignored(&self) {
+        self.stats_repository.increase_http_tracker_ids_ignored().await;
+    }
+
+    /// Sends the response of a UDP request to the statistics, skipped when `tracker_usage_statistics` is off.
+    pub async fn record_udp_response(&self, kind: Option<statistics::UdpRequestKind>, response_time: Duration, is_error: bool) {
+        self.send_stats_event(statistics::Event::UdpResponse {
+            kind,
+            response_time,
+            is_error,
+        })
+        .await;
+    }
+
+    pub async fn record_http_response(
+        &self,
+        kind: Option<statistics::HttpRequestKind>,
+        status: u16,
+        response_time: Duration,
+        is_error: bool,
+    ) {
+        self.stats_repository
+            .record_http_response(kind, status, response_time, is_error)
+            .await;
+    }
+
+    /// Adds the `listener` to the statistics, returning the counters of its requests, unless
+    /// `tracker_usage_statistics` is off.
+    #[must_use]
+    pub fn register_listener(&self, listener: &str) -> Option<Arc<statistics::ListenerCounters>> {
+        self.stats_event_sender
+            .as_ref()
+            .map(|_| self.stats_repository.register_listener(listener))
+    }
+
+    /// The requests of each UDP and HTTP tracker listener.
+    #[must_use]
+    pub fn get_listener_stats(&self) -> BTreeMap<String, statistics::ListenerMetrics> {
+        self.stats_repository.get_listener_stats()
+    }
+
+    /// The statistics of the last `secs` seconds, up to an hour.
+    pub async fn get_window_stats(&self, secs: u64) -> statistics::WindowMetrics {
+        self.stats_repository.get_window_stats(secs, Current::now()).await
+    }
+
+    /// The announces, peers and torrents of the last 24 hours, in buckets of five minutes from the oldest.
+    pub async fn get_stats_history(&self) -> Vec<statistics::HistoryBucket> {
+        self.stats_repository.get_history(Current::now()).await
+    }
+
+    /// Keeps the current number of peers and torrents in the statistics history.
+    pub async fn sample_stats_history(&self) {
+        let mut peers = 0u64;
+        let mut torrents = 0u64;
+
+        for shard in self.torrents.shards() {
+            let torrents_lock = shard.read().await;
+            torrents += torrents_lock.len() as u64;
+            for torrent_entry in torrents_lock.values() {
+                let (seeders, _, leechers) = torrent_entry.get_stats();
+                peers += u64::from(seeders) + u64::from(leechers);
+            }
+        }
+
+        self.stats_repository.sample_history(peers, torrents, Current::now()).await;
+    }
+
+    pub async fn send_stats_event(&self, event: statistics::Event) -> Option<Result<(), SendError<statistics::Event>>> {
+        match &self.stats_event_sender {
+            None => None,
+            Some(stats_event_sender) => stats_event_sender.send_event(event).await,
+        }
+    }
+
+    /// Evicts the torrents with the oldest announces until the estimated memory of all the torrents
+    /// fits in `torrent_memory_limit`. When the completed counters are persisted they are saved
+    /// first, and loaded back if the torrent is announced again. Returns how many were evicted.
+    pub async fn enforce_torrent_memory_limit(&self) -> usize {
+        let limit = self.config().torrent_memory_limit.saturating_mul(1024 * 1024);
+        if limit == 0 {
+            return 0;
+        }
+
+        let mut candidates = vec![];
+        let mut used = 0u64;
+
+        for shard in self.torrents.shards() {
+            for (info_hash, torrent_entry) in shard.read().await.iter() {
+                let size = torrent_entry.estimated_size() as u64;
+                used += size;
+                candidates.push((torrent_entry.updated, *info_hash, size));
+            }
+        }
+
+        if used <= limit {
+            return 0;
+        }
+
+        // The least recently announced first
+        candidates.sort_unstable();
+
+        let mut evicted = 0;
+
+        for (updated, info_hash, size) in candidates {
+            if used <= limit {
+                break;
+            }
+
+            // Skip the torrents announced since their size was estimated
+            let completed = match self.torrents.shard(&info_hash).read().await.get(&info_hash) {
+                Some(torrent_entry) if torrent_entry.updated == updated => torrent_entry.completed,
+                _ => continue,
+            };
+
+            // The completed stat is saved without holding the lock of the shard
+            if self.config().persistent_torrent_completed_stat && completed > 0 {
+                if let Err(e) = self.database.save_persistent_torrent(&info_hash, completed).await {
+                    error!(
+                        "Could not save the completed stat of the evicted torrent {}: {}",
+                        info_hash, e
+                    );
+                    continue;
+                }
+            }
+
+            // Then the torrent is only evicted if it was not announced meanwhile, which may have changed the stat
+            let mut torrents = self.torrents.shard(&info_hash).write().await;
+            if torrents
+                .get(&info_hash)
+                .is_none_or(|torrent_entry| torrent_entry.updated != updated)
+            {
+                continue;
+            }
+
+            if let Some(torrent_entry) = torrents.remove(&info_hash) {
+                self.torrents.unindex_torrent(&info_hash, &torrent_entry);
+            }
+            used = used.saturating_sub(size);
+            evicted += 1;
+        }
+
+        self.stats_repository.add_torrents_evicted(evicted as u64).await;
+
+        evicted
+    }
+
+    /// The persisted completed counter of a torrent that may have been evicted by the memory limit.
+    async fn load_evicted_completed_stat(&self, info_hash: &InfoHash) -> u32 {
+        match self.database.load_persistent_torrent(info_hash).await {
+            Ok(completed) => completed.unwrap_or_default(),
+            Err(e) => {
+                error!("Could not load the completed stat for torrent {}: {}", info_hash, e);
+                0
+            }
+        }
+    }
+
+    /// Removes the torrents without announces for the retention days of the tracker mode.
+    /// The whitelisted torrents are kept, and the ones whose completed count is persisted too,
+    /// otherwise the count would restart from zero on the next announce. Returns how many were removed.
+    pub async fn remove_inactive_torrents(&self) -> usize {
+        let config = self.config();
+        let days = config.torrent_retention.days(config.mode);
+        if days == 0 {
+            return 0;
+        }
+
+        let Some(cutoff) = Current::now().checked_sub(Duration::from_secs(u64::from(days) * 24 * 60 * 60)) else {
+            return 0;
+        };
+
+        let whitelist = self.whitelist.read().await;
+        let mut removed = 0;
+
+        for shard in self.torrents.shards() {
+            let mut torrents_lock = shard.write().await;
+            let torrents_before = torrents_lock.len();
+
+            torrents_lock.retain(|info_hash, torrent_entry| {
+                let retained = torrent_entry.updated > cutoff
+                    || whitelist.contains(info_hash)
+                    || (config.persistent_torrent_completed_stat && torrent_entry.completed > 0);
+                if !retained {
+                    self.torrents.unindex_torrent(info_hash, torrent_entry);
+                }
+                retained
+            });
+
+            removed += torrents_before - torrents_lock.len();
+        }
+
+        removed
+    }
+
+    /// Remove inactive peers and (optionally) peerless torrents, returning how many `(torrents, peers)` were removed
+    pub async fn cleanup_torrents(&self) -> (usize, usize) {
+        self.announce_rate_limiter.lock().await.remove_expired(Current::now());
+        self.stats_repository.remove_expired_peers(Current::now()).await;
+
+        let config = self.config();
+        let mut torrents_removed = 0;
+        let mut peers_removed = 0;
+
+        // One shard is locked at a time, so announces to the other shards are not blocked
+        for (shard_index, shard) in self.torrents.shards().iter().enumerate() {
+            let mut torrents_lock = shard.write().await;
+
+            // If we don't need to remove torrents we will use the faster iter
+            if config.remove_peerless_torrents {
+                let torrents_before = torrents_lock.len();
+
+                torrents_lock.retain(|_, torrent_entry| {
+                    peers_removed += torrent_entry.remove_inactive_peers(config.max_peer_timeout);
+
+                    if config.persistent_torrent_completed_stat {
+                        torrent_entry.completed > 0 || torrent_entry.has_peers()
+                    } else {
+                        torrent_entry.has_peers()
+                    }
+                });
+
+                torrents_removed += torrents_before - torrents_lock.len();
+            } else {
+                for (_, torrent_entry) in torrents_lock.iter_mut() {
+                    peers_removed += torrent_entry.remove_inactive_peers(config.max_peer_timeout);
+                }
             }
+
+            self.torrents.prune_peer_index(shard_index, &torrents_lock);
+        }
+
+        (torrents_removed, peers_removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+    use async_trait::async_trait;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::{audit, databases, events, full_scrape, mode, peer, statistics, torrent, torrent_query, Tracker};
+    use crate::config::Configuration;
+    use crate::protocol::clock::{Current, StoppedTime, Time, TimeNow};
+    use crate::protocol::info_hash::InfoHash;
+
+    fn configuration_with_ephemeral_database() -> Arc<Configuration> {
+        let mut config = Configuration::default();
+        config.db_path = env::temp_dir()
+            .join(format!("data_{}.db", Uuid::new_v4()))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        Arc::new(config)
+    }
+
+    fn configuration_with_buffered_completed_stats() -> Arc<Configuration> {
+        let mut config = Arc::try_unwrap(configuration_with_ephemeral_database()).unwrap();
+        config.persistent_torrent_completed_stat = true;
+        config.completed_stat_flush_interval = 10;
+        Arc::new(config)
+    }
+
+    fn private_configuration_with_buffered_key_usage() -> Arc<Configuration> {
+        let mut config = Arc::try_unwrap(configuration_with_ephemeral_database()).unwrap();
+        config.mode = mode::Mode::Private;
+        config.key_usage_flush_interval = 60;
+        Arc::new(config)
+    }
+
+    fn sample_peer() -> peer::Peer {
+        peer::Peer {
+            peer_id: peer::Id(*b"-qB00000000000000000"),
+            peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)), 8080),
+            updated: Current::now(),
+            uploaded: NumberOfBytes(0),
+            downloaded: NumberOfBytes(0),
+            left: NumberOfBytes(1000),
+            event: AnnounceEvent::Started,
+            paused: false,
+            key: None,
+            country: None,
+        }
+    }
+
+    async fn persisted_completed_stat(config: &Arc<Configuration>, info_hash: &InfoHash) -> Option<u32> {
+        let tracker = Tracker::new(config, None, statistics::Repo::new()).unwrap();
+        tracker.load_persistent_torrents().await.unwrap();
+        tracker
+            .get_torrent_stats(info_hash)
+            .await
+            .map(|(_seeders, completed, _leechers)| completed)
+    }
+
+    #[tokio::test]
+    async fn it_should_buffer_the_completed_stats_until_they_are_flushed() {
+        let config = configuration_with_buffered_completed_stats();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        let completed_peer = peer::Peer {
+            event: AnnounceEvent::Completed,
+            ..sample_peer()
+        };
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &completed_peer)
+            .await;
+
+        assert_eq!(persisted_completed_stat(&config, &info_hash).await, None);
+
+        tracker.flush_completed_stats().await.unwrap();
+
+        assert_eq!(persisted_completed_stat(&config, &info_hash).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_the_peer_from_the_ip_index_when_it_stops() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        assert_eq!(tracker.torrents.indexed_swarms(), 1);
+
+        let stopped_peer = peer::Peer {
+            event: AnnounceEvent::Stopped,
+            ..sample_peer()
+        };
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &stopped_peer)
+            .await;
+
+        assert_eq!(tracker.torrents.indexed_swarms(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_the_previous_ip_of_a_peer_moving_with_its_key_from_the_ip_index() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let peer = peer::Peer {
+            key: Some(1),
+            ..sample_peer()
+        };
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+
+        for peer_addr in ["126.0.0.2:8080", "[2001:db8::1]:8080"] {
+            let moved_peer = peer::Peer {
+                peer_addr: peer_addr.parse().unwrap(),
+                ..peer
+            };
+            tracker.update_torrent_with_peer_and_get_stats(&info_hash, &moved_peer).await;
+
+            assert_eq!(tracker.torrents.indexed_swarms(), 1);
         }
     }
+
+    #[tokio::test]
+    async fn it_should_reject_the_peer_id_announced_from_another_ip_address_with_another_key() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let peer = peer::Peer {
+            key: Some(1),
+            ..sample_peer()
+        };
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+
+        let other_client = peer::Peer {
+            peer_addr: "126.0.0.2:8080".parse().unwrap(),
+            key: Some(2),
+            ..peer
+        };
+        assert!(matches!(
+            tracker.check_peer_id(&info_hash, &other_client).await,
+            Err(torrent::Error::PeerIdTaken)
+        ));
+        assert!(tracker
+            .check_peer_id(
+                &info_hash,
+                &peer::Peer {
+                    key: Some(1),
+                    ..other_client
+                }
+            )
+            .await
+            .is_ok());
+        assert!(tracker.check_peer_id(&info_hash, &peer).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_should_not_index_the_ip_of_a_peer_that_only_stopped() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let stopped_peer = peer::Peer {
+            event: AnnounceEvent::Stopped,
+            ..sample_peer()
+        };
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &stopped_peer)
+            .await;
+
+        assert_eq!(tracker.torrents.indexed_swarms(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_the_peers_of_a_deleted_torrent_from_the_ip_index() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        tracker.remove_torrent(&info_hash, false).await.unwrap();
+
+        assert_eq!(tracker.torrents.indexed_swarms(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_a_banned_ip_from_the_ip_index() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        tracker.ban_ip(&sample_peer().peer_addr.ip()).await.unwrap();
+
+        assert_eq!(tracker.torrents.indexed_swarms(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_should_only_return_the_torrents_of_the_last_refresh_in_the_full_scrape() {
+        let config = configuration_with_ephemeral_database();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+
+        assert!(tracker.get_full_scrape().await.is_empty());
+
+        tracker.refresh_full_scrape().await;
+
+        assert_eq!(
+            *tracker.get_full_scrape().await,
+            vec![full_scrape::Entry {
+                info_hash,
+                seeders: 0,
+                completed: 0,
+                leechers: 1,
+                partial_seeds: 0,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_record_the_completed_events_when_enabled() {
+        let mut config = Arc::try_unwrap(configuration_with_ephemeral_database()).unwrap();
+        config.record_completed_events = true;
+        let config = Arc::new(config);
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        let completed_peer = peer::Peer {
+            event: AnnounceEvent::Completed,
+            ..sample_peer()
+        };
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &completed_peer)
+            .await;
+
+        assert_eq!(
+            tracker.get_completed_events(&info_hash, Duration::ZERO).await.unwrap(),
+            vec![Current::now()]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_list_the_filtered_audit_log_entries_from_the_newest() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let entry = audit::Entry {
+            created_at: Duration::from_secs(100),
+            token_name: "admin".to_string(),
+            ip: Some(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1))),
+            method: "POST".to_string(),
+            path: "/api/v1/whitelist/3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".to_string(),
+            status: 200,
+        };
+        let other_entry = audit::Entry {
+            created_at: Duration::from_secs(200),
+            token_name: "operator".to_string(),
+            ip: None,
+            method: "DELETE".to_string(),
+            path: "/api/v1/key/YZSl4lMZupRuOpSRC3krIKR5BPB14nrJ".to_string(),
+            status: 404,
+        };
+        tracker.record_audit_log_entry(&entry).await.unwrap();
+        tracker.record_audit_log_entry(&other_entry).await.unwrap();
+
+        let query = audit::Query {
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            tracker.get_audit_log(&query).await.unwrap(),
+            vec![other_entry.clone(), entry.clone()]
+        );
+
+        let query = audit::Query {
+            token_name: Some("admin".to_string()),
+            until: Some(Duration::from_secs(150)),
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(tracker.get_audit_log(&query).await.unwrap(), vec![entry]);
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_the_announces_made_before_the_rate_limit_interval_elapses() {
+        let mut config = Arc::try_unwrap(configuration_with_ephemeral_database()).unwrap();
+        config.announce_rate_limit_interval = 30;
+        let config = Arc::new(config);
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+        let ip = sample_peer().peer_addr.ip();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+
+        assert!(tracker
+            .check_announce_rate_limit(&ip, &info_hash, AnnounceEvent::Started)
+            .await
+            .is_ok());
+        assert!(matches!(
+            tracker.check_announce_rate_limit(&ip, &info_hash, AnnounceEvent::None).await,
+            Err(torrent::Error::AnnounceRateLimited { .. })
+        ));
+        // The stats changing events are always accepted
+        assert!(tracker
+            .check_announce_rate_limit(&ip, &info_hash, AnnounceEvent::Completed)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_a_torrent_with_its_peers_and_its_saved_completed_stat() {
+        let config = configuration_with_buffered_completed_stats();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        let completed_peer = peer::Peer {
+            event: AnnounceEvent::Completed,
+            ..sample_peer()
+        };
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &completed_peer)
+            .await;
+        tracker.flush_completed_stats().await.unwrap();
+
+        assert_eq!(persisted_completed_stat(&config, &info_hash).await, Some(1));
+        assert!(tracker.remove_torrent(&info_hash, true).await.unwrap());
+        assert!(tracker.get_all_torrent_peers(&info_hash).await.is_empty());
+        assert_eq!(persisted_completed_stat(&config, &info_hash).await, None);
+        assert!(!tracker.remove_torrent(&info_hash, true).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn the_info_hashes_whitelisted_by_another_tracker_sharing_the_database_should_be_looked_up() {
+        let config = configuration_with_ephemeral_database();
+        let info_hashes = [
+            "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".parse::<InfoHash>().unwrap(),
+            "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap(),
+        ];
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        let other_tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        other_tracker.add_torrents_to_whitelist(&info_hashes).await.unwrap();
+
+        assert!(tracker
+            .get_whitelist_page(&torrent_query::Query {
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .is_empty());
+        assert!(tracker.lookup_whitelisted_info_hash(&info_hashes[0]).await.unwrap());
+        assert!(tracker.lookup_whitelisted_info_hash(&info_hashes[1]).await.unwrap());
+        assert!(!tracker.lookup_whitelisted_info_hash(&InfoHash([0; 20])).await.unwrap());
+        assert_eq!(
+            tracker
+                .get_whitelist_page(&torrent_query::Query {
+                    limit: 10,
+                    ..Default::default()
+                })
+                .await,
+            vec![info_hashes[1], info_hashes[0]]
+        );
+        assert_eq!(
+            tracker
+                .get_whitelist_page(&torrent_query::Query {
+                    offset: 1,
+                    limit: 10,
+                    ..Default::default()
+                })
+                .await,
+            vec![info_hashes[0]]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_banned_ip_should_be_blacklisted_and_its_peers_removed_from_all_the_swarms() {
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let info_hashes = [
+            "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".parse::<InfoHash>().unwrap(),
+            "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap(),
+        ];
+        for info_hash in &info_hashes {
+            tracker
+                .update_torrent_with_peer_and_get_stats(info_hash, &sample_peer())
+                .await;
+        }
+
+        assert_eq!(tracker.ban_ip(&sample_peer().peer_addr.ip()).await.unwrap(), 2);
+        assert!(tracker.is_ip_blacklisted(&sample_peer().peer_addr.ip()).await);
+        for info_hash in &info_hashes {
+            assert!(tracker.get_all_torrent_peers(info_hash).await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn the_announces_before_the_min_interval_should_have_to_wait_unless_they_change_the_stats() {
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let mut peer = sample_peer();
+
+        assert_eq!(tracker.get_min_interval_wait(&info_hash, &peer, 120).await, None);
+
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+        peer.event = AnnounceEvent::None;
+
+        assert_eq!(
+            tracker.get_min_interval_wait(&info_hash, &peer, 120).await,
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(tracker.get_min_interval_wait(&info_hash, &peer, 0).await, None);
+
+        peer.event = AnnounceEvent::Completed;
+
+        assert_eq!(tracker.get_min_interval_wait(&info_hash, &peer, 120).await, None);
+    }
+
+    #[tokio::test]
+    async fn the_subscribed_listeners_should_receive_the_announce_events() {
+        struct Forwarder(mpsc::UnboundedSender<events::Event>);
+
+        #[async_trait]
+        impl events::EventListener for Forwarder {
+            async fn on_event(&self, event: &events::Event) {
+                self.0.send(event.clone()).unwrap();
+            }
+        }
+
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+        let tracker = Tracker::new(&configuration_with_ephemeral_database(), None, statistics::Repo::new()).unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tracker.subscribe(Arc::new(Forwarder(sender)));
+
+        let stopped_peer = peer::Peer {
+            event: AnnounceEvent::Stopped,
+            ..sample_peer()
+        };
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &stopped_peer)
+            .await;
+
+        assert_eq!(receiver.recv().await, Some(events::Event::TorrentAdded { info_hash }));
+        assert_eq!(
+            receiver.recv().await,
+            Some(events::Event::PeerAnnounced {
+                info_hash,
+                peer: sample_peer()
+            })
+        );
+        assert_eq!(
+            receiver.recv().await,
+            Some(events::Event::PeerStopped {
+                info_hash,
+                peer: stopped_peer
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_reject_the_announced_ports_in_the_blocked_ranges() {
+        let mut config = Arc::try_unwrap(configuration_with_ephemeral_database()).unwrap();
+        config.blocked_ports = vec!["1-1023".parse().unwrap()];
+        let tracker = Tracker::new(&Arc::new(config), None, statistics::Repo::new()).unwrap();
+
+        assert!(matches!(
+            tracker.check_port(0),
+            Err(torrent::Error::PortNotAllowed { port: 0 })
+        ));
+        assert!(tracker.check_port(22).is_err());
+        assert!(tracker.check_port(6881).is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_should_restore_the_saved_peers_after_a_restart() {
+        let config = configuration_with_ephemeral_database();
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        tracker.save_persistent_peers().await.unwrap();
+        drop(tracker);
+
+        let restarted_tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        restarted_tracker.load_persistent_peers().await.unwrap();
+
+        assert_eq!(restarted_tracker.get_all_torrent_peers(&info_hash).await, vec![sample_peer()]);
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_the_torrents_without_announces_for_the_retention_days_unless_whitelisted() {
+        let mut config = Arc::try_unwrap(configuration_with_ephemeral_database()).unwrap();
+        config.torrent_retention.public = 1;
+        let tracker = Tracker::new(&Arc::new(config), None, statistics::Repo::new()).unwrap();
+
+        let inactive = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+        let whitelisted = "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".parse::<InfoHash>().unwrap();
+        let active = "0b3aea4adc213ce32295be85d3883a63bca25446".parse::<InfoHash>().unwrap();
+
+        Current::local_set_to_system_time_now();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&inactive, &sample_peer())
+            .await;
+        tracker
+            .update_torrent_with_peer_and_get_stats(&whitelisted, &sample_peer())
+            .await;
+        tracker.add_torrent_to_whitelist(&whitelisted).await.unwrap();
+
+        Current::local_add(&Duration::from_secs(2 * 24 * 60 * 60)).unwrap();
+        tracker.update_torrent_with_peer_and_get_stats(&active, &sample_peer()).await;
+
+        assert_eq!(tracker.remove_inactive_torrents().await, 1);
+        assert!(tracker.get_torrent_stats(&inactive).await.is_none());
+        assert!(tracker.get_torrent_stats(&whitelisted).await.is_some());
+        assert!(tracker.get_torrent_stats(&active).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn it_should_evict_the_least_active_torrents_above_the_memory_limit_keeping_their_completed_stat() {
+        let mut config = Arc::try_unwrap(configuration_with_buffered_completed_stats()).unwrap();
+        config.torrent_memory_limit = 1;
+        let tracker = Tracker::new(&Arc::new(config), None, statistics::Repo::new()).unwrap();
+
+        let least_active = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+        let completed_peer = peer::Peer {
+            event: AnnounceEvent::Completed,
+            ..sample_peer()
+        };
+
+        Current::local_set_to_system_time_now();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&least_active, &sample_peer())
+            .await;
+        tracker
+            .update_torrent_with_peer_and_get_stats(&least_active, &completed_peer)
+            .await;
+
+        // Enough torrents with a peer to exceed a MiB
+        Current::local_add(&Duration::from_secs(60)).unwrap();
+        for i in 0..10_000u32 {
+            let mut info_hash = InfoHash([0xff; 20]);
+            info_hash.0[..4].copy_from_slice(&i.to_be_bytes());
+            tracker
+                .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+                .await;
+        }
+
+        assert!(tracker.enforce_torrent_memory_limit().await > 0);
+        assert!(tracker.get_torrent_stats(&least_active).await.is_none());
+        assert!(tracker.get_stats().await.torrents_evicted > 0);
+
+        let stats = tracker
+            .update_torrent_with_peer_and_get_stats(&least_active, &sample_peer())
+            .await;
+        assert_eq!(stats.completed, 1);
+    }
+
+    #[tokio::test]
+    async fn it_should_restore_the_snapshot_taken_before_a_restart() {
+        let config = configuration_with_ephemeral_database();
+        let snapshot_path = env::temp_dir().join(format!("state_{}.snapshot", Uuid::new_v4()));
+        let info_hash = "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".parse::<InfoHash>().unwrap();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &sample_peer())
+            .await;
+        tracker.save_snapshot(&snapshot_path).await.unwrap();
+        drop(tracker);
+
+        let restarted_tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        restarted_tracker.load_snapshot(&snapshot_path).await.unwrap();
+
+        assert_eq!(restarted_tracker.get_all_torrent_peers(&info_hash).await, vec![sample_peer()]);
+    }
+
+    #[tokio::test]
+    async fn it_should_save_the_key_usage_when_it_is_flushed() {
+        let config = private_configuration_with_buffered_key_usage();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        let auth_key = tracker
+            .generate_auth_key(Some(Duration::from_secs(60)), Some("alice".to_string()))
+            .await
+            .unwrap();
+        tracker.update_auth_key_usage(&auth_key.key).await;
+        tracker.update_auth_key_usage(&auth_key.key).await;
+
+        let restarted_tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        restarted_tracker.load_keys().await.unwrap();
+        assert_eq!(restarted_tracker.get_auth_keys().await[0].announces, 0);
+
+        tracker.flush_auth_keys_usage().await.unwrap();
+
+        restarted_tracker.load_keys().await.unwrap();
+        let saved_key = restarted_tracker.get_auth_keys().await.remove(0);
+        assert_eq!(saved_key.label, Some("alice".to_string()));
+        assert_eq!(saved_key.announces, 2);
+        assert!(saved_key.last_used.is_some());
+    }
+
+    #[tokio::test]
+    async fn it_should_generate_a_batch_of_labeled_keys() {
+        let config = private_configuration_with_buffered_key_usage();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        let auth_keys = tracker
+            .generate_auth_keys(3, Some(Duration::from_secs(60)), Some("user-"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            auth_keys
+                .iter()
+                .map(|auth_key| auth_key.label.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["user-1", "user-2", "user-3"]
+        );
+
+        let restarted_tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        restarted_tracker.load_keys().await.unwrap();
+        assert_eq!(restarted_tracker.get_auth_keys().await.len(), 3);
+        for auth_key in &auth_keys {
+            assert!(restarted_tracker.verify_auth_key(auth_key).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_remove_the_expired_keys() {
+        Current::local_set_to_system_time_now();
+
+        let config = private_configuration_with_buffered_key_usage();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        let expiring_key = tracker.generate_auth_key(Some(Duration::from_secs(10)), None).await.unwrap();
+        let valid_key = tracker.generate_auth_key(Some(Duration::from_secs(60)), None).await.unwrap();
+
+        Current::local_add(&Duration::from_secs(20)).unwrap();
+
+        assert_eq!(tracker.remove_expired_auth_keys().await.unwrap(), 1);
+        assert_eq!(tracker.get_auth_keys().await, vec![valid_key]);
+
+        tracker.load_keys().await.unwrap();
+        assert!(tracker
+            .get_auth_keys()
+            .await
+            .iter()
+            .all(|auth_key| auth_key.key != expiring_key.key));
+    }
+
+    #[tokio::test]
+    async fn it_should_extend_the_expiry_of_a_key_or_make_it_never_expire() {
+        Current::local_set_to_system_time_now();
+
+        let config = private_configuration_with_buffered_key_usage();
+
+        let tracker = Tracker::new(&config, None, statistics::Repo::new()).unwrap();
+        let extended_key = tracker.generate_auth_key(Some(Duration::from_secs(10)), None).await.unwrap();
+        let never_expiring_key = tracker.generate_auth_key(Some(Duration::from_secs(10)), None).await.unwrap();
+
+        let valid_until = Current::add(&Duration::from_secs(60)).unwrap();
+        tracker
+            .update_auth_key_valid_until(&extended_key.key, Some(valid_until))
+            .await
+            .unwrap();
+        tracker
+            .update_auth_key_valid_until(&never_expiring_key.key, None)
+            .await
+            .unwrap();
+
+        Current::local_add(&Duration::from_secs(3600)).unwrap();
+
+        assert_eq!(tracker.remove_expired_auth_keys().await.unwrap(), 1);
+
+        tracker.load_keys().await.unwrap();
+        let keys = tracker.get_auth_keys().await;
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].key, never_expiring_key.key);
+        assert_eq!(keys[0].valid_until, None);
+        assert!(tracker.verify_auth_key(&keys[0]).await.is_ok());
+
+        assert!(matches!(
+            tracker.update_auth_key_valid_until(&extended_key.key, None).await,
+            Err(databases::error::Error::NotFound)
+        ));
+    }
 }
@@ -4,10 +4,12 @@ use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
 use serde;
 use serde::Serialize;
 
+use super::geoip;
 use crate::http::request::Announce;
 use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Time};
 use crate::protocol::common::{AnnounceEventDef, NumberOfBytesDef};
 use crate::protocol::utils::ser_unix_time_value;
+use crate::ws::message::AnnounceRequest;
 
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Copy)]
 pub struct Peer {
@@ -23,6 +25,15 @@ pub struct Peer {
     pub left: NumberOfBytes,
     #[serde(with = "AnnounceEventDef")]
     pub event: AnnounceEvent,
+    // A partial seed, as in BEP 21: it is not downloading the rest of the torrent
+    pub paused: bool,
+    // The `key` of the UDP announces, or the one of the `tracker id` sent back in the HTTP ones, which
+    // proves it is the same peer when its IP address changes
+    #[serde(skip)]
+    pub key: Option<u32>,
+    // Only known when the GeoIP lookup is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<geoip::Country>,
 }
 
 impl Peer {
@@ -42,6 +53,9 @@ impl Peer {
             downloaded: announce_request.bytes_downloaded,
             left: announce_request.bytes_left,
             event: announce_request.event,
+            paused: false,
+            key: Some(announce_request.key.0),
+            country: None,
         }
     }
 
@@ -59,6 +73,7 @@ impl Peer {
         } else {
             AnnounceEvent::None
         };
+        let paused = announce_request.event.as_deref() == Some("paused");
 
         #[allow(clippy::cast_possible_truncation)]
         Peer {
@@ -69,12 +84,56 @@ impl Peer {
             downloaded: NumberOfBytes(i128::from(announce_request.downloaded) as i64),
             left: NumberOfBytes(i128::from(announce_request.left) as i64),
             event,
+            paused,
+            key: None,
+            country: None,
+        }
+    }
+
+    /// The peer of a WebTorrent announce, with the IP and port of its WebSocket connection
+    #[must_use]
+    pub fn from_ws_announce_request(
+        announce_request: &AnnounceRequest,
+        peer_id: Id,
+        remote_ip: IpAddr,
+        remote_port: u16,
+        host_opt_ip: Option<IpAddr>,
+    ) -> Self {
+        let peer_addr = Peer::peer_addr_from_ip_and_port_and_opt_host_ip(remote_ip, host_opt_ip, remote_port);
+
+        let event = match announce_request.event.as_deref() {
+            Some("started") => AnnounceEvent::Started,
+            Some("stopped") => AnnounceEvent::Stopped,
+            Some("completed") => AnnounceEvent::Completed,
+            _ => AnnounceEvent::None,
+        };
+
+        Peer {
+            peer_id,
+            peer_addr,
+            updated: Current::now(),
+            uploaded: NumberOfBytes(i64::try_from(announce_request.uploaded).unwrap_or(i64::MAX)),
+            downloaded: NumberOfBytes(i64::try_from(announce_request.downloaded).unwrap_or(i64::MAX)),
+            // Without the metadata of the torrent the browser is still a leecher
+            left: NumberOfBytes(
+                announce_request
+                    .left
+                    .map_or(i64::MAX, |left| i64::try_from(left).unwrap_or(i64::MAX)),
+            ),
+            event,
+            paused: false,
+            key: None,
+            country: None,
         }
     }
 
     // potentially substitute localhost ip with external ip
     #[must_use]
     pub fn peer_addr_from_ip_and_port_and_opt_host_ip(remote_ip: IpAddr, host_opt_ip: Option<IpAddr>, port: u16) -> SocketAddr {
+        // An IPv4 client of a dual-stack listener has an IPv4-mapped IPv6 address, it is the same
+        // peer as when it announces to an IPv4 listener
+        let remote_ip = remote_ip.to_canonical();
+
         if let Some(host_ip) = host_opt_ip.filter(|_| remote_ip.is_loopback()) {
             SocketAddr::new(host_ip, port)
         } else {
@@ -86,6 +145,19 @@ impl Peer {
     pub fn is_seeder(&self) -> bool {
         self.left.0 <= 0 && self.event != AnnounceEvent::Stopped
     }
+
+    /// Whether the `announce` of the same peer id can come from this peer: always from the same
+    /// IP address, and from another one only with the same `key`, unless this peer did not send one.
+    #[must_use]
+    pub fn is_announced_by(&self, announce: &Peer) -> bool {
+        self.peer_addr.ip() == announce.peer_addr.ip() || self.key.is_none() || self.key == announce.key
+    }
+
+    /// Partial seeds are leechers too, BEP 21 only asks to count them apart.
+    #[must_use]
+    pub fn is_partial_seed(&self) -> bool {
+        self.paused && !self.is_seeder() && self.event != AnnounceEvent::Stopped
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug, PartialOrd, Ord, Copy)]
@@ -102,6 +174,19 @@ impl std::fmt::Display for Id {
     }
 }
 
+impl std::str::FromStr for Id {
+    type Err = binascii::ConvertError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut id = Self([0u8; 20]);
+        if s.len() != 40 {
+            return Err(binascii::ConvertError::InvalidInputLength);
+        }
+        binascii::hex2bin(s.as_bytes(), &mut id.0)?;
+        Ok(id)
+    }
+}
+
 impl Id {
     #[must_use]
     /// # Panics
@@ -215,6 +300,24 @@ impl Serialize for Id {
 
 #[cfg(test)]
 mod test {
+    mod torrent_peer_id {
+        use std::str::FromStr;
+
+        use crate::tracker::peer;
+
+        #[test]
+        fn it_should_be_parsed_from_its_hexadecimal_representation() {
+            let id = peer::Id(*b"-qB00000000000000000");
+
+            assert_eq!(peer::Id::from_str(&id.get_id().unwrap()).unwrap(), id);
+        }
+
+        #[test]
+        fn it_should_fail_parsing_a_string_with_an_invalid_length() {
+            assert!(peer::Id::from_str("2d7142").is_err());
+        }
+    }
+
     mod torrent_peer {
 
         use std::net::{IpAddr, Ipv4Addr, SocketAddr};
@@ -234,6 +337,9 @@ mod test {
                 downloaded: NumberOfBytes(0),
                 left: NumberOfBytes(0),
                 event: AnnounceEvent::Started,
+                paused: false,
+                key: None,
+                country: None,
             };
 
             let json_serialized_value = serde_json::to_string(&torrent_peer).unwrap();
@@ -241,7 +347,7 @@ mod test {
             assert_eq!(
                 json_serialized_value,
                 // todo: compare using pretty json format to improve readability
-                r#"{"peer_id":{"id":"2d71423030303030303030303030303030303030","client":"qBittorrent"},"peer_addr":"126.0.0.1:8080","updated":0,"uploaded":0,"downloaded":0,"left":0,"event":"Started"}"#
+                r#"{"peer_id":{"id":"2d71423030303030303030303030303030303030","client":"qBittorrent"},"peer_addr":"126.0.0.1:8080","updated":0,"uploaded":0,"downloaded":0,"left":0,"event":"Started","paused":false}"#
             );
         }
     }
@@ -321,6 +427,19 @@ mod test {
             assert_eq!(torrent_peer.peer_addr, SocketAddr::new(remote_ip, announce_request.port.0));
         }
 
+        #[test]
+        fn it_should_use_the_ipv4_address_of_an_ipv4_mapped_source_ip() {
+            let ipv4 = Ipv4Addr::new(126, 0, 0, 2);
+            let announce_request = AnnounceRequestBuilder::default().into();
+
+            let torrent_peer = Peer::from_udp_announce_request(&announce_request, IpAddr::V6(ipv4.to_ipv6_mapped()), None);
+
+            assert_eq!(
+                torrent_peer.peer_addr,
+                SocketAddr::new(IpAddr::V4(ipv4), announce_request.port.0)
+            );
+        }
+
         mod when_source_udp_ip_is_a_ipv_4_loopback_ip {
 
             use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
@@ -422,6 +541,9 @@ mod test {
                 left: 0u64,
                 event: None,
                 compact: None,
+                numwant: None,
+                user_agent: None,
+                tracker_id: None,
             }
         }
 
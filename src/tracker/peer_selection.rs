@@ -0,0 +1,201 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::net::IpAddr;
+
+use rand::Rng;
+use serde;
+use serde::{Deserialize, Serialize};
+
+use super::peer;
+
+/// How the peers returned in the announce responses are chosen among the swarm.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    #[serde(rename = "random")]
+    Random,
+    // Leechers get the seeds before the other leechers, seeds get the leechers before the other seeds
+    #[serde(rename = "seeds_first")]
+    SeedsFirst,
+    // The peers closest to the requesting one first. There is no location database, the
+    // distance is how many leading bits of their IP addresses differ.
+    #[serde(rename = "geo_aware")]
+    GeoAware,
+}
+
+impl Strategy {
+    /// Chooses up to `numwant` of the `candidates` for the `requester`. Neither the requester
+    /// nor other peers behind its IP address are returned.
+    ///
+    /// The peers of the requester IP version always come first, UDP responses can only include those.
+    /// Only the `numwant` best candidates so far are kept while going through the swarm.
+    pub fn select<'a>(
+        self,
+        requester: &peer::Peer,
+        candidates: impl Iterator<Item = &'a peer::Peer>,
+        numwant: usize,
+    ) -> Vec<&'a peer::Peer> {
+        let requester_ip = requester.peer_addr.ip();
+        let wants_seeds = !requester.is_seeder();
+        let mut rng = rand::thread_rng();
+
+        // The worst of the selected candidates is on top, to be replaced by a better one
+        let mut selected: BinaryHeap<Candidate> = BinaryHeap::new();
+
+        for peer in candidates.filter(|peer| peer.peer_id != requester.peer_id && peer.peer_addr.ip() != requester_ip) {
+            let preference = match self {
+                Strategy::Random => 0,
+                Strategy::SeedsFirst => u32::from(peer.is_seeder() != wants_seeds),
+                Strategy::GeoAware => u32::MAX - common_prefix_len(&peer.peer_addr.ip(), &requester_ip),
+            };
+            // The peers the strategy does not prefer over each other are chosen at random
+            let candidate = Candidate {
+                key: (is_other_version(peer, &requester_ip), preference, rng.gen()),
+                peer,
+            };
+
+            if selected.len() < numwant {
+                selected.push(candidate);
+            } else if let Some(mut worst) = selected.peek_mut() {
+                if candidate < *worst {
+                    *worst = candidate;
+                }
+            }
+        }
+
+        selected
+            .into_sorted_vec()
+            .into_iter()
+            .map(|candidate| candidate.peer)
+            .collect()
+    }
+}
+
+/// A peer with the key it is selected by, the lower the better.
+struct Candidate<'a> {
+    key: (bool, u32, u64),
+    peer: &'a peer::Peer,
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+fn is_other_version(peer: &peer::Peer, ip: &IpAddr) -> bool {
+    peer.peer_addr.is_ipv4() != ip.is_ipv4()
+}
+
+fn common_prefix_len(a: &IpAddr, b: &IpAddr) -> u32 {
+    match (a.to_canonical(), b.to_canonical()) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(a) ^ u32::from(b)).leading_zeros(),
+        (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a) ^ u128::from(b)).leading_zeros(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+    use std::str::FromStr;
+
+    use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+
+    use super::Strategy;
+    use crate::protocol::clock::DurationSinceUnixEpoch;
+    use crate::tracker::peer;
+
+    fn peer(id: u8, ip: &str, left: i64) -> peer::Peer {
+        let mut peer_id = *b"-qB00000000000000000";
+        peer_id[19] = id;
+        peer::Peer {
+            peer_id: peer::Id(peer_id),
+            peer_addr: SocketAddr::new(IpAddr::from_str(ip).unwrap(), 8080),
+            updated: DurationSinceUnixEpoch::new(1_669_397_478_934, 0),
+            uploaded: NumberOfBytes(0),
+            downloaded: NumberOfBytes(0),
+            left: NumberOfBytes(left),
+            event: AnnounceEvent::Started,
+            paused: false,
+            key: None,
+            country: None,
+        }
+    }
+
+    #[test]
+    fn leechers_should_get_the_seeds_first() {
+        let leecher = peer(1, "126.0.0.1", 1000);
+        let other_leecher = peer(2, "126.0.0.2", 1000);
+        let seed = peer(3, "126.0.0.3", 0);
+        let ipv6_seed = peer(4, "2001:db8::1", 0);
+
+        let peers = [leecher, other_leecher, seed, ipv6_seed];
+
+        assert_eq!(
+            Strategy::SeedsFirst.select(&leecher, peers.iter(), 10),
+            vec![&seed, &other_leecher, &ipv6_seed]
+        );
+    }
+
+    #[test]
+    fn it_should_honor_numwant_and_skip_the_peers_behind_the_requester_ip() {
+        let requester = peer(1, "126.0.0.1", 1000);
+        let same_ip = peer(2, "126.0.0.1", 0);
+        let others = [peer(3, "126.0.0.3", 0), peer(4, "126.0.0.4", 0), peer(5, "126.0.0.5", 0)];
+
+        let peers: Vec<_> = [requester, same_ip].iter().chain(others.iter()).copied().collect();
+
+        let selected = Strategy::Random.select(&requester, peers.iter(), 2);
+
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|peer| others.contains(peer)));
+        assert!(Strategy::Random.select(&requester, peers.iter(), 0).is_empty());
+    }
+
+    #[test]
+    fn it_should_keep_the_preferred_peers_of_a_swarm_larger_than_numwant() {
+        let leecher = peer(0, "126.0.0.1", 1000);
+        let seeds = [peer(1, "126.0.1.1", 0), peer(2, "126.0.1.2", 0), peer(3, "126.0.1.3", 0)];
+        let other_leechers: Vec<_> = (10..=200).map(|id| peer(id, &format!("126.0.2.{id}"), 1000)).collect();
+
+        let peers: Vec<_> = other_leechers
+            .iter()
+            .chain(seeds.iter())
+            .chain(other_leechers.iter())
+            .collect();
+
+        let mut selected = Strategy::SeedsFirst.select(&leecher, peers.into_iter(), 3);
+        selected.sort_by_key(|peer| peer.peer_id);
+
+        assert_eq!(selected, vec![&seeds[0], &seeds[1], &seeds[2]]);
+    }
+
+    #[test]
+    fn geo_aware_selection_should_return_the_closest_networks_first() {
+        let requester = peer(1, "126.10.0.1", 1000);
+        let far = peer(2, "20.10.0.1", 0);
+        let near = peer(3, "126.10.200.1", 0);
+        let nearest = peer(4, "126.10.0.200", 0);
+
+        let peers = [far, near, nearest];
+
+        assert_eq!(
+            Strategy::GeoAware.select(&requester, peers.iter(), 10),
+            vec![&nearest, &near, &far]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Ranges of ports the peers cannot announce, like the well-known service ports,
+//! so the tracker is not used to direct traffic at other services.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Inclusive range written as `start-end`, or a single port.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    #[must_use]
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let parse = |port: &str| port.trim().parse::<u16>().map_err(|_| format!("invalid port range: {range}"));
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => {
+                let port = parse(range)?;
+                (port, port)
+            }
+        };
+
+        if start > end {
+            return Err(format!("invalid port range: {range}"));
+        }
+
+        Ok(PortRange { start, end })
+    }
+}
+
+impl TryFrom<String> for PortRange {
+    type Error = String;
+
+    fn try_from(range: String) -> Result<Self, Self::Error> {
+        range.parse()
+    }
+}
+
+impl From<PortRange> for String {
+    fn from(range: PortRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PortRange;
+
+    #[test]
+    fn it_should_parse_ranges_and_single_ports() {
+        assert_eq!("1-1023".parse(), Ok(PortRange { start: 1, end: 1023 }));
+        assert_eq!("25".parse(), Ok(PortRange { start: 25, end: 25 }));
+        assert_eq!(PortRange { start: 1, end: 1023 }.to_string(), "1-1023");
+        assert!("1023-1".parse::<PortRange>().is_err());
+        assert!("65536".parse::<PortRange>().is_err());
+    }
+
+    #[test]
+    fn a_range_should_contain_its_bounds() {
+        let range = PortRange { start: 20, end: 25 };
+
+        assert!(range.contains(20));
+        assert!(range.contains(25));
+        assert!(!range.contains(26));
+    }
+}
@@ -0,0 +1,119 @@
+//! Limits how often an IP address can announce the same torrent, so
+//! misbehaving clients re-announcing every few seconds are turned away.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::protocol::info_hash::InfoHash;
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    last_announces: HashMap<(IpAddr, InfoHash), DurationSinceUnixEpoch>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_announces: HashMap::new(),
+        }
+    }
+
+    /// Records the announce made at `now`, or returns how long the client has to wait before
+    /// announcing again. Rejected announces do not restart the wait.
+    ///
+    /// # Errors
+    ///
+    /// Will return the time left if the last accepted announce was less than the interval ago.
+    pub fn check(&mut self, ip: IpAddr, info_hash: InfoHash, now: DurationSinceUnixEpoch) -> Result<(), Duration> {
+        if let Some(last_announce) = self.last_announces.get(&(ip, info_hash)) {
+            let next_allowed = *last_announce + self.interval;
+
+            if now < next_allowed {
+                return Err(next_allowed - now);
+            }
+        }
+
+        self.last_announces.insert((ip, info_hash), now);
+
+        Ok(())
+    }
+
+    /// Forgets the announces made more than the interval ago, they do not limit anything.
+    pub fn remove_expired(&mut self, now: DurationSinceUnixEpoch) {
+        let interval = self.interval;
+        self.last_announces.retain(|_, last_announce| *last_announce + interval > now);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.last_announces.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::RateLimiter;
+    use crate::protocol::info_hash::InfoHash;
+
+    fn sample_info_hash() -> InfoHash {
+        InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap()
+    }
+
+    #[test]
+    fn it_should_reject_the_announces_made_before_the_interval_elapses() {
+        let mut rate_limiter = RateLimiter::new(Duration::from_secs(30));
+        let ip = IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1));
+
+        assert!(rate_limiter.check(ip, sample_info_hash(), Duration::from_secs(100)).is_ok());
+        assert_eq!(
+            rate_limiter.check(ip, sample_info_hash(), Duration::from_secs(110)),
+            Err(Duration::from_secs(20))
+        );
+        assert!(rate_limiter.check(ip, sample_info_hash(), Duration::from_secs(130)).is_ok());
+    }
+
+    #[test]
+    fn it_should_limit_each_ip_and_torrent_separately() {
+        let mut rate_limiter = RateLimiter::new(Duration::from_secs(30));
+
+        assert!(rate_limiter
+            .check(
+                IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)),
+                sample_info_hash(),
+                Duration::from_secs(100)
+            )
+            .is_ok());
+        assert!(rate_limiter
+            .check(
+                IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)),
+                sample_info_hash(),
+                Duration::from_secs(100)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn it_should_forget_the_expired_announces() {
+        let mut rate_limiter = RateLimiter::new(Duration::from_secs(30));
+
+        rate_limiter
+            .check(
+                IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)),
+                sample_info_hash(),
+                Duration::from_secs(100),
+            )
+            .unwrap();
+        rate_limiter.remove_expired(Duration::from_secs(130));
+
+        assert!(rate_limiter.is_empty());
+    }
+}
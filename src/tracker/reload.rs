@@ -0,0 +1,230 @@
+//! Reloads the lists and the keys from the database, and the settings of the configuration file which can change
+//! while the tracker runs, so the routine operations do not require a restart. The swarms are kept as they are.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use log::{info, warn, LevelFilter};
+use thiserror::Error;
+
+use super::{client_filter, Tracker};
+use crate::config::{self, Configuration};
+use crate::{databases, logging};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to reload the configuration file: {source}")]
+    Config { source: config::Error },
+
+    #[error("failed to reload from the database: {source}")]
+    Database { source: databases::error::Error },
+}
+
+impl Tracker {
+    /// Sets the file the configuration was loaded from, in the format of its extension, to reload it.
+    #[must_use]
+    pub fn with_config_path(self, config_path: &str) -> Self {
+        self.with_config_source(config::Source::new(config_path))
+    }
+
+    /// Sets where the configuration was loaded from, with the overrides of the command line, to reload it the
+    /// same way.
+    #[must_use]
+    pub fn with_config_source(mut self, config_source: config::Source) -> Self {
+        self.config_source = Some(config_source);
+        self
+    }
+
+    /// Reloads the whitelist, the blacklist and the keys from the database, then the settings of the configuration
+    /// file which can change while the tracker runs, if the tracker has one, see [`apply_runtime_settings`].
+    /// Returns the other settings changed in the file, which require a restart.
+    ///
+    /// # Errors
+    ///
+    /// Will return an `Error` if unable to load the lists or the keys, or if the configuration file is not valid.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the configuration lock is poisoned.
+    pub async fn reload(&self) -> Result<Vec<String>, Error> {
+        self.load_whitelist().await.map_err(|source| Error::Database { source })?;
+        self.load_blacklist().await.map_err(|source| Error::Database { source })?;
+        self.load_keys().await.map_err(|source| Error::Database { source })?;
+
+        let Some(config_source) = &self.config_source else {
+            return Ok(vec![]);
+        };
+        let config_path = &config_source.path;
+
+        let reloaded = config_source.load().map_err(|source| Error::Config { source })?;
+
+        let level = match &reloaded.log_level {
+            None => LevelFilter::Info,
+            Some(level) => level.parse().map_err(|_| Error::Config {
+                source: config::Error::Message(format!("invalid log_level: {level}")),
+            })?,
+        };
+
+        let config = apply_runtime_settings(&self.config(), &reloaded);
+        let restart_required = changed_settings(&config, &reloaded);
+
+        logging::set_level(level);
+        *self.client_filter.write().await = client_filter::ClientFilter::new(&config.client_filter);
+        *self.config.write().unwrap() = Arc::new(config);
+
+        info!("Configuration reloaded from {}.", config_path);
+        if !restart_required.is_empty() {
+            warn!(
+                "The changes of {} in {} require a restart.",
+                restart_required.join(", "),
+                config_path
+            );
+        }
+
+        Ok(restart_required)
+    }
+}
+
+/// The `current` configuration with the settings of the `reloaded` one which are safe to change while the tracker
+/// runs: the log level, the mode, the announce intervals, the peer limits and policies, the options of the HTTP
+/// announces, the client filter and the access tokens of the APIs.
+#[must_use]
+pub fn apply_runtime_settings(current: &Configuration, reloaded: &Configuration) -> Configuration {
+    let mut config = current.clone();
+
+    config.log_level = reloaded.log_level.clone();
+    config.mode = reloaded.mode;
+    config.announce_interval = reloaded.announce_interval;
+    config.min_announce_interval = reloaded.min_announce_interval;
+    config.min_announce_interval_enforcement = reloaded.min_announce_interval_enforcement;
+    config.dynamic_announce_interval = reloaded.dynamic_announce_interval;
+    config.max_announce_interval = reloaded.max_announce_interval;
+    config.announce_interval_swarm_size = reloaded.announce_interval_swarm_size;
+    config.announce_interval_max_rate = reloaded.announce_interval_max_rate;
+    config.max_peer_timeout = reloaded.max_peer_timeout;
+    config.max_peers_per_torrent = reloaded.max_peers_per_torrent;
+    config.blocked_ports = reloaded.blocked_ports.clone();
+    config.ipv6_peers = reloaded.ipv6_peers;
+    config.peer_eviction_policy = reloaded.peer_eviction_policy;
+    config.peer_selection_strategy = reloaded.peer_selection_strategy;
+    config.maintenance_retry_interval = reloaded.maintenance_retry_interval;
+    config.scrape_enabled = reloaded.scrape_enabled;
+    config.http_compact_peers_only = reloaded.http_compact_peers_only;
+    config.http_max_query_length = reloaded.http_max_query_length;
+    config.announce_external_ip = reloaded.announce_external_ip;
+    config.issue_tracker_id = reloaded.issue_tracker_id;
+    config.client_filter = reloaded.client_filter.clone();
+    config.http_api.access_tokens = reloaded.http_api.access_tokens.clone();
+    config.http_api.read_only = reloaded.http_api.read_only;
+    config.grpc_api.access_tokens = reloaded.grpc_api.access_tokens.clone();
+
+    config
+}
+
+/// The settings with another value in `other`, like `http_api.bind_address`, the lists compared as a whole.
+///
+/// # Panics
+///
+/// Will panic if a configuration cannot be converted into TOML, which it is saved as.
+#[must_use]
+pub fn changed_settings(config: &Configuration, other: &Configuration) -> Vec<String> {
+    let config = toml::Value::try_from(config).expect("the configuration should be valid TOML");
+    let other = toml::Value::try_from(other).expect("the configuration should be valid TOML");
+
+    let mut changed = vec![];
+    push_changed_settings("", &config, &other, &mut changed);
+    changed
+}
+
+fn push_changed_settings(path: &str, value: &toml::Value, other: &toml::Value, changed: &mut Vec<String>) {
+    match (value, other) {
+        (toml::Value::Table(table), toml::Value::Table(other_table)) => {
+            let keys: BTreeSet<&String> = table.keys().chain(other_table.keys()).collect();
+
+            for key in keys {
+                let key_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                match (table.get(key), other_table.get(key)) {
+                    (Some(value), Some(other)) => push_changed_settings(&key_path, value, other, changed),
+                    _ => changed.push(key_path),
+                }
+            }
+        }
+        (value, other) if value != other => changed.push(path.to_string()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::config::Configuration;
+    use crate::databases::driver::Driver;
+    use crate::tracker::client_filter::Mode;
+    use crate::tracker::{mode, peer, statistics, Tracker};
+
+    fn configuration() -> Configuration {
+        let mut config = Configuration::default();
+        config.db_driver = Driver::Memory;
+        config
+    }
+
+    #[tokio::test]
+    async fn the_client_filter_should_be_reloaded_from_the_configuration_file() {
+        let config_path = std::env::temp_dir().join(format!("config_{}.toml", uuid::Uuid::new_v4()));
+        let config_path = config_path.to_str().unwrap();
+        configuration().save_to_file(config_path).unwrap();
+
+        let tracker = Tracker::new(&Arc::new(configuration()), None, statistics::Repo::new())
+            .unwrap()
+            .with_config_path(config_path);
+        let xunlei = peer::Id(*b"-XL0012-000000000000");
+        assert!(tracker.get_client_filter().await.is_allowed(&xunlei, None));
+
+        let mut config = configuration();
+        config.client_filter.mode = Mode::Block;
+        config.client_filter.peer_id_prefixes = vec!["-XL".to_string()];
+        config.save_to_file(config_path).unwrap();
+
+        tracker.reload().await.unwrap();
+
+        assert!(!tracker.get_client_filter().await.is_allowed(&xunlei, None));
+
+        std::fs::remove_file(config_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_runtime_settings_should_be_applied_and_the_others_reported() {
+        let config_path = std::env::temp_dir().join(format!("config_{}.toml", uuid::Uuid::new_v4()));
+        let config_path = config_path.to_str().unwrap();
+        configuration().save_to_file(config_path).unwrap();
+
+        let tracker = Tracker::new(&Arc::new(configuration()), None, statistics::Repo::new())
+            .unwrap()
+            .with_config_path(config_path);
+
+        let mut config = configuration();
+        config.mode = mode::Mode::Listed;
+        config.announce_interval = 300;
+        config.http_api.access_tokens.insert("ci".to_string(), "CiToken".to_string());
+        config.http_api.bind_address = "127.0.0.1:1313".to_string();
+        config.torrent_shards = 4;
+        config.save_to_file(config_path).unwrap();
+
+        let restart_required = tracker.reload().await.unwrap();
+
+        assert_eq!(restart_required, ["http_api.bind_address", "torrent_shards"]);
+        assert!(tracker.is_whitelisted());
+        assert_eq!(tracker.config().announce_interval, 300);
+        assert_eq!(tracker.config().http_api.access_tokens["ci"], "CiToken");
+        assert_eq!(tracker.config().http_api.bind_address, "127.0.0.1:1212");
+        assert_eq!(tracker.config().torrent_shards, 16);
+
+        std::fs::remove_file(config_path).unwrap();
+    }
+}
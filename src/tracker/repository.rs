@@ -0,0 +1,269 @@
+//! The torrents kept in memory, split into shards so announces for different
+//! torrents do not wait for each other's lock.
+//!
+//! Each shard has an index of the torrents the peers of each IP are in, so the swarms of an IP are found
+//! without going through all the torrents. The index is updated while the shard is locked for writing, along
+//! with the peers and the torrents added and removed, except for the inactive peers, which are left in it until
+//! the cleanup removing them prunes it, so it is checked against the torrents when looked up.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use tokio::sync::RwLock;
+
+use super::{peer, torrent};
+use crate::protocol::info_hash::InfoHash;
+
+pub type Shard = RwLock<BTreeMap<InfoHash, torrent::Entry>>;
+
+type PeerIndex = Mutex<HashMap<IpAddr, BTreeSet<InfoHash>>>;
+
+pub struct Repository {
+    shards: Vec<Shard>,
+    // The index of the peers of each shard, at the same position
+    peer_indexes: Vec<PeerIndex>,
+}
+
+impl Repository {
+    /// At least one shard is always created.
+    #[must_use]
+    pub fn new(shard_count: usize) -> Self {
+        let shard_count = shard_count.max(1);
+
+        Self {
+            shards: (0..shard_count).map(|_| RwLock::new(BTreeMap::new())).collect(),
+            peer_indexes: (0..shard_count).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The shard is picked from the first bytes of the info hash, which are evenly distributed.
+    #[must_use]
+    pub fn shard(&self, info_hash: &InfoHash) -> &Shard {
+        &self.shards[self.shard_index(info_hash)]
+    }
+
+    #[must_use]
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    fn shard_index(&self, info_hash: &InfoHash) -> usize {
+        let prefix = usize::from(u16::from_be_bytes([info_hash.0[0], info_hash.0[1]]));
+        prefix % self.shards.len()
+    }
+
+    /// Adds the torrent to the swarms of the `ip` if it has a peer with it, or removes it otherwise, after its
+    /// peers with the `ip` were added or removed. To be called while the shard of the torrent is locked for writing.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the peer index lock is poisoned.
+    pub fn reindex_ip(&self, info_hash: &InfoHash, ip: &IpAddr, torrent: &torrent::Entry) {
+        let mut index = self.peer_indexes[self.shard_index(info_hash)].lock().unwrap();
+
+        if has_peer_with_ip(torrent, ip) {
+            index.entry(*ip).or_default().insert(*info_hash);
+        } else {
+            remove_from_index(&mut index, ip, info_hash);
+        }
+    }
+
+    /// Removes the torrent from the swarms of the IPs of its peers, once it is removed from its shard, while
+    /// the shard is still locked for writing.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the peer index lock is poisoned.
+    pub fn unindex_torrent(&self, info_hash: &InfoHash, torrent: &torrent::Entry) {
+        let mut index = self.peer_indexes[self.shard_index(info_hash)].lock().unwrap();
+
+        for peer in torrent.all_peers() {
+            remove_from_index(&mut index, &peer.peer_addr.ip(), info_hash);
+        }
+    }
+
+    /// Removes the `ip` from the index of the shard at `shard_index`, once its peers are removed from all the
+    /// torrents of the shard, while it is still locked for writing.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the peer index lock is poisoned.
+    pub fn unindex_ip(&self, shard_index: usize, ip: &IpAddr) {
+        self.peer_indexes[shard_index].lock().unwrap().remove(ip);
+    }
+
+    /// Removes from the index of the shard at `shard_index` the torrents the IPs no longer have peers in,
+    /// to be called with the torrents of the shard locked.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the peer index lock is poisoned.
+    pub fn prune_peer_index(&self, shard_index: usize, torrents: &BTreeMap<InfoHash, torrent::Entry>) {
+        let mut index = self.peer_indexes[shard_index].lock().unwrap();
+
+        index.retain(|ip, info_hashes| {
+            info_hashes.retain(|info_hash| torrents.get(info_hash).is_some_and(|torrent| has_peer_with_ip(torrent, ip)));
+            !info_hashes.is_empty()
+        });
+    }
+
+    /// The peers with the IP, with the torrents they are in, sorted by info hash.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the peer index lock is poisoned.
+    pub async fn get_peers_by_ip(&self, ip: &IpAddr) -> Vec<(InfoHash, peer::Peer)> {
+        let mut peers = vec![];
+
+        for (shard, index) in self.shards.iter().zip(&self.peer_indexes) {
+            let torrents = shard.read().await;
+            let info_hashes = index.lock().unwrap().get(ip).cloned().unwrap_or_default();
+
+            for info_hash in info_hashes {
+                if let Some(torrent) = torrents.get(&info_hash) {
+                    peers.extend(
+                        torrent
+                            .all_peers()
+                            .filter(|peer| peer.peer_addr.ip() == *ip)
+                            .map(|peer| (info_hash, *peer)),
+                    );
+                }
+            }
+        }
+
+        peers.sort_by_key(|(info_hash, _)| *info_hash);
+        peers
+    }
+
+    /// How many torrents are indexed for all the IPs.
+    #[cfg(test)]
+    pub(crate) fn indexed_swarms(&self) -> usize {
+        self.peer_indexes
+            .iter()
+            .map(|index| index.lock().unwrap().values().map(BTreeSet::len).sum::<usize>())
+            .sum()
+    }
+}
+
+fn has_peer_with_ip(torrent: &torrent::Entry, ip: &IpAddr) -> bool {
+    torrent.all_peers().any(|peer| peer.peer_addr.ip() == *ip)
+}
+
+fn remove_from_index(index: &mut HashMap<IpAddr, BTreeSet<InfoHash>>, ip: &IpAddr, info_hash: &InfoHash) {
+    if let Some(info_hashes) = index.get_mut(ip) {
+        info_hashes.remove(info_hash);
+        if info_hashes.is_empty() {
+            index.remove(ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+
+    use super::Repository;
+    use crate::protocol::clock::{Current, Time};
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::{peer, torrent};
+
+    fn peer(peer_id: &[u8; 20], ip: IpAddr) -> peer::Peer {
+        peer::Peer {
+            peer_id: peer::Id(*peer_id),
+            peer_addr: SocketAddr::new(ip, 8080),
+            updated: Current::now(),
+            uploaded: NumberOfBytes(0),
+            downloaded: NumberOfBytes(0),
+            left: NumberOfBytes(1000),
+            event: AnnounceEvent::Started,
+            paused: false,
+            key: None,
+            country: None,
+        }
+    }
+
+    async fn add_peer(repository: &Repository, info_hash: &InfoHash, peer: &peer::Peer) {
+        let mut torrents = repository.shard(info_hash).write().await;
+        let torrent = torrents.entry(*info_hash).or_insert_with(torrent::Entry::new);
+        torrent.update_peer(peer);
+        repository.reindex_ip(info_hash, &peer.peer_addr.ip(), torrent);
+    }
+
+    #[test]
+    fn it_should_create_at_least_one_shard() {
+        assert_eq!(Repository::new(0).shard_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_should_always_pick_the_same_shard_for_a_torrent() {
+        let repository = Repository::new(16);
+        let info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+
+        repository
+            .shard(&info_hash)
+            .write()
+            .await
+            .insert(info_hash, torrent::Entry::new());
+
+        assert!(repository.shard(&info_hash).read().await.contains_key(&info_hash));
+
+        let mut torrents = 0;
+        for shard in repository.shards() {
+            torrents += shard.read().await.len();
+        }
+        assert_eq!(torrents, 1);
+    }
+
+    #[tokio::test]
+    async fn it_should_find_the_swarms_the_peers_of_an_ip_are_in() {
+        let repository = Repository::new(16);
+        let ip = IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1));
+        let other_ip = IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2));
+        let first = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+        let second = InfoHash::from_str("99c82bb73505a3c0b453f9fa0e881d6e5a32a0c1").unwrap();
+
+        add_peer(&repository, &second, &peer(b"-qB00000000000000001", ip)).await;
+        add_peer(&repository, &first, &peer(b"-qB00000000000000002", ip)).await;
+        add_peer(&repository, &first, &peer(b"-qB00000000000000003", other_ip)).await;
+
+        let swarms: Vec<_> = repository
+            .get_peers_by_ip(&ip)
+            .await
+            .into_iter()
+            .map(|(info_hash, peer)| (info_hash, peer.peer_id))
+            .collect();
+
+        assert_eq!(
+            swarms,
+            [
+                (first, peer::Id(*b"-qB00000000000000002")),
+                (second, peer::Id(*b"-qB00000000000000001"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_peers_removed_from_the_torrents_should_be_pruned_from_the_index() {
+        let repository = Repository::new(1);
+        let ip = IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1));
+        let info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+
+        add_peer(&repository, &info_hash, &peer(b"-qB00000000000000001", ip)).await;
+        repository.shard(&info_hash).write().await.remove(&info_hash);
+
+        assert!(repository.get_peers_by_ip(&ip).await.is_empty());
+
+        repository.prune_peer_index(0, &*repository.shard(&info_hash).read().await);
+
+        assert!(repository.peer_indexes[0].lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+//! Binary snapshots of the torrents and their peers, so a restarted tracker
+//! gets back its swarms without waiting for every peer to re-announce.
+//!
+//! The file starts with [`MAGIC`] and a format version. Integers are little-endian.
+//! Every torrent is its info hash, its completed count and its peers, and every
+//! peer is its id, address, last announce time, transfer stats and last event,
+//! where the partial seeds have the `paused` event.
+
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+use thiserror::Error;
+
+use super::peer;
+use crate::protocol::clock::DurationSinceUnixEpoch;
+use crate::protocol::info_hash::InfoHash;
+
+pub const MAGIC: &[u8; 8] = b"TTSNAPSH";
+const VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("could not access the snapshot file: {source}")]
+    Io { source: io::Error },
+
+    #[error("the snapshot is not valid: {reason}")]
+    Invalid { reason: &'static str },
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { source }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Torrent {
+    pub info_hash: InfoHash,
+    pub completed: u32,
+    pub peers: Vec<peer::Peer>,
+}
+
+#[must_use]
+pub fn encode(torrents: &[Torrent]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + 8);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&(torrents.len() as u64).to_le_bytes());
+
+    for torrent in torrents {
+        bytes.extend_from_slice(&torrent.info_hash.0);
+        bytes.extend_from_slice(&torrent.completed.to_le_bytes());
+        bytes.extend_from_slice(&(torrent.peers.len() as u64).to_le_bytes());
+
+        for peer in &torrent.peers {
+            encode_peer(peer, &mut bytes);
+        }
+    }
+
+    bytes
+}
+
+/// # Errors
+///
+/// Will return `Error::Invalid` if `bytes` is not a snapshot of this version, or it is truncated.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Torrent>, Error> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(Error::Invalid {
+            reason: "it is not a tracker snapshot",
+        });
+    }
+    if reader.take(1)?[0] != VERSION {
+        return Err(Error::Invalid {
+            reason: "unsupported snapshot version",
+        });
+    }
+
+    let torrents_len = reader.u64()?;
+    // The lengths are not trusted for the allocations, a corrupted file could claim anything
+    let mut torrents = Vec::new();
+
+    for _ in 0..torrents_len {
+        let info_hash = InfoHash(reader.array()?);
+        let completed = reader.u32()?;
+        let peers_len = reader.u64()?;
+
+        let mut peers = Vec::new();
+        for _ in 0..peers_len {
+            peers.push(decode_peer(&mut reader)?);
+        }
+
+        torrents.push(Torrent {
+            info_hash,
+            completed,
+            peers,
+        });
+    }
+
+    if !reader.bytes.is_empty() {
+        return Err(Error::Invalid {
+            reason: "unexpected data after the torrents",
+        });
+    }
+
+    Ok(torrents)
+}
+
+/// Writes the snapshot into a temporary file first, so a crash while saving does not
+/// leave a truncated snapshot behind.
+///
+/// # Errors
+///
+/// Will return `Error::Io` if unable to write the file.
+pub fn save(path: &Path, torrents: &[Torrent]) -> Result<(), Error> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, encode(torrents))?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Returns `None` if there is no snapshot at `path`.
+///
+/// # Errors
+///
+/// Will return an `Error` if unable to read the file, or it is not a valid snapshot.
+pub fn load(path: &Path) -> Result<Option<Vec<Torrent>>, Error> {
+    match fs::read(path) {
+        Ok(bytes) => decode(&bytes).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn encode_peer(peer: &peer::Peer, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&peer.peer_id.0);
+
+    match peer.peer_addr.ip() {
+        IpAddr::V4(ip) => {
+            bytes.push(4);
+            bytes.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            bytes.push(6);
+            bytes.extend_from_slice(&ip.octets());
+        }
+    }
+    bytes.extend_from_slice(&peer.peer_addr.port().to_le_bytes());
+
+    bytes.extend_from_slice(&peer.updated.as_secs().to_le_bytes());
+    bytes.extend_from_slice(&peer.updated.subsec_nanos().to_le_bytes());
+    bytes.extend_from_slice(&peer.uploaded.0.to_le_bytes());
+    bytes.extend_from_slice(&peer.downloaded.0.to_le_bytes());
+    bytes.extend_from_slice(&peer.left.0.to_le_bytes());
+
+    bytes.push(match peer.event {
+        _ if peer.paused => 4,
+        AnnounceEvent::None => 0,
+        AnnounceEvent::Started => 1,
+        AnnounceEvent::Stopped => 2,
+        AnnounceEvent::Completed => 3,
+    });
+}
+
+fn decode_peer(reader: &mut Reader) -> Result<peer::Peer, Error> {
+    let peer_id = peer::Id(reader.array()?);
+
+    let ip = match reader.take(1)?[0] {
+        4 => IpAddr::V4(Ipv4Addr::from(reader.array::<4>()?)),
+        6 => IpAddr::V6(Ipv6Addr::from(reader.array::<16>()?)),
+        _ => {
+            return Err(Error::Invalid {
+                reason: "unknown IP version",
+            })
+        }
+    };
+    let port = u16::from_le_bytes(reader.array()?);
+
+    let updated = DurationSinceUnixEpoch::new(reader.u64()?, reader.u32()?);
+    let uploaded = NumberOfBytes(i64::from_le_bytes(reader.array()?));
+    let downloaded = NumberOfBytes(i64::from_le_bytes(reader.array()?));
+    let left = NumberOfBytes(i64::from_le_bytes(reader.array()?));
+
+    let (event, paused) = match reader.take(1)?[0] {
+        0 => (AnnounceEvent::None, false),
+        1 => (AnnounceEvent::Started, false),
+        2 => (AnnounceEvent::Stopped, false),
+        3 => (AnnounceEvent::Completed, false),
+        4 => (AnnounceEvent::None, true),
+        _ => return Err(Error::Invalid { reason: "unknown event" }),
+    };
+
+    Ok(peer::Peer {
+        peer_id,
+        peer_addr: SocketAddr::new(ip, port),
+        updated,
+        uploaded,
+        downloaded,
+        left,
+        event,
+        paused,
+        key: None,
+        country: None,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::Invalid {
+                reason: "the snapshot is truncated",
+            });
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
+
+    use super::{decode, encode, Error, Torrent};
+    use crate::protocol::clock::DurationSinceUnixEpoch;
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::peer;
+
+    fn sample_torrents() -> Vec<Torrent> {
+        let peer = peer::Peer {
+            peer_id: peer::Id(*b"-qB00000000000000001"),
+            peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)), 8080),
+            updated: DurationSinceUnixEpoch::new(1_669_397_478, 934_000),
+            uploaded: NumberOfBytes(10),
+            downloaded: NumberOfBytes(20),
+            left: NumberOfBytes(30),
+            event: AnnounceEvent::Started,
+            paused: false,
+            key: None,
+            country: None,
+        };
+        let ipv6_peer = peer::Peer {
+            peer_id: peer::Id(*b"-qB00000000000000002"),
+            peer_addr: SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 6881),
+            left: NumberOfBytes(0),
+            event: AnnounceEvent::Completed,
+            ..peer
+        };
+
+        vec![
+            Torrent {
+                info_hash: InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap(),
+                completed: 7,
+                peers: vec![peer, ipv6_peer],
+            },
+            Torrent {
+                info_hash: InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap(),
+                completed: 0,
+                peers: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn it_should_restore_the_encoded_torrents() {
+        let torrents = sample_torrents();
+
+        assert_eq!(decode(&encode(&torrents)).unwrap(), torrents);
+    }
+
+    #[test]
+    fn it_should_reject_truncated_snapshots() {
+        let bytes = encode(&sample_torrents());
+
+        assert!(matches!(decode(&bytes[..bytes.len() - 1]), Err(Error::Invalid { .. })));
+        assert!(matches!(decode(b"not a snapshot"), Err(Error::Invalid { .. })));
+    }
+}
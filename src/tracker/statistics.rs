@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock as StdRwLock};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use log::debug;
@@ -7,8 +10,20 @@ use mockall::{automock, predicate::str};
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::{mpsc, RwLock, RwLockReadGuard};
 
+use super::peer;
+use crate::databases::metrics::Histogram;
+use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Time};
+
 const CHANNEL_BUFFER_SIZE: usize = 65_535;
 
+pub const LAST_MINUTE_SECS: u64 = 60;
+pub const LAST_FIVE_MINUTES_SECS: u64 = 300;
+pub const LAST_HOUR_SECS: u64 = 3600;
+
+/// The history is kept in buckets of five minutes, for the last 24 hours.
+pub const HISTORY_BUCKET_SECS: u64 = 300;
+pub const HISTORY_BUCKETS: u64 = 288;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Event {
     Tcp4Announce,
@@ -21,6 +36,99 @@ pub enum Event {
     Udp6Connect,
     Udp6Announce,
     Udp6Scrape,
+    /// A UDP request of the `kind`, unknown for the packets that could not be parsed, answered in `response_time`.
+    UdpResponse {
+        kind: Option<UdpRequestKind>,
+        response_time: Duration,
+        is_error: bool,
+    },
+    /// An announce of the peer, to count the unique peers of the windows.
+    PeerAnnounced(peer::Id),
+}
+
+impl Event {
+    #[must_use]
+    pub fn is_announce(&self) -> bool {
+        matches!(
+            self,
+            Event::Tcp4Announce | Event::Tcp6Announce | Event::Udp4Announce | Event::Udp6Announce
+        )
+    }
+}
+
+/// The kinds of UDP requests, their response times are measured apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UdpRequestKind {
+    Connect,
+    Announce,
+    Scrape,
+}
+
+/// The kinds of HTTP tracker requests, their response times are measured apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpRequestKind {
+    Announce,
+    Scrape,
+}
+
+/// The kinds of requests counted apart for each listener, whatever its protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenerRequestKind {
+    Announce,
+    Scrape,
+    Other,
+}
+
+/// The requests handled by a listener, a UDP or HTTP tracker bind address, since the tracker started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ListenerMetrics {
+    pub requests: u64,
+    pub announces: u64,
+    pub scrapes: u64,
+    pub errors: u64,
+    // `None` until its first request, a listener which never gets any may not be reachable
+    pub last_request: Option<DurationSinceUnixEpoch>,
+}
+
+/// The counters of a listener, shared with it when it starts so its requests are counted without any lock.
+#[derive(Debug, Default)]
+pub struct ListenerCounters {
+    requests: AtomicU64,
+    announces: AtomicU64,
+    scrapes: AtomicU64,
+    errors: AtomicU64,
+    // Nanoseconds since the Unix epoch, zero until the first request
+    last_request: AtomicU64,
+}
+
+impl ListenerCounters {
+    /// Records a request of the `kind`, and whether it was answered with an error.
+    pub fn record(&self, kind: ListenerRequestKind, is_error: bool, now: DurationSinceUnixEpoch) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        match kind {
+            ListenerRequestKind::Announce => self.announces.fetch_add(1, Ordering::Relaxed),
+            ListenerRequestKind::Scrape => self.scrapes.fetch_add(1, Ordering::Relaxed),
+            ListenerRequestKind::Other => 0,
+        };
+        if is_error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.last_request
+            .fetch_max(u64::try_from(now.as_nanos()).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn metrics(&self) -> ListenerMetrics {
+        let last_request = self.last_request.load(Ordering::Relaxed);
+
+        ListenerMetrics {
+            requests: self.requests.load(Ordering::Relaxed),
+            announces: self.announces.load(Ordering::Relaxed),
+            scrapes: self.scrapes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            last_request: (last_request > 0).then(|| Duration::from_nanos(last_request)),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -37,6 +145,31 @@ pub struct Metrics {
     pub udp6_connections_handled: u64,
     pub udp6_announces_handled: u64,
     pub udp6_scrapes_handled: u64,
+    // Torrents evicted to keep them under the memory limit
+    pub torrents_evicted: u64,
+    // Peers evicted to make room in the full torrents
+    pub peers_evicted: u64,
+    // UDP packets dropped for coming from a filtered network, exceeding the rate limit of their IP address, or the queue of the server
+    pub udp_packets_dropped: u64,
+    // UDP connect requests dropped while the queue of the server was over the load shedding threshold
+    pub udp_connects_shed: u64,
+    // UDP packets that could not be parsed as a request
+    pub udp_requests_malformed: u64,
+    // UDP requests answered with an error response
+    pub udp_errors_sent: u64,
+    // HTTP announces of a started peer without the `tracker id` it was given, or with another one
+    pub http_tracker_ids_ignored: u64,
+    // HTTP tracker requests answered with a failure reason
+    pub http_errors_sent: u64,
+    // HTTP tracker responses by status code
+    pub http_responses_by_status: BTreeMap<u16, u64>,
+    // Time to handle each kind of HTTP tracker request, from the routing of the request to the response
+    pub http_announce_response_time: Histogram,
+    pub http_scrape_response_time: Histogram,
+    // Time to handle each kind of UDP request, from the parsing of the packet to the response
+    pub udp_connect_response_time: Histogram,
+    pub udp_announce_response_time: Histogram,
+    pub udp_scrape_response_time: Histogram,
 }
 
 impl Default for Metrics {
@@ -61,10 +194,310 @@ impl Metrics {
             udp6_connections_handled: 0,
             udp6_announces_handled: 0,
             udp6_scrapes_handled: 0,
+            torrents_evicted: 0,
+            peers_evicted: 0,
+            udp_packets_dropped: 0,
+            udp_connects_shed: 0,
+            udp_requests_malformed: 0,
+            udp_errors_sent: 0,
+            http_tracker_ids_ignored: 0,
+            http_errors_sent: 0,
+            http_responses_by_status: BTreeMap::new(),
+            http_announce_response_time: Histogram::default(),
+            http_scrape_response_time: Histogram::default(),
+            udp_connect_response_time: Histogram::default(),
+            udp_announce_response_time: Histogram::default(),
+            udp_scrape_response_time: Histogram::default(),
         }
     }
 }
 
+/// Announces and scrapes handled, split by protocol and IP version.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    pub tcp4_announces: u64,
+    pub tcp4_scrapes: u64,
+    pub tcp6_announces: u64,
+    pub tcp6_scrapes: u64,
+    pub udp4_announces: u64,
+    pub udp4_scrapes: u64,
+    pub udp6_announces: u64,
+    pub udp6_scrapes: u64,
+}
+
+impl Counters {
+    fn increase(&mut self, event: &Event) {
+        match event {
+            Event::Tcp4Announce => self.tcp4_announces += 1,
+            Event::Tcp4Scrape => self.tcp4_scrapes += 1,
+            Event::Tcp6Announce => self.tcp6_announces += 1,
+            Event::Tcp6Scrape => self.tcp6_scrapes += 1,
+            Event::Udp4Announce => self.udp4_announces += 1,
+            Event::Udp4Scrape => self.udp4_scrapes += 1,
+            Event::Udp6Announce => self.udp6_announces += 1,
+            Event::Udp6Scrape => self.udp6_scrapes += 1,
+            Event::Udp4Connect | Event::Udp6Connect | Event::UdpResponse { .. } | Event::PeerAnnounced(_) => {}
+        }
+    }
+
+    fn add(&mut self, other: &Counters) {
+        self.tcp4_announces += other.tcp4_announces;
+        self.tcp4_scrapes += other.tcp4_scrapes;
+        self.tcp6_announces += other.tcp6_announces;
+        self.tcp6_scrapes += other.tcp6_scrapes;
+        self.udp4_announces += other.udp4_announces;
+        self.udp4_scrapes += other.udp4_scrapes;
+        self.udp6_announces += other.udp6_announces;
+        self.udp6_scrapes += other.udp6_scrapes;
+    }
+
+    #[must_use]
+    pub fn tcp_announces(&self) -> u64 {
+        self.tcp4_announces + self.tcp6_announces
+    }
+
+    #[must_use]
+    pub fn udp_announces(&self) -> u64 {
+        self.udp4_announces + self.udp6_announces
+    }
+
+    #[must_use]
+    pub fn ipv4_announces(&self) -> u64 {
+        self.tcp4_announces + self.udp4_announces
+    }
+
+    #[must_use]
+    pub fn ipv6_announces(&self) -> u64 {
+        self.tcp6_announces + self.udp6_announces
+    }
+
+    #[must_use]
+    pub fn announces(&self) -> u64 {
+        self.tcp_announces() + self.udp_announces()
+    }
+
+    #[must_use]
+    pub fn tcp_scrapes(&self) -> u64 {
+        self.tcp4_scrapes + self.tcp6_scrapes
+    }
+
+    #[must_use]
+    pub fn udp_scrapes(&self) -> u64 {
+        self.udp4_scrapes + self.udp6_scrapes
+    }
+
+    #[must_use]
+    pub fn ipv4_scrapes(&self) -> u64 {
+        self.tcp4_scrapes + self.udp4_scrapes
+    }
+
+    #[must_use]
+    pub fn ipv6_scrapes(&self) -> u64 {
+        self.tcp6_scrapes + self.udp6_scrapes
+    }
+
+    #[must_use]
+    pub fn scrapes(&self) -> u64 {
+        self.tcp_scrapes() + self.udp_scrapes()
+    }
+}
+
+/// What the tracker handled in the last `secs` seconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WindowMetrics {
+    pub secs: u64,
+    pub counters: Counters,
+    pub unique_peers: u64,
+}
+
+impl WindowMetrics {
+    /// The average over the whole window, even if the tracker has been running for less time.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn announces_per_second(&self) -> f64 {
+        self.counters.announces() as f64 / self.secs as f64
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn scrapes_per_second(&self) -> f64 {
+        self.counters.scrapes() as f64 / self.secs as f64
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Slot {
+    second: u64,
+    counters: Counters,
+}
+
+/// Per-second counters of the last hour, and when each peer announced for the last time,
+/// to aggregate the statistics over rolling windows of up to an hour.
+#[derive(Debug)]
+pub struct Windows {
+    slots: Vec<Slot>,
+    peers_last_seen: HashMap<peer::Id, u64>,
+}
+
+impl Default for Windows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Windows {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            #[allow(clippy::cast_possible_truncation)]
+            slots: vec![Slot::default(); LAST_HOUR_SECS as usize],
+            peers_last_seen: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: &Event, now: DurationSinceUnixEpoch) {
+        let second = now.as_secs();
+        #[allow(clippy::cast_possible_truncation)]
+        let slot = &mut self.slots[(second % LAST_HOUR_SECS) as usize];
+
+        // The slot was last used an hour ago, or more
+        if slot.second != second {
+            *slot = Slot {
+                second,
+                counters: Counters::default(),
+            };
+        }
+
+        slot.counters.increase(event);
+    }
+
+    pub fn record_peer(&mut self, peer_id: peer::Id, now: DurationSinceUnixEpoch) {
+        self.peers_last_seen.insert(peer_id, now.as_secs());
+    }
+
+    /// Forgets the peers that did not announce in the last hour, they are not in any window.
+    pub fn remove_expired_peers(&mut self, now: DurationSinceUnixEpoch) {
+        let now = now.as_secs();
+        self.peers_last_seen
+            .retain(|_, last_seen| Self::is_within(*last_seen, now, LAST_HOUR_SECS));
+    }
+
+    /// The metrics of the last `secs` seconds, up to an hour, including the current second.
+    #[must_use]
+    pub fn metrics(&self, secs: u64, now: DurationSinceUnixEpoch) -> WindowMetrics {
+        let secs = secs.clamp(1, LAST_HOUR_SECS);
+        let now = now.as_secs();
+
+        let mut counters = Counters::default();
+        self.slots
+            .iter()
+            .filter(|slot| Self::is_within(slot.second, now, secs))
+            .for_each(|slot| counters.add(&slot.counters));
+
+        let unique_peers = self
+            .peers_last_seen
+            .values()
+            .filter(|last_seen| Self::is_within(**last_seen, now, secs))
+            .count() as u64;
+
+        WindowMetrics {
+            secs,
+            counters,
+            unique_peers,
+        }
+    }
+
+    fn is_within(second: u64, now: u64, secs: u64) -> bool {
+        now.checked_sub(second).is_some_and(|age| age < secs)
+    }
+}
+
+/// The announces handled in a bucket of the history, and the peers and torrents of its last sample.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryBucket {
+    pub start: u64,
+    pub announces: u64,
+    pub peers: u64,
+    pub torrents: u64,
+}
+
+/// A ring buffer with the buckets of the last 24 hours, the oldest bucket is reused for the next one.
+#[derive(Debug)]
+pub struct History {
+    buckets: Vec<Option<HistoryBucket>>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            #[allow(clippy::cast_possible_truncation)]
+            buckets: vec![None; HISTORY_BUCKETS as usize],
+        }
+    }
+
+    pub fn record(&mut self, event: &Event, now: DurationSinceUnixEpoch) {
+        if event.is_announce() {
+            self.bucket(now).announces += 1;
+        }
+    }
+
+    /// Keeps the number of `peers` and `torrents` in the bucket of `now`, replacing the previous sample.
+    pub fn sample(&mut self, peers: u64, torrents: u64, now: DurationSinceUnixEpoch) {
+        let bucket = self.bucket(now);
+        bucket.peers = peers;
+        bucket.torrents = torrents;
+    }
+
+    /// The buckets of the last 24 hours with something recorded, from the oldest.
+    #[must_use]
+    pub fn buckets(&self, now: DurationSinceUnixEpoch) -> Vec<HistoryBucket> {
+        let current_start = Self::start_of(now);
+        let mut buckets: Vec<HistoryBucket> = self
+            .buckets
+            .iter()
+            .flatten()
+            .filter(|bucket| {
+                current_start
+                    .checked_sub(bucket.start)
+                    .is_some_and(|age| age < HISTORY_BUCKETS * HISTORY_BUCKET_SECS)
+            })
+            .copied()
+            .collect();
+        buckets.sort_by_key(|bucket| bucket.start);
+        buckets
+    }
+
+    fn bucket(&mut self, now: DurationSinceUnixEpoch) -> &mut HistoryBucket {
+        let start = Self::start_of(now);
+        #[allow(clippy::cast_possible_truncation)]
+        let bucket = &mut self.buckets[((start / HISTORY_BUCKET_SECS) % HISTORY_BUCKETS) as usize];
+
+        // The bucket was last used 24 hours ago, or more
+        match bucket {
+            Some(bucket) if bucket.start == start => {}
+            _ => {
+                *bucket = Some(HistoryBucket {
+                    start,
+                    ..HistoryBucket::default()
+                });
+            }
+        }
+
+        bucket.as_mut().expect("the bucket was just set")
+    }
+
+    fn start_of(now: DurationSinceUnixEpoch) -> u64 {
+        now.as_secs() - now.as_secs() % HISTORY_BUCKET_SECS
+    }
+}
+
 pub struct Keeper {
     pub repository: Repo,
 }
@@ -150,8 +583,25 @@ async fn event_handler(event: Event, stats_repository: &Repo) {
         Event::Udp6Scrape => {
             stats_repository.increase_udp6_scrapes().await;
         }
+
+        // Not counted in the windows nor in the history
+        Event::UdpResponse {
+            kind,
+            response_time,
+            is_error,
+        } => {
+            stats_repository.record_udp_response(kind, response_time, is_error).await;
+            return;
+        }
+        Event::PeerAnnounced(peer_id) => {
+            stats_repository.record_peer(peer_id, Current::now()).await;
+            return;
+        }
     }
 
+    stats_repository.record_in_windows(&event, Current::now()).await;
+    stats_repository.record_in_history(&event, Current::now()).await;
+
     debug!("stats: {:?}", stats_repository.get_stats().await);
 }
 
@@ -175,6 +625,10 @@ impl EventSender for Sender {
 #[derive(Clone)]
 pub struct Repo {
     pub stats: Arc<RwLock<Metrics>>,
+    pub windows: Arc<RwLock<Windows>>,
+    pub history: Arc<RwLock<History>>,
+    // The counters of each listener, named by their protocol and bind address like `udp://0.0.0.0:6969`
+    pub listeners: Arc<StdRwLock<BTreeMap<String, Arc<ListenerCounters>>>>,
 }
 
 impl Default for Repo {
@@ -188,6 +642,9 @@ impl Repo {
     pub fn new() -> Self {
         Self {
             stats: Arc::new(RwLock::new(Metrics::new())),
+            windows: Arc::new(RwLock::new(Windows::new())),
+            history: Arc::new(RwLock::new(History::new())),
+            listeners: Arc::new(StdRwLock::new(BTreeMap::new())),
         }
     }
 
@@ -195,6 +652,128 @@ impl Repo {
         self.stats.read().await
     }
 
+    /// The metrics of the last `secs` seconds, up to an hour.
+    pub async fn get_window_stats(&self, secs: u64, now: DurationSinceUnixEpoch) -> WindowMetrics {
+        self.windows.read().await.metrics(secs, now)
+    }
+
+    pub async fn record_in_windows(&self, event: &Event, now: DurationSinceUnixEpoch) {
+        self.windows.write().await.record(event, now);
+    }
+
+    /// The buckets of the last 24 hours, from the oldest.
+    pub async fn get_history(&self, now: DurationSinceUnixEpoch) -> Vec<HistoryBucket> {
+        self.history.read().await.buckets(now)
+    }
+
+    pub async fn record_in_history(&self, event: &Event, now: DurationSinceUnixEpoch) {
+        self.history.write().await.record(event, now);
+    }
+
+    pub async fn sample_history(&self, peers: u64, torrents: u64, now: DurationSinceUnixEpoch) {
+        self.history.write().await.sample(peers, torrents, now);
+    }
+
+    pub async fn record_peer(&self, peer_id: peer::Id, now: DurationSinceUnixEpoch) {
+        self.windows.write().await.record_peer(peer_id, now);
+    }
+
+    pub async fn remove_expired_peers(&self, now: DurationSinceUnixEpoch) {
+        self.windows.write().await.remove_expired_peers(now);
+    }
+
+    pub async fn add_torrents_evicted(&self, evicted: u64) {
+        self.stats.write().await.torrents_evicted += evicted;
+    }
+
+    pub async fn add_peers_evicted(&self, evicted: u64) {
+        self.stats.write().await.peers_evicted += evicted;
+    }
+
+    pub async fn add_udp_packets_dropped(&self, dropped: u64) {
+        self.stats.write().await.udp_packets_dropped += dropped;
+    }
+
+    pub async fn add_udp_connects_shed(&self, shed: u64) {
+        self.stats.write().await.udp_connects_shed += shed;
+    }
+
+    pub async fn increase_udp_requests_malformed(&self) {
+        self.stats.write().await.udp_requests_malformed += 1;
+    }
+
+    pub async fn increase_http_tracker_ids_ignored(&self) {
+        self.stats.write().await.http_tracker_ids_ignored += 1;
+    }
+
+    /// Records the response time of a UDP request of the `kind`, unknown for the packets that
+    /// could not be parsed, and whether it was answered with an error.
+    pub async fn record_udp_response(&self, kind: Option<UdpRequestKind>, response_time: Duration, is_error: bool) {
+        let mut stats_lock = self.stats.write().await;
+        match kind {
+            Some(UdpRequestKind::Connect) => stats_lock.udp_connect_response_time.record(response_time),
+            Some(UdpRequestKind::Announce) => stats_lock.udp_announce_response_time.record(response_time),
+            Some(UdpRequestKind::Scrape) => stats_lock.udp_scrape_response_time.record(response_time),
+            None => {}
+        }
+        if is_error {
+            stats_lock.udp_errors_sent += 1;
+        }
+        drop(stats_lock);
+    }
+
+    /// Records the `status` and the response time of an HTTP tracker request of the `kind`, unknown for the
+    /// requests of other routes, and whether it was answered with a failure reason.
+    pub async fn record_http_response(
+        &self,
+        kind: Option<HttpRequestKind>,
+        status: u16,
+        response_time: Duration,
+        is_error: bool,
+    ) {
+        let mut stats_lock = self.stats.write().await;
+        match kind {
+            Some(HttpRequestKind::Announce) => stats_lock.http_announce_response_time.record(response_time),
+            Some(HttpRequestKind::Scrape) => stats_lock.http_scrape_response_time.record(response_time),
+            None => {}
+        }
+        *stats_lock.http_responses_by_status.entry(status).or_default() += 1;
+        if is_error {
+            stats_lock.http_errors_sent += 1;
+        }
+        drop(stats_lock);
+    }
+
+    /// Adds the `listener` without any request, so the ones which never get a request are listed too,
+    /// returning its counters, the same ones if it was already added.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the lock of the listeners is poisoned.
+    pub fn register_listener(&self, listener: &str) -> Arc<ListenerCounters> {
+        self.listeners
+            .write()
+            .unwrap()
+            .entry(listener.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// The requests of each listener, by their name.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the lock of the listeners is poisoned.
+    #[must_use]
+    pub fn get_listener_stats(&self) -> BTreeMap<String, ListenerMetrics> {
+        self.listeners
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(listener, counters)| (listener.clone(), counters.metrics()))
+            .collect()
+    }
+
     pub async fn increase_tcp4_announces(&self) {
         let mut stats_lock = self.stats.write().await;
         stats_lock.tcp4_announces_handled += 1;
@@ -296,7 +875,11 @@ mod tests {
     }
 
     mod event_handler {
-        use crate::tracker::statistics::{event_handler, Event, Repo};
+        use std::time::Duration;
+
+        use crate::protocol::clock::{Current, Time};
+        use crate::tracker::peer;
+        use crate::tracker::statistics::{event_handler, Event, Repo, UdpRequestKind, LAST_MINUTE_SECS};
 
         #[tokio::test]
         async fn should_increase_the_tcp4_announces_counter_when_it_receives_a_tcp4_announce_event() {
@@ -451,5 +1034,265 @@ mod tests {
 
             assert_eq!(stats.udp6_scrapes_handled, 1);
         }
+
+        #[tokio::test]
+        async fn should_record_the_udp_response_when_it_receives_a_udp_response_event() {
+            let stats_repository = Repo::new();
+
+            event_handler(
+                Event::UdpResponse {
+                    kind: Some(UdpRequestKind::Connect),
+                    response_time: Duration::from_millis(2),
+                    is_error: true,
+                },
+                &stats_repository,
+            )
+            .await;
+
+            let stats = stats_repository.get_stats().await;
+
+            assert_eq!(stats.udp_connect_response_time.count, 1);
+            assert_eq!(stats.udp_errors_sent, 1);
+        }
+
+        #[tokio::test]
+        async fn should_count_the_peer_in_the_windows_when_it_receives_a_peer_announced_event() {
+            let stats_repository = Repo::new();
+
+            event_handler(Event::PeerAnnounced(peer::Id(*b"-qB00000000000000001")), &stats_repository).await;
+
+            let last_minute = stats_repository.get_window_stats(LAST_MINUTE_SECS, Current::now()).await;
+
+            assert_eq!(last_minute.unique_peers, 1);
+            assert_eq!(last_minute.counters.announces(), 0);
+        }
+    }
+
+    mod udp_responses {
+        use std::time::Duration;
+
+        use crate::tracker::statistics::{Repo, UdpRequestKind};
+
+        #[tokio::test]
+        async fn should_record_the_response_time_of_the_kind_of_request() {
+            let stats_repository = Repo::new();
+
+            stats_repository
+                .record_udp_response(Some(UdpRequestKind::Announce), Duration::from_millis(3), false)
+                .await;
+
+            let stats = stats_repository.get_stats().await;
+
+            assert_eq!(stats.udp_announce_response_time.count, 1);
+            assert_eq!(stats.udp_announce_response_time.max, Duration::from_millis(3));
+            assert_eq!(stats.udp_connect_response_time.count, 0);
+            assert_eq!(stats.udp_errors_sent, 0);
+        }
+
+        #[tokio::test]
+        async fn should_count_the_errors_sent_even_for_the_requests_that_could_not_be_parsed() {
+            let stats_repository = Repo::new();
+
+            stats_repository
+                .record_udp_response(Some(UdpRequestKind::Scrape), Duration::from_millis(1), true)
+                .await;
+            stats_repository
+                .record_udp_response(None, Duration::from_millis(1), true)
+                .await;
+
+            let stats = stats_repository.get_stats().await;
+
+            assert_eq!(stats.udp_errors_sent, 2);
+            assert_eq!(stats.udp_scrape_response_time.count, 1);
+        }
+    }
+
+    mod http_responses {
+        use std::time::Duration;
+
+        use crate::tracker::statistics::{HttpRequestKind, Repo};
+
+        #[tokio::test]
+        async fn should_record_the_status_and_the_response_time_of_the_kind_of_request() {
+            let stats_repository = Repo::new();
+
+            stats_repository
+                .record_http_response(Some(HttpRequestKind::Announce), 200, Duration::from_millis(3), false)
+                .await;
+            stats_repository
+                .record_http_response(Some(HttpRequestKind::Scrape), 200, Duration::from_millis(1), true)
+                .await;
+            stats_repository
+                .record_http_response(None, 200, Duration::from_millis(1), true)
+                .await;
+
+            let stats = stats_repository.get_stats().await;
+
+            assert_eq!(stats.http_announce_response_time.count, 1);
+            assert_eq!(stats.http_scrape_response_time.count, 1);
+            assert_eq!(stats.http_responses_by_status.get(&200), Some(&3));
+            assert_eq!(stats.http_errors_sent, 2);
+        }
+    }
+
+    mod listeners {
+        use std::time::Duration;
+
+        use crate::tracker::statistics::{ListenerMetrics, ListenerRequestKind, Repo};
+
+        #[test]
+        fn should_count_the_requests_of_each_listener_apart() {
+            let stats_repository = Repo::new();
+            let now = Duration::from_secs(10_000);
+
+            let udp_listener = stats_repository.register_listener("udp://0.0.0.0:6969");
+            stats_repository.register_listener("http://0.0.0.0:7070");
+            udp_listener.record(ListenerRequestKind::Announce, false, now);
+            udp_listener.record(ListenerRequestKind::Other, true, now);
+
+            let listeners = stats_repository.get_listener_stats();
+
+            assert_eq!(
+                listeners.get("udp://0.0.0.0:6969"),
+                Some(&ListenerMetrics {
+                    requests: 2,
+                    announces: 1,
+                    scrapes: 0,
+                    errors: 1,
+                    last_request: Some(now),
+                })
+            );
+            assert_eq!(listeners.get("http://0.0.0.0:7070"), Some(&ListenerMetrics::default()));
+        }
+
+        #[test]
+        fn should_share_the_counters_of_a_listener_registered_again() {
+            let stats_repository = Repo::new();
+
+            stats_repository.register_listener("http://0.0.0.0:7070").record(
+                ListenerRequestKind::Scrape,
+                false,
+                Duration::from_secs(10_000),
+            );
+
+            assert_eq!(stats_repository.register_listener("http://0.0.0.0:7070").metrics().scrapes, 1);
+        }
+    }
+
+    mod windows {
+        use std::time::Duration;
+
+        use crate::tracker::peer;
+        use crate::tracker::statistics::{Event, Windows, LAST_FIVE_MINUTES_SECS, LAST_HOUR_SECS, LAST_MINUTE_SECS};
+
+        #[test]
+        fn should_only_count_the_events_within_each_window() {
+            let mut windows = Windows::new();
+            let now = Duration::from_secs(10_000);
+
+            windows.record(&Event::Tcp4Announce, now - Duration::from_secs(3000));
+            windows.record(&Event::Udp6Announce, now - Duration::from_secs(200));
+            windows.record(&Event::Udp4Scrape, now - Duration::from_secs(30));
+            windows.record(&Event::Udp4Announce, now);
+
+            let last_minute = windows.metrics(LAST_MINUTE_SECS, now);
+            assert_eq!(last_minute.counters.announces(), 1);
+            assert_eq!(last_minute.counters.scrapes(), 1);
+
+            let last_five_minutes = windows.metrics(LAST_FIVE_MINUTES_SECS, now);
+            assert_eq!(last_five_minutes.counters.ipv6_announces(), 1);
+            assert_eq!(last_five_minutes.counters.udp_announces(), 2);
+
+            let last_hour = windows.metrics(LAST_HOUR_SECS, now);
+            assert_eq!(last_hour.counters.tcp_announces(), 1);
+            assert!((last_hour.announces_per_second() - 3.0 / 3600.0).abs() < f64::EPSILON);
+        }
+
+        #[test]
+        fn should_not_count_the_events_of_the_previous_hour_sharing_a_slot() {
+            let mut windows = Windows::new();
+            let now = Duration::from_secs(10_000);
+
+            windows.record(&Event::Tcp4Announce, now - Duration::from_secs(LAST_HOUR_SECS));
+            windows.record(&Event::Tcp4Announce, now);
+
+            assert_eq!(windows.metrics(LAST_HOUR_SECS, now).counters.tcp4_announces, 1);
+        }
+
+        #[test]
+        fn should_count_each_peer_once_per_window() {
+            let mut windows = Windows::new();
+            let now = Duration::from_secs(10_000);
+
+            windows.record_peer(peer::Id(*b"-qB00000000000000001"), now - Duration::from_secs(120));
+            windows.record_peer(peer::Id(*b"-qB00000000000000002"), now - Duration::from_secs(10));
+            windows.record_peer(peer::Id(*b"-qB00000000000000002"), now);
+
+            assert_eq!(windows.metrics(LAST_MINUTE_SECS, now).unique_peers, 1);
+            assert_eq!(windows.metrics(LAST_FIVE_MINUTES_SECS, now).unique_peers, 2);
+
+            windows.remove_expired_peers(now + Duration::from_secs(LAST_HOUR_SECS - 60));
+
+            assert_eq!(
+                windows
+                    .metrics(LAST_HOUR_SECS, now + Duration::from_secs(LAST_HOUR_SECS - 60))
+                    .unique_peers,
+                1
+            );
+        }
+    }
+
+    mod history {
+        use std::time::Duration;
+
+        use crate::tracker::statistics::{Event, History, HistoryBucket, HISTORY_BUCKETS, HISTORY_BUCKET_SECS};
+
+        #[test]
+        fn should_keep_the_announces_and_the_last_sample_of_each_bucket() {
+            let mut history = History::new();
+            let now = Duration::from_secs(100_000);
+
+            history.record(&Event::Tcp4Announce, now - Duration::from_secs(HISTORY_BUCKET_SECS));
+            history.record(&Event::Udp6Announce, now);
+            history.record(&Event::Udp4Scrape, now);
+            history.sample(3, 1, now);
+            history.sample(5, 2, now);
+
+            assert_eq!(
+                history.buckets(now),
+                vec![
+                    HistoryBucket {
+                        start: 99_600,
+                        announces: 1,
+                        peers: 0,
+                        torrents: 0
+                    },
+                    HistoryBucket {
+                        start: 99_900,
+                        announces: 1,
+                        peers: 5,
+                        torrents: 2
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn should_forget_the_buckets_older_than_a_day() {
+            let mut history = History::new();
+            let now = Duration::from_secs(100_000);
+            let a_day = Duration::from_secs(HISTORY_BUCKETS * HISTORY_BUCKET_SECS);
+
+            history.record(&Event::Tcp4Announce, now - a_day);
+            history.record(&Event::Tcp4Announce, now - Duration::from_secs(HISTORY_BUCKET_SECS));
+
+            assert_eq!(history.buckets(now).len(), 1);
+
+            // The bucket of a day ago is reused
+            history.record(&Event::Tcp4Announce, now);
+
+            assert_eq!(history.buckets(now).len(), 2);
+            assert_eq!(history.buckets(now)[1].announces, 1);
+        }
     }
 }
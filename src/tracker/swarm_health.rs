@@ -0,0 +1,138 @@
+//! How healthy a swarm is, so index sites can show the availability of their torrents.
+//!
+//! The score goes from 0 to 100. It mostly depends on the seed/leech ratio, and
+//! then on how recently the torrent was announced and how stable its peers are.
+
+use crate::protocol::clock::DurationSinceUnixEpoch;
+
+pub const CHURN_WINDOW_SECS: u64 = 3600;
+
+// A leecher per seeder, or more seeders, is a fully available swarm
+const RATIO_WEIGHT: f64 = 0.6;
+const ACTIVITY_WEIGHT: f64 = 0.25;
+const STABILITY_WEIGHT: f64 = 0.15;
+
+/// The peers joining and leaving a swarm, counted over a sliding window of [`CHURN_WINDOW_SECS`].
+#[derive(Clone, Debug, Default)]
+pub struct Churn {
+    window_start: u64,
+    current: u32,
+    previous: u32,
+}
+
+impl Churn {
+    pub fn record(&mut self, now: DurationSinceUnixEpoch, changes: u32) {
+        if changes == 0 {
+            return;
+        }
+        self.roll(now.as_secs());
+        self.current = self.current.saturating_add(changes);
+    }
+
+    /// The peers that joined or left the swarm in the last window, estimated by weighting
+    /// the previous window with the part of it that is still in the last window.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn last_window(&self, now: DurationSinceUnixEpoch) -> u32 {
+        let mut churn = self.clone();
+        churn.roll(now.as_secs());
+
+        let remaining = CHURN_WINDOW_SECS - now.as_secs().saturating_sub(churn.window_start).min(CHURN_WINDOW_SECS);
+        let previous = f64::from(churn.previous) * remaining as f64 / CHURN_WINDOW_SECS as f64;
+
+        churn.current.saturating_add(previous as u32)
+    }
+
+    fn roll(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.window_start);
+
+        if elapsed >= 2 * CHURN_WINDOW_SECS {
+            self.window_start = now - now % CHURN_WINDOW_SECS;
+            self.previous = 0;
+            self.current = 0;
+        } else if elapsed >= CHURN_WINDOW_SECS {
+            self.window_start += CHURN_WINDOW_SECS;
+            self.previous = self.current;
+            self.current = 0;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Health {
+    pub seeders: u32,
+    pub leechers: u32,
+    // Seeders per leecher, the seeders when there are no leechers
+    pub ratio: f64,
+    // Peers that joined or left in the last hour
+    pub churn: u32,
+    // Seconds since the last announce
+    pub last_activity: u64,
+    pub score: u8,
+}
+
+impl Health {
+    #[must_use]
+    pub fn new(seeders: u32, leechers: u32, churn: u32, last_activity: u64) -> Self {
+        Self {
+            seeders,
+            leechers,
+            ratio: f64::from(seeders) / f64::from(leechers.max(1)),
+            churn,
+            last_activity,
+            score: score(seeders, leechers, churn, last_activity),
+        }
+    }
+}
+
+/// A swarm without peers scores 0, and one without seeders cannot score more than 40.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+#[must_use]
+pub fn score(seeders: u32, leechers: u32, churn: u32, last_activity: u64) -> u8 {
+    let peers = seeders.saturating_add(leechers);
+    if peers == 0 {
+        return 0;
+    }
+
+    let ratio = (f64::from(seeders) / f64::from(leechers.max(1))).min(1.0);
+    // Halves after an hour without announces
+    let activity = 1.0 / (1.0 + last_activity as f64 / CHURN_WINDOW_SECS as f64);
+    // Halves when as many peers come and go in an hour as there are in the swarm
+    let stability = 1.0 / (1.0 + f64::from(churn) / f64::from(peers));
+
+    (100.0 * (RATIO_WEIGHT * ratio + ACTIVITY_WEIGHT * activity + STABILITY_WEIGHT * stability)).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{score, Churn, CHURN_WINDOW_SECS};
+
+    #[test]
+    fn an_active_and_stable_swarm_with_enough_seeders_should_score_100() {
+        assert_eq!(score(10, 5, 0, 0), 100);
+    }
+
+    #[test]
+    fn the_score_should_fall_with_fewer_seeders_older_announces_and_more_churn() {
+        assert_eq!(score(0, 0, 0, 0), 0);
+        assert_eq!(score(0, 10, 0, 0), 40);
+        assert!(score(5, 10, 0, 0) < score(10, 10, 0, 0));
+        assert!(score(10, 10, 0, 7200) < score(10, 10, 0, 0));
+        assert!(score(10, 10, 40, 0) < score(10, 10, 0, 0));
+    }
+
+    #[test]
+    fn the_churn_should_slide_over_the_last_window() {
+        let start = Duration::from_secs(100 * CHURN_WINDOW_SECS);
+        let mut churn = Churn::default();
+
+        churn.record(start, 10);
+        churn.record(start + Duration::from_secs(CHURN_WINDOW_SECS), 2);
+
+        // Half of the previous window is still in the last one
+        assert_eq!(churn.last_window(start + Duration::from_secs(CHURN_WINDOW_SECS * 3 / 2)), 7);
+        assert_eq!(churn.last_window(start + Duration::from_secs(CHURN_WINDOW_SECS * 3)), 0);
+    }
+}
@@ -1,26 +1,70 @@
+use std::collections::BTreeMap;
+use std::mem::size_of;
 use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 use aquatic_udp_protocol::AnnounceEvent;
 use serde::{Deserialize, Serialize};
 
-use super::peer;
-use crate::protocol::clock::{Current, TimeNow};
+use super::{eviction, geoip, peer, swarm_health};
+use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Time, TimeNow};
 use crate::protocol::common::MAX_SCRAPE_TORRENTS;
+use crate::protocol::info_hash::InfoHash;
+
+// Rough bytes a map spends for each entry besides its key and value
+const MAP_ENTRY_OVERHEAD: usize = 32;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Entry {
+    // Peers announcing from an IPv4 address
+    #[serde(skip)]
+    pub peers: BTreeMap<peer::Id, peer::Peer>,
+    // Peers announcing from an IPv6 address, a dual-stack peer without `key` is in both lists
     #[serde(skip)]
-    pub peers: std::collections::BTreeMap<peer::Id, peer::Peer>,
+    pub peers6: BTreeMap<peer::Id, peer::Peer>,
     pub completed: u32,
+    // The last announce, or when the torrent was added if there was none since
+    #[serde(skip)]
+    pub updated: DurationSinceUnixEpoch,
+    #[serde(skip)]
+    pub churn: swarm_health::Churn,
 }
 
 impl Entry {
     #[must_use]
     pub fn new() -> Entry {
         Entry {
-            peers: std::collections::BTreeMap::new(),
+            peers: BTreeMap::new(),
+            peers6: BTreeMap::new(),
             completed: 0,
+            updated: Current::now(),
+            churn: swarm_health::Churn::default(),
+        }
+    }
+
+    /// The list for the IP version of `peer_addr`
+    fn peers_of_family_mut(&mut self, peer_addr: &SocketAddr) -> &mut BTreeMap<peer::Id, peer::Peer> {
+        self.families_mut(peer_addr).0
+    }
+
+    /// The lists for the IP version of `peer_addr` and for the other IP version
+    #[allow(clippy::type_complexity)]
+    fn families_mut(
+        &mut self,
+        peer_addr: &SocketAddr,
+    ) -> (&mut BTreeMap<peer::Id, peer::Peer>, &mut BTreeMap<peer::Id, peer::Peer>) {
+        match peer_addr.ip() {
+            IpAddr::V4(_) => (&mut self.peers, &mut self.peers6),
+            IpAddr::V6(_) => (&mut self.peers6, &mut self.peers),
+        }
+    }
+
+    /// The known peer with the id of `peer`, in the list of its IP version
+    #[must_use]
+    pub fn get_peer(&self, peer: &peer::Peer) -> Option<&peer::Peer> {
+        match peer.peer_addr.ip() {
+            IpAddr::V4(_) => self.peers.get(&peer.peer_id),
+            IpAddr::V6(_) => self.peers6.get(&peer.peer_id),
         }
     }
 
@@ -28,61 +72,228 @@ impl Entry {
     pub fn update_peer(&mut self, peer: &peer::Peer) -> bool {
         let mut did_torrent_stats_change: bool = false;
 
+        // Another client announcing the peer id from another address is ignored. The announce handlers reject
+        // it before with `Tracker::check_peer_id`, this only covers the announces racing for the same peer id.
+        if self
+            .peers_of_family_mut(&peer.peer_addr)
+            .get(&peer.peer_id)
+            .is_some_and(|known_peer| !known_peer.is_announced_by(peer))
+        {
+            return false;
+        }
+
+        self.updated = peer.updated;
+
+        let (peers, other_family_peers) = self.families_mut(&peer.peer_addr);
+
         match peer.event {
+            // A dual-stack peer stops in both IP versions
             AnnounceEvent::Stopped => {
-                let _ = self.peers.remove(&peer.peer_id);
+                let removed = peers.remove(&peer.peer_id).is_some();
+                let removed_from_other_family = other_family_peers
+                    .get(&peer.peer_id)
+                    .is_some_and(|other| other.is_announced_by(peer))
+                    && other_family_peers.remove(&peer.peer_id).is_some();
+                if removed || removed_from_other_family {
+                    self.churn.record(peer.updated, 1);
+                }
             }
             AnnounceEvent::Completed => {
-                let peer_old = self.peers.insert(peer.peer_id, *peer);
+                // A dual-stack peer announcing the completion in both IP versions completed it once
+                let completed_in_other_family = other_family_peers
+                    .get(&peer.peer_id)
+                    .is_some_and(|other| other.event == AnnounceEvent::Completed);
+                let peer_old = peers.insert(peer.peer_id, *peer);
                 // Don't count if peer was not previously known
-                if peer_old.is_some() {
-                    self.completed += 1;
+                if peer_old.is_some() && !completed_in_other_family {
                     did_torrent_stats_change = true;
+                } else if peer_old.is_none() {
+                    self.churn.record(peer.updated, 1);
                 }
             }
             _ => {
-                let _ = self.peers.insert(peer.peer_id, *peer);
+                // A peer announcing its `key` from the other IP version has moved, it is not a dual-stack peer
+                let moved_from_other_family = peer.key.is_some()
+                    && other_family_peers
+                        .get(&peer.peer_id)
+                        .is_some_and(|other| other.key == peer.key)
+                    && other_family_peers.remove(&peer.peer_id).is_some();
+                if peers.insert(peer.peer_id, *peer).is_none() && !moved_from_other_family {
+                    self.churn.record(peer.updated, 1);
+                }
             }
         }
 
+        if did_torrent_stats_change {
+            self.completed += 1;
+        }
+
         did_torrent_stats_change
     }
 
+    /// Adds the `peer` unless it has already announced from the same IP version.
+    pub fn add_peer_if_absent(&mut self, peer: peer::Peer) {
+        self.peers_of_family_mut(&peer.peer_addr).entry(peer.peer_id).or_insert(peer);
+    }
+
+    /// Removes the peer with the `peer_id` from both IP versions, returns whether it was in the swarm.
+    pub fn remove_peer(&mut self, peer_id: &peer::Id) -> bool {
+        let removed = self.peers.remove(peer_id).is_some() | self.peers6.remove(peer_id).is_some();
+        if removed {
+            self.churn.record(Current::now(), 1);
+        }
+        removed
+    }
+
+    /// Removes the peers announced from the `ip`, returns how many were in the swarm.
+    pub fn remove_peers_with_ip(&mut self, ip: &IpAddr) -> usize {
+        let peers_before = self.peers.len() + self.peers6.len();
+
+        self.peers.retain(|_, peer| peer.peer_addr.ip() != *ip);
+        self.peers6.retain(|_, peer| peer.peer_addr.ip() != *ip);
+
+        let removed = peers_before - self.peers.len() - self.peers6.len();
+        if removed > 0 {
+            self.churn.record(Current::now(), u32::try_from(removed).unwrap_or(u32::MAX));
+        }
+        removed
+    }
+
+    /// The peers of both IP versions
+    pub fn all_peers(&self) -> impl Iterator<Item = &peer::Peer> {
+        self.peers.values().chain(self.peers6.values())
+    }
+
+    #[must_use]
+    pub fn has_peers(&self) -> bool {
+        !self.peers.is_empty() || !self.peers6.is_empty()
+    }
+
+    /// Evicts peers chosen by the `policy` until there is room for the announcing `peer`
+    /// without exceeding `max_peers`. Returns the evicted peers.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn make_room_for(&mut self, peer: &peer::Peer, max_peers: usize, policy: eviction::Policy) -> Vec<peer::Peer> {
+        // Known peers are only updated and stopped peers are removed
+        if peer.event == AnnounceEvent::Stopped || self.peers_of_family_mut(&peer.peer_addr).contains_key(&peer.peer_id) {
+            return vec![];
+        }
+
+        let mut evicted = vec![];
+
+        while self.peers.len() + self.peers6.len() >= max_peers {
+            match policy.select(self.all_peers()).copied() {
+                Some(evicted_peer) => {
+                    self.peers_of_family_mut(&evicted_peer.peer_addr)
+                        .remove(&evicted_peer.peer_id);
+                    evicted.push(evicted_peer);
+                }
+                None => break,
+            }
+        }
+
+        self.churn.record(Current::now(), evicted.len() as u32);
+
+        evicted
+    }
+
+    /// Returns the peers of the same IP version as the client first, so they are preferred
+    /// when the list is truncated, followed by the peers of the other IP version.
     #[must_use]
     pub fn get_peers(&self, client_addr: Option<&SocketAddr>) -> Vec<&peer::Peer> {
-        self.peers
-            .values()
-            .filter(|peer| match client_addr {
-                // Don't filter on ip_version
-                None => true,
-                // Filter out different ip_version from remote_addr
-                Some(remote_addr) => {
+        match client_addr {
+            None => self.all_peers().take(MAX_SCRAPE_TORRENTS as usize).collect(),
+            Some(remote_addr) => {
+                let (same_family, other_family) = match remote_addr.ip() {
+                    IpAddr::V4(_) => (&self.peers, &self.peers6),
+                    IpAddr::V6(_) => (&self.peers6, &self.peers),
+                };
+
+                same_family
+                    .values()
+                    .chain(other_family.values())
                     // Skip ip address of client
-                    if peer.peer_addr.ip() == remote_addr.ip() {
-                        return false;
-                    }
-
-                    match peer.peer_addr.ip() {
-                        IpAddr::V4(_) => remote_addr.is_ipv4(),
-                        IpAddr::V6(_) => remote_addr.is_ipv6(),
-                    }
-                }
-            })
-            .take(MAX_SCRAPE_TORRENTS as usize)
-            .collect()
+                    .filter(|peer| peer.peer_addr.ip() != remote_addr.ip())
+                    .take(MAX_SCRAPE_TORRENTS as usize)
+                    .collect()
+            }
+        }
     }
 
+    /// A dual-stack peer is only counted once.
     #[allow(clippy::cast_possible_truncation)]
     #[must_use]
     pub fn get_stats(&self) -> (u32, u32, u32) {
-        let seeders: u32 = self.peers.values().filter(|peer| peer.is_seeder()).count() as u32;
-        let leechers: u32 = self.peers.len() as u32 - seeders;
+        let (mut seeders, mut leechers) = (0u32, 0u32);
+        for peer in self.unique_peers() {
+            if peer.is_seeder() {
+                seeders += 1;
+            } else {
+                leechers += 1;
+            }
+        }
+
         (seeders, self.completed, leechers)
     }
 
-    pub fn remove_inactive_peers(&mut self, max_peer_timeout: u32) {
+    /// The BEP 21 partial seeds, they are also counted as leechers in the stats.
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn get_partial_seeds(&self) -> u32 {
+        self.unique_peers().filter(|peer| peer.is_partial_seed()).count() as u32
+    }
+
+    /// Adds the peers of each country to `countries`, the ones without country are skipped.
+    pub fn count_countries(&self, countries: &mut BTreeMap<geoip::Country, u32>) {
+        for country in self.unique_peers().filter_map(|peer| peer.country) {
+            *countries.entry(country).or_default() += 1;
+        }
+    }
+
+    /// Rough bytes of the torrent in memory with its peers, including the key of the torrents map.
+    #[must_use]
+    pub fn estimated_size(&self) -> usize {
+        let torrent_size = size_of::<InfoHash>() + size_of::<Entry>() + MAP_ENTRY_OVERHEAD;
+        let peer_size = size_of::<peer::Id>() + size_of::<peer::Peer>() + MAP_ENTRY_OVERHEAD;
+
+        torrent_size + (self.peers.len() + self.peers6.len()) * peer_size
+    }
+
+    /// A dual-stack peer is only returned once, as it was in its last announce.
+    fn unique_peers(&self) -> impl Iterator<Item = &peer::Peer> {
+        let latest_peers = self.peers.values().map(|peer| match self.peers6.get(&peer.peer_id) {
+            Some(peer6) if peer6.updated > peer.updated => peer6,
+            _ => peer,
+        });
+
+        latest_peers.chain(self.peers6.values().filter(|peer| !self.peers.contains_key(&peer.peer_id)))
+    }
+
+    /// The swarm health now, see [`swarm_health`].
+    #[must_use]
+    pub fn get_health(&self) -> swarm_health::Health {
+        let now = Current::now();
+        let (seeders, _, leechers) = self.get_stats();
+
+        swarm_health::Health::new(
+            seeders,
+            leechers,
+            self.churn.last_window(now),
+            now.saturating_sub(self.updated).as_secs(),
+        )
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    /// Returns how many peers were removed.
+    pub fn remove_inactive_peers(&mut self, max_peer_timeout: u32) -> usize {
         let current_cutoff = Current::sub(&Duration::from_secs(u64::from(max_peer_timeout))).unwrap_or_default();
+        let peers_before = self.peers.len() + self.peers6.len();
+
         self.peers.retain(|_, peer| peer.updated > current_cutoff);
+        self.peers6.retain(|_, peer| peer.updated > current_cutoff);
+
+        let removed = peers_before - self.peers.len() - self.peers6.len();
+        self.churn.record(Current::now(), removed as u32);
+        removed
     }
 }
 
@@ -107,19 +318,27 @@ pub enum Error {
     NoPeersFound,
     CouldNotSendResponse,
     InvalidInfoHash,
+    // Seconds the client has to wait before announcing again
+    AnnounceRateLimited { retry_in: u64 },
+    UnderMaintenance { retry_in: u64 },
+    ClientNotAllowed,
+    PortNotAllowed { port: u16 },
+    // Another client announced the peer id from another address, with another `key`
+    PeerIdTaken,
 }
 
 #[cfg(test)]
 mod tests {
-    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::collections::BTreeMap;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
     use std::ops::Sub;
     use std::time::Duration;
 
     use aquatic_udp_protocol::{AnnounceEvent, NumberOfBytes};
 
     use crate::protocol::clock::{Current, DurationSinceUnixEpoch, Stopped, StoppedTime, Time, Working};
-    use crate::tracker::peer;
     use crate::tracker::torrent::Entry;
+    use crate::tracker::{eviction, geoip, peer};
 
     struct TorrentPeerBuilder {
         peer: peer::Peer,
@@ -135,6 +354,9 @@ mod tests {
                 downloaded: NumberOfBytes(0),
                 left: NumberOfBytes(0),
                 event: AnnounceEvent::Started,
+                paused: false,
+                key: None,
+                country: None,
             };
             TorrentPeerBuilder { peer: default_peer }
         }
@@ -371,4 +593,279 @@ mod tests {
 
         assert_eq!(torrent_entry.peers.len(), 0);
     }
+
+    #[test]
+    fn the_torrent_entry_health_should_count_the_peers_joining_and_leaving_the_swarm() {
+        let mut torrent_entry = Entry::new();
+
+        let mut torrent_peer = TorrentPeerBuilder::default().into();
+        torrent_entry.update_peer(&torrent_peer); // Joins
+        torrent_entry.update_peer(&torrent_peer); // Only updated
+        torrent_peer.event = AnnounceEvent::Stopped;
+        torrent_entry.update_peer(&torrent_peer); // Leaves
+
+        let health = torrent_entry.get_health();
+
+        assert_eq!(health.churn, 2);
+        assert_eq!((health.seeders, health.leechers, health.score), (0, 0, 0));
+    }
+
+    #[test]
+    fn the_torrent_entry_should_count_the_located_peers_of_each_country() {
+        let mut torrent_entry = Entry::new();
+
+        for (id, country) in [(1, Some(*b"ES")), (2, Some(*b"ES")), (3, Some(*b"US")), (4, None)] {
+            let torrent_peer = peer::Peer {
+                country: country.map(geoip::Country),
+                ..TorrentPeerBuilder::default().with_peer_id(peer::Id([id; 20])).into()
+            };
+            torrent_entry.update_peer(&torrent_peer);
+        }
+
+        let mut countries = BTreeMap::new();
+        torrent_entry.count_countries(&mut countries);
+
+        assert_eq!(
+            countries,
+            BTreeMap::from([(geoip::Country(*b"ES"), 2), (geoip::Country(*b"US"), 1)])
+        );
+    }
+
+    #[test]
+    fn a_full_torrent_entry_should_evict_the_peer_with_the_oldest_announce_to_make_room_for_a_new_one() {
+        let mut torrent_entry = Entry::new();
+
+        let oldest_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer_id_from_i32(1))
+            .updated_at(Duration::from_secs(10))
+            .into();
+        let newest_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer_id_from_i32(2))
+            .updated_at(Duration::from_secs(20))
+            .into();
+        torrent_entry.update_peer(&oldest_peer);
+        torrent_entry.update_peer(&newest_peer);
+
+        let new_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer_id_from_i32(3))
+            .updated_at(Duration::from_secs(30))
+            .into();
+
+        assert_eq!(
+            torrent_entry.make_room_for(&new_peer, 2, eviction::Policy::OldestAnnounceFirst),
+            vec![oldest_peer]
+        );
+        torrent_entry.update_peer(&new_peer);
+
+        assert_eq!(torrent_entry.get_peers(None), vec![&newest_peer, &new_peer]);
+    }
+
+    #[test]
+    fn a_full_torrent_entry_should_not_evict_peers_when_a_known_peer_announces() {
+        let mut torrent_entry = Entry::new();
+
+        let torrent_peer = TorrentPeerBuilder::default().into();
+        torrent_entry.update_peer(&torrent_peer);
+
+        assert!(torrent_entry
+            .make_room_for(&torrent_peer, 1, eviction::Policy::OldestAnnounceFirst)
+            .is_empty());
+        assert_eq!(torrent_entry.peers.len(), 1);
+    }
+
+    #[test]
+    fn a_torrent_entry_should_return_the_peers_of_the_same_ip_version_as_the_client_first() {
+        let mut torrent_entry = Entry::new();
+
+        let ipv4_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer_id_from_i32(1))
+            .with_peer_address(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)), 8080))
+            .into();
+        let ipv6_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer_id_from_i32(2))
+            .with_peer_address(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+                8080,
+            ))
+            .into();
+        torrent_entry.update_peer(&ipv4_peer);
+        torrent_entry.update_peer(&ipv6_peer);
+
+        let ipv4_client = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)), 8080);
+        let ipv6_client = SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)), 8080);
+
+        assert_eq!(torrent_entry.get_peers(Some(&ipv4_client)), vec![&ipv4_peer, &ipv6_peer]);
+        assert_eq!(torrent_entry.get_peers(Some(&ipv6_client)), vec![&ipv6_peer, &ipv4_peer]);
+    }
+
+    #[test]
+    fn torrent_stats_should_count_a_dual_stack_peer_once() {
+        let mut torrent_entry = Entry::new();
+
+        let ipv4_announce = TorrentPeerBuilder::default().into();
+        let ipv6_announce = TorrentPeerBuilder::default()
+            .with_peer_address(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080))
+            .into();
+        torrent_entry.update_peer(&ipv4_announce);
+        torrent_entry.update_peer(&ipv6_announce);
+
+        assert_eq!(torrent_entry.get_peers(None).len(), 2);
+        assert_eq!(torrent_entry.get_stats(), (1, 0, 0));
+    }
+
+    #[test]
+    fn a_dual_stack_peer_should_stop_in_both_ip_versions() {
+        let mut torrent_entry = Entry::new();
+
+        let ipv4_announce = TorrentPeerBuilder::default().into();
+        let ipv6_announce = TorrentPeerBuilder::default()
+            .with_peer_address(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080))
+            .into();
+        torrent_entry.update_peer(&ipv4_announce);
+        torrent_entry.update_peer(&ipv6_announce);
+
+        torrent_entry.update_peer(&peer::Peer {
+            event: AnnounceEvent::Stopped,
+            ..ipv4_announce
+        });
+
+        assert!(!torrent_entry.has_peers());
+    }
+
+    #[test]
+    fn a_peer_announcing_its_key_from_another_ip_address_should_be_updated_with_the_new_address() {
+        let mut torrent_entry = Entry::new();
+
+        let first_announce = peer::Peer {
+            key: Some(1),
+            ..TorrentPeerBuilder::default().into()
+        };
+        let moved_peer = peer::Peer {
+            peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)), 8080),
+            ..first_announce
+        };
+        torrent_entry.update_peer(&first_announce);
+        torrent_entry.update_peer(&moved_peer);
+
+        assert_eq!(torrent_entry.get_peers(None), vec![&moved_peer]);
+    }
+
+    #[test]
+    fn an_announce_of_a_known_peer_id_from_another_ip_address_without_its_key_should_be_ignored() {
+        let mut torrent_entry = Entry::new();
+
+        let peer = peer::Peer {
+            key: Some(1),
+            ..TorrentPeerBuilder::default().into()
+        };
+        torrent_entry.update_peer(&peer);
+
+        torrent_entry.update_peer(&peer::Peer {
+            peer_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)), 8080),
+            event: AnnounceEvent::Stopped,
+            key: Some(2),
+            ..peer
+        });
+
+        assert_eq!(torrent_entry.get_peers(None), vec![&peer]);
+    }
+
+    #[test]
+    fn a_peer_announcing_its_key_from_the_other_ip_version_should_be_moved_to_it() {
+        let mut torrent_entry = Entry::new();
+
+        let ipv4_announce = peer::Peer {
+            key: Some(1),
+            ..TorrentPeerBuilder::default().into()
+        };
+        let ipv6_announce = peer::Peer {
+            peer_addr: SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080),
+            ..ipv4_announce
+        };
+        torrent_entry.update_peer(&ipv4_announce);
+        torrent_entry.update_peer(&ipv6_announce);
+
+        assert!(torrent_entry.peers.is_empty());
+        assert_eq!(torrent_entry.get_peers(None), vec![&ipv6_announce]);
+    }
+
+    #[test]
+    fn a_dual_stack_peer_completing_in_both_ip_versions_should_be_counted_once() {
+        let mut torrent_entry = Entry::new();
+
+        let ipv4_announce = TorrentPeerBuilder::default().with_number_of_bytes_left(1000).into();
+        let ipv6_announce = peer::Peer {
+            peer_addr: SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080),
+            ..ipv4_announce
+        };
+        torrent_entry.update_peer(&ipv4_announce);
+        torrent_entry.update_peer(&ipv6_announce);
+
+        for announce in [ipv4_announce, ipv6_announce] {
+            torrent_entry.update_peer(&peer::Peer {
+                event: AnnounceEvent::Completed,
+                left: NumberOfBytes(0),
+                ..announce
+            });
+        }
+
+        assert_eq!(torrent_entry.get_stats(), (1, 1, 0));
+    }
+
+    #[test]
+    fn torrent_stats_should_count_a_dual_stack_peer_as_in_its_last_announce() {
+        let mut torrent_entry = Entry::new();
+
+        let ipv4_announce = TorrentPeerBuilder::default()
+            .with_number_of_bytes_left(1000)
+            .updated_at(DurationSinceUnixEpoch::from_secs(1))
+            .into();
+        let ipv6_announce = TorrentPeerBuilder::default()
+            .with_peer_address(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080))
+            .with_number_of_bytes_left(0)
+            .updated_at(DurationSinceUnixEpoch::from_secs(2))
+            .into();
+        torrent_entry.update_peer(&ipv4_announce);
+        torrent_entry.update_peer(&ipv6_announce);
+
+        assert_eq!(torrent_entry.get_stats(), (1, 0, 0));
+    }
+
+    #[test]
+    fn torrent_stats_should_count_the_partial_seeds_as_leechers_too() {
+        let mut torrent_entry = Entry::new();
+
+        let partial_seed = peer::Peer {
+            paused: true,
+            ..TorrentPeerBuilder::default().with_number_of_bytes_left(1000).into()
+        };
+        torrent_entry.update_peer(&partial_seed);
+
+        assert_eq!(torrent_entry.get_stats(), (0, 0, 1));
+        assert_eq!(torrent_entry.get_partial_seeds(), 1);
+    }
+
+    #[test]
+    fn the_peers_should_be_removed_by_their_id_or_their_ip() {
+        let mut torrent_entry = Entry::new();
+
+        let banned_ip = IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2));
+        let removed_peer = TorrentPeerBuilder::default().into();
+        let ipv4_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer::Id(*b"-qB00000000000000002"))
+            .with_peer_address(SocketAddr::new(banned_ip, 8080))
+            .into();
+        let ipv6_peer = TorrentPeerBuilder::default()
+            .with_peer_id(peer::Id(*b"-qB00000000000000003"))
+            .with_peer_address(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 8080))
+            .into();
+        for peer in [removed_peer, ipv4_peer, ipv6_peer] {
+            torrent_entry.update_peer(&peer);
+        }
+
+        assert!(torrent_entry.remove_peer(&removed_peer.peer_id));
+        assert!(!torrent_entry.remove_peer(&removed_peer.peer_id));
+        assert_eq!(torrent_entry.remove_peers_with_ip(&banned_ip), 1);
+        assert_eq!(torrent_entry.get_peers(None), vec![&ipv6_peer]);
+    }
 }
@@ -0,0 +1,233 @@
+//! The pages of the torrents listed by the API, filtered and sorted while going through the shards, so only
+//! the torrents up to the end of the page are kept instead of a copy of all of them.
+
+use std::collections::BinaryHeap;
+use std::ops::RangeInclusive;
+
+use serde::Deserialize;
+
+use crate::protocol::info_hash::InfoHash;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    #[default]
+    InfoHash,
+    Seeders,
+    Leechers,
+    Completed,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    pub offset: usize,
+    pub limit: usize,
+    pub sort: Sort,
+    // By default the info hashes are sorted in ascending order, and the counts in descending one
+    pub order: Option<Order>,
+    pub min_seeders: u32,
+    // The hex digits the info hashes start with, case insensitive
+    pub info_hash_prefix: Option<String>,
+}
+
+impl Query {
+    fn descending(&self) -> bool {
+        match self.order {
+            Some(order) => order == Order::Desc,
+            None => self.sort != Sort::InfoHash,
+        }
+    }
+
+    /// Whether the torrent is kept by the filters of the query
+    #[must_use]
+    pub fn matches(&self, info_hash: &InfoHash, (seeders, _completed, _leechers): (u32, u32, u32)) -> bool {
+        seeders >= self.min_seeders
+            && self
+                .info_hash_prefix
+                .as_deref()
+                .is_none_or(|prefix| has_prefix(info_hash, prefix))
+    }
+
+    /// The range of the info hashes starting with the prefix, so the torrents are looked up in the sorted
+    /// shards instead of all of them being filtered. The full range if the prefix is not valid hex.
+    #[must_use]
+    pub fn info_hash_range(&self) -> RangeInclusive<InfoHash> {
+        let (mut lowest, mut highest) = ([0x00; 20], [0xff; 20]);
+
+        if let Some(prefix) = self.info_hash_prefix.as_deref().filter(|prefix| prefix.len() <= 40) {
+            if let Some(nibbles) = prefix.chars().map(|digit| digit.to_digit(16)).collect::<Option<Vec<_>>>() {
+                for (i, nibble) in nibbles.into_iter().enumerate() {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let nibble = nibble as u8;
+                    let (shift, mask) = if i % 2 == 0 { (4, 0x0f) } else { (0, 0xf0) };
+                    lowest[i / 2] = (lowest[i / 2] & mask) | (nibble << shift);
+                    highest[i / 2] = (highest[i / 2] & mask) | (nibble << shift);
+                }
+            }
+        }
+
+        InfoHash(lowest)..=InfoHash(highest)
+    }
+}
+
+/// A torrent of a page: the count and the bytes of the info-hash it is sorted by, then the torrent and its stats
+type Entry = (u32, [u8; 20], InfoHash, (u32, u32, u32));
+
+/// The torrents of a page, as they are pushed in any order
+pub struct Page<'a> {
+    query: &'a Query,
+    // A max-heap of the first `offset + limit` torrents, by a key sorting them in ascending order
+    torrents: BinaryHeap<Entry>,
+}
+
+impl<'a> Page<'a> {
+    #[must_use]
+    pub fn new(query: &'a Query) -> Self {
+        Self {
+            query,
+            torrents: BinaryHeap::new(),
+        }
+    }
+
+    pub fn push(&mut self, info_hash: InfoHash, stats: (u32, u32, u32)) {
+        if !self.query.matches(&info_hash, stats) {
+            return;
+        }
+
+        let (seeders, completed, leechers) = stats;
+        let count = match self.query.sort {
+            Sort::InfoHash => 0,
+            Sort::Seeders => seeders,
+            Sort::Leechers => leechers,
+            Sort::Completed => completed,
+        };
+        let (count, bytes) = if self.query.descending() {
+            (u32::MAX - count, info_hash.0.map(|byte| !byte))
+        } else {
+            (count, info_hash.0)
+        };
+
+        self.torrents.push((count, bytes, info_hash, stats));
+        if self.torrents.len() > self.query.offset.saturating_add(self.query.limit) {
+            self.torrents.pop();
+        }
+    }
+
+    /// The `(seeders, completed, leechers)` stats of the torrents of the page, in its order
+    #[must_use]
+    pub fn into_torrents(self) -> Vec<(InfoHash, (u32, u32, u32))> {
+        self.torrents
+            .into_sorted_vec()
+            .into_iter()
+            .skip(self.query.offset)
+            .map(|(_, _, info_hash, stats)| (info_hash, stats))
+            .collect()
+    }
+}
+
+/// Whether the hex representation of the `info_hash` starts with the hex digits of the `prefix`
+fn has_prefix(info_hash: &InfoHash, prefix: &str) -> bool {
+    prefix.len() <= 40
+        && prefix.chars().enumerate().all(|(i, digit)| {
+            let byte = info_hash.0[i / 2];
+            let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0x0f };
+            digit.to_digit(16) == Some(u32::from(nibble))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Order, Page, Query, Sort};
+    use crate::protocol::info_hash::InfoHash;
+
+    fn page(query: &Query) -> Vec<(InfoHash, (u32, u32, u32))> {
+        let mut page = Page::new(query);
+        for (i, stats) in [(1, 0, 4), (3, 2, 0), (2, 9, 1), (0, 1, 7)].into_iter().enumerate() {
+            page.push(InfoHash([u8::try_from(i).unwrap() * 0x11; 20]), stats);
+        }
+        page.into_torrents()
+    }
+
+    fn seeders(torrents: &[(InfoHash, (u32, u32, u32))]) -> Vec<u32> {
+        torrents.iter().map(|(_, (seeders, _, _))| *seeders).collect()
+    }
+
+    #[test]
+    fn the_torrents_should_be_sorted_by_info_hash_unless_sorted_by_a_count() {
+        let query = Query {
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(seeders(&page(&query)), [1, 3, 2, 0]);
+
+        let query = Query {
+            limit: 10,
+            sort: Sort::Seeders,
+            ..Default::default()
+        };
+        assert_eq!(seeders(&page(&query)), [3, 2, 1, 0]);
+
+        let query = Query {
+            limit: 10,
+            sort: Sort::Completed,
+            order: Some(Order::Asc),
+            ..Default::default()
+        };
+        assert_eq!(seeders(&page(&query)), [1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn only_the_filtered_torrents_of_the_page_should_be_returned() {
+        let query = Query {
+            offset: 1,
+            limit: 2,
+            sort: Sort::Leechers,
+            ..Default::default()
+        };
+        assert_eq!(seeders(&page(&query)), [1, 2]);
+
+        let query = Query {
+            limit: 10,
+            min_seeders: 2,
+            ..Default::default()
+        };
+        assert_eq!(seeders(&page(&query)), [3, 2]);
+
+        let query = Query {
+            limit: 10,
+            info_hash_prefix: Some(String::from("2")),
+            ..Default::default()
+        };
+        assert_eq!(seeders(&page(&query)), [2]);
+    }
+
+    #[test]
+    fn the_info_hash_range_should_hold_the_info_hashes_starting_with_the_prefix() {
+        let query = Query {
+            info_hash_prefix: Some(String::from("aB1")),
+            ..Default::default()
+        };
+        let range = query.info_hash_range();
+
+        let mut first = [0x00; 20];
+        first[0] = 0xab;
+        first[1] = 0x10;
+        let mut last = [0xff; 20];
+        last[0] = 0xab;
+        last[1] = 0x1f;
+        assert_eq!(range, InfoHash(first)..=InfoHash(last));
+
+        let query = Query {
+            info_hash_prefix: Some(String::from("xyz")),
+            ..Default::default()
+        };
+        assert_eq!(query.info_hash_range(), InfoHash([0x00; 20])..=InfoHash([0xff; 20]));
+    }
+}
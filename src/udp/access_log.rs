@@ -0,0 +1,143 @@
+//! A sample of the UDP requests, one line each with the client IP, the action, the info hash
+//! and the result, written with the `udp_access` log target at the `info` level.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use aquatic_udp_protocol::Response;
+use log::info;
+
+use crate::protocol::info_hash::InfoHash;
+use crate::udp::request::{self, UdpRequest};
+
+#[derive(Debug)]
+pub struct AccessLog {
+    sample_rate: u64,
+    requests: AtomicU64,
+}
+
+impl AccessLog {
+    /// Logs one in every `sample_rate` requests, `None` if it is zero.
+    #[must_use]
+    pub fn new(sample_rate: u32) -> Option<Self> {
+        (sample_rate > 0).then(|| Self {
+            sample_rate: u64::from(sample_rate),
+            requests: AtomicU64::new(0),
+        })
+    }
+
+    /// The entry of the request in the `payload` if it is sampled, to be logged with its response.
+    #[must_use]
+    pub fn sample(&self, remote_addr: &SocketAddr, payload: &[u8]) -> Option<Entry> {
+        if !self.requests.fetch_add(1, Ordering::Relaxed).is_multiple_of(self.sample_rate) {
+            return None;
+        }
+
+        let (action, info_hash) = match request::parse(payload) {
+            Ok(UdpRequest::Connect(_)) => ("connect", None),
+            Ok(UdpRequest::Announce(wrapped_announce_request)) => ("announce", Some(wrapped_announce_request.info_hash)),
+            Ok(UdpRequest::Scrape(scrape_request)) => (
+                "scrape",
+                scrape_request.info_hashes.first().map(|info_hash| InfoHash(info_hash.0)),
+            ),
+            Err(_) => ("malformed", None),
+        };
+
+        Some(Entry {
+            remote_addr: *remote_addr,
+            action,
+            info_hash,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub remote_addr: SocketAddr,
+    pub action: &'static str,
+    // The first one of a scrape
+    pub info_hash: Option<InfoHash>,
+}
+
+impl Entry {
+    pub fn log(&self, response: &Response) {
+        info!(target: "udp_access", "{}", self.line(response));
+    }
+
+    fn line(&self, response: &Response) -> String {
+        let info_hash = self.info_hash.map(|info_hash| info_hash.to_string()).unwrap_or_default();
+        let result = match response {
+            Response::Error(error_response) => format!("\"{}\"", error_response.message),
+            _ => String::from("ok"),
+        };
+
+        format!(
+            "ip={} action={} info_hash={} result={}",
+            self.remote_addr.ip(),
+            self.action,
+            info_hash,
+            result
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use aquatic_udp_protocol::{ConnectRequest, ConnectResponse, ConnectionId, ErrorResponse, Request, Response, TransactionId};
+
+    use super::AccessLog;
+
+    fn connect_packet() -> Vec<u8> {
+        let mut payload = vec![];
+        Request::Connect(ConnectRequest {
+            transaction_id: TransactionId(0),
+        })
+        .write(&mut payload)
+        .unwrap();
+        payload
+    }
+
+    fn remote_addr() -> SocketAddr {
+        "126.0.0.1:6881".parse().unwrap()
+    }
+
+    #[test]
+    fn it_should_be_disabled_with_a_zero_sample_rate() {
+        assert!(AccessLog::new(0).is_none());
+    }
+
+    #[test]
+    fn it_should_sample_one_in_every_sample_rate_requests() {
+        let access_log = AccessLog::new(3).unwrap();
+
+        let sampled = (0..9)
+            .filter(|_| access_log.sample(&remote_addr(), &connect_packet()).is_some())
+            .count();
+
+        assert_eq!(sampled, 3);
+    }
+
+    #[test]
+    fn it_should_log_the_ip_the_action_and_the_result_of_the_request() {
+        let access_log = AccessLog::new(1).unwrap();
+
+        let entry = access_log.sample(&remote_addr(), &connect_packet()).unwrap();
+        let connected = Response::Connect(ConnectResponse {
+            connection_id: ConnectionId(0),
+            transaction_id: TransactionId(0),
+        });
+        assert_eq!(entry.line(&connected), "ip=126.0.0.1 action=connect info_hash= result=ok");
+
+        let entry = access_log.sample(&remote_addr(), &[0u8; 4]).unwrap();
+        let error = Response::Error(ErrorResponse {
+            transaction_id: TransactionId(0),
+            message: "bad request: truncated packet".into(),
+        });
+        assert_eq!(
+            entry.line(&error),
+            "ip=126.0.0.1 action=malformed info_hash= result=\"bad request: truncated packet\""
+        );
+    }
+}
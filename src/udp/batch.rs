@@ -0,0 +1,197 @@
+//! Receives and sends the datagrams of the UDP tracker in batches with `recvmmsg` and `sendmmsg`,
+//! so a single system call moves up to [`BATCH_SIZE`] packets instead of one.
+//!
+//! Only available on Linux with the `batched-udp` feature.
+
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+
+use socket2::SockAddr;
+use tokio::io::Interest;
+use tokio::net::UdpSocket;
+
+use crate::udp::MAX_PACKET_SIZE;
+
+pub const BATCH_SIZE: usize = 32;
+
+/// Waits until the socket is readable and receives all the datagrams queued in it, up to [`BATCH_SIZE`].
+///
+/// # Errors
+///
+/// Will return `Err` if the `recvmmsg` call fails.
+pub async fn recv_batch(socket: &UdpSocket) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+    loop {
+        socket.readable().await?;
+
+        match socket.try_io(Interest::READABLE, || recvmmsg(socket.as_raw_fd())) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            result => return result,
+        }
+    }
+}
+
+/// Sends the `packets` with as few `sendmmsg` calls as possible. A packet that can not be sent
+/// is skipped, as with a single `send_to` it does not matter if it reaches or not.
+///
+/// # Errors
+///
+/// Will return `Err` if the socket fails waiting to be writable.
+pub async fn send_batch(socket: &UdpSocket, packets: &[(SocketAddr, Vec<u8>)]) -> io::Result<()> {
+    let mut sent = 0;
+
+    while sent < packets.len() {
+        socket.writable().await?;
+
+        match socket.try_io(Interest::WRITABLE, || sendmmsg(socket.as_raw_fd(), &packets[sent..])) {
+            Ok(count) => sent += count,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(_) => sent += 1,
+        }
+    }
+
+    Ok(())
+}
+
+fn recvmmsg(fd: RawFd) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+    let mut buffers = vec![[0u8; MAX_PACKET_SIZE]; BATCH_SIZE];
+    // SAFETY: an all-zero `sockaddr_storage` is a valid (unspecified) address.
+    let mut addresses: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; BATCH_SIZE];
+    let mut iovecs: Vec<libc::iovec> = buffers
+        .iter_mut()
+        .map(|buffer| libc::iovec {
+            iov_base: buffer.as_mut_ptr().cast(),
+            iov_len: buffer.len(),
+        })
+        .collect();
+    let mut messages: Vec<libc::mmsghdr> = iovecs
+        .iter_mut()
+        .zip(addresses.iter_mut())
+        .map(|(iovec, address)| {
+            // SAFETY: an all-zero `mmsghdr` is valid, the fields used by `recvmmsg` are set below.
+            let mut message: libc::mmsghdr = unsafe { mem::zeroed() };
+            message.msg_hdr.msg_name = (address as *mut libc::sockaddr_storage).cast();
+            #[allow(clippy::cast_possible_truncation)]
+            let address_len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            message.msg_hdr.msg_namelen = address_len;
+            message.msg_hdr.msg_iov = iovec;
+            message.msg_hdr.msg_iovlen = 1;
+            message
+        })
+        .collect();
+
+    // SAFETY: every message points to a buffer and an address that outlive the call.
+    #[allow(clippy::cast_possible_truncation)]
+    let received = unsafe {
+        libc::recvmmsg(
+            fd,
+            messages.as_mut_ptr(),
+            messages.len() as libc::c_uint,
+            libc::MSG_DONTWAIT,
+            ptr::null_mut(),
+        )
+    };
+
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    let received = received as usize;
+
+    Ok(messages
+        .iter()
+        .zip(buffers.iter())
+        .zip(addresses.iter())
+        .take(received)
+        .filter_map(|((message, buffer), address)| {
+            // SAFETY: `recvmmsg` wrote an address of `msg_namelen` bytes.
+            let remote_addr = unsafe { SockAddr::new(*address, message.msg_hdr.msg_namelen) }.as_socket()?;
+            let valid_bytes = (message.msg_len as usize).min(MAX_PACKET_SIZE);
+
+            Some((buffer[..valid_bytes].to_vec(), remote_addr))
+        })
+        .collect())
+}
+
+fn sendmmsg(fd: RawFd, packets: &[(SocketAddr, Vec<u8>)]) -> io::Result<usize> {
+    let packets = &packets[..packets.len().min(BATCH_SIZE)];
+
+    let addresses: Vec<SockAddr> = packets.iter().map(|(remote_addr, _)| SockAddr::from(*remote_addr)).collect();
+    let mut iovecs: Vec<libc::iovec> = packets
+        .iter()
+        .map(|(_, payload)| libc::iovec {
+            iov_base: payload.as_ptr() as *mut libc::c_void,
+            iov_len: payload.len(),
+        })
+        .collect();
+    let mut messages: Vec<libc::mmsghdr> = iovecs
+        .iter_mut()
+        .zip(addresses.iter())
+        .map(|(iovec, address)| {
+            // SAFETY: an all-zero `mmsghdr` is valid, the fields used by `sendmmsg` are set below.
+            let mut message: libc::mmsghdr = unsafe { mem::zeroed() };
+            message.msg_hdr.msg_name = address.as_ptr() as *mut libc::c_void;
+            message.msg_hdr.msg_namelen = address.len();
+            message.msg_hdr.msg_iov = iovec;
+            message.msg_hdr.msg_iovlen = 1;
+            message
+        })
+        .collect();
+
+    // SAFETY: every message points to a payload and an address that outlive the call,
+    // and `sendmmsg` does not write to them.
+    #[allow(clippy::cast_possible_truncation)]
+    let sent = unsafe { libc::sendmmsg(fd, messages.as_mut_ptr(), messages.len() as libc::c_uint, libc::MSG_DONTWAIT) };
+
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    Ok(sent as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UdpSocket;
+
+    use super::{recv_batch, send_batch};
+
+    async fn bound_socket() -> UdpSocket {
+        UdpSocket::bind("127.0.0.1:0").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_should_send_and_receive_several_datagrams_in_a_batch() {
+        let server = bound_socket().await;
+        let client = bound_socket().await;
+
+        let server_addr = server.local_addr().unwrap();
+        let client_addr = client.local_addr().unwrap();
+
+        let packets = vec![
+            (server_addr, vec![1u8]),
+            (server_addr, vec![2u8, 2]),
+            (server_addr, vec![3u8, 3, 3]),
+        ];
+
+        send_batch(&client, &packets).await.unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < packets.len() {
+            received.extend(recv_batch(&server).await.unwrap());
+        }
+
+        assert_eq!(
+            received,
+            vec![
+                (vec![1u8], client_addr),
+                (vec![2u8, 2], client_addr),
+                (vec![3u8, 3, 3], client_addr)
+            ]
+        );
+    }
+}
@@ -1,9 +1,24 @@
+//! Connection ids derived from an HMAC of the client address, the time extent and a secret,
+//! so the UDP tracker can verify them without keeping state, as described in BEP 15.
+//!
+//! A connection id expires after two minutes, and the secret is replaced every
+//! `udp_connection_id_secret_rotation_interval` seconds, keeping the previous one to verify
+//! the ids already given.
+
+use std::iter;
 use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::time::Duration;
 
 use aquatic_udp_protocol::ConnectionId;
+use rand::rngs::ThreadRng;
+use rand::Rng;
 
 use super::error::Error;
+use crate::ephemeral_instance_keys::Seed;
 use crate::protocol::clock::time_extent::{Extent, TimeExtent};
+use crate::protocol::clock::{self, DurationSinceUnixEpoch, Time};
+use crate::protocol::crypto::keys::seeds::{Current, Keeper};
 
 pub type Cookie = [u8; 8];
 
@@ -11,6 +26,35 @@ pub type SinceUnixEpochTimeExtent = TimeExtent;
 
 pub const COOKIE_LIFETIME: TimeExtent = TimeExtent::from_sec(2, &60);
 
+lazy_static! {
+    static ref SECRETS: RwLock<Secrets> = RwLock::new(Secrets::new(*Current::get_seed(), clock::Current::now()));
+}
+
+struct Secrets {
+    current: Seed,
+    previous: Option<Seed>,
+    rotated_at: DurationSinceUnixEpoch,
+}
+
+impl Secrets {
+    fn new(secret: Seed, now: DurationSinceUnixEpoch) -> Self {
+        Self {
+            current: secret,
+            previous: None,
+            rotated_at: now,
+        }
+    }
+
+    fn rotate(&mut self, secret: Seed, now: DurationSinceUnixEpoch) {
+        self.previous = Some(std::mem::replace(&mut self.current, secret));
+        self.rotated_at = now;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Seed> {
+        iter::once(&self.current).chain(self.previous.iter())
+    }
+}
+
 #[must_use]
 pub fn from_connection_id(connection_id: &ConnectionId) -> Cookie {
     connection_id.0.to_le_bytes()
@@ -21,45 +65,85 @@ pub fn into_connection_id(connection_cookie: &Cookie) -> ConnectionId {
     ConnectionId(i64::from_le_bytes(*connection_cookie))
 }
 
+/// # Panics
+///
+/// It would panic if the lock of the secrets is poisoned.
 #[must_use]
 pub fn make(remote_address: &SocketAddr) -> Cookie {
     let time_extent = cookie_builder::get_last_time_extent();
 
-    //println!("remote_address: {remote_address:?}, time_extent: {time_extent:?}, cookie: {cookie:?}");
-    cookie_builder::build(remote_address, &time_extent)
+    cookie_builder::build(remote_address, &time_extent, &SECRETS.read().unwrap().current)
 }
 
 /// # Panics
 ///
-/// It would panic if the `COOKIE_LIFETIME` constant would be an unreasonably large number.
+/// It would panic if the `COOKIE_LIFETIME` constant would be an unreasonably large number,
+/// or if the lock of the secrets is poisoned.
 ///
 /// # Errors
 ///
 /// Will return a `ServerError::InvalidConnectionId` if the supplied `connection_cookie` fails to verify.
 pub fn check(remote_address: &SocketAddr, connection_cookie: &Cookie) -> Result<SinceUnixEpochTimeExtent, Error> {
+    check_with_secrets(&SECRETS.read().unwrap(), remote_address, connection_cookie)
+}
+
+/// The time between two rotations of the secret, at least as long as a connection id can be
+/// valid, so only the previous secret is needed to verify the unexpired ids.
+///
+/// # Panics
+///
+/// It would panic if the `COOKIE_LIFETIME` constant would be an unreasonably large number.
+#[must_use]
+pub fn secret_rotation_interval(seconds: u64) -> Duration {
+    Duration::from_secs(seconds).max(COOKIE_LIFETIME.total_next().unwrap().unwrap())
+}
+
+/// Replaces the secret when it is older than the `interval`, so it can be called by every
+/// UDP server without rotating it more often.
+///
+/// # Panics
+///
+/// It would panic if the lock of the secrets is poisoned.
+pub fn rotate_secret(interval: Duration) {
+    let now = clock::Current::now();
+    let mut secrets = SECRETS.write().unwrap();
+
+    if now.saturating_sub(secrets.rotated_at) >= interval {
+        secrets.rotate(Rng::gen(&mut ThreadRng::default()), now);
+    }
+}
+
+fn check_with_secrets(
+    secrets: &Secrets,
+    remote_address: &SocketAddr,
+    connection_cookie: &Cookie,
+) -> Result<SinceUnixEpochTimeExtent, Error> {
     // we loop backwards testing each time_extent until we find one that matches.
     // (or the lifetime of time_extents is exhausted)
     for offset in 0..=COOKIE_LIFETIME.amount {
         let checking_time_extent = cookie_builder::get_last_time_extent().decrease(offset).unwrap();
 
-        let checking_cookie = cookie_builder::build(remote_address, &checking_time_extent);
-        //println!("remote_address: {remote_address:?}, time_extent: {checking_time_extent:?}, cookie: {checking_cookie:?}");
+        for secret in secrets.iter() {
+            let checking_cookie = cookie_builder::build(remote_address, &checking_time_extent, secret);
 
-        if *connection_cookie == checking_cookie {
-            return Ok(checking_time_extent);
+            if openssl::memcmp::eq(connection_cookie, &checking_cookie) {
+                return Ok(checking_time_extent);
+            }
         }
     }
     Err(Error::InvalidConnectionId)
 }
 
 mod cookie_builder {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    use std::net::SocketAddr;
+    use std::net::{IpAddr, SocketAddr};
+
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
 
     use super::{Cookie, SinceUnixEpochTimeExtent, COOKIE_LIFETIME};
+    use crate::ephemeral_instance_keys::Seed;
     use crate::protocol::clock::time_extent::{DefaultTimeExtentMaker, Extent, Make, TimeExtent};
-    use crate::protocol::crypto::keys::seeds::{Current, Keeper};
 
     pub(super) fn get_last_time_extent() -> SinceUnixEpochTimeExtent {
         DefaultTimeExtentMaker::now(&COOKIE_LIFETIME.increment)
@@ -69,35 +153,51 @@ mod cookie_builder {
             .unwrap()
     }
 
-    pub(super) fn build(remote_address: &SocketAddr, time_extent: &TimeExtent) -> Cookie {
-        let seed = Current::get_seed();
-
-        let mut hasher = DefaultHasher::new();
+    /// The first bytes of the HMAC-SHA256 of the remote address and the time extent.
+    ///
+    /// # Panics
+    ///
+    /// It would panic if `OpenSSL` fails to compute the HMAC.
+    pub(super) fn build(remote_address: &SocketAddr, time_extent: &TimeExtent, secret: &Seed) -> Cookie {
+        let key = PKey::hmac(secret).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+
+        match remote_address.ip() {
+            IpAddr::V4(ip) => signer.update(&ip.octets()).unwrap(),
+            IpAddr::V6(ip) => signer.update(&ip.octets()).unwrap(),
+        }
+        signer.update(&remote_address.port().to_be_bytes()).unwrap();
+        signer.update(&time_extent.increment.as_nanos().to_be_bytes()).unwrap();
+        signer.update(&time_extent.amount.to_be_bytes()).unwrap();
 
-        remote_address.hash(&mut hasher);
-        time_extent.hash(&mut hasher);
-        seed.hash(&mut hasher);
+        let hmac = signer.sign_to_vec().unwrap();
 
-        hasher.finish().to_le_bytes()
+        let mut cookie = Cookie::default();
+        let length = cookie.len();
+        cookie.copy_from_slice(&hmac[..length]);
+        cookie
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::time::Duration;
 
     use super::cookie_builder::{self};
     use crate::protocol::clock::time_extent::{self, Extent};
     use crate::protocol::clock::{Stopped, StoppedTime};
-    use crate::udp::connection_cookie::{check, make, Cookie, COOKIE_LIFETIME};
+    use crate::protocol::crypto::keys::seeds::{Current, Keeper};
+    use crate::udp::connection_cookie::{
+        check, check_with_secrets, make, secret_rotation_interval, Cookie, Secrets, COOKIE_LIFETIME,
+    };
 
     // #![feature(const_socketaddr)]
     // const REMOTE_ADDRESS_IPV4_ZERO: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
 
     #[test]
     fn it_should_make_a_connection_cookie() {
-        // Note: This constant may need to be updated in the future as the hash is not guaranteed to to be stable between versions.
-        const ID_COOKIE: Cookie = [23, 204, 198, 29, 48, 180, 62, 19];
+        const ID_COOKIE: Cookie = [225, 128, 196, 32, 239, 95, 97, 161];
 
         let cookie = make(&SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
 
@@ -109,15 +209,12 @@ mod tests {
         let remote_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
         let time_extent_zero = time_extent::ZERO;
 
-        let cookie = cookie_builder::build(&remote_address, &time_extent_zero);
-        let cookie_2 = cookie_builder::build(&remote_address, &time_extent_zero);
+        let cookie = cookie_builder::build(&remote_address, &time_extent_zero, Current::get_seed());
+        let cookie_2 = cookie_builder::build(&remote_address, &time_extent_zero, Current::get_seed());
 
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_zero:?}, cookie: {cookie:?}");
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_zero:?}, cookie: {cookie_2:?}");
 
-        //remote_address: 127.0.0.1:8080, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [212, 9, 204, 223, 176, 190, 150, 153]
-        //remote_address: 127.0.0.1:8080, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [212, 9, 204, 223, 176, 190, 150, 153]
-
         assert_eq!(cookie, cookie_2);
     }
 
@@ -127,15 +224,12 @@ mod tests {
         let remote_address_2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 0);
         let time_extent_zero = time_extent::ZERO;
 
-        let cookie = cookie_builder::build(&remote_address, &time_extent_zero);
-        let cookie_2 = cookie_builder::build(&remote_address_2, &time_extent_zero);
+        let cookie = cookie_builder::build(&remote_address, &time_extent_zero, Current::get_seed());
+        let cookie_2 = cookie_builder::build(&remote_address_2, &time_extent_zero, Current::get_seed());
 
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_zero:?}, cookie: {cookie:?}");
         println!("remote_address: {remote_address_2:?}, time_extent: {time_extent_zero:?}, cookie: {cookie_2:?}");
 
-        //remote_address: 0.0.0.0:0, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [151, 130, 30, 157, 190, 41, 179, 135]
-        //remote_address: 255.255.255.255:0, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [217, 87, 239, 178, 182, 126, 66, 166]
-
         assert_ne!(cookie, cookie_2);
     }
 
@@ -145,15 +239,12 @@ mod tests {
         let remote_address_2 = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
         let time_extent_zero = time_extent::ZERO;
 
-        let cookie = cookie_builder::build(&remote_address, &time_extent_zero);
-        let cookie_2 = cookie_builder::build(&remote_address_2, &time_extent_zero);
+        let cookie = cookie_builder::build(&remote_address, &time_extent_zero, Current::get_seed());
+        let cookie_2 = cookie_builder::build(&remote_address_2, &time_extent_zero, Current::get_seed());
 
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_zero:?}, cookie: {cookie:?}");
         println!("remote_address: {remote_address_2:?}, time_extent: {time_extent_zero:?}, cookie: {cookie_2:?}");
 
-        //remote_address: 0.0.0.0:0, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [151, 130, 30, 157, 190, 41, 179, 135]
-        //remote_address: [::]:0, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [99, 119, 230, 177, 20, 220, 163, 187]
-
         assert_ne!(cookie, cookie_2);
     }
 
@@ -163,15 +254,12 @@ mod tests {
         let remote_address_2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1);
         let time_extent_zero = time_extent::ZERO;
 
-        let cookie = cookie_builder::build(&remote_address, &time_extent_zero);
-        let cookie_2 = cookie_builder::build(&remote_address_2, &time_extent_zero);
+        let cookie = cookie_builder::build(&remote_address, &time_extent_zero, Current::get_seed());
+        let cookie_2 = cookie_builder::build(&remote_address_2, &time_extent_zero, Current::get_seed());
 
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_zero:?}, cookie: {cookie:?}");
         println!("remote_address: {remote_address_2:?}, time_extent: {time_extent_zero:?}, cookie: {cookie_2:?}");
 
-        //remote_address: 0.0.0.0:0, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [151, 130, 30, 157, 190, 41, 179, 135]
-        //remote_address: 0.0.0.0:1, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [38, 8, 0, 102, 92, 170, 220, 11]
-
         assert_ne!(cookie, cookie_2);
     }
 
@@ -181,18 +269,46 @@ mod tests {
         let time_extent_zero = time_extent::ZERO;
         let time_extent_max = time_extent::MAX;
 
-        let cookie = cookie_builder::build(&remote_address, &time_extent_zero);
-        let cookie_2 = cookie_builder::build(&remote_address, &time_extent_max);
+        let cookie = cookie_builder::build(&remote_address, &time_extent_zero, Current::get_seed());
+        let cookie_2 = cookie_builder::build(&remote_address, &time_extent_max, Current::get_seed());
 
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_zero:?}, cookie: {cookie:?}");
         println!("remote_address: {remote_address:?}, time_extent: {time_extent_max:?}, cookie: {cookie_2:?}");
 
-        //remote_address: 0.0.0.0:0, time_extent: TimeExtent { increment: 0ns, amount: 0 }, cookie: [151, 130, 30, 157, 190, 41, 179, 135]
-        //remote_address: 0.0.0.0:0, time_extent: TimeExtent { increment: 18446744073709551615.999999999s, amount: 18446744073709551615 }, cookie: [87, 111, 109, 125, 182, 206, 3, 201]
+        assert_ne!(cookie, cookie_2);
+    }
+
+    #[test]
+    fn it_should_make_the_different_connection_cookie_for_different_secrets() {
+        let remote_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        let time_extent_zero = time_extent::ZERO;
+
+        let cookie = cookie_builder::build(&remote_address, &time_extent_zero, &[0u8; 32]);
+        let cookie_2 = cookie_builder::build(&remote_address, &time_extent_zero, &[1u8; 32]);
 
         assert_ne!(cookie, cookie_2);
     }
 
+    #[test]
+    fn it_should_be_valid_after_rotating_the_secret_once_but_not_twice() {
+        let remote_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        let mut secrets = Secrets::new([0u8; 32], Duration::ZERO);
+
+        let cookie = cookie_builder::build(&remote_address, &cookie_builder::get_last_time_extent(), &secrets.current);
+
+        secrets.rotate([1u8; 32], Duration::ZERO);
+        check_with_secrets(&secrets, &remote_address, &cookie).unwrap();
+
+        secrets.rotate([2u8; 32], Duration::ZERO);
+        assert!(check_with_secrets(&secrets, &remote_address, &cookie).is_err());
+    }
+
+    #[test]
+    fn it_should_not_rotate_the_secret_before_the_connection_ids_expire() {
+        assert_eq!(secret_rotation_interval(600), Duration::from_secs(600));
+        assert_eq!(secret_rotation_interval(0), COOKIE_LIFETIME.total_next().unwrap().unwrap());
+    }
+
     #[test]
     fn it_should_make_different_cookies_for_the_next_time_extent() {
         let remote_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
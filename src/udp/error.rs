@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::tracker::torrent;
+use crate::udp::request::ParseError;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -22,6 +23,9 @@ pub enum Error {
     #[error("torrent not on whitelist")]
     TorrentNotWhitelisted,
 
+    #[error("peer ip is blacklisted")]
+    PeerBlacklisted,
+
     #[error("peer not authenticated")]
     PeerNotAuthenticated,
 
@@ -31,8 +35,26 @@ pub enum Error {
     #[error("exceeded info_hash limit")]
     ExceededInfoHashLimit,
 
-    #[error("bad request")]
-    BadRequest,
+    #[error("scrape is disabled")]
+    ScrapeDisabled,
+
+    #[error("bad request: {0}")]
+    MalformedRequest(ParseError),
+
+    #[error("announcing too often, retry in {retry_in} seconds")]
+    AnnounceRateLimited { retry_in: u64 },
+
+    #[error("tracker under maintenance, retry in {retry_in} seconds")]
+    UnderMaintenance { retry_in: u64 },
+
+    #[error("client not allowed")]
+    ClientNotAllowed,
+
+    #[error("announced port {port} is not allowed")]
+    PortNotAllowed { port: u16 },
+
+    #[error("peer id announced by another client")]
+    PeerIdTaken,
 }
 
 impl From<torrent::Error> for Error {
@@ -44,6 +66,20 @@ impl From<torrent::Error> for Error {
             torrent::Error::NoPeersFound => Error::NoPeersFound,
             torrent::Error::CouldNotSendResponse => Error::InternalServer,
             torrent::Error::InvalidInfoHash => Error::InvalidInfoHash,
+            torrent::Error::AnnounceRateLimited { retry_in } => Error::AnnounceRateLimited { retry_in },
+            torrent::Error::UnderMaintenance { retry_in } => Error::UnderMaintenance { retry_in },
+            torrent::Error::ClientNotAllowed => Error::ClientNotAllowed,
+            torrent::Error::PortNotAllowed { port } => Error::PortNotAllowed { port },
+            torrent::Error::PeerIdTaken => Error::PeerIdTaken,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::ExceededInfoHashLimit => Error::ExceededInfoHashLimit,
+            e => Error::MalformedRequest(e),
         }
     }
 }
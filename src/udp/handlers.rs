@@ -1,49 +1,62 @@
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Instant;
 
 use aquatic_udp_protocol::{
     AnnounceInterval, AnnounceRequest, AnnounceResponse, ConnectRequest, ConnectResponse, ErrorResponse, NumberOfDownloads,
-    NumberOfPeers, Port, Request, Response, ResponsePeer, ScrapeRequest, ScrapeResponse, TorrentScrapeStatistics, TransactionId,
+    NumberOfPeers, Port, Response, ResponsePeer, ScrapeRequest, ScrapeResponse, TorrentScrapeStatistics, TransactionId,
 };
 
 use super::connection_cookie::{check, from_connection_id, into_connection_id, make};
-use crate::protocol::common::MAX_SCRAPE_TORRENTS;
 use crate::protocol::info_hash::InfoHash;
-use crate::tracker::{self, peer, statistics};
+use crate::tracker::{self, events, peer, statistics};
 use crate::udp::error::Error;
-use crate::udp::request::AnnounceWrapper;
+use crate::udp::request::{self, AnnounceWrapper, UdpRequest};
 
+/// Handles the request in the `payload`, recording its response time and whether it was
+/// malformed or answered with an error in the statistics of the `tracker`.
 pub async fn handle_packet(remote_addr: SocketAddr, payload: Vec<u8>, tracker: Arc<tracker::Tracker>) -> Response {
-    match Request::from_bytes(&payload[..payload.len()], MAX_SCRAPE_TORRENTS).map_err(|_| Error::InternalServer) {
+    let start = Instant::now();
+
+    let (kind, response) = match request::parse(&payload) {
         Ok(request) => {
-            let transaction_id = match &request {
-                Request::Connect(connect_request) => connect_request.transaction_id,
-                Request::Announce(announce_request) => announce_request.transaction_id,
-                Request::Scrape(scrape_request) => scrape_request.transaction_id,
-            };
+            let (kind, transaction_id) = (request.kind(), request.transaction_id());
+            let result = handle_request(request, remote_addr, tracker.clone()).await;
 
-            match handle_request(request, remote_addr, tracker).await {
-                Ok(response) => response,
-                Err(e) => handle_error(&e, transaction_id),
-            }
+            (Some(kind), result.unwrap_or_else(|e| handle_error(&e, transaction_id)))
         }
-        // bad request
-        Err(_) => handle_error(&Error::BadRequest, TransactionId(0)),
-    }
+        // answered with its transaction id when it could be read
+        Err(malformed_request) => {
+            tracker.increase_udp_requests_malformed().await;
+            let transaction_id = malformed_request.transaction_id.unwrap_or(TransactionId(0));
+            (
+                malformed_request.kind,
+                handle_error(&Error::from(malformed_request.error), transaction_id),
+            )
+        }
+    };
+
+    tracker
+        .record_udp_response(kind, start.elapsed(), matches!(response, Response::Error(_)))
+        .await;
+
+    response
 }
 
 /// # Errors
 ///
 /// If a error happens in the `handle_request` function, it will just return the  `ServerError`.
 pub async fn handle_request(
-    request: Request,
+    request: UdpRequest,
     remote_addr: SocketAddr,
     tracker: Arc<tracker::Tracker>,
 ) -> Result<Response, Error> {
     match request {
-        Request::Connect(connect_request) => handle_connect(remote_addr, &connect_request, tracker).await,
-        Request::Announce(announce_request) => handle_announce(remote_addr, &announce_request, tracker).await,
-        Request::Scrape(scrape_request) => handle_scrape(remote_addr, &scrape_request, tracker).await,
+        UdpRequest::Connect(connect_request) => handle_connect(remote_addr, &connect_request, tracker).await,
+        UdpRequest::Announce(wrapped_announce_request) => {
+            handle_wrapped_announce(remote_addr, &wrapped_announce_request, tracker).await
+        }
+        UdpRequest::Scrape(scrape_request) => handle_scrape(remote_addr, &scrape_request, tracker).await,
     }
 }
 
@@ -84,19 +97,57 @@ pub async fn handle_announce(
     announce_request: &AnnounceRequest,
     tracker: Arc<tracker::Tracker>,
 ) -> Result<Response, Error> {
-    check(&remote_addr, &from_connection_id(&announce_request.connection_id))?;
+    handle_wrapped_announce(remote_addr, &AnnounceWrapper::new(announce_request), tracker).await
+}
+
+/// # Errors
+///
+/// If a error happens in the `handle_wrapped_announce` function, it will just return the  `ServerError`.
+pub async fn handle_wrapped_announce(
+    remote_addr: SocketAddr,
+    wrapped_announce_request: &AnnounceWrapper,
+    tracker: Arc<tracker::Tracker>,
+) -> Result<Response, Error> {
+    check(
+        &remote_addr,
+        &from_connection_id(&wrapped_announce_request.announce_request.connection_id),
+    )?;
+
+    tracker.check_maintenance()?;
+
+    if tracker.is_ip_blacklisted(&remote_addr.ip()).await {
+        return Err(Error::PeerBlacklisted);
+    }
+
+    tracker
+        .check_client(&peer::Id(wrapped_announce_request.announce_request.peer_id.0), None)
+        .await?;
 
-    let wrapped_announce_request = AnnounceWrapper::new(announce_request);
+    tracker.check_port(wrapped_announce_request.announce_request.port.0)?;
 
+    // Private trackers authenticate the UDP announces with the key of their BEP 41 `URLData`
     tracker
-        .authenticate_request(&wrapped_announce_request.info_hash, &None)
+        .authenticate_request(&wrapped_announce_request.info_hash, &wrapped_announce_request.auth_key)
         .await?;
 
-    let peer = peer::Peer::from_udp_announce_request(
-        &wrapped_announce_request.announce_request,
-        remote_addr.ip(),
-        tracker.config.get_ext_ip(),
-    );
+    let peer = peer::Peer {
+        paused: wrapped_announce_request.paused,
+        ..peer::Peer::from_udp_announce_request(
+            &wrapped_announce_request.announce_request,
+            remote_addr.ip(),
+            tracker.config().get_ext_ip(),
+        )
+    };
+
+    tracker
+        .check_announce_rate_limit(&remote_addr.ip(), &wrapped_announce_request.info_hash, peer.event)
+        .await?;
+
+    tracker.check_peer_id(&wrapped_announce_request.info_hash, &peer).await?;
+
+    if let Some(auth_key) = &wrapped_announce_request.auth_key {
+        tracker.update_auth_key_usage(&auth_key.key).await;
+    }
 
     //let torrent_stats = tracker.update_torrent_with_peer_and_get_stats(&wrapped_announce_request.info_hash, &peer).await;
 
@@ -104,16 +155,20 @@ pub async fn handle_announce(
         .update_torrent_with_peer_and_get_stats(&wrapped_announce_request.info_hash, &peer)
         .await;
 
-    // get all peers excluding the client_addr
+    // A negative number of peers wanted means the default
+    let numwant = u32::try_from(wrapped_announce_request.announce_request.peers_wanted.0).ok();
+
     let peers = tracker
-        .get_torrent_peers(&wrapped_announce_request.info_hash, &peer.peer_addr)
+        .get_torrent_peers(&wrapped_announce_request.info_hash, &peer, numwant)
         .await;
 
+    let announce_interval = tracker.announce_interval(&torrent_stats);
+
     #[allow(clippy::cast_possible_truncation)]
     let announce_response = if remote_addr.is_ipv4() {
         Response::from(AnnounceResponse {
             transaction_id: wrapped_announce_request.announce_request.transaction_id,
-            announce_interval: AnnounceInterval(i64::from(tracker.config.announce_interval) as i32),
+            announce_interval: AnnounceInterval(i64::from(announce_interval) as i32),
             leechers: NumberOfPeers(i64::from(torrent_stats.leechers) as i32),
             seeders: NumberOfPeers(i64::from(torrent_stats.seeders) as i32),
             peers: peers
@@ -133,7 +188,7 @@ pub async fn handle_announce(
     } else {
         Response::from(AnnounceResponse {
             transaction_id: wrapped_announce_request.announce_request.transaction_id,
-            announce_interval: AnnounceInterval(i64::from(tracker.config.announce_interval) as i32),
+            announce_interval: AnnounceInterval(i64::from(announce_interval) as i32),
             leechers: NumberOfPeers(i64::from(torrent_stats.leechers) as i32),
             seeders: NumberOfPeers(i64::from(torrent_stats.seeders) as i32),
             peers: peers
@@ -165,43 +220,45 @@ pub async fn handle_announce(
     Ok(announce_response)
 }
 
-/// # Errors
+/// Returns the `(seeders, completed, leechers)` of every requested torrent, in the same order,
+/// as described in BEP 15. The unknown torrents and the ones the request is not allowed to
+/// see are answered with zeroed statistics.
 ///
-/// This function dose not ever return an error.
+/// # Errors
 ///
-/// TODO: refactor this, db lock can be a lot shorter
+/// Will return `Error::InvalidConnectionId` if the connection id could not be verified,
+/// or `Error::PeerBlacklisted` if the client IP is blacklisted.
 pub async fn handle_scrape(
     remote_addr: SocketAddr,
     request: &ScrapeRequest,
     tracker: Arc<tracker::Tracker>,
 ) -> Result<Response, Error> {
-    let db = tracker.get_torrents().await;
+    check(&remote_addr, &from_connection_id(&request.connection_id))?;
+
+    if !tracker.config().scrape_enabled {
+        return Err(Error::ScrapeDisabled);
+    }
+
+    if tracker.is_ip_blacklisted(&remote_addr.ip()).await {
+        return Err(Error::PeerBlacklisted);
+    }
 
-    let mut torrent_stats: Vec<TorrentScrapeStatistics> = Vec::new();
+    let mut torrent_stats: Vec<TorrentScrapeStatistics> = Vec::with_capacity(request.info_hashes.len());
 
     for info_hash in &request.info_hashes {
         let info_hash = InfoHash(info_hash.0);
 
-        let scrape_entry = match db.get(&info_hash) {
-            Some(torrent_info) => {
-                if tracker.authenticate_request(&info_hash, &None).await.is_ok() {
-                    let (seeders, completed, leechers) = torrent_info.get_stats();
-
-                    #[allow(clippy::cast_possible_truncation)]
-                    TorrentScrapeStatistics {
-                        seeders: NumberOfPeers(i64::from(seeders) as i32),
-                        completed: NumberOfDownloads(i64::from(completed) as i32),
-                        leechers: NumberOfPeers(i64::from(leechers) as i32),
-                    }
-                } else {
-                    TorrentScrapeStatistics {
-                        seeders: NumberOfPeers(0),
-                        completed: NumberOfDownloads(0),
-                        leechers: NumberOfPeers(0),
-                    }
+        let scrape_entry = match tracker.get_torrent_stats(&info_hash).await {
+            Some((seeders, completed, leechers)) if tracker.authenticate_request(&info_hash, &None).await.is_ok() =>
+            {
+                #[allow(clippy::cast_possible_truncation)]
+                TorrentScrapeStatistics {
+                    seeders: NumberOfPeers(i64::from(seeders) as i32),
+                    completed: NumberOfDownloads(i64::from(completed) as i32),
+                    leechers: NumberOfPeers(i64::from(leechers) as i32),
                 }
             }
-            None => TorrentScrapeStatistics {
+            _ => TorrentScrapeStatistics {
                 seeders: NumberOfPeers(0),
                 completed: NumberOfDownloads(0),
                 leechers: NumberOfPeers(0),
@@ -209,9 +266,9 @@ pub async fn handle_scrape(
         };
 
         torrent_stats.push(scrape_entry);
-    }
 
-    drop(db);
+        tracker.emit_event(events::Event::TorrentScraped { info_hash });
+    }
 
     // send stats event
     match remote_addr {
@@ -239,7 +296,6 @@ fn handle_error(e: &Error, transaction_id: TransactionId) -> Response {
 
 #[cfg(test)]
 mod tests {
-    use std::env;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
     use std::sync::Arc;
 
@@ -247,6 +303,7 @@ mod tests {
     use rand::{thread_rng, Rng};
 
     use crate::config::Configuration;
+    use crate::databases::driver::Driver;
     use crate::protocol::clock::{Current, Time};
     use crate::tracker::{self, mode, peer, statistics};
 
@@ -263,9 +320,7 @@ mod tests {
         config.http_api.bind_address = format!("127.0.0.1:{}", &port);
 
         // Ephemeral database
-        let temp_directory = env::temp_dir();
-        let temp_file = temp_directory.join(format!("data_{}.db", &port));
-        config.db_path = temp_file.to_str().unwrap().to_owned();
+        config.db_driver = Driver::Memory;
 
         config
     }
@@ -327,6 +382,9 @@ mod tests {
                 downloaded: NumberOfBytes(0),
                 left: NumberOfBytes(0),
                 event: AnnounceEvent::Started,
+                paused: false,
+                key: Some(0),
+                country: None,
             };
             TorrentPeerBuilder { peer: default_peer }
         }
@@ -551,20 +609,180 @@ mod tests {
             use std::future;
             use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
             use std::sync::Arc;
+            use std::time::Duration;
 
             use aquatic_udp_protocol::{
-                AnnounceInterval, AnnounceResponse, InfoHash as AquaticInfoHash, NumberOfPeers, PeerId as AquaticPeerId,
-                Response, ResponsePeer,
+                AnnounceInterval, AnnounceRequest, AnnounceResponse, InfoHash as AquaticInfoHash, NumberOfBytes, NumberOfPeers,
+                PeerId as AquaticPeerId, PeerKey, Request, Response, ResponsePeer,
             };
             use mockall::predicate::eq;
 
             use crate::tracker::{self, peer, statistics};
             use crate::udp::connection_cookie::{into_connection_id, make};
-            use crate::udp::handlers::handle_announce;
+            use crate::udp::error::Error;
             use crate::udp::handlers::tests::announce_request::AnnounceRequestBuilder;
             use crate::udp::handlers::tests::{
-                initialized_public_tracker, sample_ipv4_socket_address, tracker_configuration, TorrentPeerBuilder,
+                initialized_private_tracker, initialized_public_tracker, sample_ipv4_remote_addr, sample_ipv4_socket_address,
+                tracker_configuration, TorrentPeerBuilder,
             };
+            use crate::udp::handlers::{handle_announce, handle_packet};
+
+            #[tokio::test]
+            async fn the_announce_should_be_rejected_when_the_tracker_is_under_maintenance() {
+                let tracker = initialized_public_tracker();
+                tracker.set_maintenance(true);
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+
+                assert!(matches!(
+                    handle_announce(remote_addr, &request, tracker.clone()).await,
+                    Err(Error::UnderMaintenance { .. })
+                ));
+            }
+
+            #[tokio::test]
+            async fn the_announce_should_be_rejected_when_the_peer_announces_port_0() {
+                let tracker = initialized_public_tracker();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .with_port(0)
+                    .into();
+
+                assert!(matches!(
+                    handle_announce(remote_addr, &request, tracker.clone()).await,
+                    Err(Error::PortNotAllowed { port: 0 })
+                ));
+            }
+
+            #[tokio::test]
+            async fn the_announce_should_be_rejected_when_another_client_announced_the_peer_id_with_another_key() {
+                let tracker = initialized_public_tracker();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+                handle_announce(remote_addr, &request, tracker.clone()).await.unwrap();
+
+                let other_remote_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)), 8080);
+                let mut other_request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&other_remote_addr)))
+                    .into();
+                other_request.key = PeerKey(1);
+
+                assert!(matches!(
+                    handle_announce(other_remote_addr, &other_request, tracker.clone()).await,
+                    Err(Error::PeerIdTaken)
+                ));
+            }
+
+            #[tokio::test]
+            async fn a_peer_announcing_the_paused_event_should_be_tracked_as_a_partial_seed() {
+                let tracker = initialized_public_tracker();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let mut request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+                request.bytes_left = NumberOfBytes(1000);
+                let info_hash = request.info_hash;
+
+                let mut payload = vec![];
+                Request::Announce(request).write(&mut payload).unwrap();
+                // BEP 21 `paused` event
+                payload[80..84].copy_from_slice(&4i32.to_be_bytes());
+
+                handle_packet(remote_addr, payload, tracker.clone()).await;
+
+                assert_eq!(tracker.get_torrent_partial_seeds(&info_hash.0.into()).await, Some(1));
+            }
+
+            /// An announce `payload` followed by the BEP 41 `URLData` option with the `path`.
+            fn announce_packet_with_url_data(request: AnnounceRequest, path: &str) -> Vec<u8> {
+                let mut payload = vec![];
+                Request::Announce(request).write(&mut payload).unwrap();
+                payload.push(0x2);
+                payload.push(u8::try_from(path.len()).unwrap());
+                payload.extend_from_slice(path.as_bytes());
+                payload.push(0x0);
+                payload
+            }
+
+            #[tokio::test]
+            async fn a_private_tracker_should_authenticate_the_peer_with_the_key_in_the_url_data() {
+                let tracker = initialized_private_tracker();
+                let auth_key = tracker.generate_auth_key(Some(Duration::from_secs(60)), None).await.unwrap();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+                let info_hash = request.info_hash;
+
+                let payload = announce_packet_with_url_data(request, &format!("/announce/{}", auth_key.key));
+
+                let response = handle_packet(remote_addr, payload, tracker.clone()).await;
+
+                assert!(matches!(response, Response::AnnounceIpv4(_)));
+                assert_eq!(tracker.get_all_torrent_peers(&info_hash.0.into()).await.len(), 1);
+            }
+
+            #[tokio::test]
+            async fn a_private_tracker_should_record_the_usage_of_the_key_in_the_url_data() {
+                let tracker = initialized_private_tracker();
+                let auth_key = tracker.generate_auth_key(Some(Duration::from_secs(60)), None).await.unwrap();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+
+                let payload = announce_packet_with_url_data(request, &format!("/announce/{}", auth_key.key));
+
+                handle_packet(remote_addr, payload, tracker.clone()).await;
+
+                let used_key = tracker.get_auth_keys().await.remove(0);
+                assert_eq!(used_key.announces, 1);
+                assert!(used_key.last_used.is_some());
+            }
+
+            #[tokio::test]
+            async fn a_private_tracker_should_reject_the_announce_without_a_key_in_the_url_data() {
+                let tracker = initialized_private_tracker();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+
+                assert!(matches!(
+                    handle_announce(remote_addr, &request, tracker.clone()).await,
+                    Err(Error::PeerNotAuthenticated)
+                ));
+            }
+
+            #[tokio::test]
+            async fn an_announce_with_truncated_options_should_be_answered_with_an_error() {
+                let tracker = initialized_public_tracker();
+
+                let remote_addr = sample_ipv4_remote_addr();
+                let request = AnnounceRequestBuilder::default()
+                    .with_connection_id(into_connection_id(&make(&remote_addr)))
+                    .into();
+
+                let mut payload = announce_packet_with_url_data(request, "/announce");
+                payload.truncate(payload.len() - 2);
+
+                let response = handle_packet(remote_addr, payload, tracker.clone()).await;
+
+                assert!(matches!(response, Response::Error(_)));
+                assert_eq!(tracker.get_stats().await.udp_requests_malformed, 1);
+            }
 
             #[tokio::test]
             async fn an_announced_peer_should_be_added_to_the_tracker() {
@@ -707,6 +925,11 @@ mod tests {
                     .with(eq(statistics::Event::Udp4Announce))
                     .times(1)
                     .returning(|_| Box::pin(future::ready(Some(Ok(())))));
+                stats_event_sender_mock
+                    .expect_send_event()
+                    .withf(|event| matches!(event, statistics::Event::PeerAnnounced(_)))
+                    .times(1)
+                    .returning(|_| Box::pin(future::ready(Some(Ok(())))));
                 let stats_event_sender = Box::new(stats_event_sender_mock);
 
                 let tracker = Arc::new(
@@ -757,7 +980,7 @@ mod tests {
                     let peers = tracker.get_all_torrent_peers(&info_hash.0.into()).await;
 
                     let external_ip_in_tracker_configuration =
-                        tracker.config.external_ip.clone().unwrap().parse::<Ipv4Addr>().unwrap();
+                        tracker.config().external_ip.clone().unwrap().parse::<Ipv4Addr>().unwrap();
 
                     let expected_peer = TorrentPeerBuilder::default()
                         .with_peer_id(peer::Id(peer_id.0))
@@ -937,6 +1160,11 @@ mod tests {
                     .with(eq(statistics::Event::Udp6Announce))
                     .times(1)
                     .returning(|_| Box::pin(future::ready(Some(Ok(())))));
+                stats_event_sender_mock
+                    .expect_send_event()
+                    .withf(|event| matches!(event, statistics::Event::PeerAnnounced(_)))
+                    .times(1)
+                    .returning(|_| Box::pin(future::ready(Some(Ok(())))));
                 let stats_event_sender = Box::new(stats_event_sender_mock);
 
                 let tracker = Arc::new(
@@ -999,7 +1227,7 @@ mod tests {
                     let peers = tracker.get_all_torrent_peers(&info_hash.0.into()).await;
 
                     let _external_ip_in_tracker_configuration =
-                        tracker.config.external_ip.clone().unwrap().parse::<Ipv6Addr>().unwrap();
+                        tracker.config().external_ip.clone().unwrap().parse::<Ipv6Addr>().unwrap();
 
                     // There's a special type of IPv6 addresses that provide compatibility with IPv4.
                     // The last 32 bits of these addresses represent an IPv4, and are represented like this:
@@ -1017,15 +1245,18 @@ mod tests {
         use std::sync::Arc;
 
         use aquatic_udp_protocol::{
-            InfoHash, NumberOfDownloads, NumberOfPeers, Response, ScrapeRequest, ScrapeResponse, TorrentScrapeStatistics,
-            TransactionId,
+            ConnectionId, InfoHash, NumberOfDownloads, NumberOfPeers, Response, ScrapeRequest, ScrapeResponse,
+            TorrentScrapeStatistics, TransactionId,
         };
 
         use super::TorrentPeerBuilder;
         use crate::tracker::{self, peer};
         use crate::udp::connection_cookie::{into_connection_id, make};
+        use crate::udp::error::Error;
         use crate::udp::handlers::handle_scrape;
-        use crate::udp::handlers::tests::{initialized_public_tracker, sample_ipv4_remote_addr};
+        use crate::udp::handlers::tests::{
+            initialized_public_tracker, initialized_tracker, sample_ipv4_remote_addr, TrackerConfigurationBuilder,
+        };
 
         fn zeroed_torrent_statistics() -> TorrentScrapeStatistics {
             TorrentScrapeStatistics {
@@ -1098,6 +1329,21 @@ mod tests {
             handle_scrape(remote_addr, &request, tracker.clone()).await.unwrap()
         }
 
+        #[tokio::test]
+        async fn should_return_an_error_when_scrape_is_disabled() {
+            let mut configuration = TrackerConfigurationBuilder::default().into();
+            configuration.scrape_enabled = false;
+            let tracker = initialized_tracker(&Arc::new(configuration));
+            let remote_addr = sample_ipv4_remote_addr();
+
+            let request = build_scrape_request(&remote_addr, &InfoHash([0u8; 20]));
+
+            assert!(matches!(
+                handle_scrape(remote_addr, &request, tracker).await,
+                Err(Error::ScrapeDisabled)
+            ));
+        }
+
         fn match_scrape_response(response: Response) -> Option<ScrapeResponse> {
             match response {
                 Response::Scrape(scrape_response) => Some(scrape_response),
@@ -1105,6 +1351,118 @@ mod tests {
             }
         }
 
+        #[tokio::test]
+        async fn should_return_the_statistics_of_every_requested_torrent_in_the_requested_order() {
+            let tracker = initialized_public_tracker();
+
+            let remote_addr = sample_ipv4_remote_addr();
+            let unknown_info_hash = InfoHash([1u8; 20]);
+            let known_info_hash = InfoHash([2u8; 20]);
+
+            add_a_seeder(tracker.clone(), &remote_addr, &known_info_hash).await;
+
+            let request = ScrapeRequest {
+                connection_id: into_connection_id(&make(&remote_addr)),
+                transaction_id: TransactionId(0i32),
+                info_hashes: vec![unknown_info_hash, known_info_hash],
+            };
+
+            let torrent_stats =
+                match_scrape_response(handle_scrape(remote_addr, &request, tracker.clone()).await.unwrap()).unwrap();
+
+            let expected_torrent_stats = vec![
+                zeroed_torrent_statistics(),
+                TorrentScrapeStatistics {
+                    seeders: NumberOfPeers(1),
+                    completed: NumberOfDownloads(0),
+                    leechers: NumberOfPeers(0),
+                },
+            ];
+
+            assert_eq!(torrent_stats.torrent_stats, expected_torrent_stats);
+        }
+
+        #[tokio::test]
+        async fn should_fail_when_the_connection_id_is_not_valid() {
+            let remote_addr = sample_ipv4_remote_addr();
+
+            let request = ScrapeRequest {
+                connection_id: ConnectionId(0),
+                transaction_id: TransactionId(0i32),
+                info_hashes: vec![InfoHash([0u8; 20])],
+            };
+
+            assert!(matches!(
+                handle_scrape(remote_addr, &request, initialized_public_tracker()).await,
+                Err(Error::InvalidConnectionId)
+            ));
+        }
+
+        mod with_a_malformed_request {
+            use aquatic_udp_protocol::{ErrorResponse, InfoHash, Request, Response, ScrapeRequest, TransactionId};
+
+            use crate::protocol::common::MAX_SCRAPE_TORRENTS;
+            use crate::udp::connection_cookie::{into_connection_id, make};
+            use crate::udp::handlers::handle_packet;
+            use crate::udp::handlers::tests::{initialized_public_tracker, sample_ipv4_remote_addr};
+
+            fn scrape_packet(number_of_info_hashes: usize) -> Vec<u8> {
+                let request = ScrapeRequest {
+                    connection_id: into_connection_id(&make(&sample_ipv4_remote_addr())),
+                    transaction_id: TransactionId(123i32),
+                    info_hashes: vec![InfoHash([0u8; 20]); number_of_info_hashes],
+                };
+
+                let mut payload = vec![];
+                Request::Scrape(request).write(&mut payload).unwrap();
+                payload
+            }
+
+            fn error_response(response: Response) -> Option<ErrorResponse> {
+                match response {
+                    Response::Error(error_response) => Some(error_response),
+                    _ => None,
+                }
+            }
+
+            #[tokio::test]
+            async fn should_return_an_error_when_there_are_more_info_hashes_than_allowed() {
+                let payload = scrape_packet(MAX_SCRAPE_TORRENTS as usize + 1);
+
+                let response =
+                    error_response(handle_packet(sample_ipv4_remote_addr(), payload, initialized_public_tracker()).await)
+                        .unwrap();
+
+                assert_eq!(response.transaction_id, TransactionId(123i32));
+                assert_eq!(response.message, "exceeded info_hash limit");
+            }
+
+            #[tokio::test]
+            async fn should_return_an_error_when_an_info_hash_is_truncated() {
+                let mut payload = scrape_packet(2);
+                payload.pop();
+
+                let response =
+                    error_response(handle_packet(sample_ipv4_remote_addr(), payload, initialized_public_tracker()).await)
+                        .unwrap();
+
+                assert_eq!(response.transaction_id, TransactionId(123i32));
+                assert_eq!(response.message, "bad request: truncated info_hash");
+            }
+
+            #[tokio::test]
+            async fn should_return_an_error_with_the_transaction_id_when_there_are_no_info_hashes() {
+                let payload = scrape_packet(0);
+
+                let response =
+                    error_response(handle_packet(sample_ipv4_remote_addr(), payload, initialized_public_tracker()).await)
+                        .unwrap();
+
+                assert_eq!(response.transaction_id, TransactionId(123i32));
+                assert_eq!(response.message, "bad request: missing info_hash");
+            }
+        }
+
         mod with_a_public_tracker {
             use aquatic_udp_protocol::{NumberOfDownloads, NumberOfPeers, TorrentScrapeStatistics};
 
@@ -1296,4 +1654,101 @@ mod tests {
             }
         }
     }
+
+    mod handle_packet {
+        use std::future;
+        use std::sync::{Arc, Mutex};
+
+        use aquatic_udp_protocol::{ConnectRequest, InfoHash, Request, ScrapeRequest, TransactionId};
+
+        use crate::tracker::statistics::{Event, UdpRequestKind};
+        use crate::tracker::{self, statistics};
+        use crate::udp::connection_cookie::{into_connection_id, make};
+        use crate::udp::handlers::handle_packet;
+        use crate::udp::handlers::tests::{sample_ipv4_remote_addr, tracker_configuration};
+
+        fn packet(request: Request) -> Vec<u8> {
+            let mut payload = vec![];
+            request.write(&mut payload).unwrap();
+            payload
+        }
+
+        /// A tracker keeping the statistics events it sends, which are handled apart by the `Keeper`.
+        fn tracker_keeping_the_sent_events() -> (Arc<tracker::Tracker>, Arc<Mutex<Vec<Event>>>) {
+            let sent_events = Arc::new(Mutex::new(vec![]));
+
+            let mut stats_event_sender_mock = statistics::MockEventSender::new();
+            let events = sent_events.clone();
+            stats_event_sender_mock.expect_send_event().returning(move |event| {
+                events.lock().unwrap().push(event);
+                Box::pin(future::ready(Some(Ok(()))))
+            });
+
+            let tracker = Arc::new(
+                tracker::Tracker::new(
+                    &tracker_configuration(),
+                    Some(Box::new(stats_event_sender_mock)),
+                    statistics::Repo::new(),
+                )
+                .unwrap(),
+            );
+
+            (tracker, sent_events)
+        }
+
+        fn has_sent_the_udp_response(sent_events: &Mutex<Vec<Event>>, kind: Option<UdpRequestKind>, is_error: bool) -> bool {
+            sent_events.lock().unwrap().iter().any(|event| {
+                matches!(event, Event::UdpResponse { kind: sent_kind, is_error: sent_is_error, .. } if *sent_kind == kind && *sent_is_error == is_error)
+            })
+        }
+
+        #[tokio::test]
+        async fn should_send_the_response_of_a_connect_request() {
+            let (tracker, sent_events) = tracker_keeping_the_sent_events();
+
+            let payload = packet(Request::Connect(ConnectRequest {
+                transaction_id: TransactionId(0i32),
+            }));
+
+            handle_packet(sample_ipv4_remote_addr(), payload, tracker.clone()).await;
+
+            assert!(has_sent_the_udp_response(&sent_events, Some(UdpRequestKind::Connect), false));
+        }
+
+        #[tokio::test]
+        async fn should_count_a_packet_that_is_not_a_request_as_malformed_and_answered_with_an_error() {
+            let (tracker, sent_events) = tracker_keeping_the_sent_events();
+
+            handle_packet(sample_ipv4_remote_addr(), vec![0u8; 4], tracker.clone()).await;
+
+            assert_eq!(tracker.get_stats().await.udp_requests_malformed, 1);
+            assert!(has_sent_the_udp_response(&sent_events, None, true));
+        }
+
+        #[tokio::test]
+        async fn should_count_a_scrape_with_a_truncated_info_hash_as_malformed() {
+            let (tracker, sent_events) = tracker_keeping_the_sent_events();
+
+            let mut payload = packet(Request::Scrape(ScrapeRequest {
+                connection_id: into_connection_id(&make(&sample_ipv4_remote_addr())),
+                transaction_id: TransactionId(0i32),
+                info_hashes: vec![InfoHash([0u8; 20]); 2],
+            }));
+            payload.pop();
+
+            handle_packet(sample_ipv4_remote_addr(), payload, tracker.clone()).await;
+
+            assert_eq!(tracker.get_stats().await.udp_requests_malformed, 1);
+            assert!(has_sent_the_udp_response(&sent_events, Some(UdpRequestKind::Scrape), true));
+        }
+
+        #[tokio::test]
+        async fn should_not_record_the_responses_when_the_usage_statistics_are_disabled() {
+            let tracker = Arc::new(tracker::Tracker::new(&tracker_configuration(), None, statistics::Repo::new()).unwrap());
+
+            handle_packet(sample_ipv4_remote_addr(), vec![0u8; 4], tracker.clone()).await;
+
+            assert_eq!(tracker.get_stats().await.udp_errors_sent, 0);
+        }
+    }
 }
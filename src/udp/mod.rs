@@ -1,6 +1,11 @@
+pub mod access_log;
+#[cfg(all(target_os = "linux", feature = "batched-udp"))]
+pub mod batch;
 pub mod connection_cookie;
 pub mod error;
 pub mod handlers;
+pub mod network_filter;
+pub mod rate_limit;
 pub mod request;
 pub mod server;
 
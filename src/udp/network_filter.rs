@@ -0,0 +1,125 @@
+//! Networks a UDP tracker accepts packets from, checked on the socket before the packets are
+//! parsed, so the unwanted traffic is dropped without reaching the tracker.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::tracker::blacklist::parse_range;
+
+/// A CIDR range, like `10.0.0.0/8`, or a single IP address.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Network(pub IpNet);
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(network: &str) -> Result<Self, Self::Err> {
+        parse_range(network.trim())
+            .map(Network)
+            .map_err(|_| format!("invalid network: {network}"))
+    }
+}
+
+impl TryFrom<String> for Network {
+    type Error = String;
+
+    fn try_from(network: String) -> Result<Self, Self::Error> {
+        network.parse()
+    }
+}
+
+impl From<Network> for String {
+    fn from(network: Network) -> Self {
+        network.to_string()
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NetworkFilter {
+    allowed: Vec<IpNet>,
+    denied: Vec<IpNet>,
+}
+
+impl NetworkFilter {
+    /// Only the `allowed` networks are accepted when there are any, and never the `denied` ones.
+    #[must_use]
+    pub fn new(allowed: &[Network], denied: &[Network]) -> Self {
+        Self {
+            allowed: allowed.iter().map(|network| network.0).collect(),
+            denied: denied.iter().map(|network| network.0).collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty() && self.denied.is_empty()
+    }
+
+    /// IPv4-mapped IPv6 addresses are also checked against the IPv4 networks.
+    #[must_use]
+    pub fn accepts(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+
+        if self.denied.iter().any(|network| network.contains(&ip)) {
+            return false;
+        }
+
+        self.allowed.is_empty() || self.allowed.iter().any(|network| network.contains(&ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::str::FromStr;
+
+    use super::{Network, NetworkFilter};
+
+    fn networks(networks: &[&str]) -> Vec<Network> {
+        networks.iter().map(|network| network.parse().unwrap()).collect()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        IpAddr::from_str(ip).unwrap()
+    }
+
+    #[test]
+    fn it_should_parse_single_addresses_and_cidr_ranges() {
+        assert_eq!("126.0.0.1".parse::<Network>().unwrap().to_string(), "126.0.0.1/32");
+        assert_eq!("10.1.2.3/8".parse::<Network>().unwrap().to_string(), "10.0.0.0/8");
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn it_should_accept_every_address_without_networks() {
+        assert!(NetworkFilter::default().accepts(&ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn it_should_only_accept_the_allowed_networks_when_there_are_any() {
+        let filter = NetworkFilter::new(&networks(&["10.0.0.0/8"]), &[]);
+
+        assert!(filter.accepts(&ip("10.20.30.40")));
+        assert!(filter.accepts(&ip("::ffff:10.20.30.40")));
+        assert!(!filter.accepts(&ip("11.0.0.1")));
+    }
+
+    #[test]
+    fn it_should_reject_the_denied_networks_even_when_they_are_allowed() {
+        let filter = NetworkFilter::new(&networks(&["10.0.0.0/8"]), &networks(&["10.1.0.0/16"]));
+
+        assert!(filter.accepts(&ip("10.2.0.1")));
+        assert!(!filter.accepts(&ip("10.1.0.1")));
+    }
+}
@@ -0,0 +1,133 @@
+//! Token buckets that limit how many packets each IP address can send to a UDP tracker,
+//! so a flood from a single source is dropped before it reaches the tracker.
+//!
+//! The packets over the limit are dropped without a response, answering them would
+//! make the tracker a reflector for the floods sent from spoofed addresses.
+//!
+//! The API limits the requests of each IP address and of each access token with them too.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::protocol::clock::DurationSinceUnixEpoch;
+
+// How often the full buckets are forgotten
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: DurationSinceUnixEpoch,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter<K = IpAddr> {
+    packets_per_second: f64,
+    burst: f64,
+    buckets: HashMap<K, Bucket>,
+    cleaned_at: DurationSinceUnixEpoch,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    /// Every IP address can send `packets_per_second` on average, and up to `burst` packets at once.
+    #[must_use]
+    pub fn new(packets_per_second: u32, burst: u32) -> Self {
+        Self {
+            packets_per_second: f64::from(packets_per_second),
+            burst: f64::from(burst.max(1)),
+            buckets: HashMap::new(),
+            cleaned_at: DurationSinceUnixEpoch::ZERO,
+        }
+    }
+
+    /// Takes a token from the bucket of the `ip` for a packet received at `now`,
+    /// or returns `false` if the bucket is empty and the packet has to be dropped.
+    pub fn check(&mut self, ip: K, now: DurationSinceUnixEpoch) -> bool {
+        if now.saturating_sub(self.cleaned_at) >= CLEANUP_INTERVAL {
+            self.remove_full(now);
+            self.cleaned_at = now;
+        }
+
+        let bucket = self.buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated_at: now,
+        });
+
+        let elapsed = now.saturating_sub(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.packets_per_second).min(self.burst);
+        bucket.updated_at = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+
+        true
+    }
+
+    /// Forgets the buckets refilled by `now`, they do not limit anything.
+    pub fn remove_full(&mut self, now: DurationSinceUnixEpoch) {
+        let (packets_per_second, burst) = (self.packets_per_second, self.burst);
+
+        self.buckets
+            .retain(|_, bucket| bucket.tokens + now.saturating_sub(bucket.updated_at).as_secs_f64() * packets_per_second < burst);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    use super::RateLimiter;
+
+    fn sample_ip() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1))
+    }
+
+    #[test]
+    fn it_should_drop_the_packets_over_the_burst() {
+        let mut rate_limiter = RateLimiter::new(1, 2);
+        let now = Duration::from_secs(100);
+
+        assert!(rate_limiter.check(sample_ip(), now));
+        assert!(rate_limiter.check(sample_ip(), now));
+        assert!(!rate_limiter.check(sample_ip(), now));
+    }
+
+    #[test]
+    fn it_should_refill_the_bucket_at_the_packets_per_second_rate() {
+        let mut rate_limiter = RateLimiter::new(2, 1);
+
+        assert!(rate_limiter.check(sample_ip(), Duration::from_secs(100)));
+        assert!(!rate_limiter.check(sample_ip(), Duration::from_millis(100_250)));
+        assert!(rate_limiter.check(sample_ip(), Duration::from_millis(100_500)));
+    }
+
+    #[test]
+    fn it_should_limit_each_ip_separately() {
+        let mut rate_limiter = RateLimiter::new(1, 1);
+        let now = Duration::from_secs(100);
+
+        assert!(rate_limiter.check(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 1)), now));
+        assert!(rate_limiter.check(IpAddr::V4(Ipv4Addr::new(126, 0, 0, 2)), now));
+    }
+
+    #[test]
+    fn it_should_forget_the_full_buckets() {
+        let mut rate_limiter = RateLimiter::new(1, 10);
+
+        rate_limiter.check(sample_ip(), Duration::from_secs(100));
+        rate_limiter.remove_full(Duration::from_secs(101));
+
+        assert!(rate_limiter.is_empty());
+    }
+}
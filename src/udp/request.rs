@@ -1,6 +1,16 @@
-use aquatic_udp_protocol::AnnounceRequest;
+use std::borrow::Cow;
+use std::net::Ipv4Addr;
 
+use aquatic_udp_protocol::{
+    AnnounceEvent, AnnounceRequest, ConnectRequest, ConnectionId, NumberOfBytes, NumberOfPeers, PeerId, PeerKey, Port,
+    ScrapeRequest, TransactionId,
+};
+use thiserror::Error;
+
+use crate::protocol::common::MAX_SCRAPE_TORRENTS;
 use crate::protocol::info_hash::InfoHash;
+use crate::tracker::auth;
+use crate::tracker::statistics::UdpRequestKind;
 
 // struct AnnounceRequest {
 //     pub connection_id: i64,
@@ -17,9 +27,97 @@ use crate::protocol::info_hash::InfoHash;
 //     pub port: Port
 // }
 
+/// The protocol id of the connect requests, which have no connection id yet.
+const PROTOCOL_ID: i64 = 0x0417_2710_1980;
+
+/// The actions in the header of the requests.
+const CONNECT: i32 = 0;
+const ANNOUNCE: i32 = 1;
+const SCRAPE: i32 = 2;
+
+/// The `paused` event of the BEP 21 partial seeds. `AnnounceEvent` has no variant for it.
+const PAUSED_EVENT: i32 = 4;
+
+/// The BEP 41 option types, the others are followed by their length and skipped.
+const END_OF_OPTIONS: u8 = 0x0;
+const NOP: u8 = 0x1;
+const URL_DATA: u8 = 0x2;
+
+const INFO_HASH_LEN: usize = 20;
+
+/// Why a packet is not a valid BEP 15 request.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("truncated packet")]
+    Truncated,
+
+    #[error("invalid protocol id")]
+    InvalidProtocolId,
+
+    #[error("invalid action {action}")]
+    InvalidAction { action: i32 },
+
+    #[error("unexpected {len} bytes after the request")]
+    TrailingBytes { len: usize },
+
+    #[error("invalid event {event}")]
+    InvalidEvent { event: i32 },
+
+    #[error("truncated options")]
+    TruncatedOptions,
+
+    #[error("truncated info_hash")]
+    TruncatedInfoHash,
+
+    #[error("missing info_hash")]
+    MissingInfoHash,
+
+    #[error("exceeded info_hash limit")]
+    ExceededInfoHashLimit,
+}
+
+/// A packet that could not be parsed, with its `transaction_id` and `kind` when its header
+/// could be read, so it can be answered with an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MalformedRequest {
+    pub transaction_id: Option<TransactionId>,
+    pub kind: Option<UdpRequestKind>,
+    pub error: ParseError,
+}
+
+pub enum UdpRequest {
+    Connect(ConnectRequest),
+    Announce(AnnounceWrapper),
+    Scrape(ScrapeRequest),
+}
+
+impl UdpRequest {
+    #[must_use]
+    pub fn transaction_id(&self) -> TransactionId {
+        match self {
+            UdpRequest::Connect(connect_request) => connect_request.transaction_id,
+            UdpRequest::Announce(wrapped_announce_request) => wrapped_announce_request.announce_request.transaction_id,
+            UdpRequest::Scrape(scrape_request) => scrape_request.transaction_id,
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> UdpRequestKind {
+        match self {
+            UdpRequest::Connect(_) => UdpRequestKind::Connect,
+            UdpRequest::Announce(_) => UdpRequestKind::Announce,
+            UdpRequest::Scrape(_) => UdpRequestKind::Scrape,
+        }
+    }
+}
+
 pub struct AnnounceWrapper {
     pub announce_request: AnnounceRequest,
     pub info_hash: InfoHash,
+    // The announce of a BEP 21 partial seed
+    pub paused: bool,
+    // The key at the end of the BEP 41 `URLData`, like in the path of the HTTP announces
+    pub auth_key: Option<auth::Key>,
 }
 
 impl AnnounceWrapper {
@@ -28,6 +126,377 @@ impl AnnounceWrapper {
         AnnounceWrapper {
             announce_request: announce_request.clone(),
             info_hash: InfoHash(announce_request.info_hash.0),
+            paused: false,
+            auth_key: None,
+        }
+    }
+}
+
+/// Reads the big-endian fields of a packet in order, borrowing it.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>().ok_or(ParseError::Truncated)?;
+        self.0 = rest;
+        Ok(*bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, ParseError> {
+        self.bytes().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseError> {
+        self.bytes().map(u32::from_be_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, ParseError> {
+        self.bytes().map(i32::from_be_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64, ParseError> {
+        self.bytes().map(i64::from_be_bytes)
+    }
+
+    fn rest(self) -> &'a [u8] {
+        self.0
+    }
+}
+
+/// Whether the `payload` is a connect request, only by its action, to drop it without parsing it.
+#[must_use]
+pub fn is_connect(payload: &[u8]) -> bool {
+    payload.get(8..12) == Some(&CONNECT.to_be_bytes()[..])
+}
+
+/// Parses a BEP 15 request, with the BEP 21 `paused` event and the BEP 41 options of the
+/// announces, rejecting the packets with an unknown action or event, or a wrong length.
+///
+/// # Errors
+///
+/// Will return a `MalformedRequest` with the `ParseError` of the packet, and its transaction
+/// id when the header could be read.
+pub fn parse(payload: &[u8]) -> Result<UdpRequest, MalformedRequest> {
+    let mut reader = Reader(payload);
+
+    let (Ok(connection_id), Ok(action), Ok(transaction_id)) = (reader.i64(), reader.i32(), reader.i32()) else {
+        return Err(MalformedRequest {
+            transaction_id: None,
+            kind: None,
+            error: ParseError::Truncated,
+        });
+    };
+
+    let connection_id = ConnectionId(connection_id);
+    let transaction_id = TransactionId(transaction_id);
+    let body = reader.rest();
+
+    let (kind, request) = match action {
+        CONNECT => (
+            UdpRequestKind::Connect,
+            parse_connect(connection_id, transaction_id, body).map(UdpRequest::Connect),
+        ),
+        ANNOUNCE => (
+            UdpRequestKind::Announce,
+            parse_announce(connection_id, transaction_id, body).map(UdpRequest::Announce),
+        ),
+        SCRAPE => (
+            UdpRequestKind::Scrape,
+            parse_scrape(connection_id, transaction_id, body).map(UdpRequest::Scrape),
+        ),
+        action => {
+            return Err(MalformedRequest {
+                transaction_id: Some(transaction_id),
+                kind: None,
+                error: ParseError::InvalidAction { action },
+            })
         }
+    };
+
+    request.map_err(|error| MalformedRequest {
+        transaction_id: Some(transaction_id),
+        kind: Some(kind),
+        error,
+    })
+}
+
+fn parse_connect(connection_id: ConnectionId, transaction_id: TransactionId, body: &[u8]) -> Result<ConnectRequest, ParseError> {
+    if connection_id.0 != PROTOCOL_ID {
+        return Err(ParseError::InvalidProtocolId);
+    }
+
+    if !body.is_empty() {
+        return Err(ParseError::TrailingBytes { len: body.len() });
+    }
+
+    Ok(ConnectRequest { transaction_id })
+}
+
+fn parse_announce(
+    connection_id: ConnectionId,
+    transaction_id: TransactionId,
+    body: &[u8],
+) -> Result<AnnounceWrapper, ParseError> {
+    let mut reader = Reader(body);
+
+    let info_hash = reader.bytes()?;
+    let peer_id = reader.bytes()?;
+    let bytes_downloaded = reader.i64()?;
+    let bytes_left = reader.i64()?;
+    let bytes_uploaded = reader.i64()?;
+    let event = reader.i32()?;
+    let ip_address = reader.bytes::<4>()?;
+    let key = reader.u32()?;
+    let peers_wanted = reader.i32()?;
+    let port = reader.u16()?;
+
+    let paused = event == PAUSED_EVENT;
+    let event = match event {
+        0 => AnnounceEvent::None,
+        1 => AnnounceEvent::Completed,
+        2 => AnnounceEvent::Started,
+        3 => AnnounceEvent::Stopped,
+        PAUSED_EVENT => AnnounceEvent::None,
+        event => return Err(ParseError::InvalidEvent { event }),
+    };
+
+    let url_data = parse_url_data(reader.rest())?;
+
+    let announce_request = AnnounceRequest {
+        connection_id,
+        transaction_id,
+        info_hash: aquatic_udp_protocol::InfoHash(info_hash),
+        peer_id: PeerId(peer_id),
+        bytes_downloaded: NumberOfBytes(bytes_downloaded),
+        bytes_uploaded: NumberOfBytes(bytes_uploaded),
+        bytes_left: NumberOfBytes(bytes_left),
+        event,
+        ip_address: (ip_address != [0; 4]).then(|| Ipv4Addr::from(ip_address)),
+        key: PeerKey(key),
+        peers_wanted: NumberOfPeers(peers_wanted),
+        port: Port(port),
+    };
+
+    Ok(AnnounceWrapper {
+        paused,
+        auth_key: auth_key_from_url_data(&url_data),
+        ..AnnounceWrapper::new(&announce_request)
+    })
+}
+
+/// Parses the info hashes of a scrape request, up to `MAX_SCRAPE_TORRENTS` of them.
+fn parse_scrape(connection_id: ConnectionId, transaction_id: TransactionId, body: &[u8]) -> Result<ScrapeRequest, ParseError> {
+    let (info_hashes, rest) = body.as_chunks::<INFO_HASH_LEN>();
+
+    if !rest.is_empty() {
+        return Err(ParseError::TruncatedInfoHash);
+    }
+
+    if info_hashes.is_empty() {
+        return Err(ParseError::MissingInfoHash);
+    }
+
+    if info_hashes.len() > MAX_SCRAPE_TORRENTS as usize {
+        return Err(ParseError::ExceededInfoHashLimit);
+    }
+
+    Ok(ScrapeRequest {
+        connection_id,
+        transaction_id,
+        info_hashes: info_hashes.iter().copied().map(aquatic_udp_protocol::InfoHash).collect(),
+    })
+}
+
+/// Concatenates the `URLData` of the BEP 41 `options`, up to the end of the options,
+/// borrowing it when there is only one.
+///
+/// # Errors
+///
+/// Will return `ParseError::TruncatedOptions` if an option is longer than the `options` left.
+pub fn parse_url_data(mut options: &[u8]) -> Result<Cow<'_, [u8]>, ParseError> {
+    let mut url_data = Cow::Borrowed(&[][..]);
+
+    while let Some((&option_type, rest)) = options.split_first() {
+        match option_type {
+            END_OF_OPTIONS => break,
+            NOP => options = rest,
+            _ => {
+                let (&len, rest) = rest.split_first().ok_or(ParseError::TruncatedOptions)?;
+                let (data, rest) = rest.split_at_checked(len as usize).ok_or(ParseError::TruncatedOptions)?;
+                if option_type == URL_DATA {
+                    if url_data.is_empty() {
+                        url_data = Cow::Borrowed(data);
+                    } else {
+                        url_data.to_mut().extend_from_slice(data);
+                    }
+                }
+                options = rest;
+            }
+        }
+    }
+
+    Ok(url_data)
+}
+
+/// The key in the last segment of the path of the `url_data`, as in `/announce/{key}?{query}`.
+fn auth_key_from_url_data(url_data: &[u8]) -> Option<auth::Key> {
+    let url_data = std::str::from_utf8(url_data).ok()?;
+    let path = url_data.split('?').next().unwrap_or_default();
+
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .and_then(auth::Key::from_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use aquatic_udp_protocol::{
+        AnnounceEvent, AnnounceRequest, ConnectRequest, ConnectionId, InfoHash, NumberOfBytes, NumberOfPeers, PeerId, PeerKey,
+        Port, Request, ScrapeRequest, TransactionId,
+    };
+
+    use crate::tracker::statistics::UdpRequestKind;
+    use crate::udp::request::{auth_key_from_url_data, is_connect, parse, parse_url_data, ParseError, UdpRequest, PAUSED_EVENT};
+
+    fn packet(request: Request) -> Vec<u8> {
+        let mut payload = vec![];
+        request.write(&mut payload).unwrap();
+        payload
+    }
+
+    fn announce_packet() -> Vec<u8> {
+        packet(Request::Announce(AnnounceRequest {
+            connection_id: ConnectionId(1),
+            transaction_id: TransactionId(123),
+            info_hash: InfoHash([1u8; 20]),
+            peer_id: PeerId([2u8; 20]),
+            bytes_downloaded: NumberOfBytes(0),
+            bytes_uploaded: NumberOfBytes(0),
+            bytes_left: NumberOfBytes(0),
+            event: AnnounceEvent::Started,
+            ip_address: None,
+            key: PeerKey(0),
+            peers_wanted: NumberOfPeers(-1),
+            port: Port(6881),
+        }))
+    }
+
+    fn parse_error(payload: &[u8]) -> ParseError {
+        parse(payload).err().unwrap().error
+    }
+
+    #[test]
+    fn it_should_parse_the_requests_written_by_the_clients() {
+        let connect = packet(Request::Connect(ConnectRequest {
+            transaction_id: TransactionId(123),
+        }));
+        let scrape = packet(Request::Scrape(ScrapeRequest {
+            connection_id: ConnectionId(1),
+            transaction_id: TransactionId(123),
+            info_hashes: vec![InfoHash([1u8; 20]); 2],
+        }));
+
+        assert!(matches!(parse(&connect), Ok(UdpRequest::Connect(_))));
+        assert!(matches!(parse(&scrape), Ok(UdpRequest::Scrape(request)) if request.info_hashes.len() == 2));
+
+        let Ok(UdpRequest::Announce(wrapped_announce_request)) = parse(&announce_packet()) else {
+            panic!("not an announce request");
+        };
+        assert_eq!(wrapped_announce_request.announce_request.event, AnnounceEvent::Started);
+        assert_eq!(wrapped_announce_request.announce_request.port, Port(6881));
+        assert!(!wrapped_announce_request.paused);
+    }
+
+    #[test]
+    fn it_should_tell_the_connect_requests_apart_by_their_action() {
+        let connect = packet(Request::Connect(ConnectRequest {
+            transaction_id: TransactionId(123),
+        }));
+
+        assert!(is_connect(&connect));
+        assert!(!is_connect(&announce_packet()));
+        assert!(!is_connect(&connect[..8]));
+    }
+
+    #[test]
+    fn it_should_parse_the_paused_event_of_the_partial_seeds() {
+        let mut payload = announce_packet();
+        payload[80..84].copy_from_slice(&PAUSED_EVENT.to_be_bytes());
+
+        assert!(matches!(parse(&payload), Ok(UdpRequest::Announce(request)) if request.paused));
+    }
+
+    #[test]
+    fn it_should_reject_an_unknown_action_or_event_keeping_the_transaction_id() {
+        let mut payload = announce_packet();
+        payload[8..12].copy_from_slice(&3i32.to_be_bytes());
+
+        let malformed_request = parse(&payload).err().unwrap();
+        assert_eq!(malformed_request.error, ParseError::InvalidAction { action: 3 });
+        assert_eq!(malformed_request.transaction_id, Some(TransactionId(123)));
+        assert_eq!(malformed_request.kind, None);
+
+        let mut payload = announce_packet();
+        payload[80..84].copy_from_slice(&5i32.to_be_bytes());
+
+        let malformed_request = parse(&payload).err().unwrap();
+        assert_eq!(malformed_request.error, ParseError::InvalidEvent { event: 5 });
+        assert_eq!(malformed_request.kind, Some(UdpRequestKind::Announce));
+    }
+
+    #[test]
+    fn it_should_reject_a_connect_request_without_the_protocol_id_or_with_trailing_bytes() {
+        let mut payload = packet(Request::Connect(ConnectRequest {
+            transaction_id: TransactionId(123),
+        }));
+        payload.push(0);
+
+        assert_eq!(parse_error(&payload), ParseError::TrailingBytes { len: 1 });
+        assert_eq!(parse_error(&[0u8; 16]), ParseError::InvalidProtocolId);
+    }
+
+    #[test]
+    fn it_should_reject_every_truncated_request_without_panicking() {
+        let payload = announce_packet();
+
+        for len in 0..payload.len() {
+            let malformed_request = parse(&payload[..len]).err().unwrap();
+
+            assert_eq!(malformed_request.error, ParseError::Truncated);
+            assert_eq!(malformed_request.transaction_id.is_some(), len >= 16);
+        }
+    }
+
+    #[test]
+    fn it_should_concatenate_the_url_data_options_skipping_the_others() {
+        let options = [
+            &[0x2, 3][..],
+            b"/an",
+            &[0x1, 0x3, 1, 0xff, 0x2, 7],
+            b"nounce?",
+            &[0x0, 0x2, 1, b'x'],
+        ]
+        .concat();
+
+        assert_eq!(parse_url_data(&options).unwrap(), &b"/announce?"[..]);
+    }
+
+    #[test]
+    fn it_should_fail_when_an_option_is_longer_than_the_packet() {
+        assert_eq!(parse_url_data(&[0x2, 5, b'/']), Err(ParseError::TruncatedOptions));
+        assert_eq!(parse_url_data(&[0x2]), Err(ParseError::TruncatedOptions));
+    }
+
+    #[test]
+    fn it_should_take_the_auth_key_from_the_last_segment_of_the_path() {
+        let key = "YZSl4lMZupRuOpSRC3krIKR5BPB14nrJ";
+
+        assert_eq!(
+            auth_key_from_url_data(format!("/announce/{key}?info_hash=x").as_bytes())
+                .unwrap()
+                .key,
+            key
+        );
+        assert!(auth_key_from_url_data(b"/announce").is_none());
     }
 }
@@ -3,60 +3,299 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use aquatic_udp_protocol::Response;
-use log::{debug, info};
+use log::{debug, info, warn};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
 use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, watch, Mutex};
 
-use crate::tracker;
+use crate::config::UdpTracker;
+use crate::protocol::clock::{Current, Time};
+use crate::tracker::{self, statistics};
+use crate::udp::access_log::AccessLog;
+#[cfg(all(target_os = "linux", feature = "batched-udp"))]
+use crate::udp::batch;
+use crate::udp::connection_cookie;
 use crate::udp::handlers::handle_packet;
+use crate::udp::network_filter::NetworkFilter;
+use crate::udp::rate_limit::RateLimiter;
+use crate::udp::request;
 use crate::udp::MAX_PACKET_SIZE;
 
+// Packets taken from the queue at once, their responses are sent together
+const HANDLED_BATCH_SIZE: usize = 32;
+
+type Packet = (Vec<u8>, SocketAddr);
+
 pub struct Udp {
     socket: Arc<UdpSocket>,
+    // The counters of the bind address of the socket, like `udp://0.0.0.0:6969`, unless the usage statistics are off
+    listener: Option<Arc<statistics::ListenerCounters>>,
     tracker: Arc<tracker::Tracker>,
+    // Shared by all the workers of the same UDP tracker
+    network_filter: Option<Arc<NetworkFilter>>,
+    access_log: Option<Arc<AccessLog>>,
+    rate_limiter: Option<Arc<Mutex<RateLimiter>>>,
+    queue_size: usize,
+    load_shedding_threshold: usize,
 }
 
 impl Udp {
+    /// Binds a socket per worker of the `config` to each of its addresses with `SO_REUSEPORT`, so the kernel
+    /// spreads the packets among them. Where `SO_REUSEPORT` is not supported, the workers share a single socket.
+    ///
     /// # Errors
     ///
-    /// Will return `Err` unable to bind to the supplied `bind_address`.
-    pub async fn new(tracker: Arc<tracker::Tracker>, bind_address: &str) -> tokio::io::Result<Udp> {
-        let socket = UdpSocket::bind(bind_address).await?;
+    /// Will return `Err` unable to bind to any of the `bind_address` of the `config`.
+    pub async fn new_workers(tracker: Arc<tracker::Tracker>, config: &UdpTracker) -> tokio::io::Result<Vec<Udp>> {
+        let network_filter = Some(NetworkFilter::new(&config.allowed_networks, &config.denied_networks))
+            .filter(|network_filter| !network_filter.is_empty())
+            .map(Arc::new);
+
+        let access_log = AccessLog::new(config.access_log_sample_rate).map(Arc::new);
+
+        let rate_limiter = (config.rate_limit_packets_per_second > 0).then(|| {
+            Arc::new(Mutex::new(RateLimiter::new(
+                config.rate_limit_packets_per_second,
+                config.rate_limit_burst,
+            )))
+        });
+
+        let mut sockets = vec![];
+        for bind_address in config.bind_address.addresses() {
+            let workers = Udp::bind_workers(bind_address, config).await?;
 
-        Ok(Udp {
-            socket: Arc::new(socket),
-            tracker,
-        })
+            let listener = tracker.register_listener(&format!("udp://{bind_address}"));
+
+            let socket = SockRef::from(workers[0].as_ref());
+            info!(
+                "UDP server on: {} has a receive buffer of {} bytes, a send buffer of {} bytes and a queue of {} packets",
+                bind_address,
+                socket.recv_buffer_size()?,
+                socket.send_buffer_size()?,
+                config.queue_size
+            );
+
+            sockets.extend(workers.into_iter().map(|socket| (socket, listener.clone())));
+        }
+
+        Ok(sockets
+            .into_iter()
+            .map(|(socket, listener)| Udp {
+                socket,
+                listener,
+                tracker: tracker.clone(),
+                network_filter: network_filter.clone(),
+                access_log: access_log.clone(),
+                rate_limiter: rate_limiter.clone(),
+                queue_size: config.queue_size.max(1),
+                load_shedding_threshold: config.load_shedding_threshold,
+            })
+            .collect())
     }
 
+    async fn bind_workers(bind_address: &str, config: &UdpTracker) -> tokio::io::Result<Vec<Arc<UdpSocket>>> {
+        let bind_address = tokio::net::lookup_host(bind_address)
+            .await?
+            .next()
+            .ok_or_else(|| tokio::io::Error::new(tokio::io::ErrorKind::AddrNotAvailable, "could not resolve the bind address"))?;
+
+        if config.workers <= 1 {
+            return Ok(vec![Arc::new(bind_socket(bind_address, false, config)?)]);
+        }
+
+        let sockets = match bind_socket(bind_address, true, config) {
+            Ok(socket) => {
+                let mut sockets = vec![Arc::new(socket)];
+                for _ in 1..config.workers {
+                    sockets.push(Arc::new(bind_socket(bind_address, true, config)?));
+                }
+                sockets
+            }
+            Err(e) => {
+                warn!(
+                    "Could not bind the UDP tracker on: {} with SO_REUSEPORT, the workers will share a socket: {}",
+                    bind_address, e
+                );
+                let socket = Arc::new(bind_socket(bind_address, false, config)?);
+                vec![socket; config.workers]
+            }
+        };
+
+        Ok(sockets)
+    }
+
+    /// Receives the packets into a queue of `queue_size` packets, handled by another task, so
+    /// the socket is read while the requests are handled. The packets that do not fit are dropped.
+    ///
+    /// Stops receiving once `shutdown` changes to `true`, or its sender is dropped, and returns
+    /// after the responses of the packets already queued are sent.
+    ///
     /// # Panics
     ///
     /// It would panic if unable to resolve the `local_addr` from the supplied ´socket´.
-    pub async fn start(&self) {
+    pub async fn start(&self, mut shutdown: watch::Receiver<bool>) {
+        let secret_rotation_interval =
+            connection_cookie::secret_rotation_interval(self.tracker.config().udp_connection_id_secret_rotation_interval);
+        let mut secret_rotation = tokio::time::interval(secret_rotation_interval);
+        secret_rotation.tick().await;
+
+        let (queue, queued) = mpsc::channel(self.queue_size);
+        let handler = tokio::spawn(Udp::handle_queued(
+            queued,
+            self.socket.clone(),
+            self.listener.clone(),
+            self.tracker.clone(),
+            self.access_log.clone(),
+        ));
+
         loop {
-            let mut data = [0; MAX_PACKET_SIZE];
             let socket = self.socket.clone();
-            let tracker = self.tracker.clone();
 
             tokio::select! {
-                _ = tokio::signal::ctrl_c() => {
+                () = stopped(&mut shutdown) => {
                     info!("Stopping UDP server: {}..", socket.local_addr().unwrap());
                     break;
                 }
-                Ok((valid_bytes, remote_addr)) = socket.recv_from(&mut data) => {
-                    let payload = data[..valid_bytes].to_vec();
+                _ = secret_rotation.tick() => {
+                    connection_cookie::rotate_secret(secret_rotation_interval);
+                }
+                Ok(packets) = Udp::receive(&socket) => {
+                    let packets = self.drop_filtered(packets).await;
+                    let packets = self.drop_rate_limited(packets).await;
+                    let packets = self.shed_connects(packets, &queue).await;
+
+                    let dropped = packets.into_iter().map(|packet| queue.try_send(packet)).filter(Result::is_err).count();
+                    if dropped > 0 {
+                        debug!("Dropped {} packets, the queue is full", dropped);
+                        self.tracker.add_udp_packets_dropped(dropped as u64).await;
+                    }
+                }
+            }
+        }
+
+        drop(queue);
+        drop(handler.await);
+    }
+
+    async fn handle_queued(
+        mut queued: mpsc::Receiver<Packet>,
+        socket: Arc<UdpSocket>,
+        listener: Option<Arc<statistics::ListenerCounters>>,
+        tracker: Arc<tracker::Tracker>,
+        access_log: Option<Arc<AccessLog>>,
+    ) {
+        let mut packets = Vec::with_capacity(HANDLED_BATCH_SIZE);
 
-                    debug!("Received {} bytes from {}", payload.len(), remote_addr);
-                    debug!("{:?}", payload);
+        while queued.recv_many(&mut packets, HANDLED_BATCH_SIZE).await > 0 {
+            let mut responses = Vec::with_capacity(packets.len());
 
-                    let response = handle_packet(remote_addr, payload, tracker).await;
-                    Udp::send_response(socket, remote_addr, response).await;
+            for (payload, remote_addr) in packets.drain(..) {
+                debug!("Received {} bytes from {}", payload.len(), remote_addr);
+                debug!("{:?}", payload);
+
+                let access_log_entry = access_log
+                    .as_ref()
+                    .and_then(|access_log| access_log.sample(&remote_addr, &payload));
+
+                let response = handle_packet(remote_addr, payload, tracker.clone()).await;
+
+                if let Some(listener) = &listener {
+                    let kind = match &response {
+                        Response::AnnounceIpv4(_) | Response::AnnounceIpv6(_) => statistics::ListenerRequestKind::Announce,
+                        Response::Scrape(_) => statistics::ListenerRequestKind::Scrape,
+                        _ => statistics::ListenerRequestKind::Other,
+                    };
+                    listener.record(kind, matches!(response, Response::Error(_)), Current::now());
+                }
+
+                if let Some(access_log_entry) = access_log_entry {
+                    access_log_entry.log(&response);
+                }
+                if let Some(payload) = Udp::write_response(&remote_addr, response) {
+                    responses.push((remote_addr, payload));
                 }
             }
+
+            Udp::send_packets(socket.clone(), responses).await;
+        }
+    }
+
+    /// Drops the packets of the IP addresses out of the allowed networks or in the denied ones,
+    /// counting them in the statistics.
+    async fn drop_filtered(&self, mut packets: Vec<Packet>) -> Vec<Packet> {
+        let Some(network_filter) = &self.network_filter else {
+            return packets;
+        };
+
+        let received = packets.len();
+        packets.retain(|(_, remote_addr)| network_filter.accepts(&remote_addr.ip()));
+
+        let dropped = received - packets.len();
+        if dropped > 0 {
+            debug!("Dropped {} packets from filtered networks", dropped);
+            self.tracker.add_udp_packets_dropped(dropped as u64).await;
         }
+
+        packets
     }
 
-    async fn send_response(socket: Arc<UdpSocket>, remote_addr: SocketAddr, response: Response) {
-        debug!("sending response to: {:?}", &remote_addr);
+    /// Drops the connect requests while the `queue` has more packets than the `load_shedding_threshold`,
+    /// so the clients already connected keep being served instead of waiting longer, counting them in the statistics.
+    async fn shed_connects(&self, mut packets: Vec<Packet>, queue: &mpsc::Sender<Packet>) -> Vec<Packet> {
+        let queued = queue.max_capacity() - queue.capacity();
+        if self.load_shedding_threshold == 0 || queued <= self.load_shedding_threshold {
+            return packets;
+        }
+
+        let received = packets.len();
+        packets.retain(|(payload, _)| !request::is_connect(payload));
+
+        let shed = received - packets.len();
+        if shed > 0 {
+            debug!("Dropped {} connect requests, {} packets are queued", shed, queued);
+            self.tracker.add_udp_connects_shed(shed as u64).await;
+        }
+
+        packets
+    }
+
+    /// Drops the packets of the IP addresses over the rate limit, counting them in the statistics.
+    async fn drop_rate_limited(&self, mut packets: Vec<Packet>) -> Vec<Packet> {
+        let Some(rate_limiter) = &self.rate_limiter else {
+            return packets;
+        };
+
+        let received = packets.len();
+        let now = Current::now();
+
+        let mut rate_limiter = rate_limiter.lock().await;
+        packets.retain(|(_, remote_addr)| rate_limiter.check(remote_addr.ip(), now));
+        drop(rate_limiter);
+
+        let dropped = received - packets.len();
+        if dropped > 0 {
+            debug!("Dropped {} rate limited packets", dropped);
+            self.tracker.add_udp_packets_dropped(dropped as u64).await;
+        }
+
+        packets
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "batched-udp")))]
+    async fn receive(socket: &UdpSocket) -> tokio::io::Result<Vec<Packet>> {
+        let mut data = [0; MAX_PACKET_SIZE];
+        let (valid_bytes, remote_addr) = socket.recv_from(&mut data).await?;
+
+        Ok(vec![(data[..valid_bytes].to_vec(), remote_addr)])
+    }
+
+    #[cfg(all(target_os = "linux", feature = "batched-udp"))]
+    async fn receive(socket: &UdpSocket) -> tokio::io::Result<Vec<Packet>> {
+        batch::recv_batch(socket).await
+    }
+
+    fn write_response(remote_addr: &SocketAddr, response: Response) -> Option<Vec<u8>> {
+        debug!("sending response to: {:?}", remote_addr);
 
         let buffer = vec![0u8; MAX_PACKET_SIZE];
         let mut cursor = Cursor::new(buffer);
@@ -65,19 +304,76 @@ impl Udp {
             Ok(_) => {
                 #[allow(clippy::cast_possible_truncation)]
                 let position = cursor.position() as usize;
-                let inner = cursor.get_ref();
+                let mut inner = cursor.into_inner();
+                inner.truncate(position);
 
-                debug!("{:?}", &inner[..position]);
-                Udp::send_packet(socket, &remote_addr, &inner[..position]).await;
+                debug!("{:?}", &inner);
+                Some(inner)
             }
             Err(_) => {
                 debug!("could not write response to bytes.");
+                None
             }
         }
     }
 
+    #[cfg(not(all(target_os = "linux", feature = "batched-udp")))]
+    async fn send_packets(socket: Arc<UdpSocket>, packets: Vec<(SocketAddr, Vec<u8>)>) {
+        for (remote_addr, payload) in packets {
+            Udp::send_packet(socket.clone(), &remote_addr, &payload).await;
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "batched-udp"))]
+    async fn send_packets(socket: Arc<UdpSocket>, packets: Vec<(SocketAddr, Vec<u8>)>) {
+        // doesn't matter if they reach or not
+        drop(batch::send_batch(&socket, &packets).await);
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "batched-udp")))]
     async fn send_packet(socket: Arc<UdpSocket>, remote_addr: &SocketAddr, payload: &[u8]) {
         // doesn't matter if it reaches or not
         drop(socket.send_to(payload, remote_addr).await);
     }
 }
+
+/// Resolves once `shutdown` changes to `true`, or its sender is dropped.
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stop| *stop).await;
+}
+
+/// Binds a socket with the buffer sizes of the `config`, with `SO_REUSEPORT` if `reuse_port`.
+fn bind_socket(bind_address: SocketAddr, reuse_port: bool, config: &UdpTracker) -> tokio::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(bind_address), Type::DGRAM, Some(Protocol::UDP))?;
+
+    // Only IPv6, so that `[::]` and `0.0.0.0` can both be bound on the same port
+    if bind_address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    if config.socket_recv_buffer_size > 0 {
+        socket.set_recv_buffer_size(config.socket_recv_buffer_size)?;
+    }
+    if config.socket_send_buffer_size > 0 {
+        socket.set_send_buffer_size(config.socket_send_buffer_size)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&bind_address.into())?;
+
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn set_reuse_port(socket: &Socket) -> tokio::io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn set_reuse_port(_socket: &Socket) -> tokio::io::Result<()> {
+    Err(tokio::io::Error::new(
+        tokio::io::ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
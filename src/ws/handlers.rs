@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use aquatic_udp_protocol::AnnounceEvent;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, HeaderMap};
+use axum::response::Response;
+use futures::{SinkExt, StreamExt};
+use log::debug;
+use tokio::sync::mpsc;
+
+use super::message::{self, AnnounceRequest, Request, ScrapeRequest};
+use super::routes::AppState;
+use super::swarms::{Sender, Swarms, QUEUE_SIZE};
+use crate::http::error::Error;
+use crate::http::extractors::{AuthKey, PeerAddr};
+use crate::http::WebResult;
+use crate::protocol::common::MAX_SCRAPE_TORRENTS;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::{self, auth, events, peer, statistics};
+
+/// Upgrade the request to a WebSocket connection, where the peer sends its announces and scrapes
+pub async fn handle_upgrade(
+    State(state): State<AppState>,
+    AuthKey(auth_key): AuthKey,
+    PeerAddr(peer_ip): PeerAddr,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let connection = Connection {
+        tracker: state.tracker,
+        swarms: state.swarms,
+        auth_key,
+        peer_addr: SocketAddr::new(peer_ip, remote_addr.port()),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(ToString::to_string),
+        announced: HashMap::new(),
+    };
+
+    upgrade.on_upgrade(move |socket| connection.serve(socket))
+}
+
+/// The WebSocket connection of a peer, which can announce several torrents
+struct Connection {
+    tracker: Arc<tracker::Tracker>,
+    swarms: Arc<Swarms>,
+    auth_key: Option<auth::Key>,
+    peer_addr: SocketAddr,
+    user_agent: Option<String>,
+    // The last announce of each torrent, to stop the peer when the connection is closed
+    announced: HashMap<InfoHash, peer::Peer>,
+}
+
+impl Connection {
+    async fn serve(mut self, socket: WebSocket) {
+        let (mut sink, mut stream) = socket.split();
+        let (sender, mut receiver) = mpsc::channel::<message::Response>(QUEUE_SIZE);
+
+        // The responses and the messages relayed from the other peers
+        let writer = tokio::spawn(async move {
+            while let Some(response) = receiver.recv().await {
+                let Ok(text) = serde_json::to_string(&response) else {
+                    continue;
+                };
+                if sink.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        while let Some(Ok(message)) = stream.next().await {
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            let (action, result) = match serde_json::from_str::<Request>(&text) {
+                Ok(Request::Announce(announce_request)) => (Some("announce"), self.announce(announce_request, &sender).await),
+                Ok(Request::Scrape(scrape_request)) => (Some("scrape"), self.scrape(scrape_request, &sender).await),
+                Err(_) => (None, Err(Error::InvalidRequest)),
+            };
+
+            if let Err(e) = result {
+                debug!("{:?}", e);
+                let _ = sender
+                    .send(message::Response::Error(message::ErrorResponse {
+                        action,
+                        failure_reason: e.to_string(),
+                    }))
+                    .await;
+            }
+        }
+
+        self.close().await;
+
+        drop(sender);
+        let _ = writer.await;
+    }
+
+    async fn announce(&mut self, announce_request: AnnounceRequest, sender: &Sender) -> WebResult<()> {
+        let info_hash = message::info_hash(&announce_request.info_hash).ok_or(Error::InvalidInfo)?;
+        let peer_id = message::peer_id(&announce_request.peer_id).ok_or(Error::InvalidPeerId)?;
+
+        self.tracker.check_maintenance()?;
+
+        if self.tracker.is_ip_blacklisted(&self.peer_addr.ip()).await {
+            return Err(Error::PeerBlacklisted);
+        }
+
+        // Without the header the client is checked against an empty User-Agent
+        self.tracker
+            .check_client(&peer_id, Some(self.user_agent.as_deref().unwrap_or_default()))
+            .await?;
+
+        self.tracker.authenticate_request(&info_hash, &self.auth_key).await?;
+
+        // The answers are only relayed to the peer of the offer, once the peer answering is allowed to announce
+        if let (Some(answer), Some(to_peer_id), Some(offer_id)) = (
+            &announce_request.answer,
+            &announce_request.to_peer_id,
+            &announce_request.offer_id,
+        ) {
+            let to_peer_id = message::peer_id(to_peer_id).ok_or(Error::InvalidPeerId)?;
+            if let Some(to) = self.swarms.get(&info_hash, &to_peer_id).await {
+                let _ = to.try_send(message::Response::Answer(message::AnswerMessage {
+                    action: "announce",
+                    info_hash: announce_request.info_hash,
+                    peer_id: announce_request.peer_id,
+                    answer: answer.clone(),
+                    offer_id: offer_id.clone(),
+                }));
+            }
+            return Ok(());
+        }
+
+        let peer = peer::Peer::from_ws_announce_request(
+            &announce_request,
+            peer_id,
+            self.peer_addr.ip(),
+            self.peer_addr.port(),
+            self.tracker.config().get_ext_ip(),
+        );
+
+        self.tracker
+            .check_announce_rate_limit(&self.peer_addr.ip(), &info_hash, peer.event)
+            .await?;
+
+        self.tracker.check_peer_id(&info_hash, &peer).await?;
+
+        if let Some(auth_key) = &self.auth_key {
+            self.tracker.update_auth_key_usage(&auth_key.key).await;
+        }
+        let torrent_stats = self.tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+
+        if peer.event == AnnounceEvent::Stopped {
+            self.swarms.leave(&info_hash, &peer_id).await;
+            self.announced.remove(&info_hash);
+        } else {
+            self.swarms.join(info_hash, peer_id, sender.clone()).await;
+            self.announced.insert(info_hash, peer);
+        }
+
+        // send stats event
+        match self.peer_addr.ip() {
+            IpAddr::V4(_) => {
+                self.tracker.send_stats_event(statistics::Event::Tcp4Announce).await;
+            }
+            IpAddr::V6(_) => {
+                self.tracker.send_stats_event(statistics::Event::Tcp6Announce).await;
+            }
+        }
+
+        let _ = sender
+            .send(message::Response::Announce(message::AnnounceResponse {
+                action: "announce",
+                info_hash: announce_request.info_hash.clone(),
+                complete: torrent_stats.seeders,
+                incomplete: torrent_stats.leechers,
+                interval: self.tracker.announce_interval(&torrent_stats),
+            }))
+            .await;
+
+        // Each offer is relayed to a different peer, up to `numwant` of them, unless its queue is full
+        let count = announce_request.numwant.map_or(announce_request.offers.len(), |numwant| {
+            numwant.min(announce_request.offers.len())
+        });
+        let others = self.swarms.others(&info_hash, &peer_id, count).await;
+
+        for (other, offer) in others.into_iter().zip(announce_request.offers) {
+            let _ = other.try_send(message::Response::Offer(message::OfferMessage {
+                action: "announce",
+                info_hash: announce_request.info_hash.clone(),
+                peer_id: announce_request.peer_id.clone(),
+                offer: offer.offer,
+                offer_id: offer.offer_id,
+            }));
+        }
+
+        Ok(())
+    }
+
+    async fn scrape(&self, scrape_request: ScrapeRequest, sender: &Sender) -> WebResult<()> {
+        if !self.tracker.config().scrape_enabled {
+            return Err(Error::ScrapeDisabled);
+        }
+
+        if self.tracker.is_ip_blacklisted(&self.peer_addr.ip()).await {
+            return Err(Error::PeerBlacklisted);
+        }
+
+        let binary_info_hashes = scrape_request.info_hash.ok_or(Error::InvalidInfo)?;
+        let binary_info_hashes = binary_info_hashes.info_hashes();
+        if binary_info_hashes.len() > MAX_SCRAPE_TORRENTS as usize {
+            return Err(Error::ExceededInfoHashLimit);
+        }
+
+        let mut files = BTreeMap::new();
+
+        for binary_info_hash in binary_info_hashes {
+            let info_hash = message::info_hash(binary_info_hash).ok_or(Error::InvalidInfo)?;
+
+            let (complete, downloaded, incomplete) = match self.tracker.get_torrent_stats(&info_hash).await {
+                Some(stats) if self.tracker.authenticate_request(&info_hash, &self.auth_key).await.is_ok() => stats,
+                _ => (0, 0, 0),
+            };
+
+            files.insert(
+                binary_info_hash.clone(),
+                message::ScrapeEntry {
+                    complete,
+                    incomplete,
+                    downloaded,
+                },
+            );
+
+            self.tracker.emit_event(events::Event::TorrentScraped { info_hash });
+        }
+
+        // send stats event
+        match self.peer_addr.ip() {
+            IpAddr::V4(_) => {
+                self.tracker.send_stats_event(statistics::Event::Tcp4Scrape).await;
+            }
+            IpAddr::V6(_) => {
+                self.tracker.send_stats_event(statistics::Event::Tcp6Scrape).await;
+            }
+        }
+
+        let _ = sender
+            .send(message::Response::Scrape(message::ScrapeResponse { action: "scrape", files }))
+            .await;
+
+        Ok(())
+    }
+
+    /// The peer stops on every torrent it announced, as if it had sent a stopped announce
+    async fn close(&mut self) {
+        for (info_hash, mut peer) in self.announced.drain() {
+            self.swarms.leave(&info_hash, &peer.peer_id).await;
+
+            peer.event = AnnounceEvent::Stopped;
+            self.tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+        }
+    }
+}
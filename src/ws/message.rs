@@ -0,0 +1,213 @@
+//! The JSON messages of the WebTorrent tracker protocol, where the info hashes and peer ids are
+//! "binary strings" with a char for each of their bytes.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::peer;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Request {
+    Announce(AnnounceRequest),
+    Scrape(ScrapeRequest),
+}
+
+/// An announce with the WebRTC offers for the other peers of the swarm, or the answer to one of them
+#[derive(Deserialize, Debug)]
+pub struct AnnounceRequest {
+    pub info_hash: String,
+    pub peer_id: String,
+    #[serde(default)]
+    pub uploaded: u64,
+    #[serde(default)]
+    pub downloaded: u64,
+    // Unknown until the browser gets the metadata of the torrent
+    pub left: Option<u64>,
+    pub event: Option<String>,
+    pub numwant: Option<usize>,
+    #[serde(default)]
+    pub offers: Vec<Offer>,
+    pub answer: Option<Value>,
+    pub to_peer_id: Option<String>,
+    pub offer_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Offer {
+    pub offer: Value,
+    pub offer_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScrapeRequest {
+    pub info_hash: Option<InfoHashes>,
+}
+
+/// One info hash, or a list of them
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum InfoHashes {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl InfoHashes {
+    #[must_use]
+    pub fn info_hashes(&self) -> &[String] {
+        match self {
+            InfoHashes::One(info_hash) => std::slice::from_ref(info_hash),
+            InfoHashes::Many(info_hashes) => info_hashes,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Response {
+    Announce(AnnounceResponse),
+    Offer(OfferMessage),
+    Answer(AnswerMessage),
+    Scrape(ScrapeResponse),
+    Error(ErrorResponse),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AnnounceResponse {
+    pub action: &'static str,
+    pub info_hash: String,
+    pub complete: u32,
+    pub incomplete: u32,
+    pub interval: u32,
+}
+
+/// The offer of the peer `peer_id`, relayed to another peer of the swarm
+#[derive(Serialize, Debug, Clone)]
+pub struct OfferMessage {
+    pub action: &'static str,
+    pub info_hash: String,
+    pub peer_id: String,
+    pub offer: Value,
+    pub offer_id: String,
+}
+
+/// The answer of the peer `peer_id`, relayed to the peer of the offer
+#[derive(Serialize, Debug, Clone)]
+pub struct AnswerMessage {
+    pub action: &'static str,
+    pub info_hash: String,
+    pub peer_id: String,
+    pub answer: Value,
+    pub offer_id: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ScrapeResponse {
+    pub action: &'static str,
+    pub files: BTreeMap<String, ScrapeEntry>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ScrapeEntry {
+    pub complete: u32,
+    pub incomplete: u32,
+    pub downloaded: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ErrorResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<&'static str>,
+    #[serde(rename = "failure reason")]
+    pub failure_reason: String,
+}
+
+/// The info hash of a binary string, `None` unless it has 20 chars of one byte
+#[must_use]
+pub fn info_hash(binary_string: &str) -> Option<InfoHash> {
+    decode(binary_string).map(InfoHash)
+}
+
+/// The peer id of a binary string, `None` unless it has 20 chars of one byte
+#[must_use]
+pub fn peer_id(binary_string: &str) -> Option<peer::Id> {
+    decode(binary_string).map(peer::Id)
+}
+
+/// The binary string with a char for each of the `bytes`
+#[must_use]
+pub fn binary_string(bytes: &[u8]) -> String {
+    bytes.iter().copied().map(char::from).collect()
+}
+
+fn decode(binary_string: &str) -> Option<[u8; 20]> {
+    binary_string
+        .chars()
+        .map(|char| u8::try_from(char).ok())
+        .collect::<Option<Vec<u8>>>()?
+        .try_into()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{binary_string, info_hash, Request, Response, ScrapeEntry, ScrapeResponse};
+    use crate::protocol::info_hash::InfoHash;
+
+    #[test]
+    fn the_info_hashes_should_be_decoded_from_binary_strings_of_20_chars_of_one_byte() {
+        let bytes = [
+            0x9e, 0x02, 0x17, 0xd0, 0xfa, 0x71, 0xc8, 0x73, 0x32, 0xcd, 0x8b, 0xf9, 0xdb, 0xea, 0xbc, 0xb2, 0xc2, 0xcf, 0x3c,
+            0x4d,
+        ];
+
+        assert_eq!(info_hash(&binary_string(&bytes)), Some(InfoHash(bytes)));
+        assert_eq!(info_hash("too short"), None);
+        assert_eq!(info_hash(&"€".repeat(20)), None);
+    }
+
+    #[test]
+    fn the_requests_should_be_parsed_by_their_action() {
+        let announce = r#"{"action":"announce","info_hash":"aaaaaaaaaaaaaaaaaaaa","peer_id":"-WW0100-aaaaaaaaaaaa","left":0,
+            "numwant":1,"offers":[{"offer":{"type":"offer","sdp":"v=0"},"offer_id":"bbbbbbbbbbbbbbbbbbbb"}]}"#;
+
+        match serde_json::from_str(announce).unwrap() {
+            Request::Announce(announce) => {
+                assert_eq!(announce.left, Some(0));
+                assert_eq!(announce.offers[0].offer_id, "bbbbbbbbbbbbbbbbbbbb");
+            }
+            Request::Scrape(_) => panic!("not an announce"),
+        }
+
+        match serde_json::from_str(r#"{"action":"scrape","info_hash":["aaaaaaaaaaaaaaaaaaaa"]}"#).unwrap() {
+            Request::Scrape(scrape) => assert_eq!(scrape.info_hash.unwrap().info_hashes(), ["aaaaaaaaaaaaaaaaaaaa"]),
+            Request::Announce(_) => panic!("not a scrape"),
+        }
+
+        assert!(serde_json::from_str::<Request>(r#"{"action":"unknown"}"#).is_err());
+    }
+
+    #[test]
+    fn the_responses_should_be_written_with_their_action() {
+        let scrape = Response::Scrape(ScrapeResponse {
+            action: "scrape",
+            files: [(
+                String::from("aaaaaaaaaaaaaaaaaaaa"),
+                ScrapeEntry {
+                    complete: 1,
+                    incomplete: 2,
+                    downloaded: 3,
+                },
+            )]
+            .into(),
+        });
+
+        assert_eq!(
+            serde_json::to_string(&scrape).unwrap(),
+            r#"{"action":"scrape","files":{"aaaaaaaaaaaaaaaaaaaa":{"complete":1,"incomplete":2,"downloaded":3}}}"#
+        );
+    }
+}
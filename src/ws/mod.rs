@@ -0,0 +1,10 @@
+//! The WebTorrent tracker, relaying the WebRTC offers and answers of the browser peers over WebSocket.
+//!
+//! Its peers announce into the same torrents as the UDP and HTTP ones, so the swarms mixing them have
+//! the same stats on every tracker.
+
+pub mod handlers;
+pub mod message;
+pub mod routes;
+pub mod server;
+pub mod swarms;
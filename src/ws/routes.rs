@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+use axum::routing::get;
+use axum::{middleware, Router};
+
+use super::handlers::handle_upgrade;
+use super::swarms::Swarms;
+use crate::http::handlers::handle_not_found;
+use crate::http::middleware::log_request;
+use crate::http::reverse_proxy::ReverseProxy;
+use crate::tracker;
+
+/// The state shared by the connections of a WebSocket tracker
+#[derive(Clone)]
+pub struct AppState {
+    pub tracker: Arc<tracker::Tracker>,
+    pub swarms: Arc<Swarms>,
+    pub reverse_proxy: Option<Arc<ReverseProxy>>,
+}
+
+impl FromRef<AppState> for Arc<tracker::Tracker> {
+    fn from_ref(state: &AppState) -> Self {
+        state.tracker.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<ReverseProxy>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.reverse_proxy.clone()
+    }
+}
+
+/// All routes, the peers of the `swarms` can be connected to any of the WebSocket trackers
+pub fn routes(tracker: Arc<tracker::Tracker>, swarms: Arc<Swarms>) -> Router {
+    let state = AppState {
+        reverse_proxy: ReverseProxy::new(&tracker.config()).map(Arc::new),
+        tracker,
+        swarms,
+    };
+
+    Router::new()
+        // GET / or /announce or /announce/<key>, upgraded to a WebSocket
+        .route("/", get(handle_upgrade))
+        .route("/announce", get(handle_upgrade))
+        .route("/announce/:key", get(handle_upgrade))
+        .route_layer(middleware::from_fn(log_request))
+        .fallback(handle_not_found)
+        .with_state(state)
+}
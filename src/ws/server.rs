@@ -0,0 +1,60 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use log::error;
+
+use super::routes;
+use super::swarms::Swarms;
+use crate::http::connections::Connections;
+use crate::http::server::serve_tls;
+use crate::http::tls::Certificates;
+use crate::tracker;
+
+/// Server that listens on WebSocket, needs a `tracker::TorrentTracker`
+#[derive(Clone)]
+pub struct Ws {
+    tracker: Arc<tracker::Tracker>,
+    swarms: Arc<Swarms>,
+}
+
+impl Ws {
+    #[must_use]
+    pub fn new(tracker: Arc<tracker::Tracker>, swarms: Arc<Swarms>) -> Ws {
+        Ws { tracker, swarms }
+    }
+
+    /// Start the `WsServer`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the `socket_addr` cannot be bound.
+    pub fn start(&self, socket_addr: SocketAddr) -> impl Future<Output = ()> {
+        let server = axum::Server::bind(&socket_addr)
+            .serve(routes::routes(self.tracker.clone(), self.swarms.clone()).into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                tokio::signal::ctrl_c().await.expect("Failed to listen to shutdown signal.");
+            });
+
+        async move {
+            if let Err(e) = server.await {
+                error!("Could not serve the WebSocket tracker on {}: {}", socket_addr, e);
+            }
+        }
+    }
+
+    /// Start the `WsServer` in TLS mode, serving the current certificate of `certificates` to each new connection
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the `socket_addr` cannot be bound.
+    pub async fn start_tls(&self, socket_addr: SocketAddr, certificates: Arc<Certificates>) {
+        serve_tls(
+            socket_addr,
+            certificates,
+            routes::routes(self.tracker.clone(), self.swarms.clone()),
+            Connections::default(),
+        )
+        .await;
+    }
+}
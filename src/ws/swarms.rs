@@ -0,0 +1,98 @@
+//! The peers connected to the WebSocket trackers, by torrent, to relay the offers and answers between them.
+
+use std::collections::HashMap;
+
+use rand::seq::IteratorRandom;
+use tokio::sync::{mpsc, RwLock};
+
+use super::message::Response;
+use crate::protocol::info_hash::InfoHash;
+use crate::tracker::peer;
+
+/// The messages waiting to be sent to a connected peer, above which the offers and answers relayed to it are
+/// dropped, so a slow peer can not make the tracker buffer the messages of the others
+pub const QUEUE_SIZE: usize = 64;
+
+/// The messages sent to a connected peer
+pub type Sender = mpsc::Sender<Response>;
+
+#[derive(Default)]
+pub struct Swarms {
+    swarms: RwLock<HashMap<InfoHash, HashMap<peer::Id, Sender>>>,
+}
+
+impl Swarms {
+    pub async fn join(&self, info_hash: InfoHash, peer_id: peer::Id, sender: Sender) {
+        self.swarms
+            .write()
+            .await
+            .entry(info_hash)
+            .or_default()
+            .insert(peer_id, sender);
+    }
+
+    pub async fn leave(&self, info_hash: &InfoHash, peer_id: &peer::Id) {
+        let mut swarms = self.swarms.write().await;
+
+        if let Some(swarm) = swarms.get_mut(info_hash) {
+            swarm.remove(peer_id);
+            if swarm.is_empty() {
+                swarms.remove(info_hash);
+            }
+        }
+    }
+
+    pub async fn get(&self, info_hash: &InfoHash, peer_id: &peer::Id) -> Option<Sender> {
+        self.swarms.read().await.get(info_hash)?.get(peer_id).cloned()
+    }
+
+    /// Up to `count` random peers of the swarm, other than `peer_id`.
+    pub async fn others(&self, info_hash: &InfoHash, peer_id: &peer::Id, count: usize) -> Vec<Sender> {
+        self.swarms.read().await.get(info_hash).map_or_else(Vec::new, |swarm| {
+            swarm
+                .iter()
+                .filter(|(other_peer_id, _)| *other_peer_id != peer_id)
+                .map(|(_, sender)| sender.clone())
+                .choose_multiple(&mut rand::thread_rng(), count)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::Swarms;
+    use crate::protocol::info_hash::InfoHash;
+    use crate::tracker::peer;
+
+    #[tokio::test]
+    async fn the_offers_should_only_be_relayed_to_the_other_peers_of_the_swarm() {
+        let swarms = Swarms::default();
+        let info_hash = InfoHash([0u8; 20]);
+        let (sender, _receiver) = mpsc::channel(1);
+
+        swarms
+            .join(info_hash, peer::Id(*b"-qB00000000000000001"), sender.clone())
+            .await;
+        swarms
+            .join(info_hash, peer::Id(*b"-qB00000000000000002"), sender.clone())
+            .await;
+        swarms
+            .join(InfoHash([1u8; 20]), peer::Id(*b"-qB00000000000000003"), sender)
+            .await;
+
+        assert_eq!(
+            swarms.others(&info_hash, &peer::Id(*b"-qB00000000000000001"), 5).await.len(),
+            1
+        );
+
+        swarms.leave(&info_hash, &peer::Id(*b"-qB00000000000000002")).await;
+
+        assert!(swarms
+            .others(&info_hash, &peer::Id(*b"-qB00000000000000001"), 5)
+            .await
+            .is_empty());
+        assert!(swarms.get(&info_hash, &peer::Id(*b"-qB00000000000000002")).await.is_none());
+    }
+}
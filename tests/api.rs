@@ -7,7 +7,7 @@ mod common;
 
 mod tracker_api {
     use core::panic;
-    use std::env;
+    use std::collections::BTreeMap;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use std::str::FromStr;
     use std::sync::atomic::{AtomicBool, Ordering};
@@ -17,15 +17,23 @@ mod tracker_api {
     use reqwest::Response;
     use tokio::task::JoinHandle;
     use torrust_tracker::api::resource;
+    use torrust_tracker::api::resource::audit_log::AuditLogEntry;
     use torrust_tracker::api::resource::auth_key::AuthKey;
-    use torrust_tracker::api::resource::stats::Stats;
-    use torrust_tracker::api::resource::torrent::{self, Torrent};
+    use torrust_tracker::api::resource::client_filter::ClientFilter;
+    use torrust_tracker::api::resource::error::ErrorResponse;
+    use torrust_tracker::api::resource::health::{Components, Database, Health, Status};
+    use torrust_tracker::api::resource::log_level::LogLevel;
+    use torrust_tracker::api::resource::maintenance;
+    use torrust_tracker::api::resource::stats::{self, Stats};
+    use torrust_tracker::api::resource::torrent::{self, Torrent, WhitelistEntry};
     use torrust_tracker::config::Configuration;
+    use torrust_tracker::databases::driver::Driver;
+    use torrust_tracker::databases::metrics::Snapshot;
     use torrust_tracker::jobs::tracker_api;
-    use torrust_tracker::protocol::clock::DurationSinceUnixEpoch;
+    use torrust_tracker::protocol::clock::{Current, DurationSinceUnixEpoch, Time};
     use torrust_tracker::protocol::info_hash::InfoHash;
-    use torrust_tracker::tracker::statistics::Keeper;
-    use torrust_tracker::tracker::{auth, peer};
+    use torrust_tracker::tracker::statistics::{Keeper, Metrics};
+    use torrust_tracker::tracker::{auth, client_filter, peer};
     use torrust_tracker::{ephemeral_instance_keys, logging, static_time, tracker};
 
     use crate::common::ephemeral_random_port;
@@ -49,6 +57,99 @@ mod tracker_api {
             .is_ok());
     }
 
+    #[tokio::test]
+    async fn should_allow_listing_the_auth_keys_with_their_label() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let auth_key = api_client.generate_labeled_auth_key(60, "alice").await;
+
+        assert_eq!(auth_key.label, Some("alice".to_string()));
+        assert_eq!(api_client.get_auth_keys().await, vec![auth_key]);
+    }
+
+    #[tokio::test]
+    async fn should_allow_generating_a_never_expiring_key_and_extending_a_key() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let never_expiring_key = api_client
+            .generate_auth_key_with(&serde_json::json!({"label": "seedbox", "never_expires": true}))
+            .await;
+        assert_eq!(never_expiring_key.label, Some("seedbox".to_string()));
+        assert_eq!(never_expiring_key.valid_until, None);
+
+        let auth_key = api_client
+            .generate_auth_key_with(&serde_json::json!({"seconds_valid": 60}))
+            .await;
+        let extended_key = api_client
+            .update_auth_key(&auth_key.key, &serde_json::json!({"seconds_valid": 3600}))
+            .await;
+        assert!(extended_key.valid_until.unwrap() > auth_key.valid_until.unwrap());
+
+        let mut keys = api_client.get_auth_keys().await;
+        keys.sort_by_key(|key| key.valid_until);
+        assert_eq!(keys, vec![never_expiring_key, extended_key]);
+    }
+
+    #[tokio::test]
+    async fn should_allow_generating_a_batch_of_keys_as_json_or_csv() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let res = api_client
+            .generate_auth_keys(
+                "json",
+                &serde_json::json!({"count": 2, "label_prefix": "user-", "seconds_valid": 60}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        let auth_keys = res.json::<Vec<AuthKey>>().await.unwrap();
+        assert_eq!(
+            auth_keys
+                .iter()
+                .map(|auth_key| auth_key.label.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["user-1", "user-2"]
+        );
+
+        let res = api_client.generate_auth_keys("csv", &serde_json::json!({"count": 3})).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+        let csv = res.text().await.unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("key,valid_until,label"));
+        assert!(lines.all(|line| line.ends_with(",,")));
+
+        assert_eq!(api_client.get_auth_keys().await.len(), 5);
+
+        let res = api_client.generate_auth_keys("json", &serde_json::json!({"count": 0})).await;
+        assert_eq!(res.status(), 422);
+    }
+
+    #[tokio::test]
+    async fn should_reject_the_changes_when_the_api_is_read_only() {
+        let mut configuration = Arc::try_unwrap(tracker_configuration()).unwrap();
+        configuration.http_api.read_only = true;
+        let api_server = ApiServer::new_running_custom_instance(Arc::new(configuration)).await;
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let res = api_client
+            .whitelist_a_torrent("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d")
+            .await;
+        assert_eq!(res.status(), 403);
+        assert_eq!(res.json::<ErrorResponse>().await.unwrap().error.code, "read_only");
+
+        let res = api_client.generate_auth_keys("json", &serde_json::json!({"count": 1})).await;
+        assert_eq!(res.status(), 403);
+
+        assert_eq!(api_client.get_auth_keys_response().await.status(), 200);
+        assert_eq!(api_client.get_tracker_statistics().await.torrents, 0);
+    }
+
     #[tokio::test]
     async fn should_allow_whitelisting_a_torrent() {
         let api_server = ApiServer::new_running_instance().await;
@@ -84,6 +185,281 @@ mod tracker_api {
         assert_eq!(res.status(), 200);
     }
 
+    #[tokio::test]
+    async fn should_allow_whitelisting_a_list_of_torrents() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let info_hashes = vec![
+            "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".to_owned(),
+            "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".to_owned(),
+        ];
+
+        let res = ApiClient::new(api_server.get_connection_info().unwrap())
+            .whitelist_torrents(&info_hashes)
+            .await;
+
+        assert_eq!(res.status(), 200);
+
+        let tracker = api_server.tracker.unwrap();
+        for info_hash in &info_hashes {
+            assert!(
+                tracker
+                    .is_info_hash_whitelisted(&InfoHash::from_str(info_hash).unwrap())
+                    .await
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_allow_listing_the_whitelist_and_checking_an_info_hash() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let info_hashes = vec![
+            "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".to_owned(),
+            "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".to_owned(),
+        ];
+        api_client.whitelist_torrents(&info_hashes).await;
+
+        assert_eq!(
+            api_client.get_whitelist("").await,
+            vec![info_hashes[1].clone(), info_hashes[0].clone()]
+        );
+        assert_eq!(
+            api_client.get_whitelist("offset=1&limit=1").await,
+            vec![info_hashes[0].clone()]
+        );
+
+        let entry = api_client.get_whitelist_entry(&info_hashes[0]).await;
+        assert!(entry.whitelisted);
+
+        let entry = api_client
+            .get_whitelist_entry("0000000000000000000000000000000000000000")
+            .await;
+        assert!(!entry.whitelisted);
+    }
+
+    #[tokio::test]
+    async fn should_allow_removing_a_list_of_torrents_from_the_whitelist() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let info_hashes = vec![
+            "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".to_owned(),
+            "3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0".to_owned(),
+        ];
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let res = api_client.whitelist_torrents(&info_hashes).await;
+        assert_eq!(res.status(), 200);
+
+        let res = api_client.remove_torrents_from_whitelist(&info_hashes).await;
+        assert_eq!(res.status(), 200);
+
+        let tracker = api_server.tracker.unwrap();
+        for info_hash in &info_hashes {
+            assert!(
+                !tracker
+                    .is_info_hash_whitelisted(&InfoHash::from_str(info_hash).unwrap())
+                    .await
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_record_the_changes_in_the_audit_log() {
+        let api_server = ApiServer::new_running_instance().await;
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let info_hash = "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".to_owned();
+
+        api_client.whitelist_a_torrent(&info_hash).await;
+        api_client.get_auth_keys().await;
+
+        let audit_log = api_client.get_audit_log("token_name=admin").await;
+
+        assert_eq!(audit_log.len(), 1);
+        assert_eq!(audit_log[0].token_name, "admin");
+        assert_eq!(audit_log[0].ip, Some("127.0.0.1".to_string()));
+        assert_eq!(audit_log[0].method, "POST");
+        assert_eq!(audit_log[0].path, format!("/api/v1/whitelist/{info_hash}"));
+        assert_eq!(audit_log[0].status, 200);
+
+        assert!(api_client.get_audit_log("token_name=other").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_allow_blacklisting_and_unblacklisting_an_ip_range() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let tracker = api_server.tracker.unwrap();
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3));
+
+        let res = api_client.blacklist_range("10.0.0.0%2F8").await;
+        assert_eq!(res.status(), 200);
+        assert!(tracker.is_ip_blacklisted(&ip).await);
+
+        let res = api_client.remove_range_from_blacklist("10.0.0.0%2F8").await;
+        assert_eq!(res.status(), 200);
+        assert!(!tracker.is_ip_blacklisted(&ip).await);
+    }
+
+    #[tokio::test]
+    async fn should_allow_removing_a_peer_and_banning_its_ip() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let tracker = api_server.tracker.unwrap();
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+
+        let (peer, _peer_resource) = sample_torrent_peer();
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+
+        let res = api_client
+            .remove_torrent_peer(&info_hash.to_string(), &peer.peer_id.get_id().unwrap())
+            .await;
+        assert_eq!(res.status(), 200);
+        assert!(tracker.get_all_torrent_peers(&info_hash).await.is_empty());
+
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+
+        let res = api_client.ban_ip(&peer.peer_addr.ip().to_string()).await;
+        assert_eq!(res.status(), 200);
+        assert!(tracker.get_all_torrent_peers(&info_hash).await.is_empty());
+        assert!(tracker.is_ip_blacklisted(&peer.peer_addr.ip()).await);
+    }
+
+    #[tokio::test]
+    async fn should_allow_banning_and_unbanning_a_client() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let tracker = api_server.tracker.unwrap();
+        let peer_id = peer::Id(*b"-XL0012-000000000000");
+
+        let res = api_client.add_to_client_filter("peer_id", "-XL").await;
+        assert_eq!(res.status(), 200);
+        let res = api_client.add_to_client_filter("user_agent", "BadClient%2F*").await;
+        assert_eq!(res.status(), 200);
+        assert!(tracker.check_client(&peer_id, None).await.is_err());
+        assert_eq!(
+            api_client.get_client_filter().await,
+            ClientFilter {
+                mode: client_filter::Mode::Block,
+                peer_id_prefixes: vec!["-XL".to_string()],
+                user_agents: vec!["BadClient/*".to_string()],
+            }
+        );
+
+        let res = api_client.remove_from_client_filter("peer_id", "-XL").await;
+        assert_eq!(res.status(), 200);
+        assert!(tracker.check_client(&peer_id, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_lock_out_the_ip_sending_too_many_invalid_tokens() {
+        let mut configuration = Arc::try_unwrap(tracker_configuration()).unwrap();
+        configuration.http_api.rate_limit.max_failed_attempts = 2;
+        let api_server = ApiServer::new_running_custom_instance(Arc::new(configuration)).await;
+        let connection_info = api_server.get_connection_info().unwrap();
+
+        let invalid_client = ApiClient::new(ApiConnectionInfo::new(&connection_info.bind_address, "InvalidToken"));
+        assert_eq!(invalid_client.get_auth_keys_response().await.status(), 401);
+        assert_eq!(invalid_client.get_auth_keys_response().await.status(), 401);
+
+        let res = ApiClient::new(connection_info).get_auth_keys_response().await;
+        assert_eq!(res.status(), 429);
+        assert_eq!(res.headers()["retry-after"], "60");
+        assert_eq!(res.json::<ErrorResponse>().await.unwrap().error.code, "locked_out");
+    }
+
+    #[tokio::test]
+    async fn should_allow_flushing_and_cleaning_up_the_torrents_right_away() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+        let (peer, _peer_resource) = sample_torrent_peer();
+        let inactive_peer = peer::Peer {
+            updated: DurationSinceUnixEpoch::ZERO,
+            ..peer
+        };
+
+        let api_connection_info = api_server.get_connection_info().unwrap();
+
+        let tracker = api_server.tracker.unwrap();
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &inactive_peer)
+            .await;
+
+        let res = ApiClient::new(api_connection_info).flush().await;
+
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.json::<maintenance::Flush>().await.unwrap(),
+            maintenance::Flush {
+                torrents_saved: 0,
+                keys_saved: 0,
+                torrents_removed: 1,
+                peers_removed: 1,
+                peers_saved: 0,
+            }
+        );
+        assert!(tracker.get_torrent_stats(&info_hash).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_allow_reloading_the_tracker_and_changing_the_log_level() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let info_hash = "9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d".to_owned();
+        api_client.whitelist_a_torrent(&info_hash).await;
+
+        let res = api_client.reload().await;
+        assert_eq!(res.status(), 200);
+        assert!(
+            api_server
+                .tracker
+                .unwrap()
+                .is_info_hash_whitelisted(&InfoHash::from_str(&info_hash).unwrap())
+                .await
+        );
+
+        let level = log::max_level().to_string().to_lowercase();
+
+        let res = api_client.set_log_level("debug").await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.json::<LogLevel>().await.unwrap(),
+            LogLevel {
+                level: "debug".to_string()
+            }
+        );
+        assert_eq!(log::max_level(), log::LevelFilter::Debug);
+
+        assert_eq!(api_client.set_log_level("verbose").await.status(), 422);
+
+        api_client.set_log_level(&level).await;
+    }
+
+    #[tokio::test]
+    async fn should_allow_starting_and_stopping_the_maintenance_mode() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let tracker = api_server.tracker.unwrap();
+
+        let res = api_client.set_maintenance(true).await;
+        assert_eq!(res.status(), 200);
+        assert!(tracker.is_under_maintenance());
+
+        let res = api_client.set_maintenance(false).await;
+        assert_eq!(res.status(), 200);
+        assert!(!tracker.is_under_maintenance());
+    }
+
     #[tokio::test]
     async fn should_allow_getting_a_torrent_info() {
         let api_server = ApiServer::new_running_instance().await;
@@ -109,11 +485,92 @@ mod tracker_api {
                 seeders: 1,
                 completed: 0,
                 leechers: 0,
+                partial_seeds: 0,
+                countries: BTreeMap::new(),
                 peers: Some(vec![peer_resource])
             }
         );
     }
 
+    #[tokio::test]
+    async fn should_allow_removing_a_torrent_with_its_peers_and_its_whitelist_entry() {
+        let api_server = ApiServer::new_running_instance().await;
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let tracker = api_server.tracker.unwrap();
+
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+
+        let (peer, _peer_resource) = sample_torrent_peer();
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+        tracker.add_torrent_to_whitelist(&info_hash).await.unwrap();
+
+        let res = api_client.remove_torrent(&info_hash.to_string(), "whitelist=true").await;
+
+        assert_eq!(res.status(), 200);
+        assert_eq!(tracker.get_torrent_stats(&info_hash).await, None);
+        assert!(!tracker.is_info_hash_whitelisted(&info_hash).await);
+    }
+
+    #[tokio::test]
+    async fn should_allow_getting_the_swarm_health_of_a_torrent() {
+        let api_server = ApiServer::new_running_instance().await;
+        let api_connection_info = api_server.get_connection_info().unwrap();
+
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+
+        let (peer, _) = sample_torrent_peer();
+        let peer = peer::Peer {
+            updated: Current::now(),
+            ..peer
+        };
+
+        api_server
+            .tracker
+            .unwrap()
+            .update_torrent_with_peer_and_get_stats(&info_hash, &peer)
+            .await;
+
+        let health = ApiClient::new(api_connection_info)
+            .get_torrent_health(&info_hash.to_string())
+            .await;
+
+        // A single seeder that just joined
+        assert_eq!((health.seeders, health.leechers, health.churn), (1, 0, 1));
+        assert!((health.ratio - 1.0).abs() < f64::EPSILON);
+        assert!(health.last_activity <= 1);
+        assert!(health.score > 90);
+    }
+
+    #[tokio::test]
+    async fn should_allow_getting_the_completed_history_of_a_torrent() {
+        let mut configuration = Arc::try_unwrap(tracker_configuration()).unwrap();
+        configuration.record_completed_events = true;
+        let api_server = ApiServer::new_running_custom_instance(Arc::new(configuration)).await;
+        let api_connection_info = api_server.get_connection_info().unwrap();
+
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+
+        let (peer, _) = sample_torrent_peer();
+        let completed_peer = peer::Peer {
+            event: AnnounceEvent::Completed,
+            ..peer
+        };
+
+        let tracker = api_server.tracker.unwrap();
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+        tracker
+            .update_torrent_with_peer_and_get_stats(&info_hash, &completed_peer)
+            .await;
+
+        let history = ApiClient::new(api_connection_info)
+            .get_torrent_completed_history(&info_hash.to_string())
+            .await;
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].completed, 1);
+        assert_eq!(history[0].timestamp % 86_400, 0);
+    }
+
     #[tokio::test]
     async fn should_allow_getting_torrents() {
         let api_server = ApiServer::new_running_instance().await;
@@ -145,6 +602,47 @@ mod tracker_api {
         );
     }
 
+    #[tokio::test]
+    async fn should_allow_finding_the_torrents_by_info_hash_prefix_and_the_swarms_of_an_ip() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+        let tracker = api_server.tracker.unwrap();
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+        let other_info_hash = InfoHash::from_str("3b245504cf5f11bbdbe1201cea6a6bf45aee1bc0").unwrap();
+
+        let (peer, peer_resource) = sample_torrent_peer();
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+        tracker.update_torrent_with_peer_and_get_stats(&other_info_hash, &peer).await;
+
+        let torrent_resources = api_client.get_torrents_with_query("infohash_prefix=9E02").await;
+
+        assert_eq!(
+            torrent_resources
+                .iter()
+                .map(|torrent| torrent.info_hash.as_str())
+                .collect::<Vec<_>>(),
+            ["9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d"]
+        );
+
+        let swarm_peers = api_client.get_peers_by_ip(&peer.peer_addr.ip().to_string()).await;
+
+        assert_eq!(
+            swarm_peers,
+            vec![
+                resource::peer::SwarmPeer {
+                    info_hash: other_info_hash.to_string(),
+                    peer: peer_resource,
+                },
+                resource::peer::SwarmPeer {
+                    info_hash: info_hash.to_string(),
+                    peer: sample_torrent_peer().1,
+                },
+            ]
+        );
+        assert!(api_client.get_peers_by_ip("126.0.0.2").await.is_empty());
+    }
+
     #[tokio::test]
     async fn should_allow_getting_tracker_statistics() {
         let api_server = ApiServer::new_running_instance().await;
@@ -183,8 +681,155 @@ mod tracker_api {
                 udp6_connections_handled: 0,
                 udp6_announces_handled: 0,
                 udp6_scrapes_handled: 0,
+                torrents_evicted: 0,
+                peers_evicted: 0,
+                udp_packets_dropped: 0,
+                http: stats::Http::from(&Metrics::new()),
+                udp: stats::Udp::from(&Metrics::new()),
+                windows: stats::Windows {
+                    last_minute: window_with_one_peer(60),
+                    last_five_minutes: window_with_one_peer(300),
+                    last_hour: window_with_one_peer(3600),
+                },
+                listeners: BTreeMap::new(),
+                countries: BTreeMap::new(),
+                database: Some(stats::Database::from(Snapshot::default())),
+                history: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn should_allow_getting_the_tracker_statistics_history() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let info_hash = InfoHash::from_str("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d").unwrap();
+
+        let (peer, _peer_resource) = sample_torrent_peer();
+
+        let api_connection_info = api_server.get_connection_info().unwrap();
+
+        let tracker = api_server.tracker.unwrap();
+        tracker.update_torrent_with_peer_and_get_stats(&info_hash, &peer).await;
+        tracker.sample_stats_history().await;
+
+        let history = ApiClient::new(api_connection_info)
+            .get_tracker_statistics_history()
+            .await
+            .history
+            .unwrap();
+
+        let last_bucket = history.last().unwrap();
+        assert_eq!(last_bucket.peers, 1);
+        assert_eq!(last_bucket.torrents, 1);
+    }
+
+    fn window_with_one_peer(seconds: u64) -> stats::Window {
+        stats::Window {
+            seconds,
+            announces_per_second: 0.0,
+            scrapes_per_second: 0.0,
+            unique_peers: 1,
+            tcp_announces: 0,
+            udp_announces: 0,
+            ipv4_announces: 0,
+            ipv6_announces: 0,
+            tcp_scrapes: 0,
+            udp_scrapes: 0,
+            ipv4_scrapes: 0,
+            ipv6_scrapes: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_include_the_database_query_metrics_in_the_tracker_statistics() {
+        let api_server = ApiServer::new_running_instance().await;
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        api_client
+            .whitelist_a_torrent("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d")
+            .await;
+
+        let database_stats = api_client.get_tracker_statistics().await.database.unwrap();
+
+        let query = database_stats
+            .queries
+            .iter()
+            .find(|query| query.method == "add_info_hash_to_whitelist")
+            .unwrap();
+        assert_eq!(query.count, 1);
+        assert_eq!(query.errors, 0);
+    }
+
+    #[tokio::test]
+    async fn should_allow_checking_the_tracker_health_without_a_token() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let res = ApiClient::new(api_server.get_connection_info().unwrap()).get_health().await;
+
+        assert_eq!(res.status(), 200);
+        assert_eq!(
+            res.json::<Health>().await.unwrap(),
+            Health {
+                status: Status::Ok,
+                components: Components {
+                    database: Database {
+                        status: Status::Ok,
+                        driver: "Memory".to_string(),
+                        error: None,
+                        pool: None,
+                    },
+                },
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn should_keep_answering_the_unversioned_routes_as_deprecated() {
+        let api_server = ApiServer::new_running_instance().await;
+
+        let res = ApiClient::new(api_server.get_connection_info().unwrap())
+            .get_unversioned("stats")
+            .await;
+
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get("deprecation").unwrap(), "true");
+        assert!(res.json::<Stats>().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_answer_the_errors_with_their_status_and_code() {
+        let api_server = ApiServer::new_running_instance().await;
+        let api_client = ApiClient::new(api_server.get_connection_info().unwrap());
+
+        let res = api_client
+            .get_torrent_response("9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d")
+            .await;
+        assert_eq!(res.status(), 404);
+        assert_eq!(
+            res.json::<ErrorResponse>().await.unwrap(),
+            ErrorResponse {
+                error: resource::error::Error {
+                    code: "not_found".to_string(),
+                    message: "torrent not found".to_string(),
+                    details: Some(serde_json::json!({ "resource": "torrent" })),
+                }
             }
         );
+
+        let res = api_client.get_torrent_response("not-an-info-hash").await;
+        assert_eq!(res.status(), 422);
+        assert_eq!(res.json::<ErrorResponse>().await.unwrap().error.code, "invalid_param");
+
+        // The unversioned routes keep their former error body
+        let res = api_client
+            .get_unversioned("torrent/9e0217d0fa71c87332cd8bf9dbeabcb2c2cf3c4d")
+            .await;
+        assert_eq!(res.status(), 404);
+        assert_eq!(
+            res.json::<serde_json::Value>().await.unwrap(),
+            serde_json::json!({ "status": "err", "reason": "torrent not found" })
+        );
     }
 
     fn sample_torrent_peer() -> (peer::Peer, resource::peer::Peer) {
@@ -196,6 +841,9 @@ mod tracker_api {
             downloaded: NumberOfBytes(0),
             left: NumberOfBytes(0),
             event: AnnounceEvent::Started,
+            paused: false,
+            key: None,
+            country: None,
         };
         let torrent_peer_resource = resource::peer::Peer::from(torrent_peer);
 
@@ -211,9 +859,7 @@ mod tracker_api {
         config.http_api.bind_address = format!("127.0.0.1:{}", &port);
 
         // Ephemeral database
-        let temp_directory = env::temp_dir();
-        let temp_file = temp_directory.join(format!("data_{}.db", &port));
-        config.db_path = temp_file.to_str().unwrap().to_owned();
+        config.db_driver = Driver::Memory;
 
         Arc::new(config)
     }
@@ -312,23 +958,227 @@ mod tracker_api {
 
         pub async fn generate_auth_key(&self, seconds_valid: i32) -> AuthKey {
             let url = format!(
-                "http://{}/api/key/{}?token={}",
+                "http://{}/api/v1/key/{}?token={}",
                 &self.connection_info.bind_address, &seconds_valid, &self.connection_info.api_token
             );
             reqwest::Client::new().post(url).send().await.unwrap().json().await.unwrap()
         }
 
+        pub async fn generate_labeled_auth_key(&self, seconds_valid: i32, label: &str) -> AuthKey {
+            let url = format!(
+                "http://{}/api/v1/key/{}?label={}&token={}",
+                &self.connection_info.bind_address, &seconds_valid, &label, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).send().await.unwrap().json().await.unwrap()
+        }
+
+        pub async fn generate_auth_key_with(&self, new_key: &serde_json::Value) -> AuthKey {
+            let url = format!(
+                "http://{}/api/v1/key?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new()
+                .post(url)
+                .json(new_key)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap()
+        }
+
+        pub async fn generate_auth_keys(&self, format: &str, batch: &serde_json::Value) -> Response {
+            let url = format!(
+                "http://{}/api/v1/keys/batch?format={}&token={}",
+                &self.connection_info.bind_address, format, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).json(batch).send().await.unwrap()
+        }
+
+        pub async fn update_auth_key(&self, key: &str, expiry: &serde_json::Value) -> AuthKey {
+            let url = format!(
+                "http://{}/api/v1/key/{}?token={}",
+                &self.connection_info.bind_address, &key, &self.connection_info.api_token
+            );
+            reqwest::Client::new()
+                .patch(url)
+                .json(expiry)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap()
+        }
+
+        pub async fn get_audit_log(&self, query: &str) -> Vec<AuditLogEntry> {
+            let url = format!(
+                "http://{}/api/v1/audit-log?{}&token={}",
+                &self.connection_info.bind_address, query, &self.connection_info.api_token
+            );
+            reqwest::Client::new()
+                .get(url)
+                .send()
+                .await
+                .unwrap()
+                .json::<Vec<AuditLogEntry>>()
+                .await
+                .unwrap()
+        }
+
+        pub async fn get_auth_keys_response(&self) -> Response {
+            let url = format!(
+                "http://{}/api/v1/keys?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap()
+        }
+
+        pub async fn get_auth_keys(&self) -> Vec<AuthKey> {
+            let url = format!(
+                "http://{}/api/v1/keys?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
+        pub async fn get_whitelist(&self, query: &str) -> Vec<String> {
+            let url = format!(
+                "http://{}/api/v1/whitelist?{}&token={}",
+                &self.connection_info.bind_address, &query, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
+        pub async fn get_whitelist_entry(&self, info_hash: &str) -> WhitelistEntry {
+            let url = format!(
+                "http://{}/api/v1/whitelist/{}?token={}",
+                &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
         pub async fn whitelist_a_torrent(&self, info_hash: &str) -> Response {
             let url = format!(
-                "http://{}/api/whitelist/{}?token={}",
+                "http://{}/api/v1/whitelist/{}?token={}",
                 &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token
             );
             reqwest::Client::new().post(url.clone()).send().await.unwrap()
         }
 
+        pub async fn blacklist_range(&self, range: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/blacklist/{}?token={}",
+                &self.connection_info.bind_address, &range, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).send().await.unwrap()
+        }
+
+        pub async fn remove_range_from_blacklist(&self, range: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/blacklist/{}?token={}",
+                &self.connection_info.bind_address, &range, &self.connection_info.api_token
+            );
+            reqwest::Client::new().delete(url).send().await.unwrap()
+        }
+
+        pub async fn get_client_filter(&self) -> ClientFilter {
+            let url = format!(
+                "http://{}/api/v1/client_filter?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
+        pub async fn add_to_client_filter(&self, list: &str, entry: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/client_filter/{}/{}?token={}",
+                &self.connection_info.bind_address, &list, &entry, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).send().await.unwrap()
+        }
+
+        pub async fn remove_from_client_filter(&self, list: &str, entry: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/client_filter/{}/{}?token={}",
+                &self.connection_info.bind_address, &list, &entry, &self.connection_info.api_token
+            );
+            reqwest::Client::new().delete(url).send().await.unwrap()
+        }
+
+        pub async fn reload(&self) -> Response {
+            let url = format!(
+                "http://{}/api/v1/reload?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).send().await.unwrap()
+        }
+
+        pub async fn flush(&self) -> Response {
+            let url = format!(
+                "http://{}/api/v1/maintenance/flush?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).send().await.unwrap()
+        }
+
+        pub async fn set_log_level(&self, level: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/log-level?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new()
+                .put(url)
+                .json(&LogLevel {
+                    level: level.to_string(),
+                })
+                .send()
+                .await
+                .unwrap()
+        }
+
+        pub async fn set_maintenance(&self, under_maintenance: bool) -> Response {
+            let url = format!(
+                "http://{}/api/v1/maintenance?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            let client = reqwest::Client::new();
+            let request = if under_maintenance {
+                client.post(url)
+            } else {
+                client.delete(url)
+            };
+            request.send().await.unwrap()
+        }
+
+        pub async fn whitelist_torrents(&self, info_hashes: &[String]) -> Response {
+            let url = format!(
+                "http://{}/api/v1/whitelist?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).json(info_hashes).send().await.unwrap()
+        }
+
+        pub async fn remove_torrents_from_whitelist(&self, info_hashes: &[String]) -> Response {
+            let url = format!(
+                "http://{}/api/v1/whitelist?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::new().delete(url).json(info_hashes).send().await.unwrap()
+        }
+
+        pub async fn get_torrent_response(&self, info_hash: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/torrent/{}?token={}",
+                &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap()
+        }
+
         pub async fn get_torrent(&self, info_hash: &str) -> Torrent {
             let url = format!(
-                "http://{}/api/torrent/{}?token={}",
+                "http://{}/api/v1/torrent/{}?token={}",
                 &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token
             );
             reqwest::Client::builder()
@@ -343,9 +1193,58 @@ mod tracker_api {
                 .unwrap()
         }
 
+        pub async fn remove_torrent(&self, info_hash: &str, query: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/torrent/{}?token={}&{}",
+                &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token, query
+            );
+            reqwest::Client::new().delete(url).send().await.unwrap()
+        }
+
+        pub async fn remove_torrent_peer(&self, info_hash: &str, peer_id: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/torrent/{}/peer/{}?token={}",
+                &self.connection_info.bind_address, &info_hash, &peer_id, &self.connection_info.api_token
+            );
+            reqwest::Client::new().delete(url).send().await.unwrap()
+        }
+
+        pub async fn ban_ip(&self, ip: &str) -> Response {
+            let url = format!(
+                "http://{}/api/v1/ban/ip/{}?token={}",
+                &self.connection_info.bind_address, &ip, &self.connection_info.api_token
+            );
+            reqwest::Client::new().post(url).send().await.unwrap()
+        }
+
+        pub async fn get_torrent_health(&self, info_hash: &str) -> torrent::SwarmHealth {
+            let url = format!(
+                "http://{}/api/v1/torrent/{}/health?token={}",
+                &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
+        pub async fn get_torrent_completed_history(&self, info_hash: &str) -> Vec<torrent::CompletedHistoryItem> {
+            let url = format!(
+                "http://{}/api/v1/torrent/{}/completed?token={}",
+                &self.connection_info.bind_address, &info_hash, &self.connection_info.api_token
+            );
+            reqwest::Client::builder()
+                .build()
+                .unwrap()
+                .get(url)
+                .send()
+                .await
+                .unwrap()
+                .json::<Vec<torrent::CompletedHistoryItem>>()
+                .await
+                .unwrap()
+        }
+
         pub async fn get_torrents(&self) -> Vec<torrent::ListItem> {
             let url = format!(
-                "http://{}/api/torrents?token={}",
+                "http://{}/api/v1/torrents?token={}",
                 &self.connection_info.bind_address, &self.connection_info.api_token
             );
             reqwest::Client::builder()
@@ -360,9 +1259,42 @@ mod tracker_api {
                 .unwrap()
         }
 
+        pub async fn get_torrents_with_query(&self, query: &str) -> Vec<torrent::ListItem> {
+            let url = format!(
+                "http://{}/api/v1/torrents?token={}&{}",
+                &self.connection_info.bind_address, &self.connection_info.api_token, query
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
+        pub async fn get_peers_by_ip(&self, ip: &str) -> Vec<resource::peer::SwarmPeer> {
+            let url = format!(
+                "http://{}/api/v1/peers?ip={}&token={}",
+                &self.connection_info.bind_address, &ip, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap().json().await.unwrap()
+        }
+
         pub async fn get_tracker_statistics(&self) -> Stats {
             let url = format!(
-                "http://{}/api/stats?token={}",
+                "http://{}/api/v1/stats?token={}",
+                &self.connection_info.bind_address, &self.connection_info.api_token
+            );
+            reqwest::Client::builder()
+                .build()
+                .unwrap()
+                .get(url)
+                .send()
+                .await
+                .unwrap()
+                .json::<Stats>()
+                .await
+                .unwrap()
+        }
+
+        pub async fn get_tracker_statistics_history(&self) -> Stats {
+            let url = format!(
+                "http://{}/api/v1/stats?history=true&token={}",
                 &self.connection_info.bind_address, &self.connection_info.api_token
             );
             reqwest::Client::builder()
@@ -376,5 +1308,18 @@ mod tracker_api {
                 .await
                 .unwrap()
         }
+
+        pub async fn get_health(&self) -> Response {
+            let url = format!("http://{}/api/v1/health", &self.connection_info.bind_address);
+            reqwest::Client::new().get(url).send().await.unwrap()
+        }
+
+        pub async fn get_unversioned(&self, path: &str) -> Response {
+            let url = format!(
+                "http://{}/api/{}?token={}",
+                &self.connection_info.bind_address, path, &self.connection_info.api_token
+            );
+            reqwest::Client::new().get(url).send().await.unwrap()
+        }
     }
 }
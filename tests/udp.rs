@@ -7,19 +7,21 @@ mod common;
 
 mod udp_tracker_server {
     use core::panic;
-    use std::env;
     use std::io::Cursor;
     use std::net::Ipv4Addr;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
+    use std::time::Duration;
 
     use aquatic_udp_protocol::{
         AnnounceEvent, AnnounceRequest, ConnectRequest, ConnectionId, InfoHash, NumberOfBytes, NumberOfPeers, PeerId, PeerKey,
         Port, Request, Response, ScrapeRequest, TransactionId,
     };
     use tokio::net::UdpSocket;
+    use tokio::sync::watch;
     use tokio::task::JoinHandle;
-    use torrust_tracker::config::Configuration;
+    use torrust_tracker::config::{BindAddress, Configuration};
+    use torrust_tracker::databases::driver::Driver;
     use torrust_tracker::jobs::udp_tracker;
     use torrust_tracker::tracker::statistics::Keeper;
     use torrust_tracker::udp::MAX_PACKET_SIZE;
@@ -28,25 +30,29 @@ mod udp_tracker_server {
     use crate::common::ephemeral_random_port;
 
     fn tracker_configuration() -> Arc<Configuration> {
+        Arc::new(tracker_configuration_with_workers(1))
+    }
+
+    fn tracker_configuration_with_workers(workers: usize) -> Configuration {
         let mut config = Configuration::default();
         config.log_level = Some("off".to_owned());
 
         // Ephemeral socket address
         let port = ephemeral_random_port();
-        config.udp_trackers[0].bind_address = format!("127.0.0.1:{}", &port);
+        config.udp_trackers[0].bind_address = format!("127.0.0.1:{}", &port).into();
+        config.udp_trackers[0].workers = workers;
 
         // Ephemeral database
-        let temp_directory = env::temp_dir();
-        let temp_file = temp_directory.join(format!("data_{}.db", &port));
-        config.db_path = temp_file.to_str().unwrap().to_owned();
+        config.db_driver = Driver::Memory;
 
-        Arc::new(config)
+        config
     }
 
     pub struct UdpServer {
         pub started: AtomicBool,
         pub job: Option<JoinHandle<()>>,
         pub bind_address: Option<String>,
+        pub shutdown: Option<watch::Sender<bool>>,
     }
 
     impl UdpServer {
@@ -55,6 +61,7 @@ mod udp_tracker_server {
                 started: AtomicBool::new(false),
                 job: None,
                 bind_address: None,
+                shutdown: None,
             }
         }
 
@@ -82,14 +89,26 @@ mod udp_tracker_server {
 
                 let udp_tracker_config = &configuration.udp_trackers[0];
 
+                let (shutdown_sender, shutdown) = watch::channel(false);
+
                 // Start the UDP tracker job
-                self.job = Some(udp_tracker::start_job(udp_tracker_config, tracker));
+                self.job = Some(udp_tracker::start_job(udp_tracker_config, tracker, shutdown));
+                self.shutdown = Some(shutdown_sender);
 
-                self.bind_address = Some(udp_tracker_config.bind_address.clone());
+                self.bind_address = Some(udp_tracker_config.bind_address.to_string());
 
                 self.started.store(true, Ordering::Relaxed);
             }
         }
+
+        pub async fn stop(&mut self) {
+            if self.started.load(Ordering::Relaxed) {
+                self.shutdown.take().unwrap().send(true).unwrap();
+                self.job.take().unwrap().await.unwrap();
+
+                self.started.store(false, Ordering::Relaxed);
+            }
+        }
     }
 
     fn new_running_udp_server(configuration: &Arc<Configuration>) -> UdpServer {
@@ -242,6 +261,135 @@ mod udp_tracker_server {
         assert!(is_connect_response(&response, TransactionId(123)));
     }
 
+    #[tokio::test]
+    async fn should_return_a_connect_response_from_a_server_with_several_workers() {
+        let configuration = Arc::new(tracker_configuration_with_workers(4));
+
+        let udp_server = new_running_udp_server(&configuration);
+
+        let client = new_connected_udp_tracker_client(&udp_server.bind_address.unwrap()).await;
+
+        let connect_request = ConnectRequest {
+            transaction_id: TransactionId(123),
+        };
+
+        client.send(connect_request.into()).await;
+
+        let response = client.receive().await;
+
+        assert!(is_connect_response(&response, TransactionId(123)));
+    }
+
+    #[tokio::test]
+    async fn should_return_a_connect_response_from_a_server_with_tuned_socket_buffers_and_queue() {
+        let mut configuration = tracker_configuration_with_workers(1);
+        configuration.udp_trackers[0].socket_recv_buffer_size = 1 << 20;
+        configuration.udp_trackers[0].socket_send_buffer_size = 1 << 20;
+        configuration.udp_trackers[0].queue_size = 16;
+        let configuration = Arc::new(configuration);
+
+        let udp_server = new_running_udp_server(&configuration);
+
+        let client = new_connected_udp_tracker_client(&udp_server.bind_address.unwrap()).await;
+
+        let connect_request = ConnectRequest {
+            transaction_id: TransactionId(123),
+        };
+
+        client.send(connect_request.into()).await;
+
+        let response = client.receive().await;
+
+        assert!(is_connect_response(&response, TransactionId(123)));
+    }
+
+    #[tokio::test]
+    async fn should_stop_the_workers_and_free_the_socket_on_shutdown() {
+        let configuration = Arc::new(tracker_configuration_with_workers(4));
+
+        let mut udp_server = new_running_udp_server(&configuration);
+        let bind_address = udp_server.bind_address.clone().unwrap();
+
+        let client = new_connected_udp_tracker_client(&bind_address).await;
+
+        client
+            .send(
+                ConnectRequest {
+                    transaction_id: TransactionId(123),
+                }
+                .into(),
+            )
+            .await;
+
+        assert!(is_connect_response(&client.receive().await, TransactionId(123)));
+
+        tokio::time::timeout(Duration::from_secs(5), udp_server.stop())
+            .await
+            .expect("the UDP server should stop on shutdown");
+
+        assert!(UdpSocket::bind(&bind_address).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_drop_the_packets_of_a_client_over_the_rate_limit() {
+        let mut configuration = tracker_configuration_with_workers(1);
+        configuration.udp_trackers[0].rate_limit_packets_per_second = 1;
+        configuration.udp_trackers[0].rate_limit_burst = 1;
+        let configuration = Arc::new(configuration);
+
+        let udp_server = new_running_udp_server(&configuration);
+
+        let client = new_connected_udp_tracker_client(&udp_server.bind_address.unwrap()).await;
+
+        client
+            .send(
+                ConnectRequest {
+                    transaction_id: TransactionId(123),
+                }
+                .into(),
+            )
+            .await;
+
+        assert!(is_connect_response(&client.receive().await, TransactionId(123)));
+
+        client
+            .send(
+                ConnectRequest {
+                    transaction_id: TransactionId(456),
+                }
+                .into(),
+            )
+            .await;
+
+        assert!(tokio::time::timeout(Duration::from_millis(500), client.receive())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn should_drop_the_packets_of_a_client_in_a_denied_network() {
+        let mut configuration = tracker_configuration_with_workers(1);
+        configuration.udp_trackers[0].denied_networks = vec!["127.0.0.0/8".parse().unwrap()];
+        let configuration = Arc::new(configuration);
+
+        let udp_server = new_running_udp_server(&configuration);
+
+        let client = new_connected_udp_tracker_client(&udp_server.bind_address.unwrap()).await;
+
+        client
+            .send(
+                ConnectRequest {
+                    transaction_id: TransactionId(123),
+                }
+                .into(),
+            )
+            .await;
+
+        assert!(tokio::time::timeout(Duration::from_millis(500), client.receive())
+            .await
+            .is_err());
+    }
+
     async fn send_connection_request(transaction_id: TransactionId, client: &UdpTrackerClient) -> ConnectionId {
         let connect_request = ConnectRequest { transaction_id };
 
@@ -255,6 +403,70 @@ mod udp_tracker_server {
         }
     }
 
+    #[tokio::test]
+    async fn should_answer_on_every_bind_address_of_the_udp_tracker() {
+        let mut configuration = tracker_configuration_with_workers(1);
+        let bind_addresses = vec![
+            format!("127.0.0.1:{}", ephemeral_random_port()),
+            format!("127.0.0.1:{}", ephemeral_random_port()),
+        ];
+        configuration.udp_trackers[0].bind_address = BindAddress::Many(bind_addresses.clone());
+        let configuration = Arc::new(configuration);
+
+        let _udp_server = new_running_udp_server(&configuration);
+
+        for bind_address in &bind_addresses {
+            let client = new_connected_udp_tracker_client(bind_address).await;
+
+            send_connection_request(TransactionId(123), &client).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn should_answer_the_announces_on_the_ipv4_and_ipv6_wildcards_of_the_same_port() {
+        let mut configuration = tracker_configuration_with_workers(1);
+        let port = ephemeral_random_port();
+        configuration.udp_trackers[0].bind_address = BindAddress::Many(vec![format!("0.0.0.0:{port}"), format!("[::]:{port}")]);
+        let configuration = Arc::new(configuration);
+
+        let _udp_server = new_running_udp_server(&configuration);
+
+        for (local_address, remote_address) in [
+            (format!("127.0.0.1:{}", ephemeral_random_port()), format!("127.0.0.1:{port}")),
+            (format!("[::1]:{}", ephemeral_random_port()), format!("[::1]:{port}")),
+        ] {
+            let udp_client = UdpClient::bind(&local_address).await;
+            udp_client.connect(&remote_address).await;
+            let client = UdpTrackerClient { udp_client };
+
+            let connection_id = send_connection_request(TransactionId(123), &client).await;
+
+            let announce_request = AnnounceRequest {
+                connection_id,
+                transaction_id: TransactionId(123i32),
+                info_hash: InfoHash([0u8; 20]),
+                peer_id: PeerId([255u8; 20]),
+                bytes_downloaded: NumberOfBytes(0i64),
+                bytes_uploaded: NumberOfBytes(0i64),
+                bytes_left: NumberOfBytes(0i64),
+                event: AnnounceEvent::Started,
+                ip_address: None,
+                key: PeerKey(0u32),
+                peers_wanted: NumberOfPeers(1i32),
+                port: Port(client.udp_client.socket.local_addr().unwrap().port()),
+            };
+
+            client.send(announce_request.into()).await;
+
+            let response = client.receive().await;
+
+            assert!(
+                matches!(response, Response::AnnounceIpv4(_) | Response::AnnounceIpv6(_)),
+                "{remote_address}: {response:?}"
+            );
+        }
+    }
+
     #[tokio::test]
     async fn should_return_an_announce_response_when_the_client_sends_an_announce_request() {
         let configuration = tracker_configuration();